@@ -6,17 +6,74 @@ use syn::{
     parenthesized,
     parse::{Parse, ParseStream},
     parse_macro_input,
+    spanned::Spanned,
     token::{Comma, Paren},
     Attribute, Expr, ExprLit, ExprRange, Ident, Lit, LitInt, Meta, MetaNameValue, Path,
     RangeLimits, Token,
 };
 
+/// The highest bit a field can use, in a 64 bit register.
+const MAX_BIT: usize = 63;
+
+/// # Register Width
+/// The integer type a register is read and written as. Registers are 32 bits
+/// unless one of their fields uses a bit above 31.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RegisterWidth {
+    U32,
+    U64,
+}
+
+impl RegisterWidth {
+    fn for_end_bit(end: usize) -> Self {
+        if end > 31 {
+            Self::U64
+        } else {
+            Self::U32
+        }
+    }
+
+    fn tokens(self) -> proc_macro2::TokenStream {
+        match self {
+            Self::U32 => quote!(u32),
+            Self::U64 => quote!(u64),
+        }
+    }
+
+    /// The `1` literal of this width, for shifting into a single bit flag.
+    fn one(self) -> LitInt {
+        match self {
+            Self::U32 => LitInt::new("1u32", proc_macro2::Span::call_site()),
+            Self::U64 => LitInt::new("1u64", proc_macro2::Span::call_site()),
+        }
+    }
+
+    /// The type of the `_BIT_MASK` and `_SET_MASK` constants. 32 bit registers
+    /// keep `usize` masks, while 64 bit registers need `u64` masks to fit on 32
+    /// bit targets.
+    fn mask_type(self) -> proc_macro2::TokenStream {
+        match self {
+            Self::U32 => quote!(usize),
+            Self::U64 => quote!(u64),
+        }
+    }
+}
+
 #[derive(Debug)]
 enum BitRange {
     Range((Bound<usize>, Bound<usize>)),
     Single(usize),
 }
 
+impl BitRange {
+    fn end_bit(&self) -> usize {
+        match self {
+            Self::Range(range) => get_real_range(*range).1,
+            Self::Single(single) => *single,
+        }
+    }
+}
+
 impl Parse for BitRange {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         if (input.peek(LitInt) || input.peek(Token![..]))
@@ -62,13 +119,20 @@ impl Parse for BitRange {
 
             if start >= end {
                 Err(input.error("Range start must be larger then range end!"))
+            } else if end > MAX_BIT {
+                Err(input.error("Bit ranges must end at or below bit 63!"))
             } else {
                 Ok(Self::Range((first, second)))
             }
         } else if input.peek(LitInt) {
             let value: LitInt = input.parse()?;
+            let single = value.base10_parse()?;
 
-            Ok(Self::Single(value.base10_parse()?))
+            if single > MAX_BIT {
+                Err(input.error("Bits must be at or below bit 63!"))
+            } else {
+                Ok(Self::Single(single))
+            }
         } else {
             Err(input.error(
                 "Could not parse bit, must provide a single bit '0' or multiple bits '0..=10'!",
@@ -117,12 +181,71 @@ impl Parse for Access {
     }
 }
 
+/// # Register Index
+/// The `index(start..end, stride = N)` of a register repeated `end - start`
+/// times, `N` bytes apart. The register's offset constant is where index `start`
+/// is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RegisterIndex {
+    start: usize,
+    end: usize,
+    stride: usize,
+}
+
+mod index {
+    syn::custom_keyword!(index);
+    syn::custom_keyword!(stride);
+}
+
+impl Parse for RegisterIndex {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse::<index::index>()?;
+        let content;
+        parenthesized!(content in input);
+
+        let range: ExprRange = content.parse()?;
+        let int = |expr: Option<Box<Expr>>| -> syn::Result<usize> {
+            match expr.as_deref() {
+                Some(Expr::Lit(ExprLit {
+                    lit: Lit::Int(int), ..
+                })) => int.base10_parse(),
+                _ => Err(content.error("Index ranges need a literal start and end, like `0..8`!")),
+            }
+        };
+        let start = int(range.start)?;
+        let end = match range.limits {
+            RangeLimits::HalfOpen(_) => int(range.end)?,
+            RangeLimits::Closed(_) => int(range.end)? + 1,
+        };
+
+        content.parse::<Comma>()?;
+        content.parse::<index::stride>()?;
+        content.parse::<Token![=]>()?;
+        let stride: LitInt = content.parse()?;
+        let stride: usize = stride.base10_parse()?;
+
+        if start >= end {
+            Err(content.error("The index range must hold at least one index!"))
+        } else if stride == 0 || !stride.is_multiple_of(4) {
+            Err(content.error(
+                "The index stride must be a non zero number of 32 bit words, like `stride = 4`!",
+            ))
+        } else {
+            Ok(Self { start, end, stride })
+        }
+    }
+}
+
 #[derive(Debug)]
 struct BitAttribute {
     bit: BitRange,
     access: Access,
     path: syn::Path,
     register_name: String,
+    /// The optional enum the field's value is read and written as.
+    enum_type: Option<syn::Path>,
+    /// Set when the register is repeated, and its accessors take an index.
+    index: Option<RegisterIndex>,
 }
 
 impl Parse for BitAttribute {
@@ -145,11 +268,37 @@ impl Parse for BitAttribute {
             .to_string()
             .to_ascii_lowercase();
 
+        let mut enum_type = None;
+        let mut index = None;
+        while input.peek(Comma) {
+            input.parse::<Comma>()?;
+            if input.peek(index::index) && input.peek2(Paren) {
+                if index.is_some() {
+                    return Err(input.error("A field can only be given one `index(...)`!"));
+                }
+                index = Some(input.parse()?);
+                continue;
+            }
+
+            if enum_type.is_some() || index.is_some() {
+                return Err(input
+                    .error("An enum type comes before `index(...)`, and only one can be given!"));
+            }
+            if !matches!(bit, BitRange::Range(_)) {
+                return Err(input.error(
+                    "An enum type can only be given for a bit range, single bits are always `bool`!",
+                ));
+            }
+            enum_type = Some(input.parse()?);
+        }
+
         Ok(Self {
             bit,
             access,
             path,
             register_name,
+            enum_type,
+            index,
         })
     }
 }
@@ -159,6 +308,14 @@ struct BitBlock {
     doc_attr: Vec<String>,
     bit_attr: BitAttribute,
     name: Ident,
+    /// The width of this bit's register, filled in once all bits are parsed.
+    width: RegisterWidth,
+    /// Where the `#[bit(...)]` attribute is, for pointing errors at it.
+    bit_span: proc_macro2::Span,
+    /// The register's reset value from `#[reset(...)]`, and where it was given.
+    reset: Option<(u64, proc_macro2::Span)>,
+    /// Set by `#[no_dump]`, when reading the register has side effects.
+    no_dump: bool,
 }
 
 impl Parse for BitBlock {
@@ -166,6 +323,9 @@ impl Parse for BitBlock {
         let attributes = input.call(Attribute::parse_outer)?;
         let mut doc_attr: Vec<String> = Vec::new();
         let mut bit_attr: Option<BitAttribute> = None;
+        let mut bit_span = input.span();
+        let mut reset = None;
+        let mut no_dump = false;
 
         for attr in &attributes {
             if attr.path().is_ident("doc") {
@@ -184,6 +344,13 @@ impl Parse for BitBlock {
                 doc_attr.push(format!(" {}", string.value().trim_start()));
             } else if attr.path().is_ident("bit") {
                 bit_attr = Some(attr.parse_args()?);
+                bit_span = attr.span();
+            } else if attr.path().is_ident("reset") {
+                let value: LitInt = attr.parse_args()?;
+                reset = Some((value.base10_parse()?, attr.span()));
+            } else if attr.path().is_ident("no_dump") {
+                attr.meta.require_path_only()?;
+                no_dump = true;
             } else {
                 return Err(input.error("Unknown attribute"));
             }
@@ -194,6 +361,10 @@ impl Parse for BitBlock {
             bit_attr: bit_attr
                 .ok_or(input.error("Reqires a #[bit(...)] attribute before a name (ie. Ident)."))?,
             name: input.parse()?,
+            width: RegisterWidth::U32,
+            bit_span,
+            reset,
+            no_dump,
         })
     }
 }
@@ -201,6 +372,8 @@ impl Parse for BitBlock {
 #[derive(Debug)]
 struct MakeDevice {
     device_ports: DevicePorts,
+    /// The size of the address window every register must fit in, if given.
+    window_size: Option<Expr>,
     bits: Vec<BitBlock>,
 }
 
@@ -209,6 +382,7 @@ struct DevicePorts(Vec<Path>);
 
 mod device_ports {
     syn::custom_keyword!(device_ports);
+    syn::custom_keyword!(window_size);
 }
 
 impl Parse for DevicePorts {
@@ -228,27 +402,535 @@ impl Parse for DevicePorts {
 
 impl Parse for MakeDevice {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        let device_ports = input.parse()?;
+        let window_size = if input.peek(device_ports::window_size) {
+            input.parse::<device_ports::window_size>()?;
+            let content;
+            parenthesized!(content in input);
+            input.parse::<Token![;]>()?;
+            Some(content.parse()?)
+        } else {
+            None
+        };
+        let mut bits: Vec<BitBlock> = input
+            .parse_terminated(BitBlock::parse, Token![,])?
+            .into_iter()
+            .collect();
+
+        // A register is as wide as its widest field needs.
+        let mut widths: HashMap<String, RegisterWidth> = HashMap::new();
+        for b in bits.iter() {
+            let width = RegisterWidth::for_end_bit(b.bit_attr.bit.end_bit());
+            let entry = widths
+                .entry(b.bit_attr.register_name.clone())
+                .or_insert(width);
+            if width == RegisterWidth::U64 {
+                *entry = width;
+            }
+        }
+        for b in bits.iter_mut() {
+            b.width = widths[&b.bit_attr.register_name];
+        }
+
+        check_writable_overlaps(&bits)?;
+        check_resets(&bits)?;
+        check_indexes(&bits)?;
+
         Ok(MakeDevice {
-            device_ports: input.parse()?,
-            bits: input
-                .parse_terminated(BitBlock::parse, Token![,])?
-                .into_iter()
-                .collect(),
+            device_ports,
+            window_size,
+            bits,
         })
     }
 }
 
+/// Error on the first field that partly overlaps an earlier field of the same
+/// register, when both have setters. Setters only keep the bits outside their
+/// own field, so overlapping ones would overwrite each other. Read only fields
+/// may overlap anything, and a field may cover other fields entirely, like a
+/// whole register field used to save and restore it.
+fn check_writable_overlaps(bits: &[BitBlock]) -> syn::Result<()> {
+    let writable: Vec<(&BitBlock, usize, usize)> = bits
+        .iter()
+        .filter(|b| !matches!(b.bit_attr.access, Access::RO))
+        .map(|b| {
+            let (start, end) = match b.bit_attr.bit {
+                BitRange::Range(range) => get_real_range(range),
+                BitRange::Single(single) => (single, single),
+            };
+            (b, start, end)
+        })
+        .collect();
+
+    for (i, (b, start, end)) in writable.iter().enumerate() {
+        let overlapping = writable[..i]
+            .iter()
+            .find(|(other, other_start, other_end)| {
+                let overlaps = start <= other_end && other_start <= end;
+                let nested = (start <= other_start && other_end <= end)
+                    || (other_start <= start && end <= other_end);
+                let same = start == other_start && end == other_end;
+
+                other.bit_attr.register_name == b.bit_attr.register_name
+                    && overlaps
+                    && (same || !nested)
+            });
+
+        if let Some((other, other_start, other_end)) = overlapping {
+            return Err(syn::Error::new(
+                b.bit_span,
+                format!(
+                    "Writable field `{}` (bits {start}..={end}) overlaps writable field `{}` (bits {other_start}..={other_end}) in the same register!",
+                    b.name, other.name
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Error if a register is given more than one `#[reset(...)]`, a reset value
+/// that does not fit it, or a reset value while it has no writable fields.
+fn check_resets(bits: &[BitBlock]) -> syn::Result<()> {
+    for (i, b) in bits.iter().enumerate() {
+        let Some((value, span)) = b.reset else {
+            continue;
+        };
+        let same_register =
+            |other: &&BitBlock| other.bit_attr.register_name == b.bit_attr.register_name;
+
+        if bits[..i]
+            .iter()
+            .filter(same_register)
+            .any(|other| other.reset.is_some())
+        {
+            return Err(syn::Error::new(
+                span,
+                "This register already has a #[reset(...)] value, only give it once per register!",
+            ));
+        }
+        if b.width == RegisterWidth::U32 && value > u32::MAX as u64 {
+            return Err(syn::Error::new(
+                span,
+                "Reset value does not fit in this 32 bit register!",
+            ));
+        }
+        if bits
+            .iter()
+            .filter(same_register)
+            .all(|other| matches!(other.bit_attr.access, Access::RO))
+        {
+            return Err(syn::Error::new(
+                span,
+                "A read only register cannot be reset, it has no writable fields!",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Error if the fields of one register disagree on its `index(...)`, or an
+/// indexed register is given a `#[reset(...)]`.
+fn check_indexes(bits: &[BitBlock]) -> syn::Result<()> {
+    for b in bits.iter() {
+        let first = bits
+            .iter()
+            .find(|other| other.bit_attr.register_name == b.bit_attr.register_name)
+            .expect("`b` is in `bits`");
+        if first.bit_attr.index != b.bit_attr.index {
+            return Err(syn::Error::new(
+                b.bit_span,
+                "Every field of a register must give the same `index(...)`, or none!",
+            ));
+        }
+        if let (Some(_), Some((_, span))) = (b.bit_attr.index, b.reset) {
+            return Err(syn::Error::new(
+                span,
+                "An indexed register cannot be given a #[reset(...)] value!",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// How the accessors of an indexed register check the index they are given.
+#[derive(Clone, Copy)]
+enum IndexCheck {
+    /// Safe accessors always check, since a bad index reaches some other register.
+    Always,
+    /// Unsafe accessors leave a valid index to their caller, checked in debug only.
+    Debug,
+}
+
+/// The extra parameter, its argument, and the handle to read and write for the
+/// register of `bit`. Accessors of an indexed register take the index after
+/// `self`, and reach the register through its `<register>_at` function, or
+/// `<register>_at_unchecked` for an `IndexCheck::Debug`.
+fn register_handle(
+    bit: &BitBlock,
+    check: IndexCheck,
+) -> (
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+) {
+    let register_name = &bit.bit_attr.register_name;
+    match bit.bit_attr.index {
+        None => {
+            let self_dot = format_ident!("{}", register_name);
+            (quote!(), quote!(), quote!(self.#self_dot))
+        }
+        Some(_) => {
+            let at = match check {
+                IndexCheck::Always => format_ident!("{}_at", register_name),
+                IndexCheck::Debug => format_ident!("{}_at_unchecked", register_name),
+            };
+            (
+                quote!(, index: usize),
+                quote!(index),
+                quote!(self.#at(index)),
+            )
+        }
+    }
+}
+
+/// Generate the `<REGISTER>_INDEXES` constant and the private `<register>_at`
+/// function of every indexed register.
+fn generate_indexes(bits: &[BitBlock]) -> proc_macro2::TokenStream {
+    let mut seen: Vec<&String> = Vec::new();
+    let indexes = bits.iter().filter_map(|b| {
+        let RegisterIndex { start, end, stride } = b.bit_attr.index?;
+        let register_name = &b.bit_attr.register_name;
+        if seen.contains(&register_name) {
+            return None;
+        }
+        seen.push(register_name);
+
+        let self_dot = format_ident!("{}", register_name);
+        let at = format_ident!("{}_at", register_name);
+        let at_unchecked = format_ident!("{}_at_unchecked", register_name);
+        let register_type = b.width.tokens();
+        let const_name = format_ident!("{}_INDEXES", register_name.to_uppercase());
+        let const_doc_title = string_into_title(&const_name.to_string());
+        let const_doc = format!(
+            " The indexes of this register, repeated every {stride} bytes. Give one of these to the accessors of its fields."
+        );
+
+        Some(quote! {
+            #const_doc_title
+            #[doc = #const_doc]
+            pub const #const_name: core::ops::Range<usize> = #start..#end;
+
+            /// The register at `index`, `index - start` strides past the first one.
+            ///
+            /// # Panic
+            /// This will panic if `index` is outside of the register's index range.
+            #[allow(dead_code)]
+            #[inline(always)]
+            fn #at(&self, index: usize) -> hal_macros::RW<0, #register_type> {
+                assert!(<Self>::#const_name.contains(&index), "Index {index} is outside of the register indexes {}..{}!", #start, #end);
+                self.#at_unchecked(index)
+            }
+
+            /// The register at `index`, like `_at`, for the unsafe accessors.
+            ///
+            /// # Panic
+            /// **In `debug` only**, this will panic if `index` is outside of the
+            /// register's index range.
+            #[allow(dead_code)]
+            #[inline(always)]
+            fn #at_unchecked(&self, index: usize) -> hal_macros::RW<0, #register_type> {
+                use hal_macros::VolatileWrap;
+                debug_assert!(<Self>::#const_name.contains(&index), "Index {index} is outside of the register indexes {}..{}!", #start, #end);
+                let first = self.#self_dot.action_immutable(|ptr| ptr as usize);
+                hal_macros::RW::new(first + index.wrapping_sub(#start).wrapping_mul(#stride))
+                    .expect("register addresses are never null")
+            }
+        })
+    });
+
+    quote!(#(#indexes)*)
+}
+
+/// Generate a compile time check that every register (up to the last index of an
+/// indexed one) ends inside the `window_size` bytes of the device's ports.
+fn generate_window_checks(
+    bits: &[BitBlock],
+    window_size: Option<Expr>,
+) -> proc_macro2::TokenStream {
+    let Some(window_size) = window_size else {
+        return quote!();
+    };
+
+    let mut seen: Vec<&String> = Vec::new();
+    let checks = bits.iter().filter_map(|b| {
+        let register_name = &b.bit_attr.register_name;
+        if seen.contains(&register_name) {
+            return None;
+        }
+        seen.push(register_name);
+
+        let path = &b.bit_attr.path;
+        let register_type = b.width.tokens();
+        let last_index = match b.bit_attr.index {
+            Some(RegisterIndex { start, end, stride }) => (end - 1 - start) * stride,
+            None => 0,
+        };
+        let message = format!(
+            "The register `{}` does not fit in the device's window_size!",
+            quote!(#path).to_string().replace(' ', "")
+        );
+
+        Some(quote! {
+            const _: () = assert!(
+                #path + #last_index + core::mem::size_of::<#register_type>() <= #window_size,
+                #message
+            );
+        })
+    });
+
+    quote!(#(#checks)*)
+}
+
+/// Generate the `<REGISTER>_RESET` constant and `reset_<register>` function for
+/// every register given a `#[reset(...)]` value.
+fn generate_resets(bits: &[BitBlock]) -> proc_macro2::TokenStream {
+    let resets = bits.iter().filter_map(|b| {
+        let (value, _) = b.reset?;
+        let register_name = &b.bit_attr.register_name;
+        let register_type = b.width.tokens();
+        let self_dot = format_ident!("{}", register_name);
+        let const_name = format_ident!("{}_RESET", register_name.to_uppercase());
+        let set_mask = format_ident!("{}_SET_MASK", register_name.to_uppercase());
+        let fn_name = format_ident!("reset_{}", register_name);
+        let const_doc_title = string_into_title(&const_name.to_string());
+        let fn_doc_title = string_into_title(&fn_name.to_string());
+        let const_doc = format!(" The documented reset value of this register, `{value:#010x}`.");
+        let value = proc_macro2::Literal::u64_unsuffixed(value);
+
+        Some(quote! {
+            #const_doc_title
+            #[doc = #const_doc]
+            pub const #const_name: #register_type = #value;
+
+            #fn_doc_title
+            /// Write this register's reset value back, for example to undo changes
+            /// made while debugging. Flag bits (`RW1C` and `RW1O`) are masked out of
+            /// the write, like they are for the setters, so a `1` in the reset value
+            /// will not clear or trigger them.
+            ///
+            /// # Safety
+            /// It is up to the caller to verify that resetting this register will
+            /// not cause any side effects, as with each field's setter.
+            ///
+            /// # Volatile
+            /// This function only preforms **1** volatile *write*.
+            ///
+            #[inline(always)]
+            pub unsafe fn #fn_name(&mut self) {
+                use hal_macros::VolatileWrite;
+                self.#self_dot.write(<Self>::#const_name & (<Self>::#set_mask as #register_type));
+            }
+        })
+    });
+
+    quote!(#(#resets)*)
+}
+
+/// # Make Device
+/// Generate a `Registers` struct with accessors for every `#[bit(...)]` field,
+/// see the generated struct's documentation for the full syntax.
+///
+/// # Overlapping Fields
+/// Since each setter keeps the other bits of the register as they are, writable
+/// fields of one register may only overlap when one covers the other entirely,
+/// such as a whole register field. Read only fields may overlap anything:
+///
+/// ```
+/// # use hal_macros::RW;
+/// # use hal_macros_derive::make_device;
+/// # mod error { pub enum ErrorKind { NullPtr, TimeOut } }
+/// # const CTRL: usize = 0;
+/// make_device! {
+///     device_ports();
+///
+///     #[bit(0..=31, RW, CTRL)]
+///     whole_register,
+///
+///     #[bit(0..=7, RW, CTRL)]
+///     low_byte,
+///
+///     #[bit(4..=11, RO, CTRL)]
+///     middle_status,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Two writable fields partly claiming the same bits are a compile error:
+///
+/// ```compile_fail
+/// # use hal_macros::RW;
+/// # use hal_macros_derive::make_device;
+/// # mod error { pub enum ErrorKind { NullPtr, TimeOut } }
+/// # const CTRL: usize = 0;
+/// make_device! {
+///     device_ports();
+///
+///     #[bit(0..=7, RW, CTRL)]
+///     low_byte,
+///
+///     #[bit(4..=11, RW, CTRL)]
+///     typo_field,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The same goes for two fields with the same bits:
+///
+/// ```compile_fail
+/// # use hal_macros::RW;
+/// # use hal_macros_derive::make_device;
+/// # mod error { pub enum ErrorKind { NullPtr, TimeOut } }
+/// # const CTRL: usize = 0;
+/// make_device! {
+///     device_ports();
+///
+///     #[bit(3, RW, CTRL)]
+///     enable,
+///
+///     #[bit(3, RW, CTRL)]
+///     copy_pasted_enable,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Or a flag like `RW1C` that partly overlaps a writable range:
+///
+/// ```compile_fail
+/// # use hal_macros::RW;
+/// # use hal_macros_derive::make_device;
+/// # mod error { pub enum ErrorKind { NullPtr, TimeOut } }
+/// # const CTRL: usize = 0;
+/// make_device! {
+///     device_ports();
+///
+///     #[bit(0..=3, WO, CTRL)]
+///     command,
+///
+///     #[bit(3..=4, RW1C, CTRL)]
+///     done_flags,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Reset Values
+/// A `#[reset(...)]` on any one field of a register generates its
+/// `<REGISTER>_RESET` constant and `reset_<register>` function. Giving a
+/// register two reset values is a compile error:
+///
+/// ```compile_fail
+/// # use hal_macros::RW;
+/// # use hal_macros_derive::make_device;
+/// # mod error { pub enum ErrorKind { NullPtr, TimeOut } }
+/// # const CTRL: usize = 0;
+/// make_device! {
+///     device_ports();
+///
+///     #[reset(0x0000_0044)]
+///     #[bit(0..=7, RW, CTRL)]
+///     divider,
+///
+///     #[reset(0x0000_0000)]
+///     #[bit(8, RW, CTRL)]
+///     enable,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Indexed Registers
+/// A register repeated at a fixed stride, like the words of a key, is written
+/// once with `index(start..end, stride = N)` after the offset, where the offset
+/// is the register at index `start`. Its accessors take the index after `self`,
+/// and its `<REGISTER>_INDEXES` constant holds the valid indexes:
+///
+/// ```
+/// # use hal_macros::RW;
+/// # use hal_macros_derive::make_device;
+/// # mod error { pub enum ErrorKind { NullPtr, TimeOut } }
+/// # const KEY: usize = 0;
+/// make_device! {
+///     device_ports();
+///
+///     #[bit(0..=31, RW, KEY, index(0..8, stride = 4))]
+///     key,
+/// }
+///
+/// fn load_key(reg: &mut Registers, key: &[u32; 8]) {
+///     for index in Registers::KEY_INDEXES {
+///         unsafe { reg.set_key(index, key[index]) };
+///     }
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Every field of an indexed register must have the same `index(...)`:
+///
+/// ```compile_fail
+/// # use hal_macros::RW;
+/// # use hal_macros_derive::make_device;
+/// # mod error { pub enum ErrorKind { NullPtr, TimeOut } }
+/// # const TIMER: usize = 0;
+/// make_device! {
+///     device_ports();
+///
+///     #[bit(0..=15, RW, TIMER, index(0..4, stride = 4))]
+///     count,
+///
+///     #[bit(16, RW, TIMER)]
+///     enable,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Window Size
+/// An optional `window_size(...);` after `device_ports` gives the size in bytes
+/// of each port's address window. Every register offset (and every index of an
+/// indexed register) is then checked at compile time to end inside it, so a
+/// typo in an offset cannot reach into the next peripheral:
+///
+/// ```compile_fail
+/// # use hal_macros::RW;
+/// # use hal_macros_derive::make_device;
+/// # mod error { pub enum ErrorKind { NullPtr, TimeOut } }
+/// # const CTRL: usize = 0x0000;
+/// # const STATUS: usize = 0x0400;
+/// make_device! {
+///     device_ports();
+///     window_size(0x400);
+///
+///     #[bit(0, RW, CTRL)]
+///     enable,
+///
+///     #[bit(0, RO, STATUS)]
+///     ready,
+/// }
+/// # fn main() {}
+/// ```
 #[proc_macro]
 pub fn make_device(input: TokenStream) -> TokenStream {
     let parsed_scope = parse_macro_input!(input as MakeDevice);
 
-    let register_names: Vec<(String, Path)> = parsed_scope
+    let register_names: Vec<(String, Path, RegisterWidth)> = parsed_scope
         .bits
         .iter()
         .map(|bits| {
             (
                 bits.bit_attr.register_name.clone(),
                 bits.bit_attr.path.clone(),
+                bits.width,
             )
         })
         .collect();
@@ -260,14 +942,27 @@ pub fn make_device(input: TokenStream) -> TokenStream {
 
     let set_masks = generate_set_masks(&parsed_scope.bits);
     let new_fn = generate_new_constructer(&register_fields, parsed_scope.device_ports);
+    let decoders = generate_decoders(&parsed_scope.bits);
+    let (field_structs, modifiers) = generate_modifiers(&parsed_scope.bits);
+    let resets = generate_resets(&parsed_scope.bits);
+    let dumps = generate_dumps(&parsed_scope.bits);
+    let indexes = generate_indexes(&parsed_scope.bits);
+    let window_checks = generate_window_checks(&parsed_scope.bits, parsed_scope.window_size);
 
     let emit = quote! {
         #registers_struct
+        #field_structs
+        #window_checks
 
         impl Registers {
             #new_fn
 
             #set_masks
+            #decoders
+            #modifiers
+            #resets
+            #dumps
+            #indexes
             #(#bit_impl)*
         }
     };
@@ -276,7 +971,7 @@ pub fn make_device(input: TokenStream) -> TokenStream {
 }
 
 fn generate_new_constructer(
-    register_fields: &[(Ident, Path)],
+    register_fields: &[(Ident, Path, RegisterWidth)],
     device_ports: DevicePorts,
 ) -> proc_macro2::TokenStream {
     let device_ports_vec = device_ports.0;
@@ -293,7 +988,14 @@ fn generate_new_constructer(
 
     let fields: Vec<_> = register_fields
         .iter()
-        .map(|(ident, _)| quote!(#ident : RW::new(port).unwrap()))
+        .map(|(ident, _, _)| quote!(#ident : RW::new(port).unwrap()))
+        .collect();
+
+    let try_fields: Vec<_> = register_fields
+        .iter()
+        .map(
+            |(ident, _, _)| quote!(#ident : RW::new(port).ok_or(crate::error::ErrorKind::NullPtr)?),
+        )
         .collect();
 
     quote!(
@@ -332,11 +1034,348 @@ fn generate_new_constructer(
                 #(#fields,)*
             }
         }
+
+        /// # Try New
+        /// Make a new Registers struct that has the base offset of `port`, returning
+        /// `ErrorKind::NullPtr` instead of panicking if `port` is null. Use this when
+        /// `port` is computed at runtime, and `new` for the known-good constant bases.
+        ///
+        /// Unlike `new`, this does not check `port` against `device_ports`.
+        pub fn try_new(port: usize) -> core::result::Result<Self, crate::error::ErrorKind> {
+            if port == 0 {
+                return Err(crate::error::ErrorKind::NullPtr);
+            }
+
+            Ok(Self {
+                #(#try_fields,)*
+            })
+        }
     )
 }
 
+/// The name, start bit, and end bit of each field in a register.
+type RegisterFields = Vec<(String, usize, usize)>;
+
+fn generate_decoders(bits: &[BitBlock]) -> proc_macro2::TokenStream {
+    let mut registers: Vec<(String, String, RegisterWidth, RegisterFields)> = Vec::new();
+
+    for b in bits.iter() {
+        let (start, end) = match b.bit_attr.bit {
+            BitRange::Range(range) => get_real_range(range),
+            BitRange::Single(single) => (single, single),
+        };
+        let field = (b.name.to_string(), start, end);
+
+        match registers
+            .iter_mut()
+            .find(|(name, _, _, _)| *name == b.bit_attr.register_name)
+        {
+            Some((_, _, _, fields)) => fields.push(field),
+            None => {
+                let display_name = b
+                    .bit_attr
+                    .path
+                    .segments
+                    .last()
+                    .map(|segment| segment.ident.to_string())
+                    .unwrap_or_default();
+
+                registers.push((
+                    b.bit_attr.register_name.clone(),
+                    display_name,
+                    b.width,
+                    vec![field],
+                ));
+            }
+        }
+    }
+
+    let decoders: Vec<proc_macro2::TokenStream> = registers
+        .into_iter()
+        .map(|(register_name, display_name, width, mut fields)| {
+            fields.sort_by_key(|(_, start, _)| *start);
+
+            let fn_name = format_ident!("decode_{}", register_name);
+            let doc_title = string_into_title(&format!("decode {register_name}"));
+            let width = width.tokens();
+            let field_tokens = fields.iter().map(|(name, start, end)| {
+                let start = *start as u8;
+                let end = *end as u8;
+                quote!(hal_macros::RegisterField { name: #name, start: #start, end: #end })
+            });
+
+            quote! {
+                #doc_title
+                /// Break a raw value of this register down into its named fields, so it
+                /// can be printed with `{:?}`. Only generated with the `register-decode`
+                /// feature.
+                #[cfg(any(test, feature = "register-decode"))]
+                pub fn #fn_name(value: #width) -> hal_macros::RegisterDecode<#width> {
+                    hal_macros::RegisterDecode {
+                        name: #display_name,
+                        value,
+                        fields: &[#(#field_tokens),*],
+                    }
+                }
+            }
+        })
+        .collect();
+
+    quote!(
+        #( #decoders )*
+    )
+}
+
+/// The start and end bit of a field.
+fn field_range(bit: &BitBlock) -> (usize, usize) {
+    match bit.bit_attr.bit {
+        BitRange::Range(range) => get_real_range(range),
+        BitRange::Single(single) => (single, single),
+    }
+}
+
+/// Generate a `dump_<register>` function for every register with readable
+/// fields, and `fmt_registers` to print them all. Registers with a `#[no_dump]`
+/// field and indexed registers are left out, along with write only fields and
+/// fields that only cover other fields (the raw value is already printed).
+fn generate_dumps(bits: &[BitBlock]) -> proc_macro2::TokenStream {
+    let readable = |b: &&BitBlock| !matches!(b.bit_attr.access, Access::WO);
+    let mut registers: Vec<(&BitBlock, RegisterFields)> = Vec::new();
+
+    for b in bits.iter().filter(readable) {
+        let no_dump = b.bit_attr.index.is_some()
+            || bits.iter().any(|other| {
+                other.no_dump && other.bit_attr.register_name == b.bit_attr.register_name
+            });
+        let (start, end) = field_range(b);
+        let is_container = bits.iter().filter(readable).any(|other| {
+            let (other_start, other_end) = field_range(other);
+            other.bit_attr.register_name == b.bit_attr.register_name
+                && (other_start, other_end) != (start, end)
+                && start <= other_start
+                && other_end <= end
+        });
+        if no_dump || is_container {
+            continue;
+        }
+
+        let field = (b.name.to_string(), start, end);
+        match registers
+            .iter_mut()
+            .find(|(first, _)| first.bit_attr.register_name == b.bit_attr.register_name)
+        {
+            Some((_, fields)) => fields.push(field),
+            None => registers.push((b, vec![field])),
+        }
+    }
+
+    let cfg = quote!(#[cfg(any(test, debug_assertions, feature = "register-decode"))]);
+    let mut dump_names = Vec::new();
+    let dumps: Vec<proc_macro2::TokenStream> = registers
+        .into_iter()
+        .map(|(first, mut fields)| {
+            fields.sort_by_key(|(_, start, _)| *start);
+
+            let register_name = &first.bit_attr.register_name;
+            let self_dot = format_ident!("{}", register_name);
+            let width = first.width.tokens();
+            let fn_name = format_ident!("dump_{}", register_name);
+            let doc_title = string_into_title(&fn_name.to_string());
+            let display_name = first
+                .bit_attr
+                .path
+                .segments
+                .last()
+                .map(|segment| segment.ident.to_string())
+                .unwrap_or_default();
+            let field_tokens = fields.iter().map(|(name, start, end)| {
+                let start = *start as u8;
+                let end = *end as u8;
+                quote!(hal_macros::RegisterField { name: #name, start: #start, end: #end })
+            });
+            dump_names.push(fn_name.clone());
+
+            quote! {
+                #doc_title
+                /// Read this register once and break it down into its readable fields,
+                /// so it can be printed with `{:?}`. Only generated in `debug`, or with
+                /// the `register-decode` feature.
+                #cfg
+                pub fn #fn_name(&self) -> hal_macros::RegisterDecode<#width> {
+                    use hal_macros::VolatileRead;
+                    hal_macros::RegisterDecode {
+                        name: #display_name,
+                        value: self.#self_dot.read(),
+                        fields: &[#(#field_tokens),*],
+                    }
+                }
+            }
+        })
+        .collect();
+
+    quote! {
+        #(#dumps)*
+
+        /// # Fmt Registers
+        /// Print every readable register of this device, one per line, with each
+        /// of its readable fields as `name: value`. Registers whose reads have side
+        /// effects (marked `#[no_dump]`, like FIFOs) are skipped. Only generated in
+        /// `debug`, or with the `register-decode` feature.
+        #cfg
+        pub fn fmt_registers(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            #( writeln!(f, "{:?}", self.#dump_names())?; )*
+            Ok(())
+        }
+    }
+}
+
+/// Generate a `<Register>Fields` struct and a `modify_<register>` function for
+/// every register with plain `RW` or `WO` fields. Fields that cover other
+/// writable fields (like a whole register field) are left out, so every bit is
+/// only in the struct once.
+fn generate_modifiers(bits: &[BitBlock]) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let writable = |b: &&BitBlock| matches!(b.bit_attr.access, Access::RW | Access::WO);
+    let mut registers: Vec<(&BitBlock, Vec<&BitBlock>)> = Vec::new();
+
+    for b in bits.iter().filter(writable) {
+        let (start, end) = field_range(b);
+        let is_container = bits.iter().filter(writable).any(|other| {
+            let (other_start, other_end) = field_range(other);
+            other.bit_attr.register_name == b.bit_attr.register_name
+                && (other_start, other_end) != (start, end)
+                && start <= other_start
+                && other_end <= end
+        });
+        if is_container {
+            continue;
+        }
+
+        match registers
+            .iter_mut()
+            .find(|(first, _)| first.bit_attr.register_name == b.bit_attr.register_name)
+        {
+            Some((_, fields)) => fields.push(b),
+            None => registers.push((b, vec![b])),
+        }
+    }
+
+    let mut structs = Vec::new();
+    let mut functions = Vec::new();
+
+    for (first, fields) in registers {
+        let register_name = &first.bit_attr.register_name;
+        let register_type = first.width.tokens();
+        let (index_param, _, handle) = register_handle(first, IndexCheck::Debug);
+        let set_mask = format_ident!("{}_SET_MASK", register_name.to_uppercase());
+        let struct_name = format_ident!(
+            "{}Fields",
+            register_name
+                .split('_')
+                .map(|word| {
+                    let mut chars = word.chars();
+                    chars
+                        .next()
+                        .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                        .unwrap_or_default()
+                })
+                .collect::<String>()
+        );
+        let fn_name = format_ident!("modify_{}", register_name);
+        let struct_doc_title = string_into_title(&struct_name.to_string());
+        let fn_doc_title = string_into_title(&fn_name.to_string());
+        let struct_doc = format!(
+            " The writable fields of the register read and written by `Registers::{fn_name}`."
+        );
+
+        let mut members = Vec::new();
+        let mut reads = Vec::new();
+        let mut writes = Vec::new();
+
+        for b in fields {
+            let name = &b.name;
+            let doc = generate_doc_strings(&b.doc_attr);
+            let const_name = b.name.to_string().to_uppercase();
+
+            match b.bit_attr.bit {
+                BitRange::Single(_) => {
+                    let shift = format_ident!("{}_BIT", const_name);
+                    let one = b.width.one();
+                    members.push(quote!(#doc pub #name: bool));
+                    reads.push(quote!(#name: (read & (#one << <Self>::#shift)) != 0));
+                    writes.push(quote! {
+                        write = if fields.#name {
+                            write | (#one << <Self>::#shift)
+                        } else {
+                            write & !(#one << <Self>::#shift)
+                        };
+                    });
+                }
+                BitRange::Range(range) => {
+                    let (start, end) = get_real_range(range);
+                    let bit_type = min_type_for_range((start, end));
+                    let mask = format_ident!("{}_BIT_MASK", const_name);
+                    let shift = format_ident!("{}_BIT_START", const_name);
+                    let field_end = format_ident!("{}_BIT_END", const_name);
+                    let name_string = name.to_string();
+                    members.push(quote!(#doc pub #name: #bit_type));
+                    reads.push(quote!(#name: ((read & (<Self>::#mask as #register_type)) >> <Self>::#shift) as #bit_type));
+                    writes.push(quote! {
+                        debug_assert!((fields.#name as #register_type) >> ((<Self>::#field_end) - <Self>::#shift) <= 1, "Provided {} value {} is too large for its range {}..={}!", #name_string, fields.#name, #start, #end);
+                        write = (write & !(<Self>::#mask as #register_type)) | ((fields.#name as #register_type) << <Self>::#shift);
+                    });
+                }
+            }
+        }
+
+        structs.push(quote! {
+            #struct_doc_title
+            #[doc = #struct_doc]
+            #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+            pub struct #struct_name {
+                #(#members,)*
+            }
+        });
+
+        functions.push(quote! {
+            #fn_doc_title
+            /// Read the register once, let `func` change its fields in memory, then
+            /// write them all back with a single write. Unlike calling several
+            /// setters, the register never holds a mix of old and new fields, and
+            /// costs one read-modify-write.
+            ///
+            /// Flag bits (`RW1C` and `RW1O`) are written as `0`, like the setters do,
+            /// and read only bits keep the value read.
+            ///
+            /// # Safety
+            /// It is up to the caller to verify that this register write will not
+            /// cause any side effects, and that every field `func` leaves behind is
+            /// a valid value, as with each field's setter.
+            ///
+            /// # Panic
+            /// **In `debug` only**, this will panic if `func` leaves a field value
+            /// larger than its range of bits.
+            ///
+            #[inline(always)]
+            pub unsafe fn #fn_name(&mut self #index_param, func: impl FnOnce(&mut #struct_name)) {
+                use hal_macros::{VolatileRead, VolatileWrite};
+                let read: #register_type = #handle.read();
+                let mut fields = #struct_name {
+                    #(#reads,)*
+                };
+                func(&mut fields);
+
+                let mut write: #register_type = read & (<Self>::#set_mask as #register_type);
+                #(#writes)*
+                #handle.write(write);
+            }
+        });
+    }
+
+    (quote!(#(#structs)*), quote!(#(#functions)*))
+}
+
 fn generate_set_masks(bit: &[BitBlock]) -> proc_macro2::TokenStream {
-    let mut bit_map: HashMap<String, u32> = HashMap::new();
+    let mut bit_map: HashMap<String, (u64, RegisterWidth)> = HashMap::new();
 
     for b in bit.iter() {
         let key = b.bit_attr.register_name.to_string();
@@ -347,7 +1386,7 @@ fn generate_set_masks(bit: &[BitBlock]) -> proc_macro2::TokenStream {
                         BitRange::Range(range) => {
                             let (start, end) = get_real_range(range);
 
-                            let mut mask: u32 = 1;
+                            let mut mask: u64 = 1;
                             for _ in 0..(end - start) {
                                 mask <<= 1;
                                 mask |= 1;
@@ -355,27 +1394,32 @@ fn generate_set_masks(bit: &[BitBlock]) -> proc_macro2::TokenStream {
 
                             mask << start
                         }
-                        BitRange::Single(single) => 1 << (single as u32),
+                        BitRange::Single(single) => 1 << (single as u64),
                     }
                 };
 
-                if let Some(bit) = bit_map.get_mut(&key) {
-                    *bit |= bit_or_mask;
-                } else {
-                    bit_map.insert(key, bit_or_mask);
-                }
+                bit_map.entry(key).or_insert((0, b.width)).0 |= bit_or_mask;
             }
             _ => {
-                bit_map.entry(key).or_insert(0);
+                bit_map.entry(key).or_insert((0, b.width));
             }
         }
     }
 
     let set_mask_collection: Vec<proc_macro2::TokenStream> = bit_map
         .into_iter()
-        .map(|(key, value)| {
+        .map(|(key, (value, width))| {
             let const_name = format!("{key}_SET_MASK");
-            generate_const(const_name.as_str(), !value as usize, quote!(#[doc(hidden)]))
+            let set_mask = match width {
+                RegisterWidth::U32 => !(value as u32) as u64,
+                RegisterWidth::U64 => !value,
+            };
+            generate_const(
+                const_name.as_str(),
+                set_mask,
+                width.mask_type(),
+                quote!(#[doc(hidden)]),
+            )
         })
         .collect();
 
@@ -435,13 +1479,15 @@ fn string_into_title(name: &str) -> proc_macro2::TokenStream {
 
 fn generate_const(
     name: &str,
-    value: usize,
+    value: u64,
+    const_type: proc_macro2::TokenStream,
     docs: proc_macro2::TokenStream,
 ) -> proc_macro2::TokenStream {
     let name_const = name.to_uppercase().replace(' ', "_");
     let name_tokens = format_ident!("{}", name_const);
     let doc_title = string_into_title(name);
-    let doc_example_hidden = format!("# const {}: usize = {};", name_const, value);
+    let doc_example_hidden = format!("# const {}: {} = {};", name_const, const_type, value);
+    let value = proc_macro2::Literal::u64_unsuffixed(value);
     let doc_example_let = format!(" let my_const = Registers::{};", name_const);
     let doc_example_assert = format!(" assert_eq!(my_const, {});", value);
     quote!(
@@ -465,7 +1511,7 @@ fn generate_const(
         #[doc = #doc_example_let]
         #[doc = #doc_example_assert]
         /// ```
-        pub const #name_tokens: usize = #value;
+        pub const #name_tokens: #const_type = #value;
     )
 }
 
@@ -477,7 +1523,7 @@ fn min_type_for_range((start, end): (usize, usize)) -> proc_macro2::TokenStream
         8..=15 => quote!(u16),
         16..=31 => quote!(u32),
         32..=63 => quote!(u64),
-        _ => todo!("Out of range for bit range"),
+        _ => unreachable!("Bit ranges are checked to end at or below bit 63"),
     }
 }
 
@@ -488,7 +1534,8 @@ fn generate_range_get(
 ) -> proc_macro2::TokenStream {
     let name = format_ident!("{}", name.to_lowercase().replace(' ', "_"));
     let bit_type = min_type_for_range((start, end));
-    let self_dot = format_ident!("{}", bit.bit_attr.register_name);
+    let register_type = bit.width.tokens();
+    let (index_param, _, handle) = register_handle(bit, IndexCheck::Always);
     let const_name = bit.name.to_string().to_uppercase().replace(' ', "_");
     let self_mask = format_ident!("{}_BIT_MASK", const_name);
     let self_shift = format_ident!("{}_BIT_START", const_name);
@@ -511,18 +1558,97 @@ fn generate_range_get(
         /// the value and extracts the bits to return the result.
         ///
         #[inline(always)]
-        pub fn #name(&self) -> #bit_type {
+        pub fn #name(&self #index_param) -> #bit_type {
+            use hal_macros::VolatileRead;
+            ((#handle.read() & (<Self>::#self_mask as #register_type)) >> <Self>::#self_shift) as #bit_type
+        }
+    }
+}
+
+fn generate_range_enum_get(
+    name: &str,
+    bit: &BitBlock,
+    enum_type: &Path,
+    (start, end): (usize, usize),
+) -> proc_macro2::TokenStream {
+    let name = format_ident!("{}", name.to_lowercase().replace(' ', "_"));
+    let unchecked_name = format_ident!("{}_unchecked", name);
+    let bit_type = min_type_for_range((start, end));
+    let register_type = bit.width.tokens();
+    let (index_param, index_arg, handle) = register_handle(bit, IndexCheck::Always);
+    let const_name = bit.name.to_string().to_uppercase().replace(' ', "_");
+    let self_mask = format_ident!("{}_BIT_MASK", const_name);
+    let self_shift = format_ident!("{}_BIT_START", const_name);
+    let doc_title = string_into_title(name.to_string().as_str());
+    let unchecked_doc_title = string_into_title(unchecked_name.to_string().as_str());
+    let doc = generate_doc_strings(&bit.doc_attr);
+    let enum_name = quote!(#enum_type).to_string().replace(' ', "");
+    let try_from_doc = format!(
+        " Gets the value range from the given register, converted to `{enum_name}` with its `TryFrom<{bit_type}>`."
+    );
+    let unchecked_doc = format!(
+        " Gets the value range from the given register as a `{enum_name}`, without checking"
+    );
+    quote! {
+        #doc_title
+        #doc
+        ///
+        /// # Get
+        #[doc = #try_from_doc]
+        /// Returns the conversion's error if the register holds a value with no
+        /// variant, such as a reserved value.
+        ///
+        /// # Safety
+        /// It is ultimately up to the caller to ensure this function will
+        /// never cause any side effects. However, usually reading from
+        /// registers does not modify any processor state (just looks at it).
+        ///
+        /// # Volatile
+        /// This function only preforms **1** volatile *read* and immediately copies
+        /// the value and extracts the bits to return the result.
+        ///
+        #[inline(always)]
+        pub fn #name(&self #index_param) -> core::result::Result<#enum_type, <#enum_type as TryFrom<#bit_type>>::Error> {
             use hal_macros::VolatileRead;
-            (((self.#self_dot.read() as usize) & <Self>::#self_mask) >> <Self>::#self_shift) as #bit_type
+            let raw = ((#handle.read() & (<Self>::#self_mask as #register_type)) >> <Self>::#self_shift) as #bit_type;
+            <#enum_type as TryFrom<#bit_type>>::try_from(raw)
+        }
+
+        #unchecked_doc_title
+        #doc
+        ///
+        /// # Get
+        #[doc = #unchecked_doc]
+        /// that the register holds a valid variant.
+        ///
+        /// # Safety
+        /// The caller must ensure the register holds a value with a variant, for
+        /// example because it was last written with the matching setter. Reading
+        /// an invalid value is undefined behavior in release, and **only** panics
+        /// when `debug_assertions` are enabled.
+        ///
+        /// # Volatile
+        /// This function only preforms **1** volatile *read* and immediately copies
+        /// the value and extracts the bits to return the result.
+        ///
+        #[inline(always)]
+        pub unsafe fn #unchecked_name(&self #index_param) -> #enum_type {
+            let value = self.#name(#index_arg);
+            debug_assert!(value.is_ok(), "Register field {}..={} holds a value with no `{}` variant!", #start, #end, #enum_name);
+            match value {
+                Ok(value) => value,
+                Err(_) => core::hint::unreachable_unchecked(),
+            }
         }
     }
 }
 
 fn generate_single_get(name: &str, bit: &BitBlock) -> proc_macro2::TokenStream {
     let name = format_ident!("{}", name.to_lowercase().replace(' ', "_"));
-    let self_dot = format_ident!("{}", bit.bit_attr.register_name);
+    let (index_param, _, handle) = register_handle(bit, IndexCheck::Always);
     let const_name = bit.name.to_string().to_uppercase().replace(' ', "_");
     let self_shift = format_ident!("{}_BIT", const_name);
+    let one = bit.width.one();
     let doc_title = string_into_title(name.to_string().as_str());
     let doc = generate_doc_strings(&bit.doc_attr);
     quote! {
@@ -542,16 +1668,44 @@ fn generate_single_get(name: &str, bit: &BitBlock) -> proc_macro2::TokenStream {
         /// the value and extracts the bits to return the result.
         ///
         #[inline(always)]
-        pub fn #name(&self) -> bool {
+        pub fn #name(&self #index_param) -> bool {
             use hal_macros::VolatileRead;
-            (self.#self_dot.read() & (1u32 << <Self>::#self_shift)) != 0
+            (#handle.read() & (#one << <Self>::#self_shift)) != 0
+        }
+    }
+}
+
+fn generate_single_wait(bit: &BitBlock, getter_name: &str) -> proc_macro2::TokenStream {
+    let name = format_ident!(
+        "wait_{}",
+        bit.name.to_string().to_lowercase().replace(' ', "_")
+    );
+    let getter = format_ident!("{}", getter_name.to_lowercase().replace(' ', "_"));
+    let (index_param, index_arg, _) = register_handle(bit, IndexCheck::Always);
+    let doc_title = string_into_title(name.to_string().as_str());
+    quote! {
+        #doc_title
+        /// Spin until this bit reads as `desired`, reading it at most `max_spins`
+        /// times.
+        ///
+        /// # Errors
+        /// Returns `ErrorKind::TimeOut` if the bit never reads as `desired` within
+        /// the spin budget.
+        ///
+        #[inline]
+        pub fn #name(&self #index_param, desired: bool, max_spins: u32) -> core::result::Result<(), crate::error::ErrorKind> {
+            if hal_macros::spin_until(max_spins, || self.#getter(#index_arg) == desired) {
+                Ok(())
+            } else {
+                Err(crate::error::ErrorKind::TimeOut)
+            }
         }
     }
 }
 
 fn generate_single_set(name: &str, bit: &BitBlock, only_gen_one: bool) -> proc_macro2::TokenStream {
     let name = format_ident!("{}", name.to_lowercase().replace(' ', "_"));
-    let self_dot = format_ident!("{}", bit.bit_attr.register_name);
+    let (index_param, _, handle) = register_handle(bit, IndexCheck::Debug);
     let const_name = bit.name.to_string().to_uppercase().replace(' ', "_");
     let self_shift = format_ident!("{}_BIT", const_name);
     let doc_title = string_into_title(name.to_string().as_str());
@@ -563,6 +1717,8 @@ fn generate_single_set(name: &str, bit: &BitBlock, only_gen_one: bool) -> proc_m
         .to_uppercase()
         .replace(' ', "_");
     let self_mask = format_ident!("{}_SET_MASK", reg_const_name);
+    let register_type = bit.width.tokens();
+    let one = bit.width.one();
 
     let param = if only_gen_one {
         quote!()
@@ -601,16 +1757,61 @@ fn generate_single_set(name: &str, bit: &BitBlock, only_gen_one: bool) -> proc_m
         /// the internal provided function to register.
         ///
         #[inline(always)]
-        pub unsafe fn #name(&mut self #param) {
+        pub unsafe fn #name(&mut self #index_param #param) {
             use hal_macros::{VolatileRead, VolatileWrite};
-            let read_value: u32 = self.#self_dot.read() & (<Self>::#self_mask as u32);
-            let flag_value: u32 = 1 << (<Self>::#self_shift as u32);
+            let read_value: #register_type = #handle.read() & (<Self>::#self_mask as #register_type);
+            let flag_value: #register_type = #one << <Self>::#self_shift;
             let write = if #flag_or_true {
                 read_value | flag_value
             } else {
                 read_value & !flag_value
             };
-            self.#self_dot.write(write);
+            #handle.write(write);
+        }
+    }
+}
+
+fn generate_single_toggle(name: &str, bit: &BitBlock) -> proc_macro2::TokenStream {
+    let name = format_ident!("{}", name.to_lowercase().replace(' ', "_"));
+    let (index_param, _, handle) = register_handle(bit, IndexCheck::Debug);
+    let const_name = bit.name.to_string().to_uppercase().replace(' ', "_");
+    let self_shift = format_ident!("{}_BIT", const_name);
+    let doc_title = string_into_title(name.to_string().as_str());
+    let doc = generate_doc_strings(&bit.doc_attr);
+    let reg_const_name = bit
+        .bit_attr
+        .register_name
+        .to_string()
+        .to_uppercase()
+        .replace(' ', "_");
+    let self_mask = format_ident!("{}_SET_MASK", reg_const_name);
+    let register_type = bit.width.tokens();
+    let one = bit.width.one();
+
+    quote! {
+        #doc_title
+        #doc
+        ///
+        /// # Toggle
+        /// Flip the bit in the given register, leaving every other bit as it was
+        /// and writing `0` to any `RW1C` or `RW1O` bit that shares the register.
+        ///
+        /// # Safety
+        /// The same as the matching `set_` function, it is up to the caller to
+        /// verify that this register write will not cause any side effects.
+        ///
+        /// # Volatile
+        /// This function only preforms **1** volatile *read*,
+        /// immediately flips the flag and does **1** volatile *write* using
+        /// the internal provided function to register.
+        ///
+        #[inline(always)]
+        pub unsafe fn #name(&mut self #index_param) {
+            use hal_macros::{VolatileRead, VolatileWrite};
+            let read_value: #register_type = #handle.read();
+            let flag_value: #register_type = #one << <Self>::#self_shift;
+            #handle
+                .write((read_value ^ flag_value) & (<Self>::#self_mask as #register_type));
         }
     }
 }
@@ -622,15 +1823,22 @@ fn generate_range_set(
 ) -> proc_macro2::TokenStream {
     let name = format_ident!("{}", name.to_lowercase().replace(' ', "_"));
     let bit_type = min_type_for_range((start, end));
-    let self_dot = format_ident!("{}", bit.bit_attr.register_name);
+    let (index_param, _, handle) = register_handle(bit, IndexCheck::Debug);
     let const_name = bit.name.to_string().to_uppercase().replace(' ', "_");
     let self_mask = format_ident!("{}_BIT_MASK", const_name);
     let self_shift = format_ident!("{}_BIT_START", const_name);
     let self_end = format_ident!("{}_BIT_END", const_name);
     let const_reg_name = bit.bit_attr.register_name.to_uppercase().replace(' ', "_");
     let self_set_mask = format_ident!("{}_SET_MASK", const_reg_name);
+    let register_type = bit.width.tokens();
     let doc_title = string_into_title(name.to_string().as_str());
     let doc = generate_doc_strings(&bit.doc_attr);
+    // Enum fields are written as the enum's discriminant, so the enum must be
+    // fieldless.
+    let (flag_type, flag_conversion) = match &bit.bit_attr.enum_type {
+        Some(enum_type) => (quote!(#enum_type), quote!(let flag = flag as #bit_type;)),
+        None => (quote!(#bit_type), quote!()),
+    };
     quote! {
         #doc_title
         #doc
@@ -665,12 +1873,13 @@ fn generate_range_set(
         /// register.
         ///
         #[inline(always)]
-        pub unsafe fn #name(&mut self, flag: #bit_type) {
+        pub unsafe fn #name(&mut self #index_param, flag: #flag_type) {
             use hal_macros::{VolatileRead, VolatileWrite};
-            debug_assert!((flag as usize) >> ((<Self>::#self_end) - <Self>::#self_shift) <= 1, "Provided flag {flag} is too large for provided setter range {}..={}!", #start, #end);
-            let flag_shift: u32 = (flag as u32) << (<Self>::#self_shift as u32);
-            let read_value: u32 = self.#self_dot.read() & (!<Self>::#self_mask as u32) & (<Self>::#self_set_mask as u32);
-            self.#self_dot.write(read_value | flag_shift);
+            #flag_conversion
+            debug_assert!((flag as #register_type) >> ((<Self>::#self_end) - <Self>::#self_shift) <= 1, "Provided flag {flag} is too large for provided setter range {}..={}!", #start, #end);
+            let flag_shift: #register_type = (flag as #register_type) << <Self>::#self_shift;
+            let read_value: #register_type = #handle.read() & !(<Self>::#self_mask as #register_type) & (<Self>::#self_set_mask as #register_type);
+            #handle.write(read_value | flag_shift);
         }
     }
 }
@@ -689,7 +1898,7 @@ fn generate_bit_range(
 
     let doc_string = generate_doc_strings(&bit.doc_attr);
 
-    let mut mask: u32 = 1;
+    let mut mask: u64 = 1;
     for _ in 0..(end - start) {
         mask <<= 1;
         mask |= 1;
@@ -698,14 +1907,21 @@ fn generate_bit_range(
 
     let const_start = generate_const(
         &format!("{}_BIT_START", bit.name),
-        start,
+        start as u64,
+        quote!(usize),
+        doc_string.clone(),
+    );
+    let const_end = generate_const(
+        &format!("{}_BIT_END", bit.name),
+        end as u64,
+        quote!(usize),
         doc_string.clone(),
     );
-    let const_end = generate_const(&format!("{}_BIT_END", bit.name), end, doc_string.clone());
 
     let const_mask = generate_const(
         &format!("{}_BIT_MASK", bit.name),
-        mask as usize,
+        mask,
+        bit.width.mask_type(),
         doc_string.clone(),
     );
 
@@ -715,10 +1931,15 @@ fn generate_bit_range(
         _ => (true, true),
     };
 
-    let getter = if read {
-        generate_range_get(format!("get_{}", bit.name).as_str(), bit, (start, end))
-    } else {
-        quote!()
+    let getter = match (read, &bit.bit_attr.enum_type) {
+        (false, _) => quote!(),
+        (true, Some(enum_type)) => generate_range_enum_get(
+            format!("get_{}", bit.name).as_str(),
+            bit,
+            enum_type,
+            (start, end),
+        ),
+        (true, None) => generate_range_get(format!("get_{}", bit.name).as_str(), bit, (start, end)),
     };
     let setter = if write {
         generate_range_set(format!("set_{}", bit.name).as_str(), bit, (start, end))
@@ -745,7 +1966,12 @@ fn generate_bit_single(single: usize, bit: &BitBlock) -> proc_macro2::TokenStrea
         _ => ("set", false, "get_", ""),
     };
 
-    let const_start = generate_const(format!("{}_BIT", bit.name).as_str(), single, doc_string);
+    let const_start = generate_const(
+        format!("{}_BIT", bit.name).as_str(),
+        single as u64,
+        quote!(usize),
+        doc_string,
+    );
 
     let (write, read) = match bit.bit_attr.access {
         Access::RO => (false, true),
@@ -754,10 +1980,15 @@ fn generate_bit_single(single: usize, bit: &BitBlock) -> proc_macro2::TokenStrea
     };
 
     let getter = if read {
-        generate_single_get(
-            format!("{}{}{}", getter_start, bit.name, getter_name).as_str(),
-            bit,
-        )
+        let getter_name = format!("{}{}{}", getter_start, bit.name, getter_name);
+        let get = generate_single_get(getter_name.as_str(), bit);
+        // Only status and flag bits change by themselves, so only they can be
+        // waited on.
+        let wait = match bit.bit_attr.access {
+            Access::RO | Access::RW1C => generate_single_wait(bit, getter_name.as_str()),
+            _ => quote!(),
+        };
+        quote!(#get #wait)
     } else {
         quote!()
     };
@@ -770,33 +2001,44 @@ fn generate_bit_single(single: usize, bit: &BitBlock) -> proc_macro2::TokenStrea
     } else {
         quote!()
     };
+    let toggle = if matches!(bit.bit_attr.access, Access::RW) {
+        generate_single_toggle(format!("toggle_{}", bit.name).as_str(), bit)
+    } else {
+        quote!()
+    };
     quote!(
         #const_start
 
         #getter
         #setter
+        #toggle
     )
 }
 
-fn generate_reg_fields(all_register_names: &[(String, Path)]) -> Vec<(Ident, Path)> {
+fn generate_reg_fields(
+    all_register_names: &[(String, Path, RegisterWidth)],
+) -> Vec<(Ident, Path, RegisterWidth)> {
     let mut register_map = HashMap::new();
 
-    for (str, path) in all_register_names.iter() {
+    for (str, path, width) in all_register_names.iter() {
         if !register_map.contains_key(str) {
-            register_map.insert(str, path);
+            register_map.insert(str, (path, *width));
         }
     }
 
     register_map
         .into_iter()
-        .map(|(str, path)| (format_ident!("{}", str), path.clone()))
+        .map(|(str, (path, width))| (format_ident!("{}", str), path.clone(), width))
         .collect()
 }
 
-fn generate_reg_struct(reg_names: &[(Ident, Path)]) -> proc_macro2::TokenStream {
+fn generate_reg_struct(reg_names: &[(Ident, Path, RegisterWidth)]) -> proc_macro2::TokenStream {
     let properties: Vec<proc_macro2::TokenStream> = reg_names
         .iter()
-        .map(|(ident, path)| quote!(#ident: RW<{#path}, u32>))
+        .map(|(ident, path, width)| {
+            let width = width.tokens();
+            quote!(#ident: RW<{#path}, #width>)
+        })
         .collect();
 
     quote! {
@@ -804,19 +2046,36 @@ fn generate_reg_struct(reg_names: &[(Ident, Path)]) -> proc_macro2::TokenStream
         /// This struct was generated with the `make_device!` macro! This struct
         /// represents some hardware device expressed with `#[bit(...)]` attributes.
         ///
+        /// The generated `wait_<field>` reports errors with
+        /// `crate::error::ErrorKind::TimeOut`, so the crate using `make_device!` must
+        /// provide that error type.
+        ///
+        /// Registers are accessed as `u32`, unless one of their fields uses a bit
+        /// above 31 (such as `0..=63`), which makes that register a `u64` with
+        /// `u64` masks. A `u64` register must be 8 byte aligned.
+        ///
         /// # Example Of Registers
         /// ```rust
         /// use hal_macros_derive::make_device;
         /// use hal_macros::RW;
+        /// # mod error { pub enum ErrorKind { NullPtr, TimeOut } }
+        /// # fn main() {}
         ///
         /// const MY_DEVICE_PORT0: usize = 0xdeadbeef;
         /// const MY_DEVICE_PORT1: usize = 0xbadbabe3;
         ///
         /// const MY_REGISTER_OFFSET: usize = 0x0000;
+        /// const MY_FIFO_OFFSET: usize = 0x0004;
         ///
         /// make_device! {
         ///    device_ports(MY_DEVICE_PORT0, MY_DEVICE_PORT1);
+        ///    // Optional, checks at compile time that every register is inside
+        ///    // the first 0x400 bytes of each port.
+        ///    window_size(0x400);
         ///
+        ///    // The register's reset value can be given on any one of its fields,
+        ///    // which generates `MY_REGISTER_OFFSET_RESET` and `reset_my_register_offset`.
+        ///    #[reset(0x0000_0002)]
         ///    #[bit(0, RW, MY_REGISTER_OFFSET)]
         ///    my_reg_field,
         ///
@@ -825,6 +2084,31 @@ fn generate_reg_struct(reg_names: &[(Ident, Path)]) -> proc_macro2::TokenStream
         ///
         ///    #[bit(2..=10, WO, MY_REGISTER_OFFSET)]
         ///    my_reg_range_write_only,
+        ///
+        ///    // Reading a FIFO pops it, so keep it out of `fmt_registers`.
+        ///    #[no_dump]
+        ///    #[bit(0..=7, RO, MY_FIFO_OFFSET)]
+        ///    my_fifo,
+        ///
+        ///    #[bit(11..=12, RW, MY_REGISTER_OFFSET, MyMode)]
+        ///    my_reg_mode,
+        /// }
+        ///
+        /// // A field given an enum is set with the enum, and read back through
+        /// // `TryFrom` the field's integer type (`u8` for fields up to 8 bits).
+        /// #[repr(u8)]
+        /// #[derive(Clone, Copy)]
+        /// pub enum MyMode { Slow = 0, Fast = 1 }
+        ///
+        /// impl TryFrom<u8> for MyMode {
+        ///     type Error = error::ErrorKind;
+        ///     fn try_from(value: u8) -> Result<Self, Self::Error> {
+        ///         match value {
+        ///             0 => Ok(Self::Slow),
+        ///             1 => Ok(Self::Fast),
+        ///             _ => Err(error::ErrorKind::NullPtr),
+        ///         }
+        ///     }
         /// }
         ///
         /// ```
@@ -18,14 +18,55 @@ pub trait VolatileWrap<T: Sized> {
 pub trait VolatileRead<T>: VolatileWrap<T> {
     #[inline(always)]
     fn read(&self) -> T {
-        self.action_immutable(|ptr| unsafe { core::ptr::read_volatile(ptr) })
+        self.action_immutable(|ptr| {
+            #[cfg(feature = "access-log")]
+            access_log::record(access_log::Access::Read(ptr as usize));
+            unsafe { core::ptr::read_volatile(ptr) }
+        })
     }
 }
 
 pub trait VolatileWrite<T>: VolatileWrap<T> {
     #[inline(always)]
     fn write(&mut self, value: T) {
-        self.action_mutable(|ptr| unsafe { core::ptr::write_volatile(ptr, value) })
+        self.action_mutable(|ptr| {
+            #[cfg(feature = "access-log")]
+            access_log::record(access_log::Access::Write(ptr as usize));
+            unsafe { core::ptr::write_volatile(ptr, value) }
+        })
+    }
+}
+
+/// # Access Log
+/// With the `access-log` feature every `read` and `write` of a register is
+/// reported to the hook set with `set_hook`, so tests can check exactly which
+/// accesses generated code makes.
+#[cfg(feature = "access-log")]
+pub mod access_log {
+    use core::sync::atomic::{AtomicPtr, Ordering};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Access {
+        Read(usize),
+        Write(usize),
+    }
+
+    static HOOK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+    /// # Set Hook
+    /// Report every register access to `hook`, or stop reporting with `None`.
+    pub fn set_hook(hook: Option<fn(Access)>) {
+        let hook = hook.map_or(core::ptr::null_mut(), |hook| hook as *mut ());
+        HOOK.store(hook, Ordering::SeqCst);
+    }
+
+    pub(crate) fn record(access: Access) {
+        let hook = HOOK.load(Ordering::SeqCst);
+        if !hook.is_null() {
+            // Only `set_hook` stores to `HOOK`, and it only stores `fn(Access)`s.
+            let hook: fn(Access) = unsafe { core::mem::transmute(hook) };
+            hook(access);
+        }
     }
 }
 
@@ -106,3 +147,98 @@ impl<T: Sized + Copy, const OFFSET: usize> RW<OFFSET, T> {
         Some(Self(NonNull::new((base_ptr + OFFSET) as *mut T)?))
     }
 }
+
+/// # Spin Until
+/// Check `condition` at most `max_spins` times, returning `true` as soon as it
+/// holds or `false` if the budget runs out. Used by the `wait_<field>` functions
+/// of `make_device!`.
+#[inline]
+pub fn spin_until(max_spins: u32, mut condition: impl FnMut() -> bool) -> bool {
+    for _ in 0..max_spins {
+        if condition() {
+            return true;
+        }
+        core::hint::spin_loop();
+    }
+
+    false
+}
+
+/// # Register Field
+/// The name and bit range of one field of a register, generated by `make_device!`.
+pub struct RegisterField {
+    pub name: &'static str,
+    pub start: u8,
+    pub end: u8,
+}
+
+impl RegisterField {
+    /// Extract this field from the raw register `value`.
+    pub fn value<T: RegisterValue>(&self, value: T) -> T {
+        value.bits(self.start, self.end)
+    }
+}
+
+/// # Register Value
+/// The raw value of a register, a `u32` for most registers or a `u64` for those
+/// with fields above bit 31.
+pub trait RegisterValue: Copy + core::fmt::LowerHex {
+    /// The bits `start..=end` of this value, shifted down to bit 0.
+    fn bits(self, start: u8, end: u8) -> Self;
+
+    fn is_zero(self) -> bool;
+}
+
+macro_rules! register_value {
+    ($($ty:ty),*) => {$(
+        impl RegisterValue for $ty {
+            fn bits(self, start: u8, end: u8) -> Self {
+                let width = (end - start + 1) as u32;
+                let mask = if width >= <$ty>::BITS {
+                    <$ty>::MAX
+                } else {
+                    (1 << width) - 1
+                };
+
+                (self >> start) & mask
+            }
+
+            fn is_zero(self) -> bool {
+                self == 0
+            }
+        }
+    )*};
+}
+
+register_value!(u32, u64);
+
+/// # Register Decode
+/// A raw register value broken down into its named fields, made by the
+/// `decode_<register>` functions of `make_device!`. The `Debug` output looks like
+/// `I2C_CTRL=0x00000041 { i2c_peripheral_enable: true, master_mode_enable: false }`,
+/// with single bit fields shown as `bool` and wider fields in hex.
+pub struct RegisterDecode<T: RegisterValue = u32> {
+    pub name: &'static str,
+    /// The raw register value, as wide as the register.
+    pub value: T,
+    pub fields: &'static [RegisterField],
+}
+
+impl<T: RegisterValue> core::fmt::Debug for RegisterDecode<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}=0x{:08x} {{", self.name, self.value)?;
+
+        for (i, field) in self.fields.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            let value = field.value(self.value);
+
+            if field.start == field.end {
+                write!(f, "{}{}: {}", separator, field.name, !value.is_zero())?;
+            } else {
+                write!(f, "{}{}: 0x{:x}", separator, field.name, value)?;
+            }
+        }
+
+        f.write_str(" }")
+    }
+}
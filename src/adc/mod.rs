@@ -0,0 +1,326 @@
+use crate::error::ErrorKind;
+use crate::gcr::{peripheral_reset, set_adc_clock_divider, system_clock_enable};
+use crate::gcr::{AdcClockDiv, HardwareSource};
+use crate::gpio::{AnalogPin, GpioSelect};
+use crate::memory_map::mmio;
+use core::marker::PhantomData;
+use registers::Registers;
+
+pub mod registers;
+
+mod private {
+    pub trait AdcChannelCompatable {
+        const CHANNEL: u8;
+        const PIN: usize;
+    }
+}
+
+/// The largest value a conversion can produce.
+pub const ADC_MAX_VALUE: u16 = 1023;
+
+/// The internal ADC reference voltage in millivolts.
+pub const INTERNAL_REFERENCE_MV: u32 = 1220;
+
+/// How many times `ADC::read_channel` checks for the end of a conversion before
+/// giving up.
+pub const CONVERSION_SPINS: u32 = 100_000;
+
+/// How many times `ADC::init` checks for the ADC to power up before giving up.
+const POWER_UP_SPINS: u32 = 100_000;
+
+/// # Reference
+/// The voltage conversions are measured against, `ADC_MAX_VALUE` being a sample
+/// at the reference.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reference {
+    /// The internal bandgap, `INTERNAL_REFERENCE_MV`. This is what `ADC::init`
+    /// selects, and it does not move with the supply.
+    Internal,
+    /// Half of the analog supply, VDDA / 2. Use this to measure inputs relative
+    /// to the supply (like a potentiometer across it), where the supply level
+    /// cancels out.
+    HalfVdda,
+}
+
+impl From<Reference> for bool {
+    fn from(reference: Reference) -> Self {
+        matches!(reference, Reference::HalfVdda)
+    }
+}
+
+pub struct AIN0 {}
+pub struct AIN1 {}
+pub struct AIN2 {}
+pub struct AIN3 {}
+pub struct AIN4 {}
+pub struct AIN5 {}
+pub struct AIN6 {}
+pub struct AIN7 {}
+
+impl private::AdcChannelCompatable for AIN0 {
+    const CHANNEL: u8 = 0;
+    const PIN: usize = 0;
+}
+impl private::AdcChannelCompatable for AIN1 {
+    const CHANNEL: u8 = 1;
+    const PIN: usize = 1;
+}
+impl private::AdcChannelCompatable for AIN2 {
+    const CHANNEL: u8 = 2;
+    const PIN: usize = 2;
+}
+impl private::AdcChannelCompatable for AIN3 {
+    const CHANNEL: u8 = 3;
+    const PIN: usize = 3;
+}
+impl private::AdcChannelCompatable for AIN4 {
+    const CHANNEL: u8 = 4;
+    const PIN: usize = 4;
+}
+impl private::AdcChannelCompatable for AIN5 {
+    const CHANNEL: u8 = 5;
+    const PIN: usize = 5;
+}
+impl private::AdcChannelCompatable for AIN6 {
+    const CHANNEL: u8 = 6;
+    const PIN: usize = 6;
+}
+impl private::AdcChannelCompatable for AIN7 {
+    const CHANNEL: u8 = 7;
+    const PIN: usize = 7;
+}
+
+/// # ADC Channel
+/// One of the external ADC inputs `AIN0` to `AIN7`, tied to the GPIO2 pin
+/// that carries it.
+pub struct Channel<Input> {
+    pin: AnalogPin,
+    _ph: PhantomData<Input>,
+}
+
+impl<Input: private::AdcChannelCompatable> Channel<Input> {
+    /// # New
+    /// Tie `pin` to this ADC input. The pin must be the GPIO2 pin with the same
+    /// number as the input (`AIN0` is `P2.0`, and so on), otherwise this returns
+    /// `ErrorKind::BadParam`.
+    pub fn new(pin: AnalogPin) -> Result<Self, ErrorKind> {
+        if !matches!(pin.get_port(), GpioSelect::Gpio2) || pin.get_pin() != Input::PIN {
+            return Err(ErrorKind::BadParam);
+        }
+
+        Ok(Self {
+            pin,
+            _ph: PhantomData,
+        })
+    }
+
+    /// # Release
+    /// Give back the analog pin this channel was using.
+    pub fn release(self) -> AnalogPin {
+        self.pin
+    }
+}
+
+/// # ADC
+/// A one-shot driver for the ADC.
+pub struct ADC {
+    reg: Registers,
+    converting: Option<u8>,
+}
+
+impl ADC {
+    /// # Init
+    /// Reset and power up the ADC, clocking it at PCLK divided by `clock_div`. The
+    /// ADC clock must be in range for the converter to produce accurate results.
+    /// Should never be initialized more than once.
+    ///
+    /// Returns `ErrorKind::TimeOut` if the ADC reset does not finish, or the ADC
+    /// never reports it is powered up.
+    pub fn init(clock_div: AdcClockDiv) -> Result<Self, ErrorKind> {
+        system_clock_enable(HardwareSource::ADC, true);
+        peripheral_reset(HardwareSource::ADC)?;
+        set_adc_clock_divider(clock_div);
+
+        let mut reg = Registers::new(mmio::ADC);
+        unsafe {
+            reg.set_reference_select(false);
+            reg.set_clock_enable(true);
+            reg.set_reference_buffer_power(true);
+            reg.set_adc_power(true);
+        }
+
+        reg.wait_power_ready_flag(true, POWER_UP_SPINS)?;
+        unsafe { reg.clear_power_ready_flag() };
+
+        Ok(Self {
+            reg,
+            converting: None,
+        })
+    }
+
+    /// # Read
+    /// Read a single conversion from `channel`. The first call starts the
+    /// conversion, and every call returns `WouldBlock` while it is still in
+    /// progress. Once the conversion is done the raw value is returned.
+    ///
+    /// Only one conversion can be in progress at a time, reading a different
+    /// channel before the current one has finished returns `ErrorKind::Busy`.
+    ///
+    /// See `read_channel` to block until the sample is ready.
+    pub fn read<Input: private::AdcChannelCompatable>(
+        &mut self,
+        _channel: &mut Channel<Input>,
+    ) -> crate::nb::Result<u16, ErrorKind> {
+        match self.converting {
+            None => {
+                unsafe {
+                    self.reg.clear_conversion_done_flag();
+                    self.reg.set_channel_select(Input::CHANNEL);
+                    self.reg.set_start_conversion(true);
+                }
+                self.converting = Some(Input::CHANNEL);
+                Err(crate::nb::Error::WouldBlock)
+            }
+            Some(channel) if channel != Input::CHANNEL => {
+                Err(crate::nb::Error::Other(ErrorKind::Busy))
+            }
+            Some(_) => {
+                if self.reg.get_conversion_active() || !self.reg.is_conversion_done_flag_active() {
+                    return Err(crate::nb::Error::WouldBlock);
+                }
+
+                unsafe { self.reg.clear_conversion_done_flag() };
+                self.converting = None;
+                Ok(self.reg.get_conversion_data())
+            }
+        }
+    }
+
+    /// # Read Channel
+    /// Select `channel`, start a conversion, and wait for its sample.
+    ///
+    /// Returns `ErrorKind::Busy` if a conversion of another channel started with
+    /// `read` has not finished, and `ErrorKind::TimeOut` if the conversion is not
+    /// done after `CONVERSION_SPINS` checks.
+    pub fn read_channel<Input: private::AdcChannelCompatable>(
+        &mut self,
+        channel: &mut Channel<Input>,
+    ) -> Result<u16, ErrorKind> {
+        for _ in 0..CONVERSION_SPINS {
+            match self.read(channel) {
+                Ok(sample) => return Ok(sample),
+                Err(crate::nb::Error::Other(err)) => return Err(err),
+                Err(crate::nb::Error::WouldBlock) => core::hint::spin_loop(),
+            }
+        }
+
+        // Forget the conversion, so the next read starts a new one.
+        self.converting = None;
+        Err(ErrorKind::TimeOut)
+    }
+
+    /// # Set Reference
+    /// Select the voltage conversions are measured against. Convert samples with
+    /// `to_millivolts` and the reference picked here, `INTERNAL_REFERENCE_MV` for
+    /// `Reference::Internal` or half the analog supply for `Reference::HalfVdda`.
+    ///
+    /// Returns `ErrorKind::Busy` while a conversion is in progress.
+    pub fn set_reference(&mut self, reference: Reference) -> Result<(), ErrorKind> {
+        if self.converting.is_some() {
+            return Err(ErrorKind::Busy);
+        }
+
+        unsafe { self.reg.set_reference_select(reference.into()) };
+        Ok(())
+    }
+}
+
+/// # One Shot
+/// A single conversion on demand, in the shape of `embedded_hal::adc::OneShot`
+/// from embedded-hal 0.2, for drivers written against that interface. Only built
+/// with the `adc-oneshot` feature.
+#[cfg(feature = "adc-oneshot")]
+pub trait OneShot<Word, Pin> {
+    type Error;
+
+    /// Start or poll a conversion of `pin`, the same as `ADC::read`.
+    fn read(&mut self, pin: &mut Pin) -> crate::nb::Result<Word, Self::Error>;
+}
+
+#[cfg(feature = "adc-oneshot")]
+impl<Input: private::AdcChannelCompatable> OneShot<u16, Channel<Input>> for ADC {
+    type Error = ErrorKind;
+
+    fn read(&mut self, pin: &mut Channel<Input>) -> crate::nb::Result<u16, ErrorKind> {
+        ADC::read(self, pin)
+    }
+}
+
+/// # To Millivolts
+/// Convert a raw conversion value into millivolts, given the reference voltage
+/// the conversion was made against in millivolts. With the internal reference
+/// this is `to_millivolts(raw, INTERNAL_REFERENCE_MV)`.
+///
+/// If the input or reference was scaled by 1/2 in the ADC control register the
+/// result must be scaled by the caller.
+pub const fn to_millivolts(raw: u16, reference_mv: u32) -> u32 {
+    let raw = if raw > ADC_MAX_VALUE {
+        ADC_MAX_VALUE
+    } else {
+        raw
+    };
+
+    raw as u32 * reference_mv / ADC_MAX_VALUE as u32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::tests::FakeRegisters;
+    use registers::rro::{ADC_CTRL, ADC_DATA, ADC_STATUS};
+
+    fn fake_adc(fake_registers: &FakeRegisters<4>) -> ADC {
+        ADC {
+            reg: Registers::new(fake_registers.address()),
+            converting: None,
+        }
+    }
+
+    fn fake_channel() -> Channel<AIN3> {
+        let pin = crate::gpio::GpioPin::new(GpioSelect::Gpio2, 3).unwrap();
+        Channel::new(pin.into_analog()).unwrap()
+    }
+
+    #[test]
+    fn read_channel_test() {
+        let fake = FakeRegisters::new();
+        let mut adc = fake_adc(&fake);
+        let mut channel = fake_channel();
+
+        // Clearing the done flag on fake memory leaves it set, so the conversion
+        // reads as done straight after it starts.
+        fake.set_word(ADC_DATA, 0x155);
+        assert_eq!(adc.read_channel(&mut channel), Ok(0x155));
+        assert_eq!(fake.word(ADC_CTRL), (3 << 12) | 1);
+
+        assert_eq!(adc.set_reference(Reference::HalfVdda), Ok(()));
+        assert_eq!(fake.word(ADC_CTRL) & (1 << 4), 1 << 4);
+
+        fake.set_word(ADC_STATUS, 1);
+        assert_eq!(adc.read_channel(&mut channel), Err(ErrorKind::TimeOut));
+        assert_eq!(adc.set_reference(Reference::Internal), Ok(()));
+        assert_eq!(fake.word(ADC_CTRL) & (1 << 4), 0);
+    }
+
+    #[test]
+    fn to_millivolts_test() {
+        assert_eq!(to_millivolts(0, INTERNAL_REFERENCE_MV), 0);
+        assert_eq!(
+            to_millivolts(ADC_MAX_VALUE, INTERNAL_REFERENCE_MV),
+            INTERNAL_REFERENCE_MV
+        );
+        assert_eq!(to_millivolts(ADC_MAX_VALUE / 2, 1023), 511);
+        assert_eq!(to_millivolts(u16::MAX, 3300), 3300);
+    }
+}
@@ -0,0 +1,99 @@
+use hal_macros::RW;
+use hal_macros_derive::make_device;
+
+/// # ADC Register Offsets
+/// See Max 78000 User Guide, ADC Registers.
+pub(super) mod rro {
+    /// # ADC Control Register
+    pub const ADC_CTRL: usize = 0x0000;
+    /// # ADC Status Register
+    pub const ADC_STATUS: usize = 0x0004;
+    /// # ADC Output Data Register
+    pub const ADC_DATA: usize = 0x0008;
+    /// # ADC Interrupt Control Register
+    pub const ADC_INTR: usize = 0x000C;
+}
+
+make_device! {
+    device_ports(crate::memory_map::mmio::ADC);
+
+    /// Data Alignment.
+    ///
+    /// - 0: Conversion data is LSB aligned
+    /// - 1: Conversion data is MSB aligned
+    #[bit(20, RW, rro::ADC_CTRL)]
+    data_alignment,
+
+    /// Channel Select. Selects the input to convert on the next `START`.
+    ///
+    /// - 0-7: External analog inputs AIN0 to AIN7
+    #[bit(12..=16, RW, rro::ADC_CTRL)]
+    channel_select,
+
+    /// ADC Clock Enable. Must be set before starting a conversion.
+    #[bit(11, RW, rro::ADC_CTRL)]
+    clock_enable,
+
+    /// Input Scale. Scales the input by 1/2 when set.
+    #[bit(9, RW, rro::ADC_CTRL)]
+    input_scale,
+
+    /// Reference Scale. Scales the reference by 1/2 when set.
+    #[bit(8, RW, rro::ADC_CTRL)]
+    reference_scale,
+
+    /// Reference Select.
+    ///
+    /// - 0: Internal 1.22V reference
+    /// - 1: VDDA / 2
+    #[bit(4, RW, rro::ADC_CTRL)]
+    reference_select,
+
+    /// Reference Buffer Power.
+    #[bit(3, RW, rro::ADC_CTRL)]
+    reference_buffer_power,
+
+    /// ADC Power. Powers the analog front end of the ADC.
+    #[bit(1, RW, rro::ADC_CTRL)]
+    adc_power,
+
+    /// Start Conversion. Writing 1 starts a single conversion of the selected channel.
+    #[bit(0, RW, rro::ADC_CTRL)]
+    start_conversion,
+
+    /// Overflow. Set when the last conversion data overflowed.
+    #[bit(3, RO, rro::ADC_STATUS)]
+    overflow,
+
+    /// Analog Front End Power Up Active. Set while the ADC is still powering up.
+    #[bit(2, RO, rro::ADC_STATUS)]
+    power_up_active,
+
+    /// Conversion Active. Set while a conversion is in progress.
+    #[bit(0, RO, rro::ADC_STATUS)]
+    conversion_active,
+
+    /// Conversion Data.
+    #[bit(0..=15, RO, rro::ADC_DATA)]
+    conversion_data,
+
+    /// Power Ready Interrupt Flag.
+    #[bit(21, RW1C, rro::ADC_INTR)]
+    power_ready_flag,
+
+    /// Overflow Interrupt Flag.
+    #[bit(20, RW1C, rro::ADC_INTR)]
+    overflow_flag,
+
+    /// Reference Ready Interrupt Flag.
+    #[bit(17, RW1C, rro::ADC_INTR)]
+    reference_ready_flag,
+
+    /// Conversion Done Interrupt Flag.
+    #[bit(16, RW1C, rro::ADC_INTR)]
+    conversion_done_flag,
+
+    /// Conversion Done Interrupt Enable.
+    #[bit(0, RW, rro::ADC_INTR)]
+    conversion_done_interrupt_enable,
+}
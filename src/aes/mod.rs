@@ -1,8 +1,10 @@
 pub mod registers;
 
 use crate::{
+    error::{ErrorKind, Result},
     gcr::{peripheral_reset, system_clock_enable, HardwareSource},
     memory_map::mmio,
+    trng::TRNG,
 };
 use registers::Registers;
 
@@ -15,11 +17,26 @@ use registers::Registers;
 /// register is write only and you would have no way of storing the key in order to
 /// decrypt your data later.
 #[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CipherType {
     Encrypt = 0b_00,
     Decrypt = 0b_10,
 }
 
+/// # Cipher Mode
+/// How the blocks of a cipher operation are chained together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CipherMode {
+    /// Electronic codebook, every block is ciphered on its own. Equal plaintext
+    /// blocks give equal ciphertext blocks, so this should only be used for single
+    /// blocks or random data.
+    Ecb,
+    /// Cipher block chaining. Each plaintext block is XORed with the previous
+    /// ciphertext block (or `iv` for the first block) before it is encrypted. The
+    /// `iv` must be unpredictable, and the same one must be used to decrypt.
+    Cbc { iv: [u8; 16] },
+}
+
 /// A wrapper for an array containing an AES key. Used to allow multiple key sizes
 /// and assure they are the correct length.
 pub enum Key<'a> {
@@ -28,37 +45,85 @@ pub enum Key<'a> {
     Bits256(&'a [u8; 32]),
 }
 
+/// The length of an AES key. This enum is used to set the `Encryption Key Size`
+/// field of the AES Control Register. See Page 360-361, Table 24-4.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeySize {
+    Bits128 = 0b_00,
+    Bits192 = 0b_01,
+    Bits256 = 0b_10,
+}
+
+impl Key<'_> {
+    /// The size of this key.
+    pub fn size(&self) -> KeySize {
+        match self {
+            Key::Bits128(_) => KeySize::Bits128,
+            Key::Bits192(_) => KeySize::Bits192,
+            Key::Bits256(_) => KeySize::Bits256,
+        }
+    }
+}
+
+/// The number of bytes in a key of `size`.
+const fn key_len(size: KeySize) -> usize {
+    match size {
+        KeySize::Bits128 => 16,
+        KeySize::Bits192 => 24,
+        KeySize::Bits256 => 32,
+    }
+}
+
+/// Overwrite `bytes` with zeros using volatile writes, so the compiler cannot drop
+/// the wipe of a buffer that is never read again.
+fn zeroize(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        unsafe { core::ptr::write_volatile(byte, 0) };
+    }
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
+
 /// A wrapper around the AES register. Used to allow the borrow checker to keep
 /// track of who can mutate the state of AES.
 pub struct AES {
     registers: Registers,
+    key_size: KeySize,
 }
 
 impl AES {
     /// Initializes a new instance of AES. Should never be called more than once.
-    pub fn init() -> Self {
-        peripheral_reset(HardwareSource::AES);
+    ///
+    /// Returns `ErrorKind::TimeOut` if the AES reset does not finish.
+    pub fn init() -> Result<Self> {
+        peripheral_reset(HardwareSource::AES)?;
         system_clock_enable(HardwareSource::AES, true);
-        Self {
+        Ok(Self {
             registers: Registers::new(mmio::AES),
-        }
+            key_size: KeySize::Bits128,
+        })
     }
 
     /// Writes the given key to the beginning of the AES keys register. Before setting
     /// the key it will wipe all 1024 bytes of the register and after setting the key
     /// it will run a dummy encryption to assure that the first decryption will always work.
+    /// The key size of the control register is set to match the key, and is kept for
+    /// every cipher operation until the next key is set.
     pub fn set_key(&mut self, key: &Key) {
-        let (key_ptr, key_len) = match key {
-            Key::Bits128(key) => (key.as_ptr(), 16),
-            Key::Bits192(key) => (key.as_ptr(), 24),
-            Key::Bits256(key) => (key.as_ptr(), 32),
+        let key_bytes: &[u8] = match key {
+            Key::Bits128(key) => &key[..],
+            Key::Bits192(key) => &key[..],
+            Key::Bits256(key) => &key[..],
         };
+        self.wipe_key();
+        self.key_size = key.size();
+        unsafe { self.registers.set_encryption_key_size(self.key_size as u8) };
         #[cfg(not(test))]
         unsafe {
-            for i in 0..256 {
-                core::ptr::write_volatile((mmio::AES_KEYS + (i * 4)) as *mut u32, 0u32);
+            let mut keys = registers::keys::Registers::new(mmio::AES_KEYS);
+            for (index, word) in key_bytes.chunks_exact(4).enumerate() {
+                keys.set_aes_key(index, u32::from_le_bytes(word.try_into().unwrap()));
             }
-            core::ptr::copy_nonoverlapping(key_ptr, mmio::AES_KEYS as *mut u8, key_len);
             [0; 16]
                 .into_iter()
                 .cipher(self, CipherType::Encrypt)
@@ -66,7 +131,88 @@ impl AES {
         }
         #[cfg(test)]
         {
-            _ = (key_ptr, key_len);
+            _ = key_bytes;
+        }
+    }
+
+    /// Fills a key of `size` with random bytes from `trng` and loads it with `set_key`.
+    /// The key only ever lives in a stack buffer, which is zeroed once the key is in
+    /// the key register, so the caller gets a usable random key that nothing else can
+    /// read back. Like any key set here it is lost on reset, so this suits keys for
+    /// data that does not outlive the session.
+    ///
+    /// # Ordering
+    /// The TRNG runs off the AES clock, which `TRNG::init` enables without resetting
+    /// AES. Call `AES::init` before loading a key (it resets the AES block, wiping any
+    /// key), and do not disable the AES clock while either driver is in use.
+    ///
+    /// Returns `ErrorKind::TimeOut` if the TRNG does, leaving the key as it was.
+    pub fn set_key_from_trng(&mut self, trng: &mut TRNG, size: KeySize) -> Result<()> {
+        let mut buffer = [0u8; 32];
+        let len = key_len(size);
+        if let Err(err) = trng.try_fill_bytes(&mut buffer[..len]) {
+            zeroize(&mut buffer);
+            return Err(err);
+        }
+
+        match size {
+            KeySize::Bits128 => self.set_key(&Key::Bits128(buffer[..16].try_into().unwrap())),
+            KeySize::Bits192 => self.set_key(&Key::Bits192(buffer[..24].try_into().unwrap())),
+            KeySize::Bits256 => self.set_key(&Key::Bits256(&buffer)),
+        }
+
+        zeroize(&mut buffer);
+        Ok(())
+    }
+
+    /// Sets the given key once and returns a guard that can run any number of cipher
+    /// operations with it. Since `set_key` wipes the whole key register and runs a
+    /// dummy encryption, re-setting the same key for every message is expensive; the
+    /// guard only pays that cost (including the dummy encryption) once per key load.
+    /// The key register is wiped again when the guard is dropped.
+    pub fn with_key(&mut self, key: &Key) -> KeyedAes<'_> {
+        self.set_key(key);
+        KeyedAes { aes: self }
+    }
+
+    /// Runs an encrypt/decrypt round-trip of a known block with the currently loaded
+    /// key to confirm the AES block is functioning, returning `ErrorKind::Fail` if the
+    /// ciphertext matches the plaintext or the decryption does not give the block back.
+    /// This catches a mis-clocked or un-reset AES block, and is an explicit opt-in
+    /// check to run after `set_key` rather than part of it.
+    pub fn verify_key(&mut self) -> Result<()> {
+        const KNOWN_BLOCK: [u8; 16] = [
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+            0xee, 0xff,
+        ];
+
+        let mut encrypted = [0u8; 16];
+        encrypted
+            .iter_mut()
+            .zip(KNOWN_BLOCK.into_iter().cipher(self, CipherType::Encrypt))
+            .for_each(|(byte, cipher_byte)| *byte = cipher_byte);
+
+        let mut decrypted = [0u8; 16];
+        decrypted
+            .iter_mut()
+            .zip(encrypted.into_iter().cipher(self, CipherType::Decrypt))
+            .for_each(|(byte, cipher_byte)| *byte = cipher_byte);
+
+        if encrypted == KNOWN_BLOCK || decrypted != KNOWN_BLOCK {
+            return Err(ErrorKind::Fail);
+        }
+
+        Ok(())
+    }
+
+    /// Wipes all 1024 bytes of the AES keys register.
+    fn wipe_key(&mut self) {
+        #[cfg(not(test))]
+        unsafe {
+            let mut keys = registers::keys::Registers::new(mmio::AES_KEYS);
+            for index in registers::keys::Registers::AES_KEY_INDEXES {
+                keys.set_aes_key(index, 0);
+            }
         }
     }
 
@@ -85,24 +231,170 @@ impl AES {
         for word in data.iter_mut() {
             *word = self.registers.get_aes_fifo();
         }
-        let block = unsafe { &*(data.as_ptr() as *const u32 as *const u128) };
+        let block = unsafe { &*(data.as_ptr() as *const u128) };
         block.to_le_bytes()
     }
 }
 
+/// An `AES` instance with a key loaded by `AES::with_key`. Cipher operations run
+/// through this guard reuse the loaded key, and the key register is wiped on drop.
+pub struct KeyedAes<'a> {
+    aes: &'a mut AES,
+}
+
+impl KeyedAes<'_> {
+    /// Initializes a new cipher operation with the loaded key. Returns an iterator
+    /// over the ciphered bytes.
+    pub fn cipher<I>(&mut self, iter: I, cipher_type: CipherType) -> AESIter<'_, I>
+    where
+        I: Iterator,
+        I::Item: Into<u8>,
+    {
+        iter.cipher(self.aes, cipher_type)
+    }
+
+    /// Like `cipher`, chaining the blocks with `mode`.
+    pub fn cipher_mode<I>(
+        &mut self,
+        iter: I,
+        cipher_type: CipherType,
+        mode: CipherMode,
+    ) -> AESIter<'_, I>
+    where
+        I: Iterator,
+        I::Item: Into<u8>,
+    {
+        iter.cipher_mode(self.aes, cipher_type, mode)
+    }
+
+    /// Runs `iter` through CTR mode with the loaded key, see `AESIterExt::cipher_ctr`.
+    pub fn cipher_ctr<I>(&mut self, iter: I, nonce: [u8; 16]) -> AESIter<'_, I>
+    where
+        I: Iterator,
+        I::Item: Into<u8>,
+    {
+        iter.cipher_ctr(self.aes, nonce)
+    }
+
+    /// Encrypts `iter` with the loaded key.
+    pub fn encrypt<I>(&mut self, iter: I) -> AESIter<'_, I>
+    where
+        I: Iterator,
+        I::Item: Into<u8>,
+    {
+        self.cipher(iter, CipherType::Encrypt)
+    }
+
+    /// Decrypts `iter` with the loaded key.
+    pub fn decrypt<I>(&mut self, iter: I) -> AESIter<'_, I>
+    where
+        I: Iterator,
+        I::Item: Into<u8>,
+    {
+        self.cipher(iter, CipherType::Decrypt)
+    }
+}
+
+impl Drop for KeyedAes<'_> {
+    fn drop(&mut self) {
+        self.aes.wipe_key();
+    }
+}
+
 /// Holds the state of an AES cipher operation.
+///
+/// # Padding
+/// ECB and CBC work on whole 16 byte blocks, so when the input runs out part way
+/// through a block the rest of that block is filled with zeros and all 16 bytes
+/// are returned. A 5 byte message encrypts to 16 bytes, and decrypting those gives
+/// the 5 bytes followed by 11 zeros. Nothing in the ciphertext records the real
+/// length, so keep `input_len` next to it, or use CTR mode (`cipher_ctr`), which
+/// returns exactly as many bytes as it is given.
 pub struct AESIter<'a, I> {
     iter: I,
     aes: &'a mut AES,
     block_buffer: [u8; 16],
     send_index: usize,
+    cipher_type: CipherType,
+    chain: Chain,
+    /// The next CTR mode counter block, when running in CTR mode.
+    counter: Option<u128>,
+    input_len: usize,
+}
+
+impl<I> AESIter<'_, I> {
+    /// # Input Len
+    /// How many bytes have been taken from the input so far, not counting the zero
+    /// padding of a final partial block. Once the iterator is finished this is the
+    /// length to truncate the deciphered output to.
+    pub fn input_len(&self) -> usize {
+        self.input_len
+    }
+}
+
+/// The running chaining state of an `AESIter`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Chain {
+    Ecb,
+    /// The block the next block is chained with, the IV or the last ciphertext.
+    Cbc([u8; 16]),
+}
+
+impl Chain {
+    /// Cipher `block` with `block_cipher`, chaining it with the blocks before it.
+    fn block(
+        &mut self,
+        block: [u8; 16],
+        cipher_type: CipherType,
+        block_cipher: impl FnOnce([u8; 16]) -> [u8; 16],
+    ) -> [u8; 16] {
+        match (self, cipher_type) {
+            (Chain::Ecb, _) => block_cipher(block),
+            (Chain::Cbc(previous), CipherType::Encrypt) => {
+                *previous = block_cipher(xor_block(block, *previous));
+                *previous
+            }
+            (Chain::Cbc(previous), CipherType::Decrypt) => {
+                let plain = xor_block(block_cipher(block), *previous);
+                *previous = block;
+                plain
+            }
+        }
+    }
+}
+
+fn xor_block(a: [u8; 16], b: [u8; 16]) -> [u8; 16] {
+    core::array::from_fn(|i| a[i] ^ b[i])
+}
+
+/// Encrypt the big-endian `counter` block with `block_cipher` for the next 16
+/// keystream bytes, then step the counter (wrapping around after `u128::MAX`).
+fn ctr_keystream(counter: &mut u128, block_cipher: impl FnOnce([u8; 16]) -> [u8; 16]) -> [u8; 16] {
+    let keystream = block_cipher(counter.to_be_bytes());
+    *counter = counter.wrapping_add(1);
+    keystream
 }
 
 impl<I: Iterator> AESIterExt for I {}
 
 pub trait AESIterExt: Iterator {
     /// Initializes a new AES cipher operation. Returns an iterator over the ciphered bytes.
-    fn cipher<'a>(self, aes: &'a mut AES, cipher_type: CipherType) -> AESIter<Self>
+    fn cipher(self, aes: &mut AES, cipher_type: CipherType) -> AESIter<'_, Self>
+    where
+        Self::Item: Into<u8>,
+        Self: Sized,
+    {
+        self.cipher_mode(aes, cipher_type, CipherMode::Ecb)
+    }
+
+    /// Initializes a new AES cipher operation with the blocks chained by `mode`.
+    /// Returns an iterator over the ciphered bytes.
+    fn cipher_mode(
+        self,
+        aes: &mut AES,
+        cipher_type: CipherType,
+        mode: CipherMode,
+    ) -> AESIter<'_, Self>
     where
         Self::Item: Into<u8>,
         Self: Sized,
@@ -110,6 +402,7 @@ pub trait AESIterExt: Iterator {
         unsafe {
             aes.registers.set_aes_control_register(0);
             aes.registers.set_encryption_type(cipher_type as u8);
+            aes.registers.set_encryption_key_size(aes.key_size as u8);
             aes.registers.set_aes_enable(true);
         }
 
@@ -118,8 +411,33 @@ pub trait AESIterExt: Iterator {
             aes,
             block_buffer: [0; 16],
             send_index: 16,
+            cipher_type,
+            chain: match mode {
+                CipherMode::Ecb => Chain::Ecb,
+                CipherMode::Cbc { iv } => Chain::Cbc(iv),
+            },
+            counter: None,
+            input_len: 0,
         }
     }
+
+    /// Initializes a new AES CTR mode operation starting from the counter block
+    /// `nonce`. Returns an iterator over the ciphered bytes, which is exactly as
+    /// long as this iterator since no padding is needed.
+    ///
+    /// CTR mode encrypts and decrypts the same way, by XORing the data with the
+    /// encrypted counter blocks. The counter is stepped as a big-endian 128-bit
+    /// number after each block, so a `nonce` must never be reused with the same key,
+    /// and no two messages may use overlapping counter ranges.
+    fn cipher_ctr(self, aes: &mut AES, nonce: [u8; 16]) -> AESIter<'_, Self>
+    where
+        Self::Item: Into<u8>,
+        Self: Sized,
+    {
+        let mut iter = self.cipher(aes, CipherType::Encrypt);
+        iter.counter = Some(u128::from_be_bytes(nonce));
+        iter
+    }
 }
 
 impl<'a, I> Iterator for AESIter<'a, I>
@@ -132,24 +450,77 @@ where
     /// Returns the next cipher byte from the operation. `AESIter` buffers AES blocks
     /// such that every 16th call will advance the internal iterator 16 times and
     /// perform the next block cipher using the hardware.
+    ///
+    /// In CTR mode each call advances the internal iterator once instead, and every
+    /// 16th call encrypts the next counter block.
     fn next(&mut self) -> Option<u8> {
+        if let Some(counter) = self.counter.as_mut() {
+            let byte = self.iter.next()?.into();
+            self.input_len += 1;
+            if self.send_index == 16 {
+                let aes = &mut *self.aes;
+                self.block_buffer = ctr_keystream(counter, |block| {
+                    aes.load_fifo(block);
+                    aes.read_back_fifo()
+                });
+                self.send_index = 0;
+            }
+            let result = byte ^ self.block_buffer[self.send_index];
+            self.send_index += 1;
+            return Some(result);
+        }
+
         if self.send_index == 16 {
             for (i, byte) in self.block_buffer.iter_mut().enumerate() {
                 *byte = match self.iter.next() {
-                    Some(next_byte) => next_byte.into(),
+                    Some(next_byte) => {
+                        self.input_len += 1;
+                        next_byte.into()
+                    }
                     None if i == 0 => return None,
                     None => 0,
                 }
             }
-            self.aes.load_fifo(self.block_buffer);
-            self.block_buffer
-                .copy_from_slice(&self.aes.read_back_fifo());
+            let aes = &mut *self.aes;
+            self.block_buffer = self
+                .chain
+                .block(self.block_buffer, self.cipher_type, |block| {
+                    aes.load_fifo(block);
+                    aes.read_back_fifo()
+                });
             self.send_index = 0;
         }
         let result = self.block_buffer[self.send_index];
         self.send_index += 1;
         Some(result)
     }
+
+    /// The bytes left in the current block, plus the inner iterator's hint. ECB
+    /// and CBC round that up to whole blocks for the zero padding, CTR mode does
+    /// not pad so the hint is the inner one as is.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.iter.size_hint();
+        if self.counter.is_some() {
+            return (lower, upper);
+        }
+
+        let buffered = 16 - self.send_index;
+        let lower = lower
+            .div_ceil(16)
+            .saturating_mul(16)
+            .saturating_add(buffered);
+        let upper = upper
+            .and_then(|upper| upper.checked_next_multiple_of(16))
+            .and_then(|upper| upper.checked_add(buffered));
+        (lower, upper)
+    }
+}
+
+impl<I> ExactSizeIterator for AESIter<'_, I>
+where
+    I: ExactSizeIterator,
+    I::Item: Into<u8>,
+{
 }
 
 #[cfg(test)]
@@ -161,6 +532,7 @@ mod test {
         let mut fake_aes_registers: [u32; 6] = [0; 6];
         let mut aes = AES {
             registers: Registers::new(fake_aes_registers.as_mut_ptr() as usize),
+            key_size: KeySize::Bits128,
         };
         let data = [0b_01110101; 16];
         aes.load_fifo(data);
@@ -170,12 +542,265 @@ mod test {
         );
     }
 
+    #[test]
+    fn keyed_aes_multiple_messages_test() {
+        let mut fake_aes_registers: [u32; 6] = [0; 6];
+        let mut aes = AES {
+            registers: Registers::new(fake_aes_registers.as_mut_ptr() as usize),
+            key_size: KeySize::Bits128,
+        };
+        let mut keyed = aes.with_key(&Key::Bits128(&[0; 16]));
+        // With the fake registers the FIFO reads back the last word loaded.
+        let first: [u8; 16] = core::array::from_fn({
+            let mut iter = keyed.encrypt([0x11u8; 16].into_iter());
+            move |_| iter.next().unwrap()
+        });
+        let second: [u8; 16] = core::array::from_fn({
+            let mut iter = keyed.decrypt([0x22u8; 16].into_iter());
+            move |_| iter.next().unwrap()
+        });
+        drop(keyed);
+
+        assert_eq!(first, [0x11; 16]);
+        assert_eq!(second, [0x22; 16]);
+        assert_eq!(fake_aes_registers[0] >> 8, CipherType::Decrypt as u32);
+    }
+
+    #[test]
+    fn verify_key_detects_passthrough_test() {
+        let mut fake_aes_registers: [u32; 6] = [0; 6];
+        let mut aes = AES {
+            registers: Registers::new(fake_aes_registers.as_mut_ptr() as usize),
+            key_size: KeySize::Bits128,
+        };
+        aes.set_key(&Key::Bits128(&[0; 16]));
+        // With the fake registers the FIFO never actually ciphers the block.
+        assert_eq!(aes.verify_key(), Err(ErrorKind::Fail));
+    }
+
+    fn hex_block(hex: &str) -> [u8; 16] {
+        core::array::from_fn(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap())
+    }
+
+    #[test]
+    fn cbc_chaining_nist_test() {
+        // NIST SP 800-38A F.2.1/F.2.2 CBC-AES128, with the AES block step looked up
+        // from the input and output blocks listed there.
+        let iv = hex_block("000102030405060708090a0b0c0d0e0f");
+        let plaintext = [
+            "6bc1bee22e409f96e93d7e117393172a",
+            "ae2d8a571e03ac9c9eb76fac45af8e51",
+            "30c81c46a35ce411e5fbc1191a0a52ef",
+            "f69f2445df4f9b17ad2b417be66c3710",
+        ]
+        .map(hex_block);
+        let block_steps = [
+            (
+                "6bc0bce12a459991e134741a7f9e1925",
+                "7649abac8119b246cee98e9b12e9197d",
+            ),
+            (
+                "d86421fb9f1a1eda505ee1375746972c",
+                "5086cb9b507219ee95db113a917678b2",
+            ),
+            (
+                "604ed7ddf32efdff7020d0238b7c2a5d",
+                "73bed6b8e3c1743b7116e69e22229516",
+            ),
+            (
+                "8521f2fd3c8eef2cdc3da7e5c44ea206",
+                "3ff1caa1681fac09120eca307586e1a7",
+            ),
+        ]
+        .map(|(input, output)| (hex_block(input), hex_block(output)));
+        let ciphertext = block_steps.map(|(_, output)| output);
+
+        let encrypt_step = |block| {
+            block_steps
+                .iter()
+                .find(|(input, _)| *input == block)
+                .unwrap()
+                .1
+        };
+        let decrypt_step = |block| {
+            block_steps
+                .iter()
+                .find(|(_, output)| *output == block)
+                .unwrap()
+                .0
+        };
+
+        let mut chain = Chain::Cbc(iv);
+        for (plain, cipher) in plaintext.iter().zip(ciphertext.iter()) {
+            assert_eq!(
+                chain.block(*plain, CipherType::Encrypt, encrypt_step),
+                *cipher
+            );
+        }
+
+        let mut chain = Chain::Cbc(iv);
+        for (plain, cipher) in plaintext.iter().zip(ciphertext.iter()) {
+            assert_eq!(
+                chain.block(*cipher, CipherType::Decrypt, decrypt_step),
+                *plain
+            );
+        }
+    }
+
+    #[test]
+    fn cbc_mode_uses_iv_test() {
+        let mut fake_aes_registers: [u32; 6] = [0; 6];
+        let mut aes = AES {
+            registers: Registers::new(fake_aes_registers.as_mut_ptr() as usize),
+            key_size: KeySize::Bits128,
+        };
+
+        // The fake FIFO reads back the last word loaded, so the first block comes
+        // back as its last 4 bytes XOR the IV, repeated.
+        let mut iv = [0u8; 16];
+        iv[12..].copy_from_slice(&[1, 2, 3, 4]);
+        let cipher: std::vec::Vec<u8> = [0x10u8; 16]
+            .into_iter()
+            .cipher_mode(&mut aes, CipherType::Encrypt, CipherMode::Cbc { iv })
+            .collect();
+        assert_eq!(cipher, [0x11, 0x12, 0x13, 0x14].repeat(4));
+    }
+
+    #[test]
+    fn ctr_keystream_nist_test() {
+        // NIST SP 800-38A F.5.1 CTR-AES128, with the AES block step looked up from
+        // the counter and output blocks listed there.
+        let block_steps = [
+            (
+                "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff",
+                "ec8cdf7398607cb0f2d21675ea9ea1e4",
+            ),
+            (
+                "f0f1f2f3f4f5f6f7f8f9fafbfcfdff00",
+                "362b7c3c6773516318a077d7fc5073ae",
+            ),
+        ]
+        .map(|(input, output)| (hex_block(input), hex_block(output)));
+        let plaintext = [
+            "6bc1bee22e409f96e93d7e117393172a",
+            "ae2d8a571e03ac9c9eb76fac45af8e51",
+        ]
+        .map(hex_block);
+        let ciphertext = [
+            "874d6191b620e3261bef6864990db6ce",
+            "9806f66b7970fdff8617187bb9fffdff",
+        ]
+        .map(hex_block);
+
+        let step = |block| {
+            block_steps
+                .iter()
+                .find(|(input, _)| *input == block)
+                .unwrap()
+                .1
+        };
+
+        let mut counter = u128::from_be_bytes(block_steps[0].0);
+        for (plain, cipher) in plaintext.iter().zip(ciphertext.iter()) {
+            assert_eq!(
+                xor_block(*plain, ctr_keystream(&mut counter, step)),
+                *cipher
+            );
+        }
+    }
+
+    #[test]
+    fn ctr_counter_wraps_test() {
+        let mut counter = u128::MAX;
+        assert_eq!(ctr_keystream(&mut counter, |block| block), [0xff; 16]);
+        assert_eq!(counter, 0);
+        assert_eq!(ctr_keystream(&mut counter, |block| block), [0; 16]);
+        assert_eq!(counter, 1);
+    }
+
+    #[test]
+    fn ctr_mode_keeps_length_test() {
+        let mut fake_aes_registers: [u32; 6] = [0; 6];
+        let mut aes = AES {
+            registers: Registers::new(fake_aes_registers.as_mut_ptr() as usize),
+            key_size: KeySize::Bits128,
+        };
+
+        // The fake FIFO reads back the last word of the counter block, repeated.
+        let mut nonce = [0u8; 16];
+        nonce[12..].copy_from_slice(&[0, 0, 0, 0xfe]);
+        let cipher: std::vec::Vec<u8> = [0u8; 21].into_iter().cipher_ctr(&mut aes, nonce).collect();
+
+        assert_eq!(cipher.len(), 21);
+        assert_eq!(cipher[..16], [0, 0, 0, 0xfe].repeat(4));
+        assert_eq!(cipher[16..], [0, 0, 0, 0xff, 0]);
+    }
+
+    #[test]
+    fn size_hint_test() {
+        let mut fake_aes_registers: [u32; 6] = [0; 6];
+        let mut aes = AES {
+            registers: Registers::new(fake_aes_registers.as_mut_ptr() as usize),
+            key_size: KeySize::Bits128,
+        };
+        let message = [7u8; 40];
+
+        for (len, padded) in [(0, 0), (1, 16), (15, 16), (16, 16), (17, 32), (40, 48)] {
+            let ecb = message[..len]
+                .iter()
+                .copied()
+                .cipher(&mut aes, CipherType::Encrypt);
+            assert_eq!(ecb.size_hint(), (padded, Some(padded)));
+            assert_eq!(ecb.len(), padded);
+
+            let ctr = message[..len].iter().copied().cipher_ctr(&mut aes, [0; 16]);
+            assert_eq!(ctr.len(), len);
+        }
+
+        // Part way through a block the rest of it is counted with the input left.
+        let mut ecb = message[..20]
+            .iter()
+            .copied()
+            .cipher(&mut aes, CipherType::Encrypt);
+        ecb.by_ref().take(3).for_each(drop);
+        assert_eq!(ecb.size_hint(), (29, Some(29)));
+        assert_eq!(ecb.by_ref().count(), 29);
+        assert_eq!(ecb.size_hint(), (0, Some(0)));
+
+        let filtered = message.iter().copied().filter(|_| true);
+        let ecb = filtered.cipher(&mut aes, CipherType::Encrypt);
+        assert_eq!(ecb.size_hint(), (0, Some(48)));
+    }
+
+    #[test]
+    fn input_len_ignores_padding_test() {
+        let mut fake_aes_registers: [u32; 6] = [0; 6];
+        let mut aes = AES {
+            registers: Registers::new(fake_aes_registers.as_mut_ptr() as usize),
+            key_size: KeySize::Bits128,
+        };
+        let message = [1u8, 2, 3, 4, 5];
+
+        let mut ecb = message.into_iter().cipher(&mut aes, CipherType::Encrypt);
+        assert_eq!(ecb.by_ref().count(), 16);
+        assert_eq!(ecb.input_len(), 5);
+
+        let mut ctr = message.into_iter().cipher_ctr(&mut aes, [0; 16]);
+        let output: std::vec::Vec<u8> = ctr.by_ref().collect();
+        assert_eq!(output.len(), 5);
+        assert_eq!(ctr.input_len(), 5);
+
+        // The fake FIFO reads back the zero counter block as the keystream.
+        assert_eq!(output, message);
+    }
+
     #[test]
     fn read_back_fifo_test() {
         let mut fake_aes_registers: [u32; 6] = [0; 6];
         fake_aes_registers[4] = 0b_01110101_01110101_01110101_01110101;
         let aes = AES {
             registers: Registers::new(fake_aes_registers.as_mut_ptr() as usize),
+            key_size: KeySize::Bits128,
         };
         let data = aes.read_back_fifo();
         assert_eq!(
@@ -183,4 +808,65 @@ mod test {
             [0b_01110101, 0b_01110101, 0b_01110101, 0b_01110101]
         );
     }
+
+    #[test]
+    fn set_key_from_trng_test() {
+        let mut fake_aes_registers: [u32; 6] = [0; 6];
+        let ptr = fake_aes_registers.as_mut_ptr();
+        let mut aes = AES {
+            registers: Registers::new(ptr as usize),
+            key_size: KeySize::Bits128,
+        };
+        // TRNG_STATUS reports data ready and TRNG_DATA always reads the same word.
+        let mut fake_trng_registers = [0u32, 1, 0x0403_0201];
+        let mut trng = TRNG::from_registers(crate::trng::registers::Registers::new(
+            fake_trng_registers.as_mut_ptr() as usize,
+        ));
+        let key_size_bits = || (unsafe { ptr.read_volatile() } >> 6) & 0b11;
+
+        for size in [KeySize::Bits192, KeySize::Bits256, KeySize::Bits128] {
+            assert_eq!(aes.set_key_from_trng(&mut trng, size), Ok(()));
+            assert_eq!(key_size_bits(), size as u32);
+        }
+
+        // Each key length is whole words, so no spare bytes are left in the TRNG.
+        let mut next = [0u8; 2];
+        trng.fill_bytes(&mut next);
+        assert_eq!(next, [1, 2]);
+    }
+
+    #[test]
+    fn zeroize_test() {
+        let mut buffer = [0xA5u8; 32];
+        zeroize(&mut buffer[..24]);
+        assert_eq!(buffer[..24], [0; 24]);
+        assert_eq!(buffer[24..], [0xA5; 8]);
+        assert_eq!(key_len(KeySize::Bits192), 24);
+    }
+
+    #[test]
+    fn set_key_programs_key_size_test() {
+        let mut fake_aes_registers: [u32; 6] = [0; 6];
+        let ptr = fake_aes_registers.as_mut_ptr();
+        let mut aes = AES {
+            registers: Registers::new(ptr as usize),
+            key_size: KeySize::Bits128,
+        };
+        let key_size_bits = || (unsafe { ptr.read_volatile() } >> 6) & 0b11;
+
+        aes.set_key(&Key::Bits256(&[0; 32]));
+        assert_eq!(key_size_bits(), KeySize::Bits256 as u32);
+        aes.set_key(&Key::Bits192(&[0; 24]));
+        assert_eq!(key_size_bits(), KeySize::Bits192 as u32);
+        aes.set_key(&Key::Bits128(&[0; 16]));
+        assert_eq!(key_size_bits(), KeySize::Bits128 as u32);
+
+        // Starting a cipher rewrites the control register, which must keep the size.
+        aes.set_key(&Key::Bits256(&[0; 32]));
+        [0u8; 16]
+            .into_iter()
+            .cipher(&mut aes, CipherType::Encrypt)
+            .for_each(|_| {});
+        assert_eq!(key_size_bits(), KeySize::Bits256 as u32);
+    }
 }
@@ -109,6 +109,33 @@ make_device! {
     calculation_done_event_interrupt_enable,
 
     /// AES FIFO. See Page 362, Table 24-8.
+    #[no_dump]
     #[bit(0..=31, RW, rro::AES_FIFO)]
     aes_fifo,
 }
+
+/// # AES Key Registers
+/// The 1024 byte key memory at `mmio::AES_KEYS`, of which a key uses the first
+/// 32 bytes. It is its own block of the memory map, apart from the AES registers.
+pub mod keys {
+    use crate::memory_map::mmio;
+    use hal_macros::RW;
+    use hal_macros_derive::make_device;
+
+    /// # AES Key Register Offsets
+    /// See Max 78000 User Guide Page 360, Table 24-3.
+    mod rro {
+        /// # AES Key Register
+        pub const AES_KEY: usize = 0x0000;
+    }
+
+    make_device! {
+        device_ports(mmio::AES_KEYS);
+
+        /// AES Key. See Page 360, Table 24-3.
+        /// One word of the 256 word key memory, written little endian from the
+        /// start of the key.
+        #[bit(0..=31, WO, rro::AES_KEY, index(0..256, stride = 4))]
+        aes_key,
+    }
+}
@@ -1,4 +1,5 @@
-use core::ops::RangeBounds;
+use crate::error::{ErrorKind, Result};
+use core::ops::{RangeBounds, RangeInclusive};
 
 /// # Bit Manipulation
 /// A Simple trait to help with setting and un-setting bits in types.
@@ -27,6 +28,13 @@ pub trait BitManipulation: Sized {
     fn get_bit_range<R>(&self, bit: R) -> Self
     where
         R: RangeBounds<Self>;
+
+    /// # Set Fields
+    /// Set several bit ranges at once with a single combined mask. Returns
+    /// `ErrorKind::BadParam` if a range is out of bounds or overlaps another range,
+    /// and `ErrorKind::Overflow` if a value does not fit its range. Nothing is
+    /// written when an error is returned.
+    fn set_fields(&mut self, fields: &[(RangeInclusive<Self>, Self)]) -> Result<&mut Self>;
 }
 
 /// # Bit Manipulation Impl
@@ -46,8 +54,8 @@ macro_rules! bit_manipulation_impl {
             let self_bits = (core::mem::size_of::<Self>() * 8) as u8;
 
             debug_assert!(
-                bit <= self_bits,
-                "Bit '{bit}' is larger then type's total bits of '{self_bits}'!"
+                bit < self_bits,
+                "Bit '{bit}' is out of range for type's total bits of '{self_bits}'!"
             );
 
             if set {
@@ -68,8 +76,8 @@ macro_rules! bit_manipulation_impl {
             let self_bits = (core::mem::size_of::<Self>() * 8) as u8;
 
             debug_assert!(
-                bit <= self_bits,
-                "Bit '{bit}' is larger then type's total bits of '{self_bits}'!"
+                bit < self_bits,
+                "Bit '{bit}' is out of range for type's total bits of '{self_bits}'!"
             );
 
             *self & (1 << bit) != 0
@@ -95,10 +103,11 @@ macro_rules! bit_manipulation_impl {
             };
 
             debug_assert!(
-                true_bit_start <= self_bits,
-                "Bit Start '{true_bit_start}' is larger then type's total bits of '{self_bits}'!"
+                true_bit_start < self_bits,
+                "Bit Start '{true_bit_start}' is out of range for type's total bits of '{self_bits}'!"
             );
 
+            // `true_bit_end` is exclusive here, so it may equal `self_bits`.
             debug_assert!(
                 true_bit_end <= self_bits,
                 "Bit End '{true_bit_end}' is larger then type's total bits of '{self_bits}'!"
@@ -132,19 +141,20 @@ macro_rules! bit_manipulation_impl {
             let true_bit_end = match bit.end_bound() {
                 core::ops::Bound::Included(&value) => value,
                 core::ops::Bound::Excluded(&value) => value - 1,
-                core::ops::Bound::Unbounded => self_bits,
+                core::ops::Bound::Unbounded => self_bits - 1,
             };
 
             let true_bit_diff = true_bit_end - true_bit_start;
 
             debug_assert!(
-                true_bit_start <= self_bits,
-                "Bit Start '{true_bit_start}' is larger then type's total bits of '{self_bits}'!"
+                true_bit_start < self_bits,
+                "Bit Start '{true_bit_start}' is out of range for type's total bits of '{self_bits}'!"
             );
 
+            // `true_bit_end` is inclusive here, so it must be a real bit of the type.
             debug_assert!(
-                true_bit_end <= self_bits,
-                "Bit End '{true_bit_end}' is larger then type's total bits of '{self_bits}'!"
+                true_bit_end < self_bits,
+                "Bit End '{true_bit_end}' is out of range for type's total bits of '{self_bits}'!"
             );
 
             debug_assert!(
@@ -170,12 +180,85 @@ macro_rules! bit_manipulation_impl {
             *self = (*self & !mask) | (set_bits << true_bit_start);
             self
         }
+
+        /// # Set Fields
+        /// Set several bit ranges at once with a single combined mask.
+        fn set_fields(&mut self, fields: &[(RangeInclusive<Self>, Self)]) -> Result<&mut Self> {
+            let self_bits = (core::mem::size_of::<Self>() * 8) as i128;
+            let mut mask: Self = 0;
+            let mut bits: Self = 0;
+
+            for (range, value) in fields {
+                let (start, end) = (*range.start() as i128, *range.end() as i128);
+                if start < 0 || start > end || end >= self_bits {
+                    return Err(ErrorKind::BadParam);
+                }
+
+                let width = (end - start + 1) as u32;
+                let field_mask: Self = if width as i128 == self_bits {
+                    !0
+                } else {
+                    ((1 as Self) << width).wrapping_sub(1)
+                };
+
+                if *value & !field_mask != 0 {
+                    return Err(ErrorKind::Overflow);
+                }
+
+                let field_mask = field_mask << start as u32;
+                if mask & field_mask != 0 {
+                    return Err(ErrorKind::BadParam);
+                }
+
+                mask |= field_mask;
+                bits |= *value << start as u32;
+            }
+
+            *self = (*self & !mask) | bits;
+            Ok(self)
+        }
     }
     )*)
 }
 
 bit_manipulation_impl! { u8 u16 u32 u64 u128 i8 i16 i32 i64 i128 usize isize }
 
+/// # Mask Range
+/// A `u32` mask with bits `start` to `end` (inclusive) set, the same bits
+/// `set_bit_range(start..=end, ..)` would write. Usable in `const` contexts, such
+/// as building tables of register masks.
+pub const fn mask_range(start: usize, end: usize) -> u32 {
+    assert!(start <= end, "Bit Start must not be past Bit End!");
+    assert!(
+        end < u32::BITS as usize,
+        "Bit End is out of range for a u32!"
+    );
+
+    (u32::MAX >> (u32::BITS as usize - 1 - (end - start))) << start
+}
+
+/// # Extract
+/// Read bits `start` to `end` (inclusive) of `value`, shifted down to bit 0. The
+/// `const` version of `get_bit_range(start..=end)`.
+pub const fn extract(value: u32, start: usize, end: usize) -> u32 {
+    (value & mask_range(start, end)) >> start
+}
+
+/// # Insert
+/// Replace bits `start` to `end` (inclusive) of `value` with `field`, leaving the
+/// other bits alone. The `const` version of `set_bit_range(start..=end, field)`,
+/// except that a `field` too wide for the range is a compile error in `const`
+/// contexts (and a panic otherwise) rather than a debug assertion.
+pub const fn insert(value: u32, field: u32, start: usize, end: usize) -> u32 {
+    let mask = mask_range(start, end);
+    assert!(
+        field & !(mask >> start) == 0,
+        "The field value has more bits than the range!"
+    );
+
+    (value & !mask) | (field << start)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -256,4 +339,131 @@ mod test {
         assert_eq!(*0b00000u8.set_bit(3, true), 1 << 3);
         assert_eq!(*0b00000u8.set_bit(4, true), 1 << 4);
     }
+
+    #[test]
+    fn test_get_bit_highest_bit() {
+        assert!(0x8000_0000_u32.get_bit(31));
+        assert!(!0x7FFF_FFFF_u32.get_bit(31));
+        assert_eq!(*0u32.set_bit(31, true), 0x8000_0000);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "out of range")]
+    fn test_get_bit_past_end_panics() {
+        0xFFFF_FFFF_u32.get_bit(32);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "out of range")]
+    fn test_set_bit_past_end_panics() {
+        0u32.set_bit(32, true);
+    }
+
+    #[test]
+    fn test_unbounded_bit_ranges() {
+        assert_eq!(0xF0_u8.get_bit_range(4..), 0xF);
+        assert_eq!(0xF0_u8.get_bit_range(..), 0xF0);
+        assert_eq!(*0x0F_u8.set_bit_range(4.., 0xA), 0xAF);
+        assert_eq!(*0x0F_u8.set_bit_range(.., 0xA5), 0xA5);
+        assert_eq!(*0u32.set_bit_range(31.., 1u32), 0x8000_0000);
+    }
+
+    const _: () = assert!(mask_range(0, 0) == 0b1);
+    const _: () = assert!(mask_range(4, 7) == 0xF0);
+    const _: () = assert!(mask_range(0, 31) == u32::MAX);
+    const _: () = assert!(mask_range(31, 31) == 0x8000_0000);
+    const _: () = assert!(extract(0x1234_5678, 8, 15) == 0x56);
+    const _: () = assert!(extract(0x8000_0000, 31, 31) == 1);
+    const _: () = assert!(extract(0xDEAD_BEEF, 0, 31) == 0xDEAD_BEEF);
+    const _: () = assert!(insert(0xFFFF_0000, 0xA, 0, 3) == 0xFFFF_000A);
+    const _: () = assert!(insert(0x1234_5678, 0xAB, 8, 15) == 0x1234_AB78);
+    const _: () = assert!(insert(0, u32::MAX, 0, 31) == u32::MAX);
+
+    #[test]
+    fn test_const_helpers_match_trait() {
+        let value = 0x1234_5678_u32;
+        for (start, end) in [(0, 0), (3, 9), (8, 15), (16, 31), (0, 31)] {
+            assert_eq!(
+                extract(value, start, end),
+                value.get_bit_range(start as u32..=end as u32)
+            );
+
+            let field = extract(!value, start, end);
+            let mut expected = value;
+            expected.set_bit_range(start as u32..=end as u32, field);
+            assert_eq!(insert(value, field, start, end), expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "more bits than the range")]
+    fn test_insert_rejects_wide_field() {
+        insert(0, std::hint::black_box(0x10), 0, 3);
+    }
+
+    #[test]
+    fn test_set_fields_packs_multiple_fields() {
+        let mut value = 0xFFFF_0000_u32;
+        value
+            .set_fields(&[(0..=3, 0xA), (4..=7, 0x5), (16..=23, 0x12)])
+            .unwrap();
+        assert_eq!(value, 0xFF12_005A);
+
+        let mut value = 0u8;
+        value.set_fields(&[(0..=7, 0xC3)]).unwrap();
+        assert_eq!(value, 0xC3);
+
+        let mut value = 0b1111_1111u8;
+        value.set_fields(&[]).unwrap();
+        assert_eq!(value, 0b1111_1111);
+    }
+
+    #[test]
+    fn test_set_fields_matches_set_bit_range() {
+        let mut fields = 0x1234_5678_u32;
+        let mut ranges = fields;
+        fields
+            .set_fields(&[(8..=15, 0xAB), (28..=31, 0x1)])
+            .unwrap();
+        ranges
+            .set_bit_range(8..=15, 0xABu32)
+            .set_bit_range(28..=31, 0x1u32);
+        assert_eq!(fields, ranges);
+    }
+
+    #[test]
+    fn test_set_fields_rejects_overlapping_ranges() {
+        let mut value = 0x55u8;
+        assert!(matches!(
+            value.set_fields(&[(0..=3, 0x1), (3..=5, 0x1)]),
+            Err(ErrorKind::BadParam)
+        ));
+        assert!(matches!(
+            value.set_fields(&[(4..=7, 0x1), (0..=1, 0x1), (2..=4, 0x1)]),
+            Err(ErrorKind::BadParam)
+        ));
+        assert_eq!(value, 0x55);
+    }
+
+    #[test]
+    fn test_set_fields_rejects_bad_fields() {
+        let mut value = 0u16;
+        assert!(matches!(
+            value.set_fields(&[(0..=3, 0x10)]),
+            Err(ErrorKind::Overflow)
+        ));
+        assert!(matches!(
+            value.set_fields(&[(12..=16, 0x1)]),
+            Err(ErrorKind::BadParam)
+        ));
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 5..=2;
+        assert!(matches!(
+            value.set_fields(&[(reversed, 0x1)]),
+            Err(ErrorKind::BadParam)
+        ));
+        assert_eq!(value, 0);
+    }
 }
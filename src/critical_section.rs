@@ -0,0 +1,164 @@
+use core::marker::PhantomData;
+
+/// # Critical Section
+/// A token proving that the code holding it runs inside `with`, where it cannot
+/// be interrupted.
+#[derive(Clone, Copy)]
+pub struct CriticalSection<'cs> {
+    _ph: PhantomData<&'cs ()>,
+}
+
+/// # With
+/// Run `func` with interrupts disabled, restoring the previous interrupt state
+/// after. Critical sections can be nested.
+#[inline]
+pub fn with<R>(func: impl FnOnce(CriticalSection) -> R) -> R {
+    let token = acquire();
+    let result = func(CriticalSection { _ph: PhantomData });
+    release(token);
+    result
+}
+
+/// # Mutex
+/// A value that can only be accessed inside a critical section. Together with a
+/// `RefCell` this allows mutable statics without `static mut`.
+pub struct Mutex<T> {
+    inner: T,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self { inner: value }
+    }
+
+    /// # Borrow
+    /// Get the value for the lifetime of the critical section.
+    pub fn borrow<'cs>(&'cs self, _cs: CriticalSection<'cs>) -> &'cs T {
+        &self.inner
+    }
+}
+
+// This is the same reasoning as `critical_section::Mutex`, the value can only be
+// reached while no other code can run.
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+#[cfg(target_arch = "arm")]
+#[inline(always)]
+fn acquire() -> bool {
+    let primask: u32;
+    unsafe {
+        core::arch::asm!("mrs {}, PRIMASK", out(reg) primask, options(nomem, nostack, preserves_flags));
+        core::arch::asm!("cpsid i", options(nomem, nostack, preserves_flags));
+    }
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+    // If interrupts were already disabled, leave them disabled on release.
+    primask & 1 == 0
+}
+
+#[cfg(target_arch = "arm")]
+#[inline(always)]
+fn release(were_enabled: bool) {
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+    if were_enabled {
+        unsafe { core::arch::asm!("cpsie i", options(nomem, nostack, preserves_flags)) };
+    }
+}
+
+// Off target there are no interrupts to disable, so a global lock is used instead.
+// Critical sections can nest, so the lock remembers how deep the current context
+// is. Tests run on threads and keep that depth per thread; without `std` there is
+// only one context to track.
+#[cfg(not(target_arch = "arm"))]
+static HOST_LOCK: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+#[cfg(test)]
+std::thread_local! {
+    static HOST_DEPTH: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+}
+
+#[cfg(all(not(target_arch = "arm"), not(test)))]
+static HOST_DEPTH: HostDepth = HostDepth(core::sync::atomic::AtomicUsize::new(0));
+
+#[cfg(all(not(target_arch = "arm"), not(test)))]
+struct HostDepth(core::sync::atomic::AtomicUsize);
+
+#[cfg(all(not(target_arch = "arm"), not(test)))]
+impl HostDepth {
+    fn get(&self) -> usize {
+        self.0.load(core::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn set(&self, depth: usize) {
+        self.0.store(depth, core::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+#[cfg(not(target_arch = "arm"))]
+fn acquire() -> bool {
+    use core::sync::atomic::Ordering;
+
+    if HOST_DEPTH.get() > 0 {
+        HOST_DEPTH.set(HOST_DEPTH.get() + 1);
+        return false;
+    }
+
+    while HOST_LOCK
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+
+    HOST_DEPTH.set(1);
+
+    true
+}
+
+#[cfg(not(target_arch = "arm"))]
+fn release(outermost: bool) {
+    HOST_DEPTH.set(HOST_DEPTH.get() - 1);
+
+    if outermost {
+        HOST_LOCK.store(false, core::sync::atomic::Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::RefCell;
+
+    #[test]
+    fn nested_sections_test() {
+        static VALUE: Mutex<RefCell<u32>> = Mutex::new(RefCell::new(0));
+
+        with(|cs| {
+            *VALUE.borrow(cs).borrow_mut() += 1;
+            with(|cs| *VALUE.borrow(cs).borrow_mut() += 1);
+        });
+
+        assert_eq!(with(|cs| *VALUE.borrow(cs).borrow()), 2);
+    }
+
+    #[test]
+    fn serializes_threads_test() {
+        static COUNTER: Mutex<RefCell<u32>> = Mutex::new(RefCell::new(0));
+
+        let threads: std::vec::Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..1000 {
+                        with(|cs| *COUNTER.borrow(cs).borrow_mut() += 1);
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(with(|cs| *COUNTER.borrow(cs).borrow()), 4000);
+    }
+}
@@ -1,11 +1,21 @@
-static mut DEBUG_OUTPUT_STREAM: DebugStream = DebugStream(None);
+use crate::critical_section::{self, Mutex};
+use core::cell::RefCell;
 
-pub struct DebugStream(Option<&'static mut (dyn core::fmt::Write)>);
+/// The stream `debug_print!` writes to, only reached inside a critical section so
+/// main and interrupt handlers can both print.
+static DEBUG_OUTPUT_STREAM: Mutex<RefCell<DebugStream>> =
+    Mutex::new(RefCell::new(DebugStream(None)));
 
-pub fn attach_debug(stream: &'static mut (dyn core::fmt::Write)) {
-    unsafe {
-        DEBUG_OUTPUT_STREAM.0 = Some(stream);
-    }
+pub struct DebugStream(Option<&'static mut (dyn core::fmt::Write + Send)>);
+
+/// # Attach Debug
+/// Send the output of `debug_print!` and `debug_println!` to `stream`, replacing
+/// any stream attached before. The stream is written from whichever context
+/// prints, main or an interrupt handler, so it must be `Send`.
+pub fn attach_debug(stream: &'static mut (dyn core::fmt::Write + Send)) {
+    critical_section::with(|cs| {
+        DEBUG_OUTPUT_STREAM.borrow(cs).borrow_mut().0 = Some(stream);
+    });
 }
 
 impl core::fmt::Write for DebugStream {
@@ -31,10 +41,53 @@ impl core::fmt::Write for DebugStream {
     }
 }
 
+/// # Peek Registers
+/// Print `count` consecutive 32 bit registers starting at the peripheral ptr
+/// `base` to the debug stream, one `address: value` pair per line. This is for
+/// diagnosing a misbehaving peripheral by dumping its whole register file.
+///
+/// Returns `ErrorKind::BadParam` if `base` is not a peripheral ptr in
+/// `memory_map::mmio`, or if `count` registers would run past the end of its
+/// region.
+///
+/// # Safety
+/// Registers are read with volatile reads just like any access through the HAL,
+/// so reading a register with read side effects (such as a FIFO data register or
+/// a clear-on-read flag) will change the state of the peripheral. Reading a
+/// peripheral that does not have its clock enabled may also fault.
+#[cfg(debug_assertions)]
+pub unsafe fn peek_registers(base: usize, count: usize) -> crate::error::Result<()> {
+    let region = crate::memory_map::mmio::region(base).ok_or(crate::error::ErrorKind::BadParam)?;
+    let end = count
+        .checked_mul(4)
+        .and_then(|len| base.checked_add(len))
+        .ok_or(crate::error::ErrorKind::BadParam)?;
+
+    if end > region.end {
+        return Err(crate::error::ErrorKind::BadParam);
+    }
+
+    for address in (base..end).step_by(4) {
+        let value = unsafe { core::ptr::read_volatile(address as *const u32) };
+        _print(format_args!("0x{:08x}: 0x{:08x}\n", address, value));
+    }
+
+    Ok(())
+}
+
+/// Write `args` to the debug stream in one critical section, so output from an
+/// interrupt handler never lands in the middle of it.
+///
+/// If the stream itself prints (or panics while printing and the panic handler
+/// prints), the nested output is dropped instead of borrowing the stream twice.
 #[doc(hidden)]
 pub fn _print(args: ::core::fmt::Arguments) {
     use core::fmt::Write;
-    unsafe { DEBUG_OUTPUT_STREAM.write_fmt(args).unwrap() };
+    critical_section::with(|cs| {
+        if let Ok(mut stream) = DEBUG_OUTPUT_STREAM.borrow(cs).try_borrow_mut() {
+            stream.write_fmt(args).unwrap();
+        }
+    });
 }
 
 #[macro_export]
@@ -49,8 +102,61 @@ macro_rules! debug_print {
 macro_rules! debug_println {
     () => {$crate::debug_print!("\n")};
     ($($arg:tt)*) => {{
+        // One write for the line and its newline, so nothing can come in between.
         #[cfg(debug_assertions)]
-        $crate::debug::_print(format_args!($($arg)*));
-        $crate::debug_print!("\n");
+        $crate::debug::_print(format_args!("{}\n", format_args!($($arg)*)));
     }};
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::String;
+
+    /// Everything printed while the test stream is attached, shared with the other
+    /// tests that print.
+    static OUTPUT: Mutex<RefCell<String>> = Mutex::new(RefCell::new(String::new()));
+
+    struct TestStream;
+
+    impl core::fmt::Write for TestStream {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            // Printing from inside the stream must not borrow it twice.
+            _print(format_args!("nested"));
+            critical_section::with(|cs| OUTPUT.borrow(cs).borrow_mut().push_str(s));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn concurrent_writers_test() {
+        const LINES: usize = 200;
+        attach_debug(std::boxed::Box::leak(std::boxed::Box::new(TestStream)));
+
+        let writers: std::vec::Vec<_> = (0..2)
+            .map(|writer| {
+                std::thread::spawn(move || {
+                    for line in 0..LINES {
+                        crate::debug_println!("writer {} line {}", writer, line);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let output = critical_section::with(|cs| OUTPUT.borrow(cs).borrow().clone());
+        assert!(!output.contains("nested"));
+        for writer in 0..2 {
+            let prefix = std::format!("writer {} line ", writer);
+            let lines: std::vec::Vec<_> = output
+                .lines()
+                .filter_map(|line| line.strip_prefix(prefix.as_str()))
+                .collect();
+            let expected: std::vec::Vec<_> =
+                (0..LINES).map(|line| std::format!("{}", line)).collect();
+            assert_eq!(lines, expected);
+        }
+    }
+}
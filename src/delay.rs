@@ -0,0 +1,98 @@
+/// Roughly how many core clock cycles one pass of the delay loop takes on the
+/// Cortex-M4 (the loop counter, the `black_box` and the branch).
+const CYCLES_PER_SPIN: u64 = 4;
+
+/// # Delay Us
+/// Busy-wait for at least `us` microseconds, counting loop passes at the current
+/// core clock (`SYSTEM_CORE_CLOCK`). Interrupts that fire during the wait make it
+/// longer, so this is only suitable for short, coarse delays like bus timing.
+pub fn delay_us(us: usize) {
+    let core_clock = unsafe { crate::SYSTEM_CORE_CLOCK };
+    spin(spins_for_us(core_clock, us));
+}
+
+/// # Spin Core Clock
+/// The core clock in Hz that `spin_us` assumes, since it runs before
+/// `SYSTEM_CORE_CLOCK` can be trusted. This is the 60MHz ISO the core runs from
+/// out of reset, or with the `spin-delay-ipo` feature the 100MHz IPO that the
+/// MSDK startup code switches to.
+#[cfg(not(feature = "spin-delay-ipo"))]
+pub const SPIN_CORE_CLOCK: u32 = 60_000_000;
+#[cfg(feature = "spin-delay-ipo")]
+pub const SPIN_CORE_CLOCK: u32 = 100_000_000;
+
+/// # Spin Us
+/// Busy-wait for roughly `us` microseconds, assuming the core runs at
+/// `SPIN_CORE_CLOCK`. This needs no timer or clock setup, so it works in early
+/// boot, but it is only as right as that assumption: at a slower core clock it
+/// waits longer, at a faster one shorter. With the default 60MHz that is 15
+/// passes of the loop per microsecond. Once the clocks are set up, `delay_us`
+/// follows the real core clock instead.
+///
+/// # Example
+/// ```
+/// use max78000_hal::delay;
+///
+/// // Give an external part time to power up, before anything is configured.
+/// delay::spin_us(50);
+/// delay::spin_cycles(1_000);
+/// ```
+pub fn spin_us(us: usize) {
+    spin(spins_for_us(SPIN_CORE_CLOCK, us));
+}
+
+/// # Spin Cycles
+/// Busy-wait for roughly `cycles` core clock cycles, at any core clock.
+pub fn spin_cycles(cycles: u32) {
+    spin((cycles as u64).div_ceil(CYCLES_PER_SPIN));
+}
+
+/// The number of delay loop passes that take at least `us` microseconds at
+/// `core_clock_hz`.
+fn spins_for_us(core_clock_hz: u32, us: usize) -> u64 {
+    let cycles = (core_clock_hz as u64)
+        .saturating_mul(us as u64)
+        .div_ceil(1_000_000);
+    cycles.div_ceil(CYCLES_PER_SPIN)
+}
+
+#[inline(never)]
+fn spin(spins: u64) {
+    for spin in 0..spins {
+        // Keep the compiler from removing or collapsing the loop.
+        core::hint::black_box(spin);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spins_scale_with_time_test() {
+        assert_eq!(spins_for_us(100_000_000, 0), 0);
+        assert_eq!(spins_for_us(100_000_000, 1), 25);
+        assert_eq!(spins_for_us(100_000_000, 10), 250);
+        assert_eq!(spins_for_us(50_000_000, 10), 125);
+
+        // Never shorter than asked for, even at slow clocks.
+        assert_eq!(spins_for_us(7_372_800, 1), 2);
+        // Absurdly long delays saturate instead of overflowing.
+        assert_eq!(
+            spins_for_us(u32::MAX, usize::MAX),
+            u64::MAX.div_ceil(1_000_000).div_ceil(4)
+        );
+    }
+
+    #[test]
+    fn spin_delays_return_test() {
+        assert_eq!(
+            spins_for_us(SPIN_CORE_CLOCK, 1),
+            SPIN_CORE_CLOCK as u64 / 4_000_000
+        );
+        spin_us(0);
+        spin_us(2);
+        spin_cycles(0);
+        spin_cycles(17);
+    }
+}
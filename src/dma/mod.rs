@@ -0,0 +1,345 @@
+//! # DMA
+//! A minimal driver for the standard DMA controller, enough to move a buffer to
+//! or from a peripheral FIFO on one channel at a time. Channels are handed out by
+//! `Dma::init`, and peripherals with a DMA path (like
+//! `I2C::master_transaction_dma`) take the channel to use by `&mut`.
+
+pub mod registers;
+
+use crate::error::{ErrorKind, Result};
+use crate::gcr::{peripheral_reset, system_clock_enable, HardwareSource};
+use core::sync::atomic::AtomicBool;
+use registers::Registers;
+
+/// The number of channels on the standard DMA controller.
+pub const CHANNELS: usize = 4;
+
+/// The most bytes one transfer can move, the size of the 24 bit count register.
+pub const MAX_COUNT: usize = (1 << 24) - 1;
+
+/// # Request
+/// The peripheral request that paces a channel, the `REQUEST` field values from
+/// the MSDK `dma_regs.h`.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Request {
+    /// No peripheral, the channel runs as fast as it can.
+    MemoryToMemory = 0x00,
+    I2C0Receive = 0x07,
+    I2C1Receive = 0x08,
+    I2C2Receive = 0x0A,
+    UART0Transmit = 0x24,
+    UART1Transmit = 0x25,
+    I2C0Transmit = 0x27,
+    I2C1Transmit = 0x28,
+    I2C2Transmit = 0x2A,
+    UART2Transmit = 0x2E,
+    UART3Transmit = 0x3C,
+}
+
+impl Request {
+    /// # I2C Receive
+    /// The receive request of I2C port `port`.
+    pub const fn i2c_receive(port: usize) -> Option<Self> {
+        match port {
+            0 => Some(Self::I2C0Receive),
+            1 => Some(Self::I2C1Receive),
+            2 => Some(Self::I2C2Receive),
+            _ => None,
+        }
+    }
+
+    /// # UART Transmit
+    /// The transmit request of UART port `port`, where 3 is the LPUART.
+    pub const fn uart_transmit(port: usize) -> Option<Self> {
+        match port {
+            0 => Some(Self::UART0Transmit),
+            1 => Some(Self::UART1Transmit),
+            2 => Some(Self::UART2Transmit),
+            3 => Some(Self::UART3Transmit),
+            _ => None,
+        }
+    }
+
+    /// # I2C Transmit
+    /// The transmit request of I2C port `port`.
+    pub const fn i2c_transmit(port: usize) -> Option<Self> {
+        match port {
+            0 => Some(Self::I2C0Transmit),
+            1 => Some(Self::I2C1Transmit),
+            2 => Some(Self::I2C2Transmit),
+            _ => None,
+        }
+    }
+}
+
+/// # Transfer
+/// One byte wide transfer of `count` bytes from `source` to `destination`. A FIFO
+/// end keeps the same address (no increment), a buffer end increments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transfer {
+    pub request: Request,
+    pub source: usize,
+    pub source_increment: bool,
+    pub destination: usize,
+    pub destination_increment: bool,
+    pub count: usize,
+}
+
+/// Set once `Dma::init` has handed out the channels. It is never cleared, since
+/// channels split out with `into_channels` can outlive the controller.
+static DMA_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// # DMA
+/// The DMA controller, made with `Dma::init`. It only holds the channels until
+/// they are taken out.
+pub struct Dma {
+    channels: [Channel; CHANNELS],
+}
+
+impl Dma {
+    /// # Init
+    /// Reset the DMA controller and enable its clock. This only succeeds once,
+    /// since every channel it hands out must be the only handle to it.
+    ///
+    /// Returns `ErrorKind::Busy` if the channels were already handed out, and
+    /// `ErrorKind::TimeOut` if the DMA reset does not finish.
+    pub fn init() -> Result<Self> {
+        crate::util::init_once(&DMA_TAKEN, || {
+            peripheral_reset(HardwareSource::DMA)?;
+            system_clock_enable(HardwareSource::DMA, true);
+
+            let bases = [
+                registers::CHANNEL_0,
+                registers::CHANNEL_1,
+                registers::CHANNEL_2,
+                registers::CHANNEL_3,
+            ];
+            Ok(Self {
+                channels: core::array::from_fn(|number| Channel {
+                    reg: Registers::new(bases[number]),
+                    number,
+                }),
+            })
+        })
+    }
+
+    /// # Channel
+    /// Borrow channel `number`, or `None` if there is no such channel.
+    pub fn channel(&mut self, number: usize) -> Option<&mut Channel> {
+        self.channels.get_mut(number)
+    }
+
+    /// # Into Channels
+    /// Split the controller into its channels, so they can be owned by different
+    /// drivers.
+    pub fn into_channels(self) -> [Channel; CHANNELS] {
+        self.channels
+    }
+}
+
+/// # Channel
+/// One DMA channel. A channel only runs one `Transfer` at a time.
+pub struct Channel {
+    reg: Registers,
+    number: usize,
+}
+
+impl Channel {
+    /// Make channel `number` around `reg`, for tests that run on fake memory.
+    #[cfg(test)]
+    pub(crate) fn from_registers(reg: Registers, number: usize) -> Self {
+        Self { reg, number }
+    }
+
+    /// # Number
+    /// Which of the `CHANNELS` channels this is.
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// # Start
+    /// Program `transfer` and start the channel.
+    ///
+    /// Returns `ErrorKind::BadParam` if the count is 0 or above `MAX_COUNT`, and
+    /// `ErrorKind::Busy` if the channel is still running.
+    ///
+    /// # Safety
+    /// `source` and `destination` must be valid for `count` bytes for as long as
+    /// the channel runs, which is until `poll` or `wait` report it done, or `stop`
+    /// is called. The DMA controller writes memory behind the borrow checker's
+    /// back, so a buffer given here must not be used or dropped before then.
+    pub unsafe fn start(&mut self, transfer: &Transfer) -> Result<()> {
+        if transfer.count == 0 || transfer.count > MAX_COUNT {
+            return Err(ErrorKind::BadParam);
+        }
+
+        if self.is_active() {
+            return Err(ErrorKind::Busy);
+        }
+
+        self.reg.clear_count_to_zero_flag();
+        self.reg.clear_bus_error_flag();
+        self.reg.clear_timeout_flag();
+
+        self.reg.set_source(transfer.source as u32);
+        self.reg.set_destination(transfer.destination as u32);
+        self.reg.set_count(transfer.count as u32);
+
+        self.reg.modify_dma_ctrl(|ctrl| {
+            ctrl.reload_enable = false;
+            ctrl.priority = 0;
+            ctrl.request = transfer.request as u8;
+            ctrl.source_width = 0;
+            ctrl.source_increment = transfer.source_increment;
+            ctrl.destination_width = 0;
+            ctrl.destination_increment = transfer.destination_increment;
+            ctrl.burst_size = 0;
+        });
+        self.reg.set_enable(true);
+
+        Ok(())
+    }
+
+    /// # Is Active
+    /// Check if the channel is still running a transfer.
+    pub fn is_active(&self) -> bool {
+        self.reg.get_active()
+    }
+
+    /// # Remaining
+    /// The number of bytes the running (or stopped) transfer has left to move.
+    pub fn remaining(&self) -> usize {
+        self.reg.get_count() as usize
+    }
+
+    /// # Poll
+    /// Check if the transfer is done, clearing its done flag once it is.
+    ///
+    /// Returns `ErrorKind::ComError` if the channel stopped on a bus error.
+    pub fn poll(&mut self) -> crate::nb::Result<(), ErrorKind> {
+        if self.reg.is_bus_error_flag_active() {
+            unsafe { self.reg.clear_bus_error_flag() };
+            return Err(crate::nb::Error::Other(ErrorKind::ComError));
+        }
+
+        if self.reg.is_count_to_zero_flag_active() {
+            unsafe { self.reg.clear_count_to_zero_flag() };
+            return Ok(());
+        }
+
+        Err(crate::nb::Error::WouldBlock)
+    }
+
+    /// # Wait
+    /// Block until the transfer is done, checking at most `max_spins` times.
+    ///
+    /// Returns `ErrorKind::ComError` on a bus error, and `ErrorKind::TimeOut` if
+    /// the transfer is not done in time, in which case the channel is stopped.
+    pub fn wait(&mut self, max_spins: u32) -> Result<()> {
+        for _ in 0..max_spins {
+            match self.poll() {
+                Ok(()) => return Ok(()),
+                Err(crate::nb::Error::Other(err)) => return Err(err),
+                Err(crate::nb::Error::WouldBlock) => core::hint::spin_loop(),
+            }
+        }
+
+        self.stop();
+        Err(ErrorKind::TimeOut)
+    }
+
+    /// # Stop
+    /// Stop the channel, leaving `remaining` at however many bytes were not moved.
+    pub fn stop(&mut self) {
+        unsafe { self.reg.set_enable(false) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fake_channel(fake_registers: &mut [u32; 8]) -> Channel {
+        Channel::from_registers(Registers::new(fake_registers.as_mut_ptr() as usize), 0)
+    }
+
+    #[test]
+    fn start_programs_channel_test() {
+        let mut fake_registers = [0u32; 8];
+        let ptr = fake_registers.as_mut_ptr();
+        let mut channel = fake_channel(&mut fake_registers);
+
+        let transfer = Transfer {
+            request: Request::I2C1Transmit,
+            source: 0x2000_0010,
+            source_increment: true,
+            destination: 0x4001_e02c,
+            destination_increment: false,
+            count: 300,
+        };
+        assert_eq!(unsafe { channel.start(&transfer) }, Ok(()));
+
+        let word = |index: usize| unsafe { ptr.add(index).read_volatile() };
+        assert_eq!(word(0), (1 << 22) | (0x28 << 4) | 1);
+        assert_eq!(word(2), 0x2000_0010);
+        assert_eq!(word(3), 0x4001_e02c);
+        assert_eq!(word(4), 300);
+        assert_eq!(channel.remaining(), 300);
+    }
+
+    #[test]
+    fn start_checks_count_and_busy_test() {
+        let mut fake_registers = [0u32; 8];
+        let ptr = fake_registers.as_mut_ptr();
+        let mut channel = fake_channel(&mut fake_registers);
+        let mut transfer = Transfer {
+            request: Request::MemoryToMemory,
+            source: 0x2000_0000,
+            source_increment: true,
+            destination: 0x2000_1000,
+            destination_increment: true,
+            count: 0,
+        };
+
+        assert_eq!(
+            unsafe { channel.start(&transfer) },
+            Err(ErrorKind::BadParam)
+        );
+        transfer.count = MAX_COUNT + 1;
+        assert_eq!(
+            unsafe { channel.start(&transfer) },
+            Err(ErrorKind::BadParam)
+        );
+
+        transfer.count = MAX_COUNT;
+        unsafe { ptr.add(1).write_volatile(1) };
+        assert_eq!(unsafe { channel.start(&transfer) }, Err(ErrorKind::Busy));
+    }
+
+    #[test]
+    fn poll_and_wait_test() {
+        let mut fake_registers = [0u32; 8];
+        let ptr = fake_registers.as_mut_ptr();
+        let mut channel = fake_channel(&mut fake_registers);
+        let set_status = |status: u32| unsafe { ptr.add(1).write_volatile(status) };
+
+        assert!(matches!(channel.poll(), Err(crate::nb::Error::WouldBlock)));
+        unsafe { ptr.write_volatile(1) };
+        assert_eq!(channel.wait(10), Err(ErrorKind::TimeOut));
+        assert_eq!(unsafe { ptr.read_volatile() } & 1, 0);
+
+        set_status(1 << 2);
+        assert_eq!(channel.wait(10), Ok(()));
+
+        set_status(1 << 5);
+        assert!(matches!(
+            channel.poll(),
+            Err(crate::nb::Error::Other(ErrorKind::ComError))
+        ));
+
+        assert_eq!(Request::i2c_receive(2), Some(Request::I2C2Receive));
+        assert_eq!(Request::i2c_transmit(3), None);
+        assert_eq!(Request::uart_transmit(3), Some(Request::UART3Transmit));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::memory_map::mmio;
+use hal_macros::RW;
+use hal_macros_derive::make_device;
+
+/// # Channel Register Blocks
+/// Each DMA channel has its own block of registers, starting at `0x100` and
+/// `0x20` bytes apart, matching `mxc_dma_ch_regs_t` in the MSDK.
+pub const CHANNEL_0: usize = mmio::STANDARD_DMA + 0x100;
+pub const CHANNEL_1: usize = mmio::STANDARD_DMA + 0x120;
+pub const CHANNEL_2: usize = mmio::STANDARD_DMA + 0x140;
+pub const CHANNEL_3: usize = mmio::STANDARD_DMA + 0x160;
+
+/// # Relative Register Offsets
+/// The offsets of the registers within one channel block.
+pub(crate) mod rro {
+    /// # DMA Channel Control Register
+    pub const DMA_CTRL: usize = 0x0000;
+    /// # DMA Channel Status Register
+    pub const DMA_STATUS: usize = 0x0004;
+    /// # DMA Channel Source Register
+    pub const DMA_SRC: usize = 0x0008;
+    /// # DMA Channel Destination Register
+    pub const DMA_DST: usize = 0x000C;
+    /// # DMA Channel Count Register
+    pub const DMA_CNT: usize = 0x0010;
+}
+
+make_device! {
+    device_ports(CHANNEL_0, CHANNEL_1, CHANNEL_2, CHANNEL_3);
+
+    /// Channel Enable. `MXC_F_DMA_CTRL_EN`.
+    /// Start the channel. Cleared by hardware when the count reaches zero.
+    #[bit(0, RW, rro::DMA_CTRL)]
+    enable,
+
+    /// Reload Enable. `MXC_F_DMA_CTRL_RLDEN`.
+    #[bit(1, RW, rro::DMA_CTRL)]
+    reload_enable,
+
+    /// Channel Priority. `MXC_F_DMA_CTRL_PRI`.
+    /// - 0: Highest
+    /// - 3: Lowest
+    #[bit(2..=3, RW, rro::DMA_CTRL)]
+    priority,
+
+    /// Request Select. `MXC_F_DMA_CTRL_REQUEST`.
+    /// The peripheral whose requests pace this channel.
+    #[bit(4..=9, RW, rro::DMA_CTRL)]
+    request,
+
+    /// Source Width. `MXC_F_DMA_CTRL_SRCWD`.
+    /// - 0: Byte
+    /// - 1: Half word
+    /// - 2: Word
+    #[bit(16..=17, RW, rro::DMA_CTRL)]
+    source_width,
+
+    /// Source Increment. `MXC_F_DMA_CTRL_SRCINC`.
+    #[bit(22, RW, rro::DMA_CTRL)]
+    source_increment,
+
+    /// Destination Width. `MXC_F_DMA_CTRL_DSTWD`.
+    /// - 0: Byte
+    /// - 1: Half word
+    /// - 2: Word
+    #[bit(24..=25, RW, rro::DMA_CTRL)]
+    destination_width,
+
+    /// Destination Increment. `MXC_F_DMA_CTRL_DSTINC`.
+    #[bit(27, RW, rro::DMA_CTRL)]
+    destination_increment,
+
+    /// Burst Size. `MXC_F_DMA_CTRL_BURST_SIZE`.
+    /// Bytes moved per request, minus one.
+    #[bit(28..=31, RW, rro::DMA_CTRL)]
+    burst_size,
+
+    /// Channel Active. `MXC_F_DMA_STATUS_STATUS`.
+    #[bit(0, RO, rro::DMA_STATUS)]
+    active,
+
+    /// Count To Zero Flag. `MXC_F_DMA_STATUS_CTZ_IF`.
+    /// Set once the count reaches zero and the transfer is done.
+    #[bit(2, RW1C, rro::DMA_STATUS)]
+    count_to_zero_flag,
+
+    /// Bus Error Flag. `MXC_F_DMA_STATUS_BUS_ERR`.
+    /// Set when the channel hit a bus error and stopped.
+    #[bit(5, RW1C, rro::DMA_STATUS)]
+    bus_error_flag,
+
+    /// Timeout Flag. `MXC_F_DMA_STATUS_TO_IF`.
+    #[bit(6, RW1C, rro::DMA_STATUS)]
+    timeout_flag,
+
+    /// Source Address. `MXC_F_DMA_SRC_ADDR`.
+    #[bit(0..=31, RW, rro::DMA_SRC)]
+    source,
+
+    /// Destination Address. `MXC_F_DMA_DST_ADDR`.
+    #[bit(0..=31, RW, rro::DMA_DST)]
+    destination,
+
+    /// Count. `MXC_F_DMA_CNT_CNT`.
+    /// Bytes left to move, counting down to zero.
+    #[bit(0..=23, RW, rro::DMA_CNT)]
+    count,
+}
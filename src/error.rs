@@ -2,7 +2,7 @@
 /// Errors for states internal to the MAX78000 microcontroller.
 #[non_exhaustive]
 #[repr(u8)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ErrorKind {
     /// # Null Ptr
     /// Some critical ptr was found to be null, and could not continue.
@@ -99,6 +99,60 @@ impl core::fmt::Debug for ErrorKind {
     }
 }
 
+impl core::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::NullPtr => "null pointer",
+            Self::NoDevice => "no such device",
+            Self::BadParam => "bad parameter",
+            Self::Invalid => "invalid operation or value",
+            Self::Uninitialized => "not initialized",
+            Self::Busy => "hardware busy",
+            Self::BadState => "bad state",
+            Self::Unknown => "unknown error",
+            Self::ComError => "communication error",
+            Self::TimeOut => "timed out",
+            Self::NoResponse => "no response",
+            Self::Overflow => "overflow",
+            Self::Underflow => "underflow",
+            Self::NoneAvailable => "none available",
+            Self::Shutdown => "shut down",
+            Self::Abort => "aborted",
+            Self::NotSupported => "not supported",
+            Self::Fail => "operation failed",
+        })
+    }
+}
+
+impl core::error::Error for ErrorKind {}
+
 /// # Result
 /// Result type that includes the `ErrorKind` enum as error.
 pub type Result<T> = core::result::Result<T, ErrorKind>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn error_kind_display_test() {
+        assert_eq!(std::format!("{}", ErrorKind::NullPtr), "null pointer");
+        assert_eq!(
+            std::format!("{}", ErrorKind::ComError),
+            "communication error"
+        );
+
+        // The compact `Debug` stays as is.
+        #[cfg(debug_assertions)]
+        assert_eq!(std::format!("{:?}", ErrorKind::ComError), "CE");
+    }
+
+    #[test]
+    fn error_kind_boxed_error_test() {
+        fn fails() -> core::result::Result<(), std::boxed::Box<dyn core::error::Error>> {
+            Err(ErrorKind::TimeOut)?
+        }
+
+        assert_eq!(std::format!("{}", fails().unwrap_err()), "timed out");
+    }
+}
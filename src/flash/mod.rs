@@ -0,0 +1,443 @@
+//! # Flash
+//! Erasing and writing the internal flash from the running program, for keeping
+//! things like calibration data across resets.
+//!
+//! # Danger
+//! The program itself runs from this flash. Erasing or writing a page that holds
+//! code, the vector table, or constants still in use corrupts the running
+//! program, and nothing here can tell which pages those are. Keep data in pages
+//! the linker script leaves out of the program, usually the last pages of flash.
+//!
+//! Interrupts are disabled for each write or erase, so nothing else runs until it
+//! is done. The vendor SDK runs its flash routines from SRAM, while these run from
+//! wherever the program is linked, usually the flash being written. That is why
+//! writing and erasing are `unsafe`.
+
+pub mod registers;
+
+use crate::critical_section;
+use crate::error::{ErrorKind, Result};
+use crate::gcr::flush_instruction_cache;
+use crate::memory_map::mmio;
+use core::sync::atomic::AtomicBool;
+use registers::Registers;
+
+/// The address the internal flash starts at.
+pub const FLASH_BASE: usize = 0x1000_0000;
+
+/// The size of the internal flash in bytes, 512KiB.
+pub const FLASH_SIZE: usize = 0x8_0000;
+
+/// The size of one erasable page in bytes, 8KiB.
+pub const PAGE_SIZE: usize = 0x2000;
+
+/// The flash is written 128 bits at a time, so writes are 16 byte aligned.
+pub const WRITE_SIZE: usize = 16;
+
+/// The `unlock` value that lets the controller start writes and erases.
+const FLASH_UNLOCK_CODE: u8 = 0x2;
+
+/// The `erase_code` value that allows a page erase.
+const PAGE_ERASE_CODE: u8 = 0x55;
+
+/// The controller clock, which writes and erases are timed from.
+const FLASH_CLOCK_HZ: u32 = 1_000_000;
+
+/// How many times a write or erase is checked for completion before giving up. A
+/// page erase takes tens of milliseconds.
+const FLASH_BUSY_SPINS: u32 = 10_000_000;
+
+/// Set once `Flash::init` has handed out the controller.
+static FLASH_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// # Flash
+/// The flash controller, made with `Flash::init`. There is only ever one handle,
+/// since two could interleave the steps of a write.
+pub struct Flash {
+    reg: Registers,
+}
+
+impl Flash {
+    /// # Init
+    /// Set the controller clock divisor from `SYSTEM_CORE_CLOCK`. `update_clock`
+    /// has to be called if the system clock changes, or writes and erases are
+    /// timed wrong.
+    ///
+    /// Returns `ErrorKind::Busy` if the controller was already handed out, and
+    /// `ErrorKind::BadParam` if the system clock is too slow or too fast to divide
+    /// down to the 1MHz the controller needs.
+    pub fn init() -> Result<Self> {
+        crate::util::init_once(&FLASH_TAKEN, || {
+            let mut flash = Self {
+                reg: Registers::new(mmio::FLASH_CONTROLLER_0),
+            };
+
+            flash.update_clock()?;
+            Ok(flash)
+        })
+    }
+
+    /// # Update Clock
+    /// Set the controller clock divisor from `SYSTEM_CORE_CLOCK` again, after the
+    /// system clock changed. Returns the same errors as `init`, other than
+    /// `ErrorKind::Busy`.
+    pub fn update_clock(&mut self) -> Result<()> {
+        self.set_clock(unsafe { crate::SYSTEM_CORE_CLOCK })
+    }
+
+    fn set_clock(&mut self, system_clock_hz: u32) -> Result<()> {
+        let divisor = system_clock_hz / FLASH_CLOCK_HZ;
+        if divisor == 0 || divisor > u8::MAX as u32 {
+            return Err(ErrorKind::BadParam);
+        }
+
+        unsafe { self.reg.set_clock_divisor(divisor as u8) };
+        Ok(())
+    }
+
+    /// # Page Erase
+    /// Erase the page holding `addr`, setting all of its bytes to `0xFF`.
+    ///
+    /// Returns `ErrorKind::BadParam` if `addr` is outside the flash or not the
+    /// start of a page, `ErrorKind::BadState` if the controller refused the erase,
+    /// and `ErrorKind::TimeOut` if it never finished.
+    ///
+    /// # Safety
+    /// The page must not hold any code, the vector table, or data that is still
+    /// in use, including anything a reference points into. See the module docs.
+    pub unsafe fn page_erase(&mut self, addr: usize) -> Result<()> {
+        check_range(addr, PAGE_SIZE, PAGE_SIZE)?;
+
+        self.run(|reg| unsafe {
+            reg.set_address(addr as u32);
+            reg.set_erase_code(PAGE_ERASE_CODE);
+            reg.set_page_erase(true);
+        })
+    }
+
+    /// # Write Word
+    /// Write `word` to the 4 bytes at `addr`. The other 12 bytes of its 16 byte
+    /// line are written with ones, which leaves them as they are.
+    ///
+    /// Writing can only clear bits, so `addr` should be erased first unless `word`
+    /// only clears bits that are still set.
+    ///
+    /// Returns `ErrorKind::BadParam` if `addr` is outside the flash or not 4 byte
+    /// aligned, otherwise the same errors as `write`.
+    ///
+    /// # Safety
+    /// The same as `write`, for the 16 byte line holding `addr`.
+    pub unsafe fn write_word(&mut self, addr: usize, word: u32) -> Result<()> {
+        check_range(addr, 4, 4)?;
+
+        let line = addr & !(WRITE_SIZE - 1);
+        let mut words = [u32::MAX; WRITE_SIZE / 4];
+        words[(addr - line) / 4] = word;
+        self.write_line(line, words)
+    }
+
+    /// # Write
+    /// Write `data` to the flash starting at `addr`, 16 bytes at a time. As with
+    /// `write_word`, the flash being written should be erased first.
+    ///
+    /// Returns `ErrorKind::BadParam` if `data` does not fit in the flash from
+    /// `addr`, `addr` is not 16 byte aligned, or `data` is not a whole number of
+    /// 16 byte lines. Returns `ErrorKind::BadState` if the controller refused a
+    /// write, and `ErrorKind::TimeOut` if one never finished, in both cases
+    /// leaving the lines before it written.
+    ///
+    /// # Safety
+    /// The flash being written must not hold any code, the vector table, or data
+    /// that is still in use, including anything a reference points into. See the
+    /// module docs.
+    pub unsafe fn write(&mut self, addr: usize, data: &[u8]) -> Result<()> {
+        if !data.len().is_multiple_of(WRITE_SIZE) {
+            return Err(ErrorKind::BadParam);
+        }
+        check_range(addr, data.len(), WRITE_SIZE)?;
+
+        for (line, chunk) in data.chunks_exact(WRITE_SIZE).enumerate() {
+            let words = core::array::from_fn(|word| {
+                u32::from_le_bytes(chunk[word * 4..word * 4 + 4].try_into().unwrap())
+            });
+            self.write_line(addr + line * WRITE_SIZE, words)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the 16 byte line at `line`, which is already checked.
+    fn write_line(&mut self, line: usize, words: [u32; WRITE_SIZE / 4]) -> Result<()> {
+        self.run(|reg| unsafe {
+            reg.set_address(line as u32);
+            reg.set_erase_code(0);
+            reg.set_data_0(words[0]);
+            reg.set_data_1(words[1]);
+            reg.set_data_2(words[2]);
+            reg.set_data_3(words[3]);
+            reg.set_write(true);
+        })
+    }
+
+    /// Check if a write or erase is still running.
+    fn busy(&self) -> bool {
+        self.reg.get_write() || self.reg.get_page_erase() || self.reg.get_pending()
+    }
+
+    /// Unlock the controller, `start` a write or erase, wait for it and lock the
+    /// controller again, all with interrupts disabled so nothing can run from (or
+    /// read) the flash halfway through.
+    fn run(&mut self, start: impl FnOnce(&mut Registers)) -> Result<()> {
+        critical_section::with(|_| {
+            if !hal_macros::spin_until(FLASH_BUSY_SPINS, || !self.busy()) {
+                return Err(ErrorKind::TimeOut);
+            }
+
+            unsafe {
+                self.reg.set_done_flag(false);
+                self.reg.set_access_fail_flag(false);
+                self.reg.set_unlock(FLASH_UNLOCK_CODE);
+            }
+
+            start(&mut self.reg);
+            let done = hal_macros::spin_until(FLASH_BUSY_SPINS, || !self.busy());
+
+            unsafe { self.reg.set_unlock(0) };
+
+            if !done {
+                return Err(ErrorKind::TimeOut);
+            }
+
+            if self.reg.get_access_fail_flag() {
+                unsafe { self.reg.set_access_fail_flag(false) };
+                return Err(ErrorKind::BadState);
+            }
+
+            flush_instruction_cache()
+        })
+    }
+}
+
+/// Check that the `len` bytes at `addr` are all in the flash, with `addr` a
+/// multiple of `align`.
+fn check_range(addr: usize, len: usize, align: usize) -> Result<()> {
+    let in_flash = addr >= FLASH_BASE
+        && addr
+            .checked_add(len)
+            .is_some_and(|end| end <= FLASH_BASE + FLASH_SIZE);
+
+    if !in_flash || !addr.is_multiple_of(align) {
+        return Err(ErrorKind::BadParam);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tests::FakeRegisters;
+    use core::sync::atomic::Ordering;
+    use hal_macros::access_log::Access;
+    use registers::rro::{FLC_ADDR, FLC_CLKDIV, FLC_CTRL, FLC_DATA0, FLC_INTR};
+
+    const WRITE: u32 = 1 << 0;
+    const PAGE_ERASE: u32 = 1 << 2;
+    const UNLOCKED: u32 = (FLASH_UNLOCK_CODE as u32) << 28;
+    const ACCESS_FAIL_FLAG: u32 = 1 << 1;
+    const DONE_FLAG: u32 = 1 << 0;
+    /// The instruction cache flush bit of `GCR_SYSCTRL`.
+    const ICC0_CACHE_FLUSH: u32 = 1 << 6;
+
+    /// Fake flash controller registers, shared with a thread playing the
+    /// controller.
+    type FakeFlash = FakeRegisters<16>;
+
+    fn fake_flash(fake_registers: &FakeFlash) -> Flash {
+        Flash {
+            reg: Registers::new(fake_registers.address()),
+        }
+    }
+
+    /// Play the controller on another thread until `stop` is set, returning the
+    /// address, control word and data of each write or erase it saw started. Each
+    /// one started while unlocked is finished, and refused with the access fail
+    /// flag while `refuse` is set.
+    fn spawn_fake_controller(
+        fake: &'static FakeFlash,
+        refuse: &'static AtomicBool,
+        stop: &'static AtomicBool,
+    ) -> std::thread::JoinHandle<std::vec::Vec<(u32, u32, [u32; 4])>> {
+        std::thread::spawn(move || {
+            let mut started = std::vec::Vec::new();
+
+            while !stop.load(Ordering::SeqCst) {
+                let ctrl = fake.word(FLC_CTRL);
+                if ctrl & (WRITE | PAGE_ERASE) != 0 && ctrl & UNLOCKED == UNLOCKED {
+                    let data = core::array::from_fn(|n| fake.word(FLC_DATA0 + n * 4));
+                    started.push((fake.word(FLC_ADDR), ctrl, data));
+
+                    // The flags are set before the busy bits clear, like the
+                    // controller finishing.
+                    let flags = if refuse.load(Ordering::SeqCst) {
+                        ACCESS_FAIL_FLAG | DONE_FLAG
+                    } else {
+                        DONE_FLAG
+                    };
+                    fake.set_bits(FLC_INTR, flags);
+                    fake.clear_bits(FLC_CTRL, WRITE | PAGE_ERASE);
+                }
+
+                std::thread::yield_now();
+            }
+
+            started
+        })
+    }
+
+    #[test]
+    fn alignment_and_range_test() {
+        let fake = FakeRegisters::new();
+        let mut flash = fake_flash(&fake);
+        let last_page = FLASH_BASE + FLASH_SIZE - PAGE_SIZE;
+
+        assert_eq!(
+            unsafe { flash.page_erase(last_page + 4) },
+            Err(ErrorKind::BadParam)
+        );
+        assert_eq!(
+            unsafe { flash.page_erase(FLASH_BASE + FLASH_SIZE) },
+            Err(ErrorKind::BadParam)
+        );
+        assert_eq!(
+            unsafe { flash.page_erase(0x2000_0000) },
+            Err(ErrorKind::BadParam)
+        );
+        assert_eq!(
+            unsafe { flash.write_word(last_page + 2, 0) },
+            Err(ErrorKind::BadParam)
+        );
+        assert_eq!(
+            unsafe { flash.write(last_page + 4, &[0; 16]) },
+            Err(ErrorKind::BadParam)
+        );
+        assert_eq!(
+            unsafe { flash.write(last_page, &[0; 12]) },
+            Err(ErrorKind::BadParam)
+        );
+        assert_eq!(
+            unsafe { flash.write(FLASH_BASE + FLASH_SIZE - 16, &[0; 32]) },
+            Err(ErrorKind::BadParam)
+        );
+        assert_eq!(unsafe { flash.write(last_page, &[]) }, Ok(()));
+
+        // Nothing was started.
+        assert_eq!(fake.word(FLC_CTRL), 0);
+
+        assert_eq!(flash.set_clock(100_000_000), Ok(()));
+        assert_eq!(fake.word(FLC_CLKDIV), 100);
+        assert_eq!(flash.set_clock(999_999), Err(ErrorKind::BadParam));
+        assert_eq!(flash.set_clock(256_000_000), Err(ErrorKind::BadParam));
+    }
+
+    #[test]
+    fn write_and_erase_sequence_test() {
+        static FAKE: FakeFlash = FakeRegisters::new();
+        static REFUSE: AtomicBool = AtomicBool::new(false);
+        static STOP: AtomicBool = AtomicBool::new(false);
+        let mut flash = fake_flash(&FAKE);
+        let last_page = FLASH_BASE + FLASH_SIZE - PAGE_SIZE;
+
+        // With nothing playing the controller the write bit never clears, so the
+        // write times out once it has been started, and the controller is locked
+        // again.
+        assert_eq!(
+            unsafe { flash.write_word(last_page + 8, 0x1234_5678) },
+            Err(ErrorKind::TimeOut)
+        );
+        assert_eq!(FAKE.word(FLC_ADDR), last_page as u32);
+        assert_eq!(FAKE.word(FLC_CTRL), WRITE);
+
+        // Busy from the last write, so the erase is never started.
+        assert_eq!(
+            unsafe { flash.page_erase(last_page) },
+            Err(ErrorKind::TimeOut)
+        );
+        assert_eq!(FAKE.word(FLC_CTRL), WRITE);
+
+        FAKE.reset();
+        let controller = spawn_fake_controller(&FAKE, &REFUSE, &STOP);
+
+        // The cache flush only waits a short while, too short to count on another
+        // thread being scheduled, so it is finished from the access hook instead,
+        // as its bit is polled.
+        let flushes = std::rc::Rc::new(core::cell::Cell::new(0));
+        crate::tests::on_register_access({
+            let flushes = flushes.clone();
+            move |access| {
+                let sysctrl = crate::gcr::fake_gcr_sysctrl();
+                if access == Access::Read(sysctrl.as_ptr() as usize)
+                    && sysctrl.fetch_and(!ICC0_CACHE_FLUSH, Ordering::SeqCst) & ICC0_CACHE_FLUSH
+                        != 0
+                {
+                    flushes.set(flushes.get() + 1);
+                }
+            }
+        });
+
+        assert_eq!(
+            unsafe { flash.write_word(last_page + 8, 0x1234_5678) },
+            Ok(())
+        );
+        assert_eq!(unsafe { flash.page_erase(last_page) }, Ok(()));
+        let bytes: std::vec::Vec<u8> = (0..32).collect();
+        assert_eq!(unsafe { flash.write(last_page + 16, &bytes) }, Ok(()));
+        // Locked again after each one.
+        assert_eq!(FAKE.word(FLC_CTRL) & UNLOCKED, 0);
+
+        // A refused write is a bad state, with its flag cleared and the lines
+        // after it not started.
+        REFUSE.store(true, Ordering::SeqCst);
+        assert_eq!(
+            unsafe { flash.write(last_page + 48, &bytes) },
+            Err(ErrorKind::BadState)
+        );
+        assert_eq!(FAKE.word(FLC_INTR) & ACCESS_FAIL_FLAG, 0);
+        assert_eq!(FAKE.word(FLC_CTRL) & UNLOCKED, 0);
+
+        STOP.store(true, Ordering::SeqCst);
+        assert_eq!(
+            controller.join().unwrap(),
+            [
+                (
+                    last_page as u32,
+                    UNLOCKED | WRITE,
+                    [u32::MAX, u32::MAX, 0x1234_5678, u32::MAX]
+                ),
+                (
+                    last_page as u32,
+                    UNLOCKED | (u32::from(PAGE_ERASE_CODE) << 8) | PAGE_ERASE,
+                    [u32::MAX, u32::MAX, 0x1234_5678, u32::MAX]
+                ),
+                (
+                    last_page as u32 + 16,
+                    UNLOCKED | WRITE,
+                    [0x0302_0100, 0x0706_0504, 0x0B0A_0908, 0x0F0E_0D0C]
+                ),
+                (
+                    last_page as u32 + 32,
+                    UNLOCKED | WRITE,
+                    [0x1312_1110, 0x1716_1514, 0x1B1A_1918, 0x1F1E_1D1C]
+                ),
+                (
+                    last_page as u32 + 48,
+                    UNLOCKED | WRITE,
+                    [0x0302_0100, 0x0706_0504, 0x0B0A_0908, 0x0F0E_0D0C]
+                ),
+            ]
+        );
+        // The instruction cache is flushed after each finished write or erase,
+        // but not after the refused one.
+        assert_eq!(flushes.get(), 4);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::memory_map::mmio;
+use hal_macros::RW;
+use hal_macros_derive::make_device;
+
+/// # Flash Controller Register Offsets
+/// See Max 78000 User Guide, Flash Controller Registers.
+pub(super) mod rro {
+    /// # Flash Controller Address Pointer Register
+    pub const FLC_ADDR: usize = 0x0000;
+    /// # Flash Controller Clock Divisor Register
+    pub const FLC_CLKDIV: usize = 0x0004;
+    /// # Flash Controller Control Register
+    pub const FLC_CTRL: usize = 0x0008;
+    /// # Flash Controller Interrupt Register
+    pub const FLC_INTR: usize = 0x0024;
+    /// # Flash Controller Data Register 0
+    pub const FLC_DATA0: usize = 0x0030;
+    /// # Flash Controller Data Register 1
+    pub const FLC_DATA1: usize = 0x0034;
+    /// # Flash Controller Data Register 2
+    pub const FLC_DATA2: usize = 0x0038;
+    /// # Flash Controller Data Register 3
+    pub const FLC_DATA3: usize = 0x003C;
+}
+
+make_device! {
+    device_ports(mmio::FLASH_CONTROLLER_0);
+
+    /// Flash Address. The flash address the next write or page erase acts on.
+    #[bit(0..=31, RW, rro::FLC_ADDR)]
+    address,
+
+    /// Flash Clock Divisor. The system clock is divided by this to clock the flash
+    /// controller, which must run at 1MHz for writes and erases to be timed right.
+    #[bit(0..=7, RW, rro::FLC_CLKDIV)]
+    clock_divisor,
+
+    /// Flash Unlock. Writes and erases are only started while this is
+    /// `FLASH_UNLOCK_CODE`, any other value locks the controller.
+    #[bit(28..=31, RW, rro::FLC_CTRL)]
+    unlock,
+
+    /// Flash Busy. Set while a write or erase is in progress.
+    #[bit(24, RO, rro::FLC_CTRL)]
+    pending,
+
+    /// Erase Code. Must be `PAGE_ERASE_CODE` before a page erase is started, and 0
+    /// for a write.
+    #[bit(8..=15, RW, rro::FLC_CTRL)]
+    erase_code,
+
+    /// Page Erase. Setting this erases the page at `address`, and it clears once
+    /// the erase is done.
+    #[bit(2, RW, rro::FLC_CTRL)]
+    page_erase,
+
+    /// Write. Setting this writes the 128 bits of the data registers to `address`,
+    /// and it clears once the write is done.
+    #[bit(0, RW, rro::FLC_CTRL)]
+    write,
+
+    /// Access Fail Flag. Set when a write or erase was refused, like one started
+    /// while locked or on a protected page. Cleared by writing 0.
+    #[bit(1, RW, rro::FLC_INTR)]
+    access_fail_flag,
+
+    /// Done Flag. Set when a write or erase finishes. Cleared by writing 0.
+    #[bit(0, RW, rro::FLC_INTR)]
+    done_flag,
+
+    /// Flash Data 0. Bits 0-31 of the 128 bits to write.
+    #[no_dump]
+    #[bit(0..=31, RW, rro::FLC_DATA0)]
+    data_0,
+
+    /// Flash Data 1. Bits 32-63 of the 128 bits to write.
+    #[no_dump]
+    #[bit(0..=31, RW, rro::FLC_DATA1)]
+    data_1,
+
+    /// Flash Data 2. Bits 64-95 of the 128 bits to write.
+    #[no_dump]
+    #[bit(0..=31, RW, rro::FLC_DATA2)]
+    data_2,
+
+    /// Flash Data 3. Bits 96-127 of the 128 bits to write.
+    #[no_dump]
+    #[bit(0..=31, RW, rro::FLC_DATA3)]
+    data_3,
+}
@@ -1,22 +1,110 @@
+use crate::critical_section::{self, Mutex};
+use crate::error::{ErrorKind, Result};
 use crate::memory_map::mmio;
+use crate::scs;
+use core::cell::RefCell;
 
 pub mod registers;
+pub mod trims;
 
-static mut GLOBAL_CONTROL_REGISTER: Option<registers::Registers> = None;
+static GLOBAL_CONTROL_REGISTER: Mutex<RefCell<Option<GlobalControl>>> =
+    Mutex::new(RefCell::new(None));
+
+struct GlobalControl {
+    gcr: registers::Registers,
+    lpgcr: registers::lpgcr::Registers,
+}
+
+// The registers are only ever reached through `GLOBAL_CONTROL_REGISTER` inside a
+// critical section.
+unsafe impl Send for GlobalControl {}
 
 /// # Setup Global Control Register
 /// Init the global control register, allows you to call other functions on the global
 /// control register.
 #[inline(always)]
 pub fn init_global_control_register() {
-    if unsafe { GLOBAL_CONTROL_REGISTER.is_none() } {
-        unsafe { GLOBAL_CONTROL_REGISTER = Some(registers::Registers::new(mmio::GLOBAL_CONTROL)) };
-    }
+    with_global_control(|_| ());
+}
+
+/// Run `func` on the global control registers inside a critical section, so
+/// interrupts on this core never interleave their read-modify-write of a
+/// register. The critical section only masks PRIMASK on the Cortex-M4, it does
+/// not stop the RISC-V core from writing these registers at the same time.
+#[inline(always)]
+fn with_global_control<R>(func: impl FnOnce(&mut GlobalControl) -> R) -> R {
+    critical_section::with(|cs| {
+        let mut control = GLOBAL_CONTROL_REGISTER.borrow(cs).borrow_mut();
+        func(control.get_or_insert_with(|| GlobalControl {
+            gcr: registers::Registers::new(gcr_ports().0),
+            lpgcr: registers::lpgcr::Registers::new(gcr_ports().1),
+        }))
+    })
 }
 
 #[inline(always)]
-fn ensure_gcr() {
-    init_global_control_register();
+fn with_gcr<R>(func: impl FnOnce(&mut registers::Registers) -> R) -> R {
+    with_global_control(|control| func(&mut control.gcr))
+}
+
+/// The base addresses of the GCR and the low power GCR.
+#[cfg(not(test))]
+fn gcr_ports() -> (usize, usize) {
+    (mmio::GLOBAL_CONTROL, mmio::LOW_POWER_CONTROL)
+}
+
+#[cfg(test)]
+const FAKE_GCR_WORDS: usize = 0x24;
+
+/// While testing there is no GCR hardware, so the GCR and low power GCR are
+/// backed by plain memory instead.
+#[cfg(test)]
+static FAKE_GCR: [[core::sync::atomic::AtomicU32; FAKE_GCR_WORDS]; 2] =
+    [const { [const { core::sync::atomic::AtomicU32::new(0) }; FAKE_GCR_WORDS] }; 2];
+
+#[cfg(test)]
+fn gcr_ports() -> (usize, usize) {
+    (FAKE_GCR[0].as_ptr() as usize, FAKE_GCR[1].as_ptr() as usize)
+}
+
+/// Run `func` in a critical section, and put the fake GCR back the way it was
+/// after, for tests of other drivers that go through the GCR (like a
+/// `peripheral_reset` that never finishes on the fake).
+#[cfg(test)]
+pub(crate) fn with_fake_gcr_restored<R>(func: impl FnOnce() -> R) -> R {
+    critical_section::with(|_| {
+        let original = test::fake_gcr_snapshot();
+        let result = func();
+        test::fake_gcr_restore(original);
+        result
+    })
+}
+
+/// The fake `GCR_SYSCTRL` (the first GCR word), for tests that play the
+/// hardware clearing its bits, like an instruction cache flush finishing.
+#[cfg(test)]
+pub(crate) fn fake_gcr_sysctrl() -> &'static core::sync::atomic::AtomicU32 {
+    &FAKE_GCR[0][0]
+}
+
+/// The base address of the Cortex-M system control space.
+#[cfg(not(test))]
+fn scs_port() -> usize {
+    scs::SYSTEM_CONTROL_SPACE
+}
+
+/// The words of the system control space the fake covers, up to `SCB_SCR`.
+#[cfg(test)]
+const FAKE_SCS_WORDS: usize = 0x0D10 / 4 + 1;
+
+/// The system control space is faked the same way as the GCR.
+#[cfg(test)]
+static FAKE_SCS: [core::sync::atomic::AtomicU32; FAKE_SCS_WORDS] =
+    [const { core::sync::atomic::AtomicU32::new(0) }; FAKE_SCS_WORDS];
+
+#[cfg(test)]
+fn scs_port() -> usize {
+    FAKE_SCS.as_ptr() as usize
 }
 
 /// # Hardware Source
@@ -52,28 +140,102 @@ pub enum HardwareSource {
     CPU1,
     WDT1,
     LPCOMP,
+    UART3,
+}
+
+impl HardwareSource {
+    /// # Base Address
+    /// Get the `mmio` base address of this device's registers, or `None` if it is
+    /// not a memory mapped peripheral (like the second CPU core).
+    pub const fn base_address(&self) -> Option<usize> {
+        Some(match self {
+            HardwareSource::GPIO0 => mmio::GPIO_PORT_0,
+            HardwareSource::GPIO1 => mmio::GPIO_PORT_1,
+            HardwareSource::DMA => mmio::STANDARD_DMA,
+            HardwareSource::SPI1 => mmio::SPI_1,
+            HardwareSource::UART0 => mmio::UART_0,
+            HardwareSource::UART1 => mmio::UART_1,
+            HardwareSource::I2C0 => mmio::I2C_PORT_0,
+            HardwareSource::TMR0 => mmio::TIMER_0,
+            HardwareSource::TMR1 => mmio::TIMER_1,
+            HardwareSource::TMR2 => mmio::TIMER_2,
+            HardwareSource::TMR3 => mmio::TIMER_3,
+            HardwareSource::ADC => mmio::ADC,
+            HardwareSource::CNN => mmio::CNN_GLOBAL_CONTROL,
+            HardwareSource::I2C1 => mmio::I2C_PORT_1,
+            HardwareSource::PT => mmio::PULSE_TRAIN_ENGINE,
+            HardwareSource::UART2 => mmio::UART_2,
+            HardwareSource::TRNG => mmio::TRNG,
+            HardwareSource::SMPHR => mmio::SEMAPHORE,
+            HardwareSource::OWIRE => mmio::ONE_WIRE_MASTER,
+            HardwareSource::CRC => mmio::CRC,
+            HardwareSource::AES => mmio::AES,
+            HardwareSource::I2S => mmio::I2S,
+            HardwareSource::SPI0 => mmio::SPI_0,
+            HardwareSource::I2C2 => mmio::I2C_PORT_2,
+            HardwareSource::WDT0 => mmio::WATCHDOG_TIMER0,
+            HardwareSource::CPU1 => return None,
+            HardwareSource::WDT1 => mmio::LOW_POWER_WATCHDOG_TIMER_0,
+            HardwareSource::LPCOMP => mmio::LOW_POWER_COMPARATORS,
+            HardwareSource::UART3 => mmio::LOW_POWER_UART_0,
+        })
+    }
 }
 
 /// # Controller Reset
 /// Preform a complete reset of the controller.
 pub fn controller_reset() -> ! {
-    ensure_gcr();
-    unsafe {
-        GLOBAL_CONTROL_REGISTER
-            .as_mut()
-            .unwrap()
-            .activate_system_reset()
-    };
-    loop {}
+    with_gcr(|gcr| unsafe { gcr.activate_system_reset() });
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// The value `reboot_to_bootloader` leaves in the general purpose register to
+/// ask early startup code to enter the bootloader.
+pub const BOOTLOADER_REQUEST_MAGIC: u32 = 0xB007_10AD;
+
+/// # Get Scratch Register
+/// Read the general purpose (scratch) register. This register is not used by the
+/// hardware, and keeps its value across a system reset.
+pub fn get_scratch_register() -> u32 {
+    with_gcr(|gcr| gcr.get_general_purpose_register())
+}
+
+/// # Set Scratch Register
+/// Write the general purpose (scratch) register.
+pub fn set_scratch_register(value: u32) {
+    with_gcr(|gcr| unsafe { gcr.set_general_purpose_register(value) });
+}
+
+/// # Reboot To Bootloader
+/// Write `BOOTLOADER_REQUEST_MAGIC` to the scratch register and reset the
+/// controller. Early startup code should call `check_bootloader_request` and
+/// jump to the bootloader when it returns `true`.
+///
+/// This relies on the general purpose register surviving the system reset done
+/// by `controller_reset`; a power-on reset clears it and the request is lost.
+pub fn reboot_to_bootloader() -> ! {
+    set_scratch_register(BOOTLOADER_REQUEST_MAGIC);
+    controller_reset()
+}
+
+/// # Check Bootloader Request
+/// Check if the last reset came from `reboot_to_bootloader`, i.e. the scratch
+/// register holds exactly `BOOTLOADER_REQUEST_MAGIC`. The request is cleared
+/// (the scratch register is set to 0) so the next reset boots normally.
+pub fn check_bootloader_request() -> bool {
+    let requested = get_scratch_register() == BOOTLOADER_REQUEST_MAGIC;
+    if requested {
+        set_scratch_register(0);
+    }
+    requested
 }
 
 /// # System Clock Enable
 /// Enable/Disable a `HardwareSource`'s clock.
 pub fn system_clock_enable(clock: HardwareSource, enable: bool) {
-    ensure_gcr();
-
-    let gcr = unsafe { GLOBAL_CONTROL_REGISTER.as_mut().unwrap() };
-    unsafe {
+    with_global_control(|GlobalControl { gcr, lpgcr }| unsafe {
         match clock {
             HardwareSource::GPIO0 => gcr.set_gpio0_port_and_pad_logic_clock_disable(!enable),
             HardwareSource::GPIO1 => gcr.set_gpio1_port_and_pad_logic_clock_disable(!enable),
@@ -101,19 +263,42 @@ pub fn system_clock_enable(clock: HardwareSource, enable: bool) {
             HardwareSource::SPI0 => gcr.set_spi0_clock_disable(!enable),
             HardwareSource::WDT0 => gcr.set_watchdog_timer0_disable(!enable),
             HardwareSource::CPU1 => gcr.set_cpu1_risv32_clock_disable(!enable),
-            HardwareSource::WDT1 => gcr.set_watchdog_timer0_disable(!enable),
-            HardwareSource::LPCOMP => gcr.set_adc_clock_disable(!enable),
+            HardwareSource::WDT1 => lpgcr.set_watchdog_timer1_clock_disable(!enable),
+            HardwareSource::LPCOMP => lpgcr.set_low_power_comparator_clock_disable(!enable),
+            HardwareSource::UART3 => lpgcr.set_low_power_uart_clock_disable(!enable),
         }
-    }
+    });
 }
 
+/// How many times `peripheral_reset` polls the reset status before giving up.
+const RESET_SPINS: u32 = 100_000;
+
 /// # Peripheral Reset
 /// Reset the given device to default settings and configuration.
-pub fn peripheral_reset(device: HardwareSource) {
-    ensure_gcr();
+///
+/// Returns `ErrorKind::TimeOut` if the reset does not finish, for example if the
+/// reset bit never clears because the device is held in reset some other way.
+pub fn peripheral_reset(device: HardwareSource) -> Result<()> {
+    start_reset(device);
 
-    let gcr = unsafe { GLOBAL_CONTROL_REGISTER.as_mut().unwrap() };
-    unsafe {
+    // Wait until reset is complete, outside the critical section so interrupts
+    // are not held off for the whole reset.
+    let done = hal_macros::spin_until(RESET_SPINS, || {
+        with_global_control(|GlobalControl { gcr, lpgcr }| {
+            gcr.get_reset_status0() | gcr.get_reset_status1() | lpgcr.get_reset_status()
+        }) == 0
+    });
+
+    if done {
+        Ok(())
+    } else {
+        Err(ErrorKind::TimeOut)
+    }
+}
+
+/// Set the reset bit of `device`, without waiting for the reset to finish.
+fn start_reset(device: HardwareSource) {
+    with_global_control(|GlobalControl { gcr, lpgcr }| unsafe {
         match device {
             HardwareSource::GPIO0 => gcr.activate_gpio0_reset(),
             HardwareSource::GPIO1 => gcr.activate_gpio1_reset(),
@@ -141,11 +326,819 @@ pub fn peripheral_reset(device: HardwareSource) {
             HardwareSource::SPI0 => gcr.activate_spi0_reset(),
             HardwareSource::WDT0 => gcr.activate_watchdog_timer0_reset(),
             HardwareSource::CPU1 => gcr.activate_cpu1_riscv32_reset(),
-            HardwareSource::WDT1 => gcr.activate_watchdog_timer0_reset(),
-            HardwareSource::LPCOMP => gcr.activate_adc_reset(),
+            HardwareSource::WDT1 => lpgcr.activate_watchdog_timer1_reset(),
+            HardwareSource::LPCOMP => lpgcr.activate_low_power_comparator_reset(),
+            HardwareSource::UART3 => lpgcr.activate_low_power_uart_reset(),
+        }
+    });
+}
+
+/// # Oscillator
+/// The clock oscillators the GCR controls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Oscillator {
+    /// The 100MHz internal primary oscillator.
+    IPO,
+    /// The 60MHz internal secondary oscillator, which the core starts on.
+    ISO,
+    /// The 7.3728MHz internal baud rate oscillator. Always enabled.
+    IBRO,
+    /// The 8kHz to 30kHz internal nano ring oscillator. Always enabled.
+    INRO,
+    /// The external 32.768kHz RTC crystal oscillator.
+    ERTCO,
+}
+
+impl Oscillator {
+    /// The `GCR_CLKCTRL.sysclk_sel` value that runs the system clock from this
+    /// oscillator, from the MSDK `gcr_regs.h`.
+    const fn system_clock_select(self) -> u8 {
+        match self {
+            Self::ISO => 0,
+            Self::INRO => 3,
+            Self::IPO => 4,
+            Self::IBRO => 5,
+            Self::ERTCO => 6,
+        }
+    }
+}
+
+/// How many times `enable_oscillator` checks for the oscillator to become ready
+/// before giving up. The ERTCO crystal is the slowest, taking a few hundred
+/// milliseconds to start.
+const OSCILLATOR_READY_SPINS: u32 = 10_000_000;
+
+/// # Enable Oscillator
+/// Enable `osc` and wait for it to report ready, so the system clock or a
+/// peripheral can be switched over to it. The IBRO and INRO are always enabled,
+/// so for them this only waits.
+///
+/// Returns `ErrorKind::TimeOut` if the oscillator never becomes ready, like the
+/// ERTCO with no crystal fitted.
+pub fn enable_oscillator(osc: Oscillator) -> Result<()> {
+    with_gcr(|gcr| unsafe {
+        match osc {
+            Oscillator::IPO => gcr.set_internal_primary_oscillator_enable(true),
+            Oscillator::ISO => gcr.set_internal_secondary_oscillator_enable(true),
+            Oscillator::ERTCO => gcr.set_external_rtc_oscillator_enable(true),
+            Oscillator::IBRO | Oscillator::INRO => {}
+        }
+    });
+
+    // Waited on outside the critical section, like `peripheral_reset`.
+    crate::util::wait_until(OSCILLATOR_READY_SPINS, || oscillator_ready(osc))
+}
+
+/// # Disable Oscillator
+/// Turn `osc` off. Anything still clocked from it stops.
+///
+/// Returns `ErrorKind::NotSupported` for the IBRO and INRO, which cannot be
+/// turned off, and `ErrorKind::BadState` if `osc` is running the system clock.
+pub fn disable_oscillator(osc: Oscillator) -> Result<()> {
+    with_gcr(|gcr| {
+        if gcr.get_sys_clock_source_select() == osc.system_clock_select() {
+            return Err(ErrorKind::BadState);
+        }
+
+        unsafe {
+            match osc {
+                Oscillator::IPO => gcr.set_internal_primary_oscillator_enable(false),
+                Oscillator::ISO => gcr.set_internal_secondary_oscillator_enable(false),
+                Oscillator::ERTCO => gcr.set_external_rtc_oscillator_enable(false),
+                Oscillator::IBRO | Oscillator::INRO => return Err(ErrorKind::NotSupported),
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// # Power Down Oscillator
+/// Set whether `osc` is powered down while the part is in a low power mode, with
+/// its `GCR_PM` power down bit.
+///
+/// Returns `ErrorKind::NotSupported` for the INRO and ERTCO, which have no power
+/// down control.
+pub fn power_down_oscillator(osc: Oscillator, power_down: bool) -> Result<()> {
+    with_gcr(|gcr| unsafe {
+        match osc {
+            Oscillator::IPO => gcr.set_internal_primary_oscillator_power_down(power_down),
+            Oscillator::ISO => gcr.set_internal_secondary_oscillator_power_down(power_down),
+            Oscillator::IBRO => gcr.set_internal_baud_rate_oscillator_power_down(power_down),
+            Oscillator::INRO | Oscillator::ERTCO => return Err(ErrorKind::NotSupported),
+        }
+
+        Ok(())
+    })
+}
+
+/// # Oscillator Ready
+/// Check if `osc` is running and stable.
+pub fn oscillator_ready(osc: Oscillator) -> bool {
+    with_gcr(|gcr| match osc {
+        Oscillator::IPO => gcr.get_internal_primary_oscillator_ready(),
+        Oscillator::ISO => gcr.get_internal_secondary_oscillator_ready(),
+        Oscillator::IBRO => gcr.get_internal_baud_rate_oscillator_ready(),
+        Oscillator::INRO => gcr.get_internal_nano_ring_oscillator_ready(),
+        Oscillator::ERTCO => gcr.get_external_rtc_oscillator_ready(),
+    })
+}
+
+/// # ADC Clock Divider
+/// The ADC peripheral clock is the peripheral clock (PCLK) divided by this
+/// value. Divider values of 0 and 1 are reserved by the hardware.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AdcClockDiv {
+    Div2 = 2,
+    Div3 = 3,
+    Div4 = 4,
+    Div5 = 5,
+    Div6 = 6,
+    Div7 = 7,
+    Div8 = 8,
+    Div9 = 9,
+    Div10 = 10,
+    Div11 = 11,
+    Div12 = 12,
+    Div13 = 13,
+    Div14 = 14,
+    Div15 = 15,
+}
+
+impl TryFrom<u8> for AdcClockDiv {
+    type Error = ErrorKind;
+
+    fn try_from(value: u8) -> Result<Self> {
+        Ok(match value {
+            2 => Self::Div2,
+            3 => Self::Div3,
+            4 => Self::Div4,
+            5 => Self::Div5,
+            6 => Self::Div6,
+            7 => Self::Div7,
+            8 => Self::Div8,
+            9 => Self::Div9,
+            10 => Self::Div10,
+            11 => Self::Div11,
+            12 => Self::Div12,
+            13 => Self::Div13,
+            14 => Self::Div14,
+            15 => Self::Div15,
+            _ => return Err(ErrorKind::BadParam),
+        })
+    }
+}
+
+/// # CNN Clock Source
+/// The clock the CNN peripheral clock is derived from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CnnClockSource {
+    /// The peripheral clock (PCLK).
+    Pclk,
+    /// The internal secondary oscillator (ISO).
+    Iso,
+}
+
+/// # CNN Clock Divider
+/// The divider applied to the selected `CnnClockSource`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CnnClockDiv {
+    Div1,
+    Div2,
+    Div4,
+    Div8,
+    Div16,
+}
+
+impl CnnClockDiv {
+    const fn field_value(self) -> u8 {
+        match self {
+            Self::Div2 => 0b000,
+            Self::Div4 => 0b001,
+            Self::Div8 => 0b010,
+            Self::Div16 => 0b011,
+            Self::Div1 => 0b100,
+        }
+    }
+}
+
+impl TryFrom<u8> for CnnClockDiv {
+    type Error = ErrorKind;
+
+    fn try_from(value: u8) -> Result<Self> {
+        Ok(match value {
+            1 => Self::Div1,
+            2 => Self::Div2,
+            4 => Self::Div4,
+            8 => Self::Div8,
+            16 => Self::Div16,
+            _ => return Err(ErrorKind::BadParam),
+        })
+    }
+}
+
+/// How many times `flush_instruction_cache` polls the flush bit before giving up.
+const CACHE_FLUSH_SPINS: u32 = 100_000;
+
+/// # Flush Instruction Cache
+/// Invalidate the CM4 instruction cache (ICC0), so the next fetches come from
+/// flash again. Needed after the flash is written or erased, or the cache can
+/// keep serving what was there before.
+///
+/// Returns `ErrorKind::TimeOut` if the flush does not finish.
+pub fn flush_instruction_cache() -> Result<()> {
+    with_gcr(|gcr| {
+        unsafe { gcr.set_icc0_cache_flush(true) };
+        crate::util::wait_until(CACHE_FLUSH_SPINS, || !gcr.get_icc0_cache_flush())
+    })
+}
+
+/// # Set GPIO Wakeup Enable
+/// Let (`true`) or stop (`false`) the GPIO ports waking the part from a low
+/// power mode. Only the pins given `GpioPin::enable_wakeup` can wake it.
+pub fn set_gpio_wakeup_enable(enable: bool) {
+    with_gcr(|gcr| unsafe { gcr.set_gpio_wakeup_enable(enable) });
+}
+
+/// # Get GPIO Wakeup Enable
+/// Check if the GPIO ports can wake the part, see `set_gpio_wakeup_enable`.
+pub fn get_gpio_wakeup_enable() -> bool {
+    with_gcr(|gcr| gcr.get_gpio_wakeup_enable())
+}
+
+/// # Low Power Mode
+/// The low power modes `enter_low_power` can put the part in.
+///
+/// # Lost State
+/// - `Sleep`: nothing is lost. Only the core clock stops, every peripheral keeps
+///   running, and any enabled interrupt wakes the core.
+/// - `DeepSleep`: the user guide's STANDBY mode. The system clock stops, along
+///   with every oscillator given `power_down_oscillator`, so the peripherals
+///   clocked from them (UART, SPI, I2C, the ADC and timers on PCLK) stop where
+///   they are. Anything in flight on their lines is cut off, but their registers
+///   and all of SRAM are kept. Only the `WakeupSources` wake it.
+/// - `Backup`: the core and the digital peripherals lose power, and so does the
+///   SRAM not kept by the power sequencer. Only the RTC and the wakeup logic keep
+///   running, and waking is a reset, so `enter_low_power` does not return.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LowPowerMode {
+    Sleep,
+    DeepSleep,
+    Backup,
+}
+
+impl LowPowerMode {
+    /// The `GCR_PM.mode` value of this mode, from the MSDK `gcr_regs.h`. Sleep is
+    /// reached from ACTIVE with `SLEEPDEEP` left clear.
+    const fn operating_mode(self) -> u8 {
+        match self {
+            Self::Sleep => 0x0,
+            Self::DeepSleep => 0x2,
+            Self::Backup => 0x4,
+        }
+    }
+}
+
+/// # Wakeup Sources
+/// What is allowed to wake the part from `LowPowerMode::DeepSleep` or
+/// `LowPowerMode::Backup`, with the wakeup enables of `GCR_PM`. Each source still
+/// has to be set up to raise its event, like a pin with `GpioPin::enable_wakeup`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WakeupSources {
+    /// The GPIO pins given `GpioPin::enable_wakeup`.
+    pub gpio: bool,
+    /// The RTC alarm.
+    pub rtc_alarm: bool,
+    /// The wakeup timer.
+    pub wakeup_timer: bool,
+    /// The analog input comparators.
+    pub analog_comparator: bool,
+}
+
+/// The oscillators running and the system clock selected before a low power
+/// mode, to bring back after waking from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ClockState {
+    enabled: [(Oscillator, bool); 3],
+    system_clock_select: u8,
+}
+
+/// How many times `enter_low_power` checks for the system clock to be ready
+/// after switching back to it.
+const SYSTEM_CLOCK_READY_SPINS: u32 = 100_000;
+
+/// # Enter Low Power
+/// Put the part in `mode` until one of `wakeup` (or for `LowPowerMode::Sleep`, any
+/// enabled interrupt) wakes it. The wakeup enables not in `wakeup` are turned off.
+/// See `LowPowerMode` for what each mode loses.
+///
+/// After waking, every oscillator that was enabled before is enabled again, and
+/// the system clock is switched back to the one it ran from.
+///
+/// Returns `ErrorKind::TimeOut` if an oscillator or the system clock is not ready
+/// again after waking.
+pub fn enter_low_power(mode: LowPowerMode, wakeup: WakeupSources) -> Result<()> {
+    let clocks = prepare_low_power(mode, wakeup);
+    if mode == LowPowerMode::Backup {
+        // Waking from BACKUP is a reset, so a WFI that returns was not a wakeup
+        // (like an interrupt pending as the mode was entered). Go back to sleep.
+        loop {
+            wait_for_interrupt();
         }
     }
 
-    // Wait until reset is complete
-    while gcr.get_reset_status0() | gcr.get_reset_status1() != 0 {}
+    wait_for_interrupt();
+    wake_from_low_power(clocks)
+}
+
+/// Program the wakeup enables, `GCR_PM.mode` and `SLEEPDEEP` for `mode`,
+/// returning the clocks to restore once awake.
+fn prepare_low_power(mode: LowPowerMode, wakeup: WakeupSources) -> ClockState {
+    let mut scs = scs::Registers::new(scs_port());
+
+    with_gcr(|gcr| unsafe {
+        let clocks = ClockState {
+            enabled: [
+                (
+                    Oscillator::IPO,
+                    gcr.get_internal_primary_oscillator_enable(),
+                ),
+                (
+                    Oscillator::ISO,
+                    gcr.get_internal_secondary_oscillator_enable(),
+                ),
+                (Oscillator::ERTCO, gcr.get_external_rtc_oscillator_enable()),
+            ],
+            system_clock_select: gcr.get_sys_clock_source_select(),
+        };
+
+        gcr.set_gpio_wakeup_enable(wakeup.gpio);
+        gcr.set_rtc_alarm_wakeup_enable(wakeup.rtc_alarm);
+        gcr.set_wake_up_timer_enable(wakeup.wakeup_timer);
+        gcr.set_analog_input_comparator_wakeup_enable(wakeup.analog_comparator);
+
+        scs.set_sleep_deep(mode != LowPowerMode::Sleep);
+        gcr.set_operating_mode_select(mode.operating_mode());
+
+        clocks
+    })
+}
+
+/// Go back to ACTIVE and bring back the clocks saved by `prepare_low_power`.
+fn wake_from_low_power(clocks: ClockState) -> Result<()> {
+    let mut scs = scs::Registers::new(scs_port());
+    unsafe { scs.set_sleep_deep(false) };
+    with_gcr(|gcr| unsafe { gcr.set_operating_mode_select(0) });
+
+    for (osc, enabled) in clocks.enabled {
+        if enabled {
+            enable_oscillator(osc)?;
+        }
+    }
+
+    let switched = with_gcr(|gcr| {
+        if gcr.get_sys_clock_source_select() == clocks.system_clock_select {
+            return false;
+        }
+
+        unsafe { gcr.set_sys_clock_source_select(clocks.system_clock_select) };
+        true
+    });
+    if !switched {
+        return Ok(());
+    }
+
+    // Waited on outside the critical section, like `enable_oscillator`.
+    crate::util::wait_until(SYSTEM_CLOCK_READY_SPINS, || {
+        with_gcr(|gcr| gcr.get_sys_clock_source_ready())
+    })
+}
+
+/// Wait for an interrupt or wakeup event with WFI.
+#[cfg(target_arch = "arm")]
+#[inline(always)]
+fn wait_for_interrupt() {
+    unsafe {
+        core::arch::asm!(
+            "dsb",
+            "wfi",
+            "isb",
+            options(nomem, nostack, preserves_flags)
+        )
+    };
+}
+
+// Off target there is nothing to wake up from, so this returns straight away.
+#[cfg(not(target_arch = "arm"))]
+fn wait_for_interrupt() {}
+
+/// # Set ADC Clock Divider
+/// Set the divider used to derive the ADC peripheral clock from PCLK. The ADC
+/// needs its clock in the correct range for accurate conversions.
+pub fn set_adc_clock_divider(div: AdcClockDiv) {
+    with_gcr(|gcr| unsafe { gcr.set_adc_peripheral_clock_frequency_select(div as u8) });
+}
+
+/// # Set CNN Clock
+/// Select the source and divider for the CNN peripheral clock.
+pub fn set_cnn_clock(source: CnnClockSource, div: CnnClockDiv) {
+    with_gcr(|gcr| unsafe {
+        gcr.set_cnn_peripheral_clock_select(source == CnnClockSource::Iso);
+        gcr.set_cnn_peripheral_clock_frequency_divider(div.field_value());
+    });
+}
+
+/// # Factory Trims
+/// The oscillator trim values in effect, as read back by `apply_factory_trims`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FactoryTrims {
+    /// The IPO (100MHz) trim loaded from the factory value.
+    pub ipo_trim: u16,
+    /// The INRO 16kHz trim.
+    pub inro_16khz_trim: u8,
+    /// The INRO 30kHz trim.
+    pub inro_30khz_trim: u8,
+    /// The RTC 32kHz crystal oscillator X1 load trim.
+    pub rtc_x1_trim: u8,
+    /// The RTC 32kHz crystal oscillator X2 load trim.
+    pub rtc_x2_trim: u8,
+}
+
+/// # Apply Factory Trims
+/// Load the factory oscillator trims and return the trims now in effect.
+///
+/// # Applied Trims
+/// - IPO: automatic calibration is stopped and the factory trim in
+///   `FCR_AUTOCAL1` is loaded into the oscillator, undoing any drift left by an
+///   earlier calibration run.
+/// - INRO and RTC: the boot ROM copies these into the TRIMSIR registers, which
+///   drive the oscillators directly. They are only read back here.
+/// - IBRO: this has no software trim field, its trim is applied by hardware.
+///
+/// Returns `ErrorKind::BadState` if the RTC trims are not locked, which means the
+/// boot ROM never loaded the TRIMSIR trims.
+///
+/// # Boot Order
+/// Call this during early init, before switching the system clock or configuring
+/// any peripheral that derives its timing from the IPO or IBRO (UART baud rates,
+/// I2C timing), since a trim change moves the oscillator frequency underneath it.
+pub fn apply_factory_trims() -> Result<FactoryTrims> {
+    let mut fcr = trims::fcr::Registers::new(mmio::FUNCTION_CONTROL);
+    let trimsir = trims::trimsir::Registers::new(mmio::TRIM_SYSTEM_INITIALIZATION);
+
+    unsafe {
+        fcr.set_ipo_autocalibration_run(false);
+        fcr.set_ipo_autocalibration_enable(false);
+        fcr.set_ipo_load_trim(true);
+        fcr.set_ipo_load_trim(false);
+    }
+
+    if !trimsir.get_rtc_trim_lock() {
+        return Err(ErrorKind::BadState);
+    }
+
+    Ok(FactoryTrims {
+        ipo_trim: fcr.get_ipo_initial_trim(),
+        inro_16khz_trim: trimsir.get_inro_16khz_trim(),
+        inro_30khz_trim: trimsir.get_inro_30khz_trim(),
+        rtc_x1_trim: trimsir.get_rtc_x1_trim(),
+        rtc_x2_trim: trimsir.get_rtc_x2_trim(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hardware_source_base_address_test() {
+        assert_eq!(HardwareSource::I2C1.base_address(), Some(mmio::I2C_PORT_1));
+        assert_eq!(HardwareSource::UART0.base_address(), Some(mmio::UART_0));
+        assert_eq!(HardwareSource::TMR3.base_address(), Some(mmio::TIMER_3));
+        assert_eq!(HardwareSource::AES.base_address(), Some(mmio::AES));
+        assert_eq!(
+            HardwareSource::WDT1.base_address(),
+            Some(mmio::LOW_POWER_WATCHDOG_TIMER_0)
+        );
+        assert_eq!(HardwareSource::CPU1.base_address(), None);
+    }
+
+    #[test]
+    fn concurrent_system_clock_enable_test() {
+        // Each thread only touches its own clock, so a lost update from another
+        // thread's read-modify-write would show up as its bit flipping back.
+        fn hammer(source: fn() -> HardwareSource, disabled: fn() -> bool) {
+            for i in 0..2_000 {
+                let enable = i % 2 == 0;
+                system_clock_enable(source(), enable);
+                assert_eq!(disabled(), !enable);
+            }
+            system_clock_enable(source(), false);
+        }
+
+        let threads = [
+            std::thread::spawn(|| {
+                hammer(
+                    || HardwareSource::UART0,
+                    || with_gcr(|gcr| gcr.get_uart0_clock_disable()),
+                )
+            }),
+            std::thread::spawn(|| {
+                hammer(
+                    || HardwareSource::TMR1,
+                    || with_gcr(|gcr| gcr.get_timer1_clock_disable()),
+                )
+            }),
+            std::thread::spawn(|| {
+                hammer(
+                    || HardwareSource::I2C0,
+                    || with_gcr(|gcr| gcr.get_i2c0_clock_disable()),
+                )
+            }),
+            std::thread::spawn(|| {
+                hammer(
+                    || HardwareSource::TRNG,
+                    || with_gcr(|gcr| gcr.get_trng_clock_disable()),
+                )
+            }),
+        ];
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        with_gcr(|gcr| {
+            assert!(gcr.get_uart0_clock_disable());
+            assert!(gcr.get_timer1_clock_disable());
+            assert!(gcr.get_i2c0_clock_disable());
+            assert!(gcr.get_trng_clock_disable());
+        });
+    }
+
+    fn all_sources() -> [HardwareSource; 29] {
+        use HardwareSource::*;
+        [
+            GPIO0, GPIO1, DMA, SPI1, UART0, UART1, I2C0, TMR0, TMR1, TMR2, TMR3, ADC, CNN, I2C1,
+            PT, UART2, TRNG, SMPHR, OWIRE, CRC, AES, I2S, SPI0, I2C2, WDT0, CPU1, WDT1, LPCOMP,
+            UART3,
+        ]
+    }
+
+    pub(super) fn fake_gcr_snapshot() -> [[u32; FAKE_GCR_WORDS]; 2] {
+        FAKE_GCR.each_ref().map(|block| {
+            block
+                .each_ref()
+                .map(|word| word.load(core::sync::atomic::Ordering::Relaxed))
+        })
+    }
+
+    pub(super) fn fake_gcr_restore(snapshot: [[u32; FAKE_GCR_WORDS]; 2]) {
+        for (block, values) in FAKE_GCR.iter().zip(snapshot) {
+            for (word, value) in block.iter().zip(values) {
+                word.store(value, core::sync::atomic::Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// The one `(block, word, bit)` that differs between two snapshots.
+    fn changed_bit(
+        before: [[u32; FAKE_GCR_WORDS]; 2],
+        after: [[u32; FAKE_GCR_WORDS]; 2],
+    ) -> (usize, usize, u32) {
+        let changed: std::vec::Vec<_> = (0..2)
+            .flat_map(|block| (0..FAKE_GCR_WORDS).map(move |word| (block, word)))
+            .filter(|&(block, word)| before[block][word] != after[block][word])
+            .map(|(block, word)| (block, word, before[block][word] ^ after[block][word]))
+            .collect();
+
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].2.count_ones(), 1);
+        changed[0]
+    }
+
+    fn assert_distinct(bits: std::vec::Vec<(usize, usize, u32)>) {
+        for (i, bit) in bits.iter().enumerate() {
+            assert!(!bits[..i].contains(bit), "source {i} shares {bit:?}");
+        }
+    }
+
+    #[test]
+    fn hardware_source_distinct_bits_test() {
+        // Hold a critical section so no other test touches the fake GCR meanwhile.
+        critical_section::with(|_| {
+            let original = fake_gcr_snapshot();
+
+            let clock_bits = all_sources()
+                .into_iter()
+                .zip(all_sources())
+                .map(|(disable, enable)| {
+                    system_clock_enable(enable, true);
+                    let before = fake_gcr_snapshot();
+                    system_clock_enable(disable, false);
+                    changed_bit(before, fake_gcr_snapshot())
+                })
+                .collect();
+
+            fake_gcr_restore(original);
+
+            let reset_bits = all_sources()
+                .into_iter()
+                .map(|source| {
+                    let before = fake_gcr_snapshot();
+                    start_reset(source);
+                    let bit = changed_bit(before, fake_gcr_snapshot());
+                    fake_gcr_restore(before);
+                    bit
+                })
+                .collect();
+
+            fake_gcr_restore(original);
+            assert_distinct(clock_bits);
+            assert_distinct(reset_bits);
+        });
+    }
+
+    #[test]
+    fn stuck_peripheral_reset_times_out_test() {
+        critical_section::with(|_| {
+            // The fake reset bits never clear, like a device stuck in reset.
+            let original = fake_gcr_snapshot();
+            assert_eq!(
+                peripheral_reset(HardwareSource::UART1),
+                Err(ErrorKind::TimeOut)
+            );
+            assert_eq!(
+                peripheral_reset(HardwareSource::WDT1),
+                Err(ErrorKind::TimeOut)
+            );
+            fake_gcr_restore(original);
+        });
+    }
+
+    #[test]
+    fn oscillator_control_test() {
+        const CLKCTRL_WORD: usize = 0x08 / 4;
+        const PM_WORD: usize = 0x0C / 4;
+        let word = |word: usize| FAKE_GCR[0][word].load(core::sync::atomic::Ordering::Relaxed);
+        let set_word = |word: usize, value: u32| {
+            FAKE_GCR[0][word].store(value, core::sync::atomic::Ordering::Relaxed)
+        };
+
+        critical_section::with(|_| {
+            let original = fake_gcr_snapshot();
+
+            // The ISO runs the system clock, and the IPO is already ready.
+            set_word(CLKCTRL_WORD, 1 << 27);
+            assert_eq!(enable_oscillator(Oscillator::IPO), Ok(()));
+            assert_eq!(word(CLKCTRL_WORD), 1 << 27 | 1 << 19);
+            assert!(oscillator_ready(Oscillator::IPO));
+            assert!(!oscillator_ready(Oscillator::ERTCO));
+
+            assert_eq!(disable_oscillator(Oscillator::IPO), Ok(()));
+            assert_eq!(word(CLKCTRL_WORD), 1 << 27);
+            assert_eq!(
+                disable_oscillator(Oscillator::ISO),
+                Err(ErrorKind::BadState)
+            );
+            assert_eq!(
+                disable_oscillator(Oscillator::IBRO),
+                Err(ErrorKind::NotSupported)
+            );
+
+            assert_eq!(power_down_oscillator(Oscillator::ISO, true), Ok(()));
+            assert_eq!(power_down_oscillator(Oscillator::IBRO, true), Ok(()));
+            assert_eq!(word(PM_WORD), 1 << 17 | 1 << 15);
+            assert_eq!(
+                power_down_oscillator(Oscillator::ERTCO, true),
+                Err(ErrorKind::NotSupported)
+            );
+
+            fake_gcr_restore(original);
+        });
+    }
+
+    #[test]
+    fn oscillator_ready_wait_test() {
+        const CLKCTRL_WORD: usize = 0x08 / 4;
+        const ERTCO_ENABLE: u32 = 1 << 17;
+        const ERTCO_READY: u32 = 1 << 25;
+
+        critical_section::with(|_| {
+            let original = fake_gcr_snapshot();
+            FAKE_GCR[0][CLKCTRL_WORD].store(0, core::sync::atomic::Ordering::Relaxed);
+
+            // The crystal comes up a while after being enabled. The fake is set
+            // directly, since this thread holds the critical section.
+            let crystal = std::thread::spawn(|| {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                FAKE_GCR[0][CLKCTRL_WORD]
+                    .fetch_or(ERTCO_READY, core::sync::atomic::Ordering::Relaxed);
+            });
+
+            assert_eq!(enable_oscillator(Oscillator::ERTCO), Ok(()));
+            crystal.join().unwrap();
+            assert_eq!(
+                FAKE_GCR[0][CLKCTRL_WORD].load(core::sync::atomic::Ordering::Relaxed),
+                ERTCO_ENABLE | ERTCO_READY
+            );
+
+            fake_gcr_restore(original);
+        });
+    }
+
+    #[test]
+    fn gpio_wakeup_enable_test() {
+        /// `GCR_PM` is at offset 0x000C.
+        const PM_WORD: usize = 3;
+        let word = || FAKE_GCR[0][PM_WORD].load(core::sync::atomic::Ordering::Relaxed);
+
+        critical_section::with(|_| {
+            let original = fake_gcr_snapshot();
+
+            set_gpio_wakeup_enable(true);
+            assert_eq!(word() & 1 << 4, 1 << 4);
+            assert!(get_gpio_wakeup_enable());
+
+            set_gpio_wakeup_enable(false);
+            assert_eq!(word() & 1 << 4, 0);
+            assert!(!get_gpio_wakeup_enable());
+
+            fake_gcr_restore(original);
+        });
+    }
+
+    #[test]
+    fn low_power_programming_test() {
+        const CLKCTRL_WORD: usize = 0x08 / 4;
+        const PM_WORD: usize = 0x0C / 4;
+        /// `SCB_SCR` is at offset 0x0D10.
+        const SCR_WORD: usize = 0x0D10 / 4;
+        const SLEEPDEEP: u32 = 1 << 2;
+        let word = |word: usize| FAKE_GCR[0][word].load(core::sync::atomic::Ordering::Relaxed);
+        let set_word = |word: usize, value: u32| {
+            FAKE_GCR[0][word].store(value, core::sync::atomic::Ordering::Relaxed)
+        };
+        let scr = || FAKE_SCS[SCR_WORD].load(core::sync::atomic::Ordering::Relaxed);
+
+        critical_section::with(|_| {
+            let original = fake_gcr_snapshot();
+
+            // The system clock runs from the IPO, which is ready, as is the
+            // system clock.
+            set_word(CLKCTRL_WORD, 1 << 27 | 1 << 19 | 1 << 13 | 4 << 9);
+            set_word(PM_WORD, 1 << 16 | 1 << 7);
+
+            let wakeup = WakeupSources {
+                gpio: true,
+                rtc_alarm: true,
+                ..Default::default()
+            };
+            let clocks = prepare_low_power(LowPowerMode::DeepSleep, wakeup);
+            assert_eq!(word(PM_WORD), 1 << 16 | 1 << 5 | 1 << 4 | 0x2);
+            assert_eq!(scr(), SLEEPDEEP);
+            assert_eq!(clocks.system_clock_select, 4);
+            assert_eq!(clocks.enabled[0], (Oscillator::IPO, true));
+            assert_eq!(clocks.enabled[1], (Oscillator::ISO, false));
+
+            // Woken back up on the ISO.
+            set_word(CLKCTRL_WORD, 1 << 27 | 1 << 19 | 1 << 13);
+            assert_eq!(wake_from_low_power(clocks), Ok(()));
+            assert_eq!(word(PM_WORD) & 0xF, 0);
+            assert_eq!(scr(), 0);
+            assert_eq!(word(CLKCTRL_WORD), 1 << 27 | 1 << 19 | 1 << 13 | 4 << 9);
+
+            // A system clock that never reports ready times out.
+            set_word(CLKCTRL_WORD, 1 << 27 | 1 << 19);
+            assert_eq!(wake_from_low_power(clocks), Err(ErrorKind::TimeOut));
+            assert_eq!(word(CLKCTRL_WORD), 1 << 27 | 1 << 19 | 4 << 9);
+
+            // Sleep leaves SLEEPDEEP clear and the mode at ACTIVE.
+            assert_eq!(
+                enter_low_power(LowPowerMode::Sleep, WakeupSources::default()),
+                Ok(())
+            );
+            assert_eq!(word(PM_WORD), 1 << 16);
+            assert_eq!(scr(), 0);
+
+            fake_gcr_restore(original);
+        });
+    }
+
+    #[test]
+    fn adc_clock_div_from_u8_test() {
+        assert_eq!(AdcClockDiv::try_from(2), Ok(AdcClockDiv::Div2));
+        assert_eq!(AdcClockDiv::try_from(15), Ok(AdcClockDiv::Div15));
+        assert_eq!(AdcClockDiv::try_from(0), Err(ErrorKind::BadParam));
+        assert_eq!(AdcClockDiv::try_from(1), Err(ErrorKind::BadParam));
+        assert_eq!(AdcClockDiv::try_from(16), Err(ErrorKind::BadParam));
+    }
+
+    #[test]
+    fn cnn_clock_div_from_u8_test() {
+        assert_eq!(CnnClockDiv::try_from(1), Ok(CnnClockDiv::Div1));
+        assert_eq!(CnnClockDiv::try_from(16), Ok(CnnClockDiv::Div16));
+        assert_eq!(CnnClockDiv::try_from(3), Err(ErrorKind::BadParam));
+        assert_eq!(CnnClockDiv::try_from(32), Err(ErrorKind::BadParam));
+    }
 }
@@ -49,6 +49,7 @@ mod rro {
 
 make_device! {
     device_ports(mmio::GLOBAL_CONTROL);
+    window_size(0x400);
 
     #[bit(0..=31, RO, rro::GCR_RST0)]
     reset_status0,
@@ -392,3 +393,49 @@ make_device! {
     #[bit(0..=31, RW, rro::GCR_GPR0)]
     general_purpose_register
 }
+
+/// # Low Power Global Control Registers
+/// The reset and clock disable bits for the peripherals in the low power domain
+/// (like `WDT1`, the LPUART and the low power comparators), which are not in the main GCR.
+pub mod lpgcr {
+    use hal_macros::RW;
+    use hal_macros_derive::make_device;
+
+    use crate::memory_map::mmio;
+
+    /// # Relative Register Offsets
+    /// These are the offsets for the LPGCR registers that the
+    /// Maxim Integrated - spec shows.
+    mod rro {
+        /// # Low Power Reset Register
+        pub const LPGCR_RST: usize = 0x0008;
+        /// # Low Power Peripheral Clock Disable Register
+        pub const LPGCR_PCLKDIS: usize = 0x000C;
+    }
+
+    make_device! {
+        device_ports(mmio::LOW_POWER_CONTROL);
+        window_size(0x400);
+
+        #[bit(0..=31, RO, rro::LPGCR_RST)]
+        reset_status,
+
+        #[bit(6, RW1O, rro::LPGCR_RST)]
+        low_power_comparator_reset,
+
+        #[bit(4, RW1O, rro::LPGCR_RST)]
+        low_power_uart_reset,
+
+        #[bit(1, RW1O, rro::LPGCR_RST)]
+        watchdog_timer1_reset,
+
+        #[bit(6, RW, rro::LPGCR_PCLKDIS)]
+        low_power_comparator_clock_disable,
+
+        #[bit(4, RW, rro::LPGCR_PCLKDIS)]
+        low_power_uart_clock_disable,
+
+        #[bit(1, RW, rro::LPGCR_PCLKDIS)]
+        watchdog_timer1_clock_disable
+    }
+}
@@ -0,0 +1,74 @@
+//! # Factory Trims
+//! The oscillator trim fields, split over the Trim System Initialization (TRIMSIR)
+//! registers and the Function Control (FCR) registers.
+
+/// # Trim System Initialization Registers
+pub mod trimsir {
+    use hal_macros::RW;
+    use hal_macros_derive::make_device;
+
+    use crate::memory_map::mmio;
+
+    /// # Relative Register Offsets
+    /// These are the offsets for the TRIMSIR registers that the
+    /// Maxim Integrated - spec shows.
+    mod rro {
+        /// # RTC Trim Register
+        pub const TRIMSIR_RTC: usize = 0x0008;
+        /// # Internal Nano-Ring Oscillator Trim Register
+        pub const TRIMSIR_INRO: usize = 0x0044;
+    }
+
+    make_device! {
+        device_ports(mmio::TRIM_SYSTEM_INITIALIZATION);
+
+        #[bit(31, RO, rro::TRIMSIR_RTC)]
+        rtc_trim_lock,
+
+        #[bit(21..=25, RW, rro::TRIMSIR_RTC)]
+        rtc_x2_trim,
+
+        #[bit(16..=20, RW, rro::TRIMSIR_RTC)]
+        rtc_x1_trim,
+
+        #[bit(3..=5, RW, rro::TRIMSIR_INRO)]
+        inro_30khz_trim,
+
+        #[bit(0..=2, RW, rro::TRIMSIR_INRO)]
+        inro_16khz_trim
+    }
+}
+
+/// # Function Control Registers
+pub mod fcr {
+    use hal_macros::RW;
+    use hal_macros_derive::make_device;
+
+    use crate::memory_map::mmio;
+
+    /// # Relative Register Offsets
+    /// These are the offsets for the FCR registers that the
+    /// Maxim Integrated - spec shows.
+    mod rro {
+        /// # Automatic Calibration 0 Register
+        pub const FCR_AUTOCAL0: usize = 0x0004;
+        /// # Automatic Calibration 1 Register
+        pub const FCR_AUTOCAL1: usize = 0x0008;
+    }
+
+    make_device! {
+        device_ports(mmio::FUNCTION_CONTROL);
+
+        #[bit(2, RW, rro::FCR_AUTOCAL0)]
+        ipo_load_trim,
+
+        #[bit(1, RW, rro::FCR_AUTOCAL0)]
+        ipo_autocalibration_run,
+
+        #[bit(0, RW, rro::FCR_AUTOCAL0)]
+        ipo_autocalibration_enable,
+
+        #[bit(0..=8, RW, rro::FCR_AUTOCAL1)]
+        ipo_initial_trim
+    }
+}
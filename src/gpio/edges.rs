@@ -0,0 +1,219 @@
+use super::GpioSelect;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicUsize, Ordering};
+
+/// How many edge events an `EdgeQueue` holds before it starts dropping the oldest ones.
+pub const EDGE_QUEUE_CAPACITY: usize = 16;
+
+crate::const_assert!(EDGE_QUEUE_CAPACITY.is_power_of_two());
+
+/// The number of pins that can have an edge queue attached (3 ports of 32 pins).
+const MAX_EDGE_PINS: usize = 3 * 32;
+
+static EDGE_QUEUES: [AtomicPtr<EdgeQueue>; MAX_EDGE_PINS] =
+    [const { AtomicPtr::new(core::ptr::null_mut()) }; MAX_EDGE_PINS];
+
+/// # Edge Event
+/// A single edge captured on a pin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EdgeEvent {
+    /// The time the edge was recorded at, in ticks of whatever clock the
+    /// interrupt handler read.
+    pub timestamp: u32,
+    /// `true` for a rising edge and `false` for a falling edge.
+    pub rising: bool,
+}
+
+/// # Edge Queue
+/// A lock-free ring buffer of `EdgeEvent`s, filled from the GPIO interrupt handler
+/// and drained from the main loop.
+///
+/// The queue holds `EDGE_QUEUE_CAPACITY` events. When it is full, recording a new
+/// edge drops the oldest one, so the main loop always sees the most recent edges.
+/// To not lose any edges the queue must be drained at least every
+/// `EDGE_QUEUE_CAPACITY / edge_rate` seconds, i.e. every 16ms for a 1kHz signal.
+///
+/// This queue supports a single producer (the interrupt handler) and a single
+/// consumer (the main loop).
+pub struct EdgeQueue {
+    timestamps: [AtomicU32; EDGE_QUEUE_CAPACITY],
+    rising: [AtomicBool; EDGE_QUEUE_CAPACITY],
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl EdgeQueue {
+    pub const fn new() -> Self {
+        Self {
+            timestamps: [const { AtomicU32::new(0) }; EDGE_QUEUE_CAPACITY],
+            rising: [const { AtomicBool::new(false) }; EDGE_QUEUE_CAPACITY],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// # Push
+    /// Record `event`, dropping the oldest event if the queue is full.
+    pub fn push(&self, event: EdgeEvent) {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+
+        if head.wrapping_sub(tail) >= EDGE_QUEUE_CAPACITY
+            && self
+                .tail
+                .compare_exchange(
+                    tail,
+                    tail.wrapping_add(1),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let slot = head % EDGE_QUEUE_CAPACITY;
+        self.timestamps[slot].store(event.timestamp, Ordering::Relaxed);
+        self.rising[slot].store(event.rising, Ordering::Relaxed);
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+    }
+
+    /// # Pop
+    /// Take the oldest event out of the queue.
+    pub fn pop(&self) -> Option<EdgeEvent> {
+        loop {
+            let tail = self.tail.load(Ordering::Acquire);
+            let head = self.head.load(Ordering::Acquire);
+            if tail == head {
+                return None;
+            }
+
+            let slot = tail % EDGE_QUEUE_CAPACITY;
+            let event = EdgeEvent {
+                timestamp: self.timestamps[slot].load(Ordering::Relaxed),
+                rising: self.rising[slot].load(Ordering::Relaxed),
+            };
+
+            // If the producer dropped this event while we were reading it, the slot
+            // may have been overwritten, so try again with the new oldest event.
+            if self
+                .tail
+                .compare_exchange(
+                    tail,
+                    tail.wrapping_add(1),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                return Some(event);
+            }
+        }
+    }
+
+    /// # Dropped
+    /// The number of events dropped because the queue was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for EdgeQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn queue_index(port: GpioSelect, pin: usize) -> Option<usize> {
+    (pin < 32).then_some(port as usize * 32 + pin)
+}
+
+/// # Record Edge
+/// Push `event` into the queue attached to `pin` of `port`, if there is one. This
+/// is meant to be called from the GPIO interrupt handler, and only does a queue
+/// push so the handler stays short.
+pub fn record_edge(port: GpioSelect, pin: usize, event: EdgeEvent) {
+    let Some(index) = queue_index(port, pin) else {
+        return;
+    };
+
+    let queue = EDGE_QUEUES[index].load(Ordering::Acquire);
+    if let Some(queue) = unsafe { queue.as_ref() } {
+        queue.push(event);
+    }
+}
+
+pub(super) fn attach(port: GpioSelect, pin: usize, queue: Option<&'static EdgeQueue>) {
+    let Some(index) = queue_index(port, pin) else {
+        return;
+    };
+
+    let queue = queue.map_or(core::ptr::null_mut(), |queue| {
+        queue as *const EdgeQueue as *mut EdgeQueue
+    });
+    EDGE_QUEUES[index].store(queue, Ordering::Release);
+}
+
+pub(super) fn attached(port: GpioSelect, pin: usize) -> Option<&'static EdgeQueue> {
+    let index = queue_index(port, pin)?;
+    unsafe { EDGE_QUEUES[index].load(Ordering::Acquire).as_ref() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn edge(timestamp: u32) -> EdgeEvent {
+        EdgeEvent {
+            timestamp,
+            rising: timestamp.is_multiple_of(2),
+        }
+    }
+
+    #[test]
+    fn push_pop_in_order_test() {
+        let queue = EdgeQueue::new();
+        assert_eq!(queue.pop(), None);
+
+        for timestamp in 0..4 {
+            queue.push(edge(timestamp));
+        }
+        for timestamp in 0..4 {
+            assert_eq!(queue.pop(), Some(edge(timestamp)));
+        }
+        assert_eq!(queue.pop(), None);
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[test]
+    fn overflow_drops_oldest_test() {
+        let queue = EdgeQueue::new();
+        let extra = 5;
+
+        for timestamp in 0..(EDGE_QUEUE_CAPACITY as u32 + extra) {
+            queue.push(edge(timestamp));
+        }
+
+        assert_eq!(queue.dropped(), extra as usize);
+        for timestamp in extra..(EDGE_QUEUE_CAPACITY as u32 + extra) {
+            assert_eq!(queue.pop(), Some(edge(timestamp)));
+        }
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn record_edge_routes_to_attached_queue_test() {
+        static QUEUE: EdgeQueue = EdgeQueue::new();
+
+        record_edge(GpioSelect::Gpio1, 7, edge(1));
+        attach(GpioSelect::Gpio1, 7, Some(&QUEUE));
+        record_edge(GpioSelect::Gpio1, 7, edge(2));
+        record_edge(GpioSelect::Gpio1, 8, edge(3));
+        attach(GpioSelect::Gpio1, 7, None);
+        record_edge(GpioSelect::Gpio1, 7, edge(4));
+
+        assert_eq!(QUEUE.pop(), Some(edge(2)));
+        assert_eq!(QUEUE.pop(), None);
+    }
+}
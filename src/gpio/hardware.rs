@@ -47,24 +47,44 @@ XC_GPIO_PAD_NONE, MXC_GPIO_VSSEL_VDDIO, MXC_GPIO_DRVSTR_0 };
 MXC_GPIO_PAD_NONE, MXC_GPIO_VSSEL_VDDIO, MXC_GPIO_DRVSTR_0 };
 */
 
+/// The GPIO port and pin numbers of the I2C pins for port n.
+fn i2c_layout(port: usize) -> (super::GpioSelect, usize, usize) {
+    match port {
+        0 => (super::GpioSelect::Gpio0, 10, 11),
+        1 => (super::GpioSelect::Gpio0, 16, 17),
+        2 => (super::GpioSelect::Gpio0, 30, 31),
+
+        _ => panic!("Cannot have a port higher then 2"),
+    }
+}
+
 /// # I2C (n)
 /// Get the i2c GPIO pins for port n.
 pub fn i2c_n(port: usize) -> Option<[GpioPin; 2]> {
-    let pins = match port {
-        0 => (10, 11),
-        1 => (16, 17),
-        2 => (30, 31),
+    let (gpio_port, pin_0, pin_1) = i2c_layout(port);
 
-        _ => panic!("Cannot have a port higher then 2"),
-    };
+    let pins = [
+        GpioPin::new(gpio_port, pin_0)?,
+        GpioPin::new(gpio_port, pin_1)?,
+    ];
 
-    let gpio_0 = GpioPin::new(super::GpioSelect::Gpio0, pins.0)?;
-    let gpio_1 = GpioPin::new(super::GpioSelect::Gpio0, pins.1)?;
+    configure_i2c_n(port, &pins)?;
+    Some(pins)
+}
+
+/// # Configure I2C (n)
+/// Switch already owned `pins` over to the I2C alternate function for port n.
+/// Returns `None` without touching the pins if they are not the I2C pins of that
+/// port, in the same order `i2c_n` gives them.
+pub fn configure_i2c_n(port: usize, pins: &[GpioPin; 2]) -> Option<()> {
+    let (gpio_port, pin_0, pin_1) = i2c_layout(port);
+    is_layout(pins, gpio_port, [pin_0, pin_1]).then_some(())?;
 
-    gpio_0.configure_input(super::ResistorStrength::None, super::PinFunction::AF1);
-    gpio_1.configure_input(super::ResistorStrength::None, super::PinFunction::AF1);
+    for pin in pins {
+        pin.configure_input(super::ResistorStrength::None, super::PinFunction::AF1);
+    }
 
-    Some([gpio_0, gpio_1])
+    Some(())
 }
 
 // UART 0 P0_0 Rx P0_1 Tx
@@ -72,24 +92,124 @@ pub fn i2c_n(port: usize) -> Option<[GpioPin; 2]> {
 // UART 2 P1_0 Rx P1_1 Tx
 // LPUART P2_6 Rx P2_7 Tx
 
+/// The GPIO port and (Rx, Tx) pin numbers of the UART pins for port n.
+fn uart_layout(port: usize) -> (super::GpioSelect, usize, usize) {
+    match port {
+        0 => (super::GpioSelect::Gpio0, 0, 1),
+        1 => (super::GpioSelect::Gpio0, 12, 13),
+        2 => (super::GpioSelect::Gpio1, 0, 1),
+        3 => (super::GpioSelect::Gpio2, 6, 7),
+
+        _ => panic!("Cannot have a port higher than 3"),
+    }
+}
+
 /// # UART (n)
 /// Get the UART GPIO pins for port n.
 pub fn uart_n(port: usize) -> Option<[GpioPin; 2]> {
-    // (Rx, Tx, GPIO_port)
-    let pins = match port {
-        0 => (0, 1, super::GpioSelect::Gpio0),
-        1 => (12, 13, super::GpioSelect::Gpio0),
-        2 => (0, 1, super::GpioSelect::Gpio1),
-        3 => (6, 7, super::GpioSelect::Gpio2),
+    let (gpio_port, rx, tx) = uart_layout(port);
 
-        _ => panic!("Cannot have a port higher than 3"),
-    };
+    let pins = [GpioPin::new(gpio_port, rx)?, GpioPin::new(gpio_port, tx)?];
+
+    configure_uart_n(port, &pins)?;
+    Some(pins)
+}
+
+/// # Configure UART (n)
+/// Switch already owned `pins` over to the UART alternate function for port n.
+/// Returns `None` without touching the pins if they are not the `[Rx, Tx]` pins
+/// of that port.
+pub fn configure_uart_n(port: usize, pins: &[GpioPin; 2]) -> Option<()> {
+    let (gpio_port, rx, tx) = uart_layout(port);
+    is_layout(pins, gpio_port, [rx, tx]).then_some(())?;
+
+    for pin in pins {
+        pin.configure_input(super::ResistorStrength::None, super::PinFunction::AF1);
+    }
+
+    Some(())
+}
+
+// SPI0 P0_5 MOSI P0_6 MISO P0_7 SCK
+// SPI1 P0_21 MOSI P0_22 MISO P0_23 SCK
 
-    let gpio_rx = GpioPin::new(pins.2, pins.0)?;
-    let gpio_tx = GpioPin::new(pins.2, pins.1)?;
+/// The GPIO port and (MOSI, MISO, SCK) pin numbers of the SPI pins for port n.
+fn spi_layout(port: usize) -> (super::GpioSelect, [usize; 3]) {
+    match port {
+        0 => (super::GpioSelect::Gpio0, [5, 6, 7]),
+        1 => (super::GpioSelect::Gpio0, [21, 22, 23]),
 
-    gpio_rx.configure_input(super::ResistorStrength::None, super::PinFunction::AF1);
-    gpio_tx.configure_input(super::ResistorStrength::None, super::PinFunction::AF1);
+        _ => panic!("Cannot have a port higher than 1"),
+    }
+}
+
+/// # SPI (n)
+/// Get the SPI GPIO pins for port n, as `[MOSI, MISO, SCK]`. Slave select is left
+/// to the user, usually as a plain GPIO output.
+pub fn spi_n(port: usize) -> Option<[GpioPin; 3]> {
+    let (gpio_port, numbers) = spi_layout(port);
+
+    let pins = [
+        GpioPin::new(gpio_port, numbers[0])?,
+        GpioPin::new(gpio_port, numbers[1])?,
+        GpioPin::new(gpio_port, numbers[2])?,
+    ];
+
+    configure_spi_n(port, &pins)?;
+    Some(pins)
+}
+
+/// # Configure SPI (n)
+/// Switch already owned `pins` over to the SPI alternate function for port n.
+/// Returns `None` without touching the pins if they are not the
+/// `[MOSI, MISO, SCK]` pins of that port.
+pub fn configure_spi_n(port: usize, pins: &[GpioPin; 3]) -> Option<()> {
+    let (gpio_port, numbers) = spi_layout(port);
+    is_layout(pins, gpio_port, numbers).then_some(())?;
+
+    for pin in pins {
+        pin.configure_input(super::ResistorStrength::None, super::PinFunction::AF1);
+    }
+
+    Some(())
+}
+
+// TMR0 P0_2, TMR1 P0_14, TMR2 P0_26, TMR3 P1_6 (TimerA output)
+
+/// The GPIO port and pin number of the TimerA output pin for timer n.
+fn timer_output_layout(port: usize) -> (super::GpioSelect, usize) {
+    match port {
+        0 => (super::GpioSelect::Gpio0, 2),
+        1 => (super::GpioSelect::Gpio0, 14),
+        2 => (super::GpioSelect::Gpio0, 26),
+        3 => (super::GpioSelect::Gpio1, 6),
+
+        _ => panic!("Cannot have a timer higher than 3"),
+    }
+}
+
+/// # Configure Timer Output (n)
+/// Switch an already owned `pin` over to the TimerA output alternate function
+/// (`AF2`) for timer n. Returns `None` without touching the pin if it is not the
+/// output pin of that timer.
+pub fn configure_timer_output_n(port: usize, pin: &GpioPin) -> Option<()> {
+    let (gpio_port, number) = timer_output_layout(port);
+    (pin.get_port() as u8 == gpio_port as u8 && pin.get_pin() == number).then_some(())?;
+
+    pin.configure_output(
+        super::OutputDriveStrength::Strength0(super::VoltageSelect::VddIO),
+        super::PinFunction::AF2,
+    );
+
+    Some(())
+}
 
-    Some([gpio_rx, gpio_tx])
+fn is_layout<const N: usize>(
+    pins: &[GpioPin; N],
+    port: super::GpioSelect,
+    numbers: [usize; N],
+) -> bool {
+    pins.iter()
+        .zip(numbers)
+        .all(|(pin, number)| pin.get_port() as u8 == port as u8 && pin.get_pin() == number)
 }
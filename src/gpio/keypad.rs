@@ -0,0 +1,165 @@
+use super::{GpioPin, OutputDriveStrength, PinFunction, ResistorStrength, VoltageSelect};
+use crate::timer::MonotonicSource;
+
+/// How long to wait after driving a row for the columns to settle, in spin loops.
+const SETTLE_SPINS: usize = 64;
+
+/// # Keypad
+/// A row/column matrix keypad scanner over `R` row pins and `C` column pins.
+///
+/// # Pin Configuration
+/// The columns are inputs with weak pull-ups, so they read high while no key is
+/// pressed. Rows are scanned one at a time: the row being scanned is an output
+/// driven low, and every other row is left as an input with no pull (high
+/// impedance). A pressed key in the scanned row pulls its column low.
+///
+/// Only ever driving one row, and only ever driving it low, emulates an
+/// open-drain output. If several keys are pressed at once no two driven rows can
+/// be shorted together through the column, the worst case is a ghost key being
+/// reported instead of one of the real ones.
+pub struct Keypad<const R: usize, const C: usize> {
+    rows: [GpioPin; R],
+    columns: [GpioPin; C],
+    debouncer: Debouncer,
+}
+
+impl<const R: usize, const C: usize> Keypad<R, C> {
+    /// # New
+    /// Make a keypad from its row and column pins, configuring them as described in
+    /// `Keypad`. A key has to read the same for `debounce_us` microseconds before
+    /// `scan_debounced` reports it.
+    pub fn new(rows: [GpioPin; R], columns: [GpioPin; C], debounce_us: u32) -> Self {
+        for row in rows.iter() {
+            row.configure_input(ResistorStrength::None, PinFunction::IO);
+        }
+
+        for column in columns.iter() {
+            column.configure_input(ResistorStrength::WeakPullup, PinFunction::IO);
+        }
+
+        Self {
+            rows,
+            columns,
+            debouncer: Debouncer::new(debounce_us),
+        }
+    }
+
+    /// # Scan
+    /// Scan every row once and return the `(row, column)` of the first pressed key
+    /// found, or `None` if no key is pressed. This is not debounced.
+    pub fn scan(&self) -> Option<(usize, usize)> {
+        self.rows.iter().enumerate().find_map(|(row_index, row)| {
+            row.set_output(false);
+            row.configure_output(
+                OutputDriveStrength::Strength0(VoltageSelect::VddIO),
+                PinFunction::IO,
+            );
+
+            for _ in 0..SETTLE_SPINS {
+                core::hint::spin_loop();
+            }
+
+            let column_index = self.columns.iter().position(|column| !column.get_input());
+            row.configure_input(ResistorStrength::None, PinFunction::IO);
+
+            column_index.map(|column_index| (row_index, column_index))
+        })
+    }
+
+    /// # Scan Debounced
+    /// Scan the keypad and return the pressed key, only changing the reported key
+    /// once a new reading has held steady for the debounce time given to `new`.
+    /// This should be called regularly, at least a few times per debounce period.
+    pub fn scan_debounced(
+        &mut self,
+        monotonic: &mut impl MonotonicSource,
+    ) -> Option<(usize, usize)> {
+        let reading = self.scan();
+        self.debouncer.update(reading, monotonic.now_us())
+    }
+
+    /// # Release
+    /// Give back the row and column pins.
+    pub fn release(self) -> ([GpioPin; R], [GpioPin; C]) {
+        (self.rows, self.columns)
+    }
+}
+
+/// Tracks how long a reading has been stable, kept apart from the pins.
+struct Debouncer {
+    debounce_us: u32,
+    stable: Option<(usize, usize)>,
+    candidate: Option<(usize, usize)>,
+    candidate_since: u32,
+}
+
+impl Debouncer {
+    const fn new(debounce_us: u32) -> Self {
+        Self {
+            debounce_us,
+            stable: None,
+            candidate: None,
+            candidate_since: 0,
+        }
+    }
+
+    /// Take in a reading at `now_us`, returning the debounced key.
+    fn update(&mut self, reading: Option<(usize, usize)>, now_us: u32) -> Option<(usize, usize)> {
+        if reading == self.stable {
+            self.candidate = reading;
+        } else if reading != self.candidate {
+            self.candidate = reading;
+            self.candidate_since = now_us;
+        } else if now_us.wrapping_sub(self.candidate_since) >= self.debounce_us {
+            self.stable = reading;
+        }
+
+        self.stable
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::gpio::registers::{fake, rro, GPIO_1};
+    use crate::gpio::GpioSelect;
+
+    #[test]
+    fn debouncer_ignores_bounces_test() {
+        let mut debouncer = Debouncer::new(1_000);
+
+        assert_eq!(debouncer.update(Some((0, 1)), 0), None);
+        assert_eq!(debouncer.update(None, 200), None);
+        assert_eq!(debouncer.update(Some((0, 1)), 400), None);
+        assert_eq!(debouncer.update(Some((0, 1)), 1_300), None);
+        assert_eq!(debouncer.update(Some((0, 1)), 1_400), Some((0, 1)));
+
+        // Releasing is debounced too.
+        assert_eq!(debouncer.update(None, 1_500), Some((0, 1)));
+        assert_eq!(debouncer.update(Some((0, 1)), 1_600), Some((0, 1)));
+        assert_eq!(debouncer.update(None, 1_700), Some((0, 1)));
+        assert_eq!(debouncer.update(None, 2_700), None);
+    }
+
+    #[test]
+    fn scan_finds_low_column_test() {
+        let rows = [
+            GpioPin::new(GpioSelect::Gpio1, 0).unwrap(),
+            GpioPin::new(GpioSelect::Gpio1, 1).unwrap(),
+        ];
+        let columns = [
+            GpioPin::new(GpioSelect::Gpio1, 4).unwrap(),
+            GpioPin::new(GpioSelect::Gpio1, 5).unwrap(),
+            GpioPin::new(GpioSelect::Gpio1, 6).unwrap(),
+        ];
+        let keypad = Keypad::new(rows, columns, 1_000);
+
+        fake::write(rro::GPIO_IN, GPIO_1, 0b111 << 4);
+        assert_eq!(keypad.scan(), None);
+
+        // With the fake registers the column reads low for every row, so the
+        // first row is reported.
+        fake::write(rro::GPIO_IN, GPIO_1, 0b101 << 4);
+        assert_eq!(keypad.scan(), Some((0, 1)));
+    }
+}
@@ -1,5 +1,8 @@
+pub mod edges;
 pub mod hardware;
+pub mod keypad;
 mod ownership;
+pub mod port;
 pub mod registers;
 
 /// # GPIO Select
@@ -48,6 +51,33 @@ pub enum PinFunction {
     IO,
 }
 
+/// # Interrupt Trigger
+/// The pin condition that raises a GPIO interrupt, see `GpioPin::enable_interrupt`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterruptTrigger {
+    RisingEdge,
+    FallingEdge,
+    BothEdges,
+    /// Keeps interrupting for as long as the pin reads high.
+    HighLevel,
+    /// Keeps interrupting for as long as the pin reads low.
+    LowLevel,
+}
+
+impl InterruptTrigger {
+    /// The `(INTMODE, INTPOL, DUALEDGE)` bits of this trigger, where INTMODE is set
+    /// for edges and INTPOL is set for rising edges or high levels.
+    const fn mode_bits(self) -> (bool, bool, bool) {
+        match self {
+            Self::RisingEdge => (true, true, false),
+            Self::FallingEdge => (true, false, false),
+            Self::BothEdges => (true, false, true),
+            Self::HighLevel => (false, true, false),
+            Self::LowLevel => (false, false, false),
+        }
+    }
+}
+
 pub struct GpioPin(u8);
 
 impl GpioPin {
@@ -92,43 +122,133 @@ impl GpioPin {
         }
     }
 
+    /// Select `function` for the pin, then run `func` to write the pad, pull and drive
+    /// configuration. This follows `MXC_GPIO_RevA_SetAF` in the MSDK: the pin is
+    /// switched to IO mode first, then EN2/EN1/EN0 are programmed to the final
+    /// function, so the configuration writes never happen with the pin left in IO
+    /// mode on its way to an alternate function.
     fn switch_function<Func>(&self, function: PinFunction, func: Func)
     where
         Func: FnOnce(),
     {
         unsafe {
-            self.set_bit(registers::rro::GPIO_EN0_SET, true);
-
-            func();
+            // Alt Functions need the pin in IO mode (EN0 set) before they can be entered
+            self.write_pin_bit(registers::rro::GPIO_EN0_SET);
 
             match function {
                 PinFunction::AF1 => {
-                    // Alt Functions need EN0 set before ALT1 can be entered
-                    self.set_bit(registers::rro::GPIO_EN0_CLR, true);
-                    self.set_bit(registers::rro::GPIO_EN1_CLR, true);
-                    self.set_bit(registers::rro::GPIO_EN2_CLR, true);
-                    true
+                    self.write_pin_bit(registers::rro::GPIO_EN2_CLR);
+                    self.write_pin_bit(registers::rro::GPIO_EN1_CLR);
+                    self.write_pin_bit(registers::rro::GPIO_EN0_CLR);
                 }
                 PinFunction::AF2 => {
-                    // Alt Functions need EN0 set before ALT2 can be entered
-                    self.set_bit(registers::rro::GPIO_EN0_CLR, true);
-                    self.set_bit(registers::rro::GPIO_EN1_SET, true);
-                    self.set_bit(registers::rro::GPIO_EN2_CLR, true);
-                    true
+                    self.write_pin_bit(registers::rro::GPIO_EN2_CLR);
+                    self.write_pin_bit(registers::rro::GPIO_EN1_SET);
+                    self.write_pin_bit(registers::rro::GPIO_EN0_CLR);
                 }
                 PinFunction::IO => {
                     // The different IO modes do not change pin behavior
-                    self.set_bit(registers::rro::GPIO_EN0_SET, true);
-                    self.set_bit(registers::rro::GPIO_EN1_CLR, true);
-                    self.set_bit(registers::rro::GPIO_EN2_CLR, true);
-                    false
+                    self.write_pin_bit(registers::rro::GPIO_EN1_CLR);
+                    self.write_pin_bit(registers::rro::GPIO_EN2_CLR);
                 }
-            };
+            }
+
+            func();
         }
     }
 
+    /// # Set Output
+    /// Drive the pin high or low with a single write of this pin's bit to
+    /// `GPIO_OUT_SET` or `GPIO_OUT_CLR`, without reading the port first. Pins of the
+    /// same port can be driven from different contexts (like `main` and an
+    /// interrupt handler) without racing, which a read-modify-write of `GPIO_OUT`
+    /// could lose updates to. `set_high`, `set_low` and `toggle` go through this
+    /// too, and `set_high_atomic`/`set_low_atomic` make the same writes.
     pub fn set_output(&self, output_enable: bool) {
-        unsafe { self.set_bit(registers::rro::GPIO_OUT, output_enable) };
+        let register = if output_enable {
+            registers::rro::GPIO_OUT_SET
+        } else {
+            registers::rro::GPIO_OUT_CLR
+        };
+
+        unsafe { self.write_pin_bit(register) };
+    }
+
+    /// # Get Output State
+    /// Check if the pin is set to drive high, read back from `GPIO_OUT`.
+    pub fn get_output_state(&self) -> bool {
+        unsafe {
+            (registers::read_gpio(registers::rro::GPIO_OUT, self.get_port().into())
+                & (1 << self.get_pin()))
+                != 0
+        }
+    }
+
+    /// # Toggle
+    /// Drive the pin to the opposite of its current output state.
+    pub fn toggle(&self) {
+        self.set_output(!self.get_output_state());
+    }
+
+    /// # Set High
+    /// Drive the pin high, the same as `set_output(true)`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use max78000_hal::gpio::hardware::led_red;
+    ///
+    /// let led = led_red().unwrap();
+    /// led.set_high();
+    /// assert!(led.is_set_high());
+    /// led.toggle();
+    /// assert!(led.is_set_low());
+    /// ```
+    pub fn set_high(&self) {
+        self.set_output(true);
+    }
+
+    /// # Set Low
+    /// Drive the pin low, the same as `set_output(false)`.
+    pub fn set_low(&self) {
+        self.set_output(false);
+    }
+
+    /// # Set High Atomic
+    /// Drive the pin high with a single write of this pin's bit to `GPIO_OUT_SET`,
+    /// so it never races other pins of the port, see `set_output`.
+    pub fn set_high_atomic(&self) {
+        unsafe { self.write_pin_bit(registers::rro::GPIO_OUT_SET) };
+    }
+
+    /// # Set Low Atomic
+    /// Drive the pin low with a single write of this pin's bit to `GPIO_OUT_CLR`,
+    /// so it never races other pins of the port, see `set_output`.
+    pub fn set_low_atomic(&self) {
+        unsafe { self.write_pin_bit(registers::rro::GPIO_OUT_CLR) };
+    }
+
+    /// # Is Set High
+    /// Check if the pin is set to drive high, see `get_output_state`.
+    pub fn is_set_high(&self) -> bool {
+        self.get_output_state()
+    }
+
+    /// # Is Set Low
+    /// Check if the pin is set to drive low.
+    pub fn is_set_low(&self) -> bool {
+        !self.get_output_state()
+    }
+
+    /// # Is High
+    /// Check if the pin reads high, see `get_input`.
+    pub fn is_high(&self) -> bool {
+        self.get_input()
+    }
+
+    /// # Is Low
+    /// Check if the pin reads low.
+    pub fn is_low(&self) -> bool {
+        !self.get_input()
     }
 
     pub fn get_input(&self) -> bool {
@@ -139,7 +259,69 @@ impl GpioPin {
         }
     }
 
+    /// # Sample Majority
+    /// Read the input `n` times in a tight loop and return the majority value. Ties
+    /// (only possible with an even `n`) and `n == 0` return `false`.
+    ///
+    /// This is a simple software filter for noisy digital inputs. It is blocking, and
+    /// since the samples are taken back-to-back the window it filters over depends
+    /// on the core clock speed.
+    pub fn sample_majority(&self, n: usize) -> bool {
+        majority_of(n, || self.get_input())
+    }
+
+    /// # Sample All Equal
+    /// Read the input `n` times in a tight loop and return the value only if every
+    /// sample agreed, otherwise `None`. Returns `None` when `n == 0`.
+    ///
+    /// Like `sample_majority`, this is blocking and timing-dependent.
+    pub fn sample_all_equal(&self, n: usize) -> Option<bool> {
+        all_equal_of(n, || self.get_input())
+    }
+
     pub fn configure_input(&self, res: ResistorStrength, function: PinFunction) {
+        self.switch_function(function, || unsafe {
+            self.set_resistor(res);
+            self.write_pin_bit(registers::rro::GPIO_OUTEN_CLR);
+            self.set_bit(registers::rro::GPIO_INEN, true);
+        });
+    }
+
+    /// # Configure Open Drain
+    /// Set the pin up as an open-drain line, like the ones I2C and 1-Wire share
+    /// between devices. The GPIO on this part has no open-drain mode (the user
+    /// guide GPIO registers only select push-pull drive), so it is emulated: the
+    /// output is held low, and `set_open_drain_output` switches the driver on to
+    /// pull the line low, or off to release it. A released line floats up on its
+    /// pull-up, so pair this with `ResistorStrength::WeakPullup` (or a stronger
+    /// external pull-up on a longer bus).
+    ///
+    /// The pin starts out released, with its input enabled so `get_input` reads
+    /// the line whoever is pulling it.
+    pub fn configure_open_drain(&self, res: ResistorStrength, function: PinFunction) {
+        self.switch_function(function, || unsafe {
+            self.set_resistor(res);
+            self.write_pin_bit(registers::rro::GPIO_OUTEN_CLR);
+            self.write_pin_bit(registers::rro::GPIO_OUT_CLR);
+            self.set_bit(registers::rro::GPIO_INEN, true);
+        });
+    }
+
+    /// # Set Open Drain Output
+    /// Release (`true`) or pull low (`false`) a pin set up with
+    /// `configure_open_drain`. This only switches the output driver, so the line is
+    /// never driven high.
+    pub fn set_open_drain_output(&self, high: bool) {
+        let register = if high {
+            registers::rro::GPIO_OUTEN_CLR
+        } else {
+            registers::rro::GPIO_OUTEN_SET
+        };
+
+        unsafe { self.write_pin_bit(register) };
+    }
+
+    unsafe fn set_resistor(&self, res: ResistorStrength) {
         let (pad_ctrl1, pad_ctrl0, pull_ctrl, power_ctrl) = match res {
             ResistorStrength::None => (false, false, false, false),
             ResistorStrength::WeakPullup => (false, true, false, false),
@@ -148,14 +330,10 @@ impl GpioPin {
             ResistorStrength::StrongPulldown => (true, false, true, true),
         };
 
-        self.switch_function(function, || unsafe {
-            self.set_bit(registers::rro::GPIO_PADCTRL0, pad_ctrl0);
-            self.set_bit(registers::rro::GPIO_PADCTRL1, pad_ctrl1);
-            self.set_bit(registers::rro::GPIO_PS, pull_ctrl);
-            self.set_bit(registers::rro::GPIO_VSSEL, power_ctrl);
-            self.set_bit(registers::rro::GPIO_OUTEN_CLR, true);
-            self.set_bit(registers::rro::GPIO_INEN, true);
-        });
+        self.set_bit(registers::rro::GPIO_PADCTRL0, pad_ctrl0);
+        self.set_bit(registers::rro::GPIO_PADCTRL1, pad_ctrl1);
+        self.set_bit(registers::rro::GPIO_PS, pull_ctrl);
+        self.set_bit(registers::rro::GPIO_VSSEL, power_ctrl);
     }
 
     pub fn configure_output(&self, strength: OutputDriveStrength, function: PinFunction) {
@@ -175,21 +353,423 @@ impl GpioPin {
             self.set_bit(registers::rro::GPIO_DS0, ds_ctrl0);
             self.set_bit(registers::rro::GPIO_VSSEL, v_sel);
             self.set_bit(registers::rro::GPIO_INEN, false);
-            self.set_bit(registers::rro::GPIO_OUTEN_SET, true);
+            self.write_pin_bit(registers::rro::GPIO_OUTEN_SET);
         });
     }
 
+    /// # Enable Interrupt
+    /// Raise this pin's GPIO port interrupt on `trigger`. Any flag left over from
+    /// before is cleared first, so only new events interrupt.
+    ///
+    /// This only sets up the GPIO side. The port's interrupt (`GPIO0` to `GPIO2`)
+    /// must still be enabled in the NVIC with a handler registered for it, and that
+    /// handler must `clear_interrupt` every pending pin or it is re-entered straight
+    /// away. A level trigger keeps re-raising the flag until the level goes away.
+    pub fn enable_interrupt(&self, trigger: InterruptTrigger) {
+        let (edge_mode, active_high, dual_edge) = trigger.mode_bits();
+
+        unsafe {
+            self.write_pin_bit(registers::rro::GPIO_INTEN_CLR);
+            self.set_bit(registers::rro::GPIO_INTMODE, edge_mode);
+            self.set_bit(registers::rro::GPIO_INTPOL, active_high);
+            self.set_bit(registers::rro::GPIO_DUALEDGE, dual_edge);
+            self.write_pin_bit(registers::rro::GPIO_INTFL_CLR);
+            self.write_pin_bit(registers::rro::GPIO_INTEN_SET);
+        }
+    }
+
+    /// # Disable Interrupt
+    /// Stop this pin from raising its GPIO port interrupt.
+    pub fn disable_interrupt(&self) {
+        unsafe { self.write_pin_bit(registers::rro::GPIO_INTEN_CLR) };
+    }
+
+    /// # Is Interrupt Pending
+    /// Check if this pin's interrupt flag is set.
+    pub fn is_interrupt_pending(&self) -> bool {
+        unsafe {
+            (registers::read_gpio(registers::rro::GPIO_INTFL, self.get_port().into())
+                & (1 << self.get_pin()))
+                != 0
+        }
+    }
+
+    /// # Clear Interrupt
+    /// Clear this pin's interrupt flag, without touching the other pins of the port.
+    pub fn clear_interrupt(&self) {
+        unsafe { self.write_pin_bit(registers::rro::GPIO_INTFL_CLR) };
+    }
+
+    /// # Enable Wakeup
+    /// Let (`true`) or stop (`false`) this pin waking the part from a low power
+    /// mode, through `GPIO_WKEN_SET`/`GPIO_WKEN_CLR`. The pin wakes on the same
+    /// event its interrupt triggers on, so set that up with `enable_interrupt`, and
+    /// turn on GPIO wakeups as a whole with `gcr::set_gpio_wakeup_enable`.
+    pub fn enable_wakeup(&self, enable: bool) {
+        let register = if enable {
+            registers::rro::GPIO_WKEN_SET
+        } else {
+            registers::rro::GPIO_WKEN_CLR
+        };
+
+        unsafe { self.write_pin_bit(register) };
+    }
+
+    /// # Is Wakeup Enabled
+    /// Check if this pin can wake the part, read back from `GPIO_WKEN`.
+    pub fn is_wakeup_enabled(&self) -> bool {
+        unsafe {
+            (registers::read_gpio(registers::rro::GPIO_WKEN, self.get_port().into())
+                & (1 << self.get_pin()))
+                != 0
+        }
+    }
+
+    /// Write only this pin's bit to one of the write-1 `_SET`/`_CLR` registers, which
+    /// must not be read back and rewritten like `set_bit` does. Rewriting what was
+    /// read would also set or clear every other pin that read back as `1`.
+    unsafe fn write_pin_bit(&self, reg_offset: registers::BaseOffset) {
+        registers::write_gpio(reg_offset, self.get_port().into(), 1 << self.get_pin());
+    }
+
+    /// # Attach Edge Queue
+    /// Capture the edges of this pin into `queue`. Each edge recorded for this pin
+    /// with `edges::record_edge` from the GPIO interrupt handler is pushed into the
+    /// queue, and can be drained with `poll_edges`. See `edges::EdgeQueue` for how
+    /// the queue should be sized.
+    ///
+    /// The queue is detached when this pin is dropped.
+    pub fn attach_edge_queue(&self, queue: &'static edges::EdgeQueue) {
+        edges::attach(self.get_port(), self.get_pin(), Some(queue));
+    }
+
+    /// # Poll Edges
+    /// Drain the edge events captured since the last poll, oldest first. This is
+    /// empty if no queue is attached with `attach_edge_queue`.
+    pub fn poll_edges(&self) -> impl Iterator<Item = edges::EdgeEvent> {
+        let queue = edges::attached(self.get_port(), self.get_pin());
+        core::iter::from_fn(move || queue?.pop())
+    }
+
+    /// # Into Analog
+    /// Configure this pin for use as an analog input, disabling its digital input
+    /// buffer, output driver, and pull resistors. The returned `AnalogPin` can be
+    /// handed to a peripheral like the ADC.
+    pub fn into_analog(self) -> AnalogPin {
+        unsafe {
+            self.write_pin_bit(registers::rro::GPIO_OUTEN_CLR);
+            self.set_bit(registers::rro::GPIO_INEN, false);
+            self.set_bit(registers::rro::GPIO_PADCTRL0, false);
+            self.set_bit(registers::rro::GPIO_PADCTRL1, false);
+        }
+
+        AnalogPin(self)
+    }
+
+    /// # Raw Output Enable
+    /// Enable the output driver of this pin without touching any other pin configuration.
+    ///
+    /// # Safety
+    /// The caller must ensure the pin has already been configured so that driving
+    /// it will not conflict with any attached hardware.
     pub unsafe fn raw_output_enable(&self) {
-        self.set_bit(registers::rro::GPIO_OUTEN_SET, true);
+        self.write_pin_bit(registers::rro::GPIO_OUTEN_SET);
     }
 
+    /// # Raw Input Enable
+    /// Enable the input buffer of this pin without touching any other pin configuration.
+    ///
+    /// # Safety
+    /// The caller must ensure the pin has already been configured for the intended
+    /// function before enabling its input.
     pub unsafe fn raw_input_enable(&self) {
         self.set_bit(registers::rro::GPIO_INEN, true);
     }
 }
 
+/// # Analog Pin
+/// A `GpioPin` that has been configured as an analog input with `GpioPin::into_analog`.
+pub struct AnalogPin(GpioPin);
+
+impl AnalogPin {
+    #[inline]
+    pub fn get_port(&self) -> GpioSelect {
+        self.0.get_port()
+    }
+
+    #[inline]
+    pub fn get_pin(&self) -> usize {
+        self.0.get_pin()
+    }
+}
+
+fn majority_of<Read>(n: usize, mut read: Read) -> bool
+where
+    Read: FnMut() -> bool,
+{
+    let high_count = (0..n).filter(|_| read()).count();
+    high_count * 2 > n
+}
+
+fn all_equal_of<Read>(n: usize, mut read: Read) -> Option<bool>
+where
+    Read: FnMut() -> bool,
+{
+    let first = (n > 0).then(&mut read)?;
+    let all_equal = (1..n).fold(true, |equal, _| read() == first && equal);
+    all_equal.then_some(first)
+}
+
 impl Drop for GpioPin {
     fn drop(&mut self) {
+        edges::attach(self.get_port(), self.get_pin(), None);
         ownership::disown_pin(self);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn scripted(samples: &[bool]) -> impl FnMut() -> bool + '_ {
+        let mut iter = samples.iter().copied();
+        move || iter.next().unwrap()
+    }
+
+    #[test]
+    fn test_majority_of() {
+        assert!(majority_of(5, scripted(&[true, false, true, true, false])));
+        assert!(!majority_of(
+            5,
+            scripted(&[false, false, true, true, false])
+        ));
+        assert!(!majority_of(4, scripted(&[true, true, false, false])));
+        assert!(majority_of(1, scripted(&[true])));
+        assert!(!majority_of(0, scripted(&[])));
+    }
+
+    #[test]
+    fn test_all_equal_of() {
+        assert_eq!(all_equal_of(3, scripted(&[true, true, true])), Some(true));
+        assert_eq!(
+            all_equal_of(3, scripted(&[false, false, false])),
+            Some(false)
+        );
+        assert_eq!(all_equal_of(3, scripted(&[true, false, true])), None);
+        assert_eq!(all_equal_of(0, scripted(&[])), None);
+    }
+
+    #[test]
+    fn enable_interrupt_programs_trigger_test() {
+        use registers::{fake, rro, GPIO_2};
+
+        let pin = GpioPin::new(GpioSelect::Gpio2, 5).unwrap();
+        let bit = 1 << 5;
+        fake::write(rro::GPIO_INTMODE, GPIO_2, 0b1);
+        fake::write(rro::GPIO_DUALEDGE, GPIO_2, bit);
+
+        pin.enable_interrupt(InterruptTrigger::RisingEdge);
+        assert_eq!(fake::read(rro::GPIO_INTMODE, GPIO_2), 0b1 | bit);
+        assert_eq!(fake::read(rro::GPIO_INTPOL, GPIO_2), bit);
+        assert_eq!(fake::read(rro::GPIO_DUALEDGE, GPIO_2), 0);
+        assert_eq!(fake::read(rro::GPIO_INTFL_CLR, GPIO_2), bit);
+        assert_eq!(fake::read(rro::GPIO_INTEN_SET, GPIO_2), bit);
+
+        pin.enable_interrupt(InterruptTrigger::LowLevel);
+        assert_eq!(fake::read(rro::GPIO_INTMODE, GPIO_2), 0b1);
+        assert_eq!(fake::read(rro::GPIO_INTPOL, GPIO_2), 0);
+
+        pin.enable_interrupt(InterruptTrigger::BothEdges);
+        assert_eq!(fake::read(rro::GPIO_DUALEDGE, GPIO_2), bit);
+
+        assert!(!pin.is_interrupt_pending());
+        fake::write(rro::GPIO_INTFL, GPIO_2, bit);
+        assert!(pin.is_interrupt_pending());
+
+        fake::write(rro::GPIO_INTFL_CLR, GPIO_2, 0);
+        pin.clear_interrupt();
+        assert_eq!(fake::read(rro::GPIO_INTFL_CLR, GPIO_2), bit);
+
+        pin.disable_interrupt();
+        assert_eq!(fake::read(rro::GPIO_INTEN_CLR, GPIO_2), bit);
+    }
+
+    #[test]
+    fn enable_wakeup_test() {
+        use registers::{fake, rro, GPIO_1};
+
+        let pin = GpioPin::new(GpioSelect::Gpio1, 6).unwrap();
+        let bit = 1 << 6;
+        let other_pins = 0b1000_0001;
+        fake::write(rro::GPIO_WKEN, GPIO_1, other_pins);
+        fake::take_accesses();
+
+        pin.enable_wakeup(true);
+        assert!(pin.is_wakeup_enabled());
+        assert_eq!(fake::read(rro::GPIO_WKEN, GPIO_1), other_pins | bit);
+
+        pin.enable_wakeup(false);
+        assert!(!pin.is_wakeup_enabled());
+        assert_eq!(fake::read(rro::GPIO_WKEN, GPIO_1), other_pins);
+        assert_eq!(
+            fake::take_accesses(),
+            [
+                fake::Access::Write(rro::GPIO_WKEN_SET, bit),
+                fake::Access::Read(rro::GPIO_WKEN),
+                fake::Access::Write(rro::GPIO_WKEN_CLR, bit),
+                fake::Access::Read(rro::GPIO_WKEN),
+            ]
+        );
+    }
+
+    #[test]
+    fn toggle_output_test() {
+        use registers::{fake, rro, GPIO_1};
+
+        let pin = GpioPin::new(GpioSelect::Gpio1, 3).unwrap();
+        let other_pins = 0b1000_0001;
+        fake::write(rro::GPIO_OUT, GPIO_1, other_pins);
+
+        pin.set_output(true);
+        assert!(pin.get_output_state());
+        assert_eq!(fake::read(rro::GPIO_OUT, GPIO_1), other_pins | 1 << 3);
+
+        pin.toggle();
+        assert!(!pin.get_output_state());
+        assert_eq!(fake::read(rro::GPIO_OUT, GPIO_1), other_pins);
+
+        pin.toggle();
+        assert!(pin.get_output_state());
+        assert_eq!(fake::read(rro::GPIO_OUT_SET, GPIO_1), 1 << 3);
+
+        pin.set_low();
+        assert!(pin.is_set_low());
+        pin.set_high();
+        assert!(pin.is_set_high());
+        assert_eq!(fake::read(rro::GPIO_OUT_CLR, GPIO_1), 1 << 3);
+    }
+
+    #[test]
+    fn atomic_output_register_traffic_test() {
+        use registers::{fake, rro, GPIO_2};
+
+        let pin = GpioPin::new(GpioSelect::Gpio2, 4).unwrap();
+        let bit = 1 << 4;
+        let other_pins = 0b1000_0011;
+        fake::write(rro::GPIO_OUT, GPIO_2, other_pins);
+        fake::write(rro::GPIO_OUTEN, GPIO_2, other_pins);
+        fake::take_accesses();
+
+        pin.set_high();
+        pin.set_low();
+        pin.set_high_atomic();
+        pin.set_low_atomic();
+        assert_eq!(
+            fake::take_accesses(),
+            [
+                fake::Access::Write(rro::GPIO_OUT_SET, bit),
+                fake::Access::Write(rro::GPIO_OUT_CLR, bit),
+                fake::Access::Write(rro::GPIO_OUT_SET, bit),
+                fake::Access::Write(rro::GPIO_OUT_CLR, bit),
+            ]
+        );
+
+        // The read-modify-write path reads the port and writes every pin back.
+        unsafe { pin.set_bit(rro::GPIO_OUT, true) };
+        assert_eq!(
+            fake::take_accesses(),
+            [
+                fake::Access::Read(rro::GPIO_OUT),
+                fake::Access::Write(rro::GPIO_OUT, other_pins | bit),
+            ]
+        );
+
+        // Enabling the output only writes this pin's bit.
+        unsafe { pin.raw_output_enable() };
+        assert_eq!(
+            fake::take_accesses(),
+            [fake::Access::Write(rro::GPIO_OUTEN_SET, bit)]
+        );
+        assert_eq!(fake::read(rro::GPIO_OUTEN, GPIO_2), other_pins | bit);
+    }
+
+    #[test]
+    fn switch_function_selects_before_configuring_test() {
+        use registers::{fake, rro, GPIO_0};
+
+        let pin = GpioPin::new(GpioSelect::Gpio0, 9).unwrap();
+        let bit = 1 << 9;
+        let other_pins = 0b0110;
+        let enables =
+            || [rro::GPIO_EN0, rro::GPIO_EN1, rro::GPIO_EN2].map(|reg| fake::read(reg, GPIO_0));
+
+        for reg in [rro::GPIO_EN0, rro::GPIO_EN1, rro::GPIO_EN2] {
+            fake::write(reg, GPIO_0, other_pins | bit);
+        }
+
+        // (function, EN0, EN1, EN2) from the MSDK `MXC_GPIO_RevA_SetAF`.
+        let cases = [
+            (PinFunction::AF1, 0, 0, 0),
+            (PinFunction::AF2, 0, bit, 0),
+            (PinFunction::IO, bit, 0, 0),
+        ];
+
+        for (function, en0, en1, en2) in cases {
+            let expected = [other_pins | en0, other_pins | en1, other_pins | en2];
+            let mut configured = false;
+
+            pin.switch_function(function, || {
+                assert_eq!(enables(), expected);
+                configured = true;
+            });
+
+            assert!(configured);
+            assert_eq!(enables(), expected);
+        }
+    }
+
+    #[test]
+    fn open_drain_configuration_test() {
+        use registers::{fake, rro, GPIO_1};
+
+        let pin = GpioPin::new(GpioSelect::Gpio1, 6).unwrap();
+        let bit = 1 << 6;
+        let other_pins = 0b1001;
+        for reg in [rro::GPIO_OUT, rro::GPIO_OUTEN, rro::GPIO_PADCTRL1] {
+            fake::write(reg, GPIO_1, other_pins | bit);
+        }
+
+        pin.configure_open_drain(ResistorStrength::WeakPullup, PinFunction::IO);
+        assert_eq!(fake::read(rro::GPIO_OUT, GPIO_1), other_pins);
+        assert_eq!(fake::read(rro::GPIO_OUTEN, GPIO_1), other_pins);
+        assert_eq!(fake::read(rro::GPIO_PADCTRL0, GPIO_1), bit);
+        assert_eq!(fake::read(rro::GPIO_PADCTRL1, GPIO_1), other_pins);
+        assert_eq!(fake::read(rro::GPIO_PS, GPIO_1), 0);
+        assert_eq!(fake::read(rro::GPIO_INEN, GPIO_1) & bit, bit);
+
+        fake::take_accesses();
+        pin.set_open_drain_output(false);
+        assert_eq!(fake::read(rro::GPIO_OUTEN, GPIO_1), other_pins | bit);
+        pin.set_open_drain_output(true);
+        assert_eq!(fake::read(rro::GPIO_OUTEN, GPIO_1), other_pins);
+        // Only the driver is switched, the output stays low.
+        assert_eq!(
+            fake::take_accesses(),
+            [
+                fake::Access::Write(rro::GPIO_OUTEN_SET, bit),
+                fake::Access::Write(rro::GPIO_OUTEN_CLR, bit),
+            ]
+        );
+        assert_eq!(fake::read(rro::GPIO_OUT, GPIO_1), other_pins);
+    }
+
+    #[test]
+    fn test_all_equal_of_takes_every_sample() {
+        let mut reads = 0;
+        let _ = all_equal_of(4, || {
+            reads += 1;
+            reads == 1
+        });
+        assert_eq!(reads, 4);
+    }
+}
@@ -2,23 +2,44 @@ use crate::bits::BitManipulation;
 
 use super::GpioPin;
 
+#[cfg(not(test))]
 static mut PINS_OWNED: [u32; 4] = [0_u32; 4];
 
+// Tests run on many threads at once, so each test thread gets its own pins.
+#[cfg(test)]
+std::thread_local! {
+    static PINS_OWNED: core::cell::RefCell<[u32; 4]> = const { core::cell::RefCell::new([0_u32; 4]) };
+}
+
 fn pin_mode(pin: &GpioPin) -> (usize, usize) {
     (pin.get_port() as u8 as usize, pin.get_pin())
 }
 
+#[cfg(not(test))]
+fn with_pins_owned<R>(func: impl FnOnce(&mut [u32; 4]) -> R) -> R {
+    func(unsafe { &mut *core::ptr::addr_of_mut!(PINS_OWNED) })
+}
+
+#[cfg(test)]
+fn with_pins_owned<R>(func: impl FnOnce(&mut [u32; 4]) -> R) -> R {
+    PINS_OWNED.with_borrow_mut(func)
+}
+
 pub fn is_owned(pin: &GpioPin) -> bool {
     let (port, pin) = pin_mode(pin);
-    unsafe { PINS_OWNED[port].get_bit(pin as u8) }
+    with_pins_owned(|owned| owned[port].get_bit(pin as u8))
 }
 
 pub fn set_owned(pin: &GpioPin) {
     let (port, pin) = pin_mode(pin);
-    unsafe { PINS_OWNED[port].set_bit(pin as u8, true) };
+    with_pins_owned(|owned| {
+        owned[port].set_bit(pin as u8, true);
+    });
 }
 
 pub fn disown_pin(pin: &GpioPin) {
     let (port, pin) = pin_mode(pin);
-    unsafe { PINS_OWNED[port].set_bit(pin as u8, false) };
+    with_pins_owned(|owned| {
+        owned[port].set_bit(pin as u8, false);
+    });
 }
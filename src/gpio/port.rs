@@ -0,0 +1,157 @@
+use super::{registers, GpioPin, GpioSelect};
+use crate::error::{ErrorKind, Result};
+
+/// # GPIO Port
+/// A group of `N` pins of the same GPIO port, written and read all at once. Each
+/// `write` is one read of `GPIO_OUT` and one write back, and each `read` is one
+/// read of `GPIO_IN`, instead of one read-modify-write per pin. This is what an 8
+/// bit parallel bus wants, where every data line must change at the same time.
+///
+/// The group owns its pins, so every bit it touches belongs to a `GpioPin` no one
+/// else holds. Masks are in port bit order, bit `n` being pin `n` of the port.
+///
+/// # Example
+/// ```no_run
+/// use max78000_hal::gpio::port::GpioPort;
+/// use max78000_hal::gpio::{GpioPin, GpioSelect};
+///
+/// let pins = core::array::from_fn(|n| GpioPin::new(GpioSelect::Gpio1, n).unwrap());
+/// let bus = GpioPort::<8>::new(pins).ok().unwrap();
+/// bus.write(0xFF, 0xA5).unwrap();
+/// let value = bus.read() & 0xFF;
+/// ```
+pub struct GpioPort<const N: usize> {
+    pins: [GpioPin; N],
+    mask: u32,
+}
+
+impl<const N: usize> GpioPort<N> {
+    /// # New
+    /// Group `pins` into one port. The pins are not reconfigured, so each should
+    /// already be set up as an input or output.
+    ///
+    /// Returns `ErrorKind::BadParam` if the pins are not all on the same port,
+    /// together with the pins so they can be used elsewhere.
+    pub fn new(pins: [GpioPin; N]) -> core::result::Result<Self, ([GpioPin; N], ErrorKind)> {
+        let port = pins.first().map(|pin| pin.get_port() as u8);
+        if pins.iter().any(|pin| Some(pin.get_port() as u8) != port) {
+            return Err((pins, ErrorKind::BadParam));
+        }
+
+        let mask = pins.iter().fold(0, |mask, pin| mask | (1 << pin.get_pin()));
+        Ok(Self { pins, mask })
+    }
+
+    /// # Get Port
+    /// The GPIO port every pin of the group is on, or `None` for an empty group.
+    pub fn get_port(&self) -> Option<GpioSelect> {
+        self.pins.first().map(GpioPin::get_port)
+    }
+
+    /// # Mask
+    /// The bits of the pins in this group.
+    pub fn mask(&self) -> u32 {
+        self.mask
+    }
+
+    /// # Write
+    /// Drive the pins in `mask` to their bits of `values` in a single write to
+    /// `GPIO_OUT`. Pins of the port outside `mask` keep their output.
+    ///
+    /// This reads the port first, so it races with anything driving other pins of
+    /// the same port from another context, unlike `GpioPin::set_high_atomic`.
+    ///
+    /// Returns `ErrorKind::BadParam` if `mask` has a pin this group does not own,
+    /// without writing anything.
+    pub fn write(&self, mask: u32, values: u32) -> Result<()> {
+        if mask & !self.mask != 0 {
+            return Err(ErrorKind::BadParam);
+        }
+
+        let Some(port) = self.get_port() else {
+            return Ok(());
+        };
+
+        unsafe {
+            let out = registers::read_gpio(registers::rro::GPIO_OUT, port.into());
+            registers::write_gpio(
+                registers::rro::GPIO_OUT,
+                port.into(),
+                (out & !mask) | (values & mask),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// # Read
+    /// Read the input of every pin of the group in a single read of `GPIO_IN`,
+    /// with the bits of pins outside the group cleared.
+    pub fn read(&self) -> u32 {
+        let Some(port) = self.get_port() else {
+            return 0;
+        };
+
+        unsafe { registers::read_gpio(registers::rro::GPIO_IN, port.into()) & self.mask }
+    }
+
+    /// # Into Pins
+    /// Split the group back into its pins.
+    pub fn into_pins(self) -> [GpioPin; N] {
+        self.pins
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use registers::{fake, rro, GPIO_1};
+
+    #[test]
+    fn masked_write_test() {
+        let pins = [2, 3, 4, 5].map(|n| GpioPin::new(GpioSelect::Gpio1, n).unwrap());
+        let port = GpioPort::new(pins).ok().unwrap();
+        assert_eq!(port.mask(), 0b11_1100);
+
+        let other_pins = 0b1100_0011;
+        fake::write(rro::GPIO_OUT, GPIO_1, other_pins);
+        fake::take_accesses();
+
+        port.write(0b11_1100, 0xFF_FF_10_14).unwrap();
+        assert_eq!(
+            fake::take_accesses(),
+            [
+                fake::Access::Read(rro::GPIO_OUT),
+                fake::Access::Write(rro::GPIO_OUT, other_pins | 0b01_0100),
+            ]
+        );
+
+        port.write(0b00_1100, 0).unwrap();
+        assert_eq!(fake::read(rro::GPIO_OUT, GPIO_1), other_pins | 0b01_0000);
+
+        // Pin 6 belongs to someone else.
+        assert_eq!(port.write(0b100_0000, 0), Err(ErrorKind::BadParam));
+        assert_eq!(fake::read(rro::GPIO_OUT, GPIO_1), other_pins | 0b01_0000);
+
+        fake::write(rro::GPIO_IN, GPIO_1, 0xFFFF_FF2A);
+        assert_eq!(port.read(), 0b10_1000);
+    }
+
+    #[test]
+    fn mixed_ports_test() {
+        let pins = [
+            GpioPin::new(GpioSelect::Gpio1, 0).unwrap(),
+            GpioPin::new(GpioSelect::Gpio2, 1).unwrap(),
+        ];
+        let Err(([first, second], err)) = GpioPort::new(pins) else {
+            panic!("pins of two ports were grouped");
+        };
+        assert_eq!(err, ErrorKind::BadParam);
+
+        // The pins come back with the error, and still group with their own port.
+        assert_eq!(first.get_port() as u8, GpioSelect::Gpio1 as u8);
+        let port = GpioPort::new([second]).ok().unwrap();
+        let [pin] = port.into_pins();
+        assert_eq!(pin.get_pin(), 1);
+    }
+}
@@ -90,6 +90,7 @@ pub(super) mod rro {
 
 /// # Write GPIO
 /// Write to a GPIO register and port.
+#[cfg(not(test))]
 pub(crate) unsafe fn write_gpio(base: BaseOffset, port: PortOffset, value: u32) {
     let ptr = (base + port) as *mut u32;
 
@@ -98,12 +99,25 @@ pub(crate) unsafe fn write_gpio(base: BaseOffset, port: PortOffset, value: u32)
 
 /// # Read GPIO
 /// Read from the GPIO register and port.
+#[cfg(not(test))]
 pub(crate) unsafe fn read_gpio(base: BaseOffset, port: PortOffset) -> u32 {
     let ptr = (base + port) as *const u32;
 
     core::ptr::read_volatile(ptr)
 }
 
+#[cfg(test)]
+pub(crate) unsafe fn write_gpio(base: BaseOffset, port: PortOffset, value: u32) {
+    fake::record(fake::Access::Write(base, value));
+    fake::write(base, port, value);
+}
+
+#[cfg(test)]
+pub(crate) unsafe fn read_gpio(base: BaseOffset, port: PortOffset) -> u32 {
+    fake::record(fake::Access::Read(base));
+    fake::read(base, port)
+}
+
 /// # Enable Bit
 /// Enable the bit for the given GPIO port and register.
 pub(crate) unsafe fn enable_bit(base: BaseOffset, port: PortOffset, bit: usize) {
@@ -119,3 +133,84 @@ pub(crate) unsafe fn disable_bit(base: BaseOffset, port: PortOffset, bit: usize)
     let bit = 1 << bit;
     write_gpio(base, port, read & (!bit));
 }
+
+/// # Fake GPIO
+/// While testing there is no GPIO hardware, so reads and writes go to a per
+/// test thread copy of the GPIO registers instead.
+#[cfg(test)]
+pub(crate) mod fake {
+    use super::{BaseOffset, PortOffset, GPIO_0, GPIO_1, GPIO_2};
+    use core::cell::RefCell;
+
+    const FAKE_REGISTER_WORDS: usize = 64;
+
+    std::thread_local! {
+        static FAKE_GPIO: RefCell<[[u32; FAKE_REGISTER_WORDS]; 3]> =
+            const { RefCell::new([[0; FAKE_REGISTER_WORDS]; 3]) };
+        static ACCESSES: RefCell<std::vec::Vec<Access>> =
+            const { RefCell::new(std::vec::Vec::new()) };
+    }
+
+    /// One register access made through `read_gpio`/`write_gpio`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub(crate) enum Access {
+        Read(BaseOffset),
+        Write(BaseOffset, u32),
+    }
+
+    /// Take the accesses made through `read_gpio`/`write_gpio` on this thread since
+    /// the last call.
+    pub(crate) fn take_accesses() -> std::vec::Vec<Access> {
+        ACCESSES.take()
+    }
+
+    pub(crate) fn record(access: Access) {
+        ACCESSES.with_borrow_mut(|accesses| accesses.push(access));
+    }
+
+    fn with_register<R>(base: BaseOffset, port: PortOffset, func: impl FnOnce(&mut u32) -> R) -> R {
+        let port_index = match port {
+            GPIO_0 => 0,
+            GPIO_1 => 1,
+            GPIO_2 => 2,
+            _ => panic!("Unknown GPIO port {port:#x}"),
+        };
+
+        FAKE_GPIO.with_borrow_mut(|ports| func(&mut ports[port_index][base / 4]))
+    }
+
+    pub(crate) fn write(base: BaseOffset, port: PortOffset, value: u32) {
+        with_register(base, port, |register| *register = value);
+
+        // Like the hardware, writing a 1 to one of the `_SET`/`_CLR` registers sets or
+        // clears that bit of the register it controls.
+        use super::rro::*;
+        let (target, set) = match base {
+            GPIO_OUT_SET => (GPIO_OUT, true),
+            GPIO_OUT_CLR => (GPIO_OUT, false),
+            GPIO_OUTEN_SET => (GPIO_OUTEN, true),
+            GPIO_OUTEN_CLR => (GPIO_OUTEN, false),
+            GPIO_EN0_SET => (GPIO_EN0, true),
+            GPIO_EN0_CLR => (GPIO_EN0, false),
+            GPIO_EN1_SET => (GPIO_EN1, true),
+            GPIO_EN1_CLR => (GPIO_EN1, false),
+            GPIO_EN2_SET => (GPIO_EN2, true),
+            GPIO_EN2_CLR => (GPIO_EN2, false),
+            GPIO_WKEN_SET => (GPIO_WKEN, true),
+            GPIO_WKEN_CLR => (GPIO_WKEN, false),
+            _ => return,
+        };
+
+        with_register(target, port, |register| {
+            if set {
+                *register |= value
+            } else {
+                *register &= !value
+            }
+        });
+    }
+
+    pub(crate) fn read(base: BaseOffset, port: PortOffset) -> u32 {
+        with_register(base, port, |register| *register)
+    }
+}
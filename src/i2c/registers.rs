@@ -45,8 +45,12 @@ mod rro {
     pub const I2C_SLAVE: usize = 0x004C;
 }
 
+/// The offset of the FIFO data register, which a DMA channel reads or writes.
+pub(super) const FIFO_OFFSET: usize = rro::I2C_FIFO;
+
 make_device! {
     device_ports(crate::memory_map::mmio::I2C_PORT_0, crate::memory_map::mmio::I2C_PORT_1, crate::memory_map::mmio::I2C_PORT_2);
+    window_size(0x1000);
 
     /// The I2C entire control register field.
     #[bit(0..=15, RW, rro::I2C_CTRL)]
@@ -752,6 +756,7 @@ make_device! {
     /// Write to the transmit FIFO (pushes the data onto the transmit FIFO).
     ///
     /// If the FIFO is full, this operation is ignored (the data will be lost).
+    #[no_dump]
     #[bit(0..=7, RW, rro::I2C_FIFO)]
     fifo_data,
 
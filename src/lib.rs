@@ -1,15 +1,35 @@
 #![no_std]
+
+#[cfg(test)]
+extern crate std;
+
+pub mod adc;
 pub mod aes;
 pub mod bits;
+pub mod critical_section;
 pub mod debug;
+pub mod delay;
+pub mod dma;
 pub mod error;
+pub mod flash;
 pub mod gcr;
 pub mod gpio;
 pub mod i2c;
 pub mod memory_map;
+pub mod nb;
+#[cfg(feature = "panic-uart")]
+pub mod panic_uart;
+pub mod port;
+pub mod rtc;
+pub mod scs;
+pub mod spi;
+#[cfg(feature = "systick-time")]
+pub mod time;
 pub mod timer;
 pub mod trng;
 pub mod uart;
+pub mod util;
+pub mod wdt;
 
 #[cfg(test)]
 pub mod tests;
@@ -19,6 +39,13 @@ extern "C" {
     pub static SYSTEM_CORE_CLOCK: u32;
 }
 
+// On the host there is no startup code to provide the core clock, so tests get
+// a fixed 100MHz clock.
+#[cfg(test)]
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+static SystemCoreClock: u32 = 100_000_000;
+
 /// # Core Peripheral Clock
 /// Get the peripheral clock used for timing things like I2C and UART for the CPU.
 pub fn core_peripheral_clock() -> u32 {
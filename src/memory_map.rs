@@ -158,4 +158,85 @@ pub mod mmio {
     /// # CNNx16 Quadrant 3 (CNNx16_3)
     /// The CNNx16 Quadrant 3 ptr.
     pub const CNNX16_QUADRANT_3: usize = 0x50d0_0000;
+
+    /// # Region
+    /// The address range taken up by the register block of the peripheral at
+    /// `base`, or `None` if `base` is not one of the peripheral ptrs above.
+    /// Found at: MAX78000 Pg 31 & 37
+    pub const fn region(base: usize) -> Option<core::ops::Range<usize>> {
+        let span = match base {
+            GLOBAL_CONTROL
+            | SYSTEM_INTERFACE
+            | FUNCTION_CONTROL
+            | WATCHDOG_TIMER0
+            | DYNAMIC_VOLTAGE_SCALING
+            | SIMO
+            | TRIM_SYSTEM_INITIALIZATION
+            | GENERAL_CONTROL_FUNCTION
+            | REAL_TIME_CLOCK
+            | WAKEUP_TIMER
+            | POWER_SEQUENCER
+            | MISCELLANEOUS_CONTROL
+            | AES
+            | AES_KEYS
+            | LOW_POWER_CONTROL
+            | GPIO_PORT_2
+            | LOW_POWER_WATCHDOG_TIMER_0
+            | LOW_POWER_TIMER_0
+            | LOW_POWER_TIMER_1
+            | LOW_POWER_UART_0
+            | LOW_POWER_COMPARATORS => 0x400,
+            ICC_0_CM4 | ICC_1_RV32 => 0x800,
+            GPIO_PORT_0
+            | GPIO_PORT_1
+            | PARALLEL_CAMERA_INTERFACE
+            | CRC
+            | TIMER_0
+            | TIMER_1
+            | TIMER_2
+            | TIMER_3
+            | I2C_PORT_0
+            | I2C_PORT_1
+            | I2C_PORT_2
+            | STANDARD_DMA
+            | FLASH_CONTROLLER_0
+            | ADC
+            | PULSE_TRAIN_ENGINE
+            | ONE_WIRE_MASTER
+            | SEMAPHORE
+            | UART_0
+            | UART_1
+            | UART_2
+            | SPI_1
+            | TRNG
+            | I2S
+            | SPI_0 => 0x1000,
+            CNN_GLOBAL_CONTROL => 0x10_0000,
+            CNNX16_QUADRANT_0 | CNNX16_QUADRANT_1 | CNNX16_QUADRANT_2 | CNNX16_QUADRANT_3 => {
+                0x40_0000
+            }
+            _ => return None,
+        };
+
+        Some(base..(base + span))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::mmio;
+
+    #[test]
+    fn region_test() {
+        assert_eq!(
+            mmio::region(mmio::GLOBAL_CONTROL),
+            Some(0x4000_0000..0x4000_0400)
+        );
+        assert_eq!(mmio::region(mmio::UART_0), Some(0x4004_2000..0x4004_3000));
+        assert_eq!(
+            mmio::region(mmio::ICC_0_CM4),
+            Some(0x4002_a000..mmio::ICC_1_RV32)
+        );
+        assert_eq!(mmio::region(mmio::UART_0 + 4), None);
+    }
 }
@@ -0,0 +1,39 @@
+/// # Non-Blocking Error
+/// The error type for non-blocking operations. This mirrors the API of the `nb`
+/// crate, so drivers can be polled from a cooperative superloop without pulling in
+/// an extra dependency.
+#[derive(Clone, Copy, Debug)]
+pub enum Error<E> {
+    /// # Other
+    /// The operation failed with some other error.
+    Other(E),
+    /// # Would Block
+    /// The operation cannot make any more progress right now, call it again later.
+    WouldBlock,
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(error: E) -> Self {
+        Self::Other(error)
+    }
+}
+
+/// # Result
+/// Result type for non-blocking operations.
+pub type Result<T, E> = core::result::Result<T, Error<E>>;
+
+/// # Block
+/// Turn a non-blocking expression into a blocking one by polling it until
+/// it no longer returns `WouldBlock`.
+#[macro_export]
+macro_rules! block {
+    ($e:expr) => {
+        loop {
+            match $e {
+                Err($crate::nb::Error::Other(error)) => break Err(error),
+                Err($crate::nb::Error::WouldBlock) => {}
+                Ok(value) => break Ok(value),
+            }
+        }
+    };
+}
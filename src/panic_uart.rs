@@ -0,0 +1,44 @@
+//! # Panic UART
+//! A `#[panic_handler]` that writes the panic message to a UART and then resets
+//! the controller. This is only built with the `panic-uart` feature.
+//!
+//! Only one panic handler can exist per binary, so this feature must not be
+//! enabled together with another panic handler crate (like `panic-halt`).
+
+use crate::critical_section::{self, Mutex};
+use core::cell::RefCell;
+#[cfg(not(test))]
+use core::fmt::Write;
+
+static PANIC_UART: Mutex<RefCell<PanicStream>> = Mutex::new(RefCell::new(PanicStream(None)));
+
+#[cfg_attr(test, allow(dead_code))]
+struct PanicStream(Option<&'static mut dyn core::fmt::Write>);
+
+// The stream is only ever reached through `PANIC_UART` inside a critical section.
+unsafe impl Send for PanicStream {}
+
+/// # Set Panic UART
+/// Set the UART (or any other `core::fmt::Write` stream) that panic messages are
+/// written to. Until this is called a panic resets the controller silently.
+pub fn set_panic_uart(uart: &'static mut dyn core::fmt::Write) {
+    critical_section::with(|cs| {
+        PANIC_UART.borrow(cs).replace(PanicStream(Some(uart)));
+    });
+}
+
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    critical_section::with(|cs| {
+        // If the panic happened while the UART was being set, skip the message
+        // rather than panicking again.
+        if let Ok(mut uart) = PANIC_UART.borrow(cs).try_borrow_mut() {
+            if let Some(uart) = uart.0.as_mut() {
+                let _ = writeln!(uart, "{info}");
+            }
+        }
+    });
+
+    crate::gcr::controller_reset()
+}
@@ -0,0 +1,29 @@
+pub(crate) mod private {
+    pub trait Sealed {}
+}
+
+/// # Port
+/// A peripheral port type, like `uart::UART0` or `i2c::I2CPort1`. Every port type
+/// of every peripheral implements this, so code can be generic over the port of
+/// any peripheral. This trait is sealed and cannot be implemented outside this
+/// crate.
+///
+/// # Example
+/// ```
+/// use max78000_hal::memory_map::mmio;
+/// use max78000_hal::port::Port;
+///
+/// /// The register block of any peripheral port.
+/// fn register_block<P: Port>() -> core::ops::Range<usize> {
+///     mmio::region(P::BASE).unwrap()
+/// }
+///
+/// assert_eq!(register_block::<max78000_hal::uart::UART1>().start, mmio::UART_1);
+/// assert_eq!(<max78000_hal::i2c::I2CPort2 as Port>::NUM, 2);
+/// ```
+pub trait Port: private::Sealed {
+    /// The base address of the port's registers in `memory_map::mmio`.
+    const BASE: usize;
+    /// The number of the port, i.e. `1` for `UART1`.
+    const NUM: usize;
+}
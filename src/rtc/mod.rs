@@ -0,0 +1,317 @@
+//! # Real-Time Clock
+//! The RTC counts seconds and 1/4096 second ticks from the 32.768kHz crystal, and
+//! keeps counting through every reset but a power-on reset.
+//!
+//! # Register Handshake
+//! The RTC runs in its own clock domain, so its registers are reached through a
+//! handshake (see the user guide RTC chapter):
+//! - Before any write to an RTC register, wait for `RTC_CTRL.busy` to clear, and
+//!   wait for it again before the next one.
+//! - The counters and `RTC_CTRL.en` can only be written with `RTC_CTRL.wr_en` set,
+//!   and the counters only while the RTC is disabled.
+//! - The counters are only read after clearing `RTC_CTRL.rdy` and waiting for
+//!   the RTC to set it again, which it does just after they update, so the
+//!   seconds and sub-seconds read belong together.
+
+pub mod registers;
+
+use crate::error::{ErrorKind, Result};
+use crate::gcr::{enable_oscillator, Oscillator};
+use crate::memory_map::mmio;
+use core::sync::atomic::AtomicBool;
+use registers::Registers;
+
+/// The rate of the sub-second counter, in ticks a second.
+pub const SUBSECOND_HZ: u32 = 4096;
+
+/// The largest sub-second count, one tick before the next second.
+pub const MAX_SUBSECONDS: u16 = (SUBSECOND_HZ - 1) as u16;
+
+/// The bits of the seconds counter the time-of-day alarm is compared against.
+pub const ALARM_MASK: u32 = 0xF_FFFF;
+
+/// How many times the RTC is checked for `busy` to clear or `ready` to set before
+/// giving up. A write takes a few RTC clocks to synchronize, and `ready` sets on
+/// every sub-second tick, so both are well under a millisecond.
+const HANDSHAKE_SPINS: u32 = 1_000_000;
+
+/// Set once `RTC::init` has handed out the RTC.
+static RTC_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// # RTC
+/// The real-time clock, made with `RTC::init`.
+pub struct RTC {
+    reg: Registers,
+}
+
+impl RTC {
+    /// # Init
+    /// Enable the 32.768kHz crystal and start the RTC counting from whatever time
+    /// it holds. The RTC is not reset, so the time set before a system reset is
+    /// kept.
+    ///
+    /// Returns `ErrorKind::Busy` if the RTC was already handed out, and
+    /// `ErrorKind::TimeOut` if the crystal does not start (like with none fitted),
+    /// or the RTC stays busy.
+    pub fn init() -> Result<Self> {
+        crate::util::init_once(&RTC_TAKEN, || {
+            enable_oscillator(Oscillator::ERTCO)?;
+
+            let mut rtc = Self {
+                reg: Registers::new(mmio::REAL_TIME_CLOCK),
+            };
+            rtc.write(|reg| {
+                unsafe { reg.set_enable(true) };
+                wait_not_busy(reg)
+            })?;
+
+            Ok(rtc)
+        })
+    }
+
+    /// # Set Time
+    /// Set the clock to `seconds` and `subseconds` 1/4096 second ticks. The RTC is
+    /// stopped while the counters are written, and started again after.
+    ///
+    /// Returns `ErrorKind::BadParam` if `subseconds` is above `MAX_SUBSECONDS`, and
+    /// `ErrorKind::TimeOut` if the RTC stays busy.
+    pub fn set_time(&mut self, seconds: u32, subseconds: u16) -> Result<()> {
+        if subseconds > MAX_SUBSECONDS {
+            return Err(ErrorKind::BadParam);
+        }
+
+        self.write(|reg| unsafe {
+            reg.set_enable(false);
+            wait_not_busy(reg)?;
+            reg.set_subseconds(subseconds);
+            wait_not_busy(reg)?;
+            reg.set_seconds(seconds);
+            wait_not_busy(reg)?;
+            reg.set_enable(true);
+            wait_not_busy(reg)
+        })
+    }
+
+    /// # Get Time
+    /// The seconds and 1/4096 second ticks counted, read together once the RTC
+    /// reports them ready.
+    ///
+    /// Returns `ErrorKind::TimeOut` if the RTC stays busy or never becomes ready,
+    /// like when it is not running.
+    pub fn get_time(&mut self) -> Result<(u32, u16)> {
+        wait_not_busy(&self.reg)?;
+        unsafe { self.reg.set_ready(false) };
+        crate::util::wait_until(HANDSHAKE_SPINS, || self.reg.get_ready())?;
+
+        Ok((self.reg.get_seconds(), self.reg.get_subseconds()))
+    }
+
+    /// # Set Alarm
+    /// Raise the RTC alarm the next time the low 20 bits of the seconds counter
+    /// are those of `seconds`, which is `seconds` itself while it is less than
+    /// `ALARM_MASK` seconds (about 12 days) ahead. Any earlier alarm is cleared,
+    /// and the alarm interrupt is enabled, so it can wake the part with
+    /// `WakeupSources::rtc_alarm`.
+    ///
+    /// Returns `ErrorKind::TimeOut` if the RTC stays busy.
+    pub fn set_alarm(&mut self, seconds: u32) -> Result<()> {
+        wait_not_busy(&self.reg)?;
+        unsafe { self.reg.set_time_of_day_alarm_interrupt_enable(false) };
+        wait_not_busy(&self.reg)?;
+        unsafe { self.reg.set_time_of_day_alarm(seconds & ALARM_MASK) };
+        self.clear_alarm()?;
+        unsafe { self.reg.set_time_of_day_alarm_interrupt_enable(true) };
+        wait_not_busy(&self.reg)
+    }
+
+    /// # Disable Alarm
+    /// Stop the alarm set with `set_alarm` from raising its interrupt.
+    ///
+    /// Returns `ErrorKind::TimeOut` if the RTC stays busy.
+    pub fn disable_alarm(&mut self) -> Result<()> {
+        wait_not_busy(&self.reg)?;
+        unsafe { self.reg.set_time_of_day_alarm_interrupt_enable(false) };
+        wait_not_busy(&self.reg)
+    }
+
+    /// # Is Alarm Triggered
+    /// Check if the alarm matched since it was last cleared.
+    pub fn is_alarm_triggered(&self) -> bool {
+        self.reg.get_time_of_day_alarm_flag()
+    }
+
+    /// # Clear Alarm
+    /// Clear the alarm flag, which the RTC interrupt handler must do before it
+    /// returns.
+    ///
+    /// Returns `ErrorKind::TimeOut` if the RTC stays busy.
+    pub fn clear_alarm(&mut self) -> Result<()> {
+        wait_not_busy(&self.reg)?;
+        unsafe { self.reg.set_time_of_day_alarm_flag(false) };
+        wait_not_busy(&self.reg)
+    }
+
+    /// Run `func` with `write_enable` set, clearing it again even if `func` fails.
+    fn write(&mut self, func: impl FnOnce(&mut Registers) -> Result<()>) -> Result<()> {
+        wait_not_busy(&self.reg)?;
+        unsafe { self.reg.set_write_enable(true) };
+        let result = wait_not_busy(&self.reg).and_then(|()| func(&mut self.reg));
+        unsafe { self.reg.set_write_enable(false) };
+        result
+    }
+}
+
+/// Wait for the last write to the RTC to be synchronized.
+fn wait_not_busy(reg: &Registers) -> Result<()> {
+    reg.wait_busy(false, HANDSHAKE_SPINS)
+}
+
+/// # Date Time Access
+/// Reading and setting the clock, in the shape of `rtcc::DateTimeAccess`, with
+/// the clock's seconds (counted from an epoch of the caller's choosing) instead
+/// of a chrono `NaiveDateTime`. Only built with the `rtcc` feature.
+#[cfg(feature = "rtcc")]
+pub trait DateTimeAccess {
+    type Error;
+
+    /// Read the seconds counted, the same as `RTC::get_time` without the
+    /// sub-seconds.
+    fn datetime(&mut self) -> core::result::Result<u32, Self::Error>;
+
+    /// Set the clock to `seconds`, the same as `RTC::set_time(seconds, 0)`.
+    fn set_datetime(&mut self, seconds: u32) -> core::result::Result<(), Self::Error>;
+}
+
+#[cfg(feature = "rtcc")]
+impl DateTimeAccess for RTC {
+    type Error = ErrorKind;
+
+    fn datetime(&mut self) -> Result<u32> {
+        self.get_time().map(|(seconds, _)| seconds)
+    }
+
+    fn set_datetime(&mut self, seconds: u32) -> Result<()> {
+        self.set_time(seconds, 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tests::FakeRegisters;
+    use registers::rro::{RTC_CTRL, RTC_SEC, RTC_SSEC, RTC_TODA};
+
+    const ENABLE: u32 = 1 << 0;
+    const ALARM_INTERRUPT_ENABLE: u32 = 1 << 1;
+    const BUSY: u32 = 1 << 3;
+    const READY: u32 = 1 << 4;
+    const ALARM_FLAG: u32 = 1 << 6;
+
+    /// Fake RTC registers, shared with a thread playing the RTC side of the
+    /// handshake.
+    type FakeRtc = FakeRegisters<5>;
+
+    fn fake_rtc(fake_registers: &'static FakeRtc) -> RTC {
+        RTC {
+            reg: Registers::new(fake_registers.address()),
+        }
+    }
+
+    /// Run `rtc_side` on another thread, and only return once it is running.
+    fn spawn_rtc_side(rtc_side: impl FnOnce() + Send + 'static) -> std::thread::JoinHandle<()> {
+        let started = std::sync::Arc::new(std::sync::Barrier::new(2));
+        let thread = std::thread::spawn({
+            let started = started.clone();
+            move || {
+                started.wait();
+                rtc_side();
+            }
+        });
+        started.wait();
+        thread
+    }
+
+    #[test]
+    fn write_waits_for_busy_test() {
+        static FAKE: FakeRtc = FakeRegisters::new();
+        let mut rtc = fake_rtc(&FAKE);
+
+        // Stuck busy, so nothing is written.
+        FAKE.set_word(RTC_CTRL, BUSY);
+        assert_eq!(rtc.set_time(100, 5), Err(ErrorKind::TimeOut));
+        assert_eq!(FAKE.word(RTC_SEC), 0);
+        assert_eq!(FAKE.word(RTC_SSEC), 0);
+        assert_eq!(FAKE.word(RTC_CTRL), BUSY);
+
+        // Busy for a while longer, during which the counters must stay untouched.
+        let rtc_side = spawn_rtc_side(|| {
+            for _ in 0..10_000 {
+                assert_eq!(FAKE.word(RTC_SEC), 0);
+                assert_eq!(FAKE.word(RTC_SSEC), 0);
+            }
+            FAKE.clear_bits(RTC_CTRL, BUSY);
+        });
+        assert_eq!(rtc.set_time(100, 5), Ok(()));
+        rtc_side.join().unwrap();
+
+        assert_eq!(FAKE.word(RTC_SEC), 100);
+        assert_eq!(FAKE.word(RTC_SSEC), 5);
+        // Running, with the write enable cleared again.
+        assert_eq!(FAKE.word(RTC_CTRL), ENABLE);
+
+        assert_eq!(
+            rtc.set_time(0, MAX_SUBSECONDS + 1),
+            Err(ErrorKind::BadParam)
+        );
+    }
+
+    #[test]
+    fn read_waits_for_ready_test() {
+        static FAKE: FakeRtc = FakeRegisters::new();
+        let mut rtc = fake_rtc(&FAKE);
+
+        // A ready bit left over from before must be cleared and waited on again,
+        // or the counters read could be from either side of an update.
+        FAKE.set_word(RTC_CTRL, ENABLE | READY);
+        FAKE.set_word(RTC_SEC, 41);
+        let rtc_side = spawn_rtc_side(|| {
+            while FAKE.word(RTC_CTRL) & READY != 0 {
+                std::hint::spin_loop();
+            }
+            FAKE.set_word(RTC_SEC, 42);
+            FAKE.set_word(RTC_SSEC, 7);
+            FAKE.set_bits(RTC_CTRL, READY);
+        });
+        assert_eq!(rtc.get_time(), Ok((42, 7)));
+        rtc_side.join().unwrap();
+
+        // Never ready, like a stopped RTC.
+        assert_eq!(rtc.get_time(), Err(ErrorKind::TimeOut));
+        assert_eq!(FAKE.word(RTC_CTRL), ENABLE);
+    }
+
+    #[test]
+    fn alarm_test() {
+        static FAKE: FakeRtc = FakeRegisters::new();
+        let mut rtc = fake_rtc(&FAKE);
+
+        FAKE.set_word(RTC_CTRL, ENABLE | ALARM_FLAG);
+        assert!(rtc.is_alarm_triggered());
+        assert_eq!(rtc.set_alarm(0x12_3456), Ok(()));
+        assert_eq!(FAKE.word(RTC_TODA), 0x2_3456);
+        assert_eq!(FAKE.word(RTC_CTRL), ENABLE | ALARM_INTERRUPT_ENABLE);
+        assert!(!rtc.is_alarm_triggered());
+
+        FAKE.set_bits(RTC_CTRL, ALARM_FLAG);
+        assert!(rtc.is_alarm_triggered());
+        assert_eq!(rtc.clear_alarm(), Ok(()));
+        assert!(!rtc.is_alarm_triggered());
+
+        assert_eq!(rtc.disable_alarm(), Ok(()));
+        assert_eq!(FAKE.word(RTC_CTRL), ENABLE);
+
+        FAKE.set_word(RTC_CTRL, BUSY);
+        assert_eq!(rtc.set_alarm(10), Err(ErrorKind::TimeOut));
+        assert_eq!(FAKE.word(RTC_TODA), 0x2_3456);
+    }
+}
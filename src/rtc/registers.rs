@@ -0,0 +1,65 @@
+use crate::memory_map::mmio;
+use hal_macros::RW;
+use hal_macros_derive::make_device;
+
+/// # RTC Register Offsets
+/// These match `mxc_rtc_regs_t` in the MSDK.
+pub(super) mod rro {
+    /// # RTC Seconds Counter Register
+    pub const RTC_SEC: usize = 0x0000;
+    /// # RTC Sub-second Counter Register
+    pub const RTC_SSEC: usize = 0x0004;
+    /// # RTC Time-of-day Alarm Register
+    pub const RTC_TODA: usize = 0x0008;
+    /// # RTC Control Register
+    pub const RTC_CTRL: usize = 0x0010;
+}
+
+make_device! {
+    device_ports(mmio::REAL_TIME_CLOCK);
+
+    /// Seconds Counter. `MXC_F_RTC_SEC_SEC`.
+    /// Only written with `write_enable` set and the RTC disabled.
+    #[bit(0..=31, RW, rro::RTC_SEC)]
+    seconds,
+
+    /// Sub-second Counter. `MXC_F_RTC_SSEC_SSEC`.
+    /// Counts at 4096Hz, carrying into `seconds` when it wraps.
+    #[bit(0..=11, RW, rro::RTC_SSEC)]
+    subseconds,
+
+    /// Time-of-day Alarm. `MXC_F_RTC_TODA_TOD_ALARM`.
+    /// Compared against the low 20 bits of `seconds`.
+    #[bit(0..=19, RW, rro::RTC_TODA)]
+    time_of_day_alarm,
+
+    /// RTC Enable. `MXC_F_RTC_CTRL_EN`.
+    #[bit(0, RW, rro::RTC_CTRL)]
+    enable,
+
+    /// Time-of-day Alarm Interrupt Enable. `MXC_F_RTC_CTRL_TOD_ALARM_IE`.
+    #[bit(1, RW, rro::RTC_CTRL)]
+    time_of_day_alarm_interrupt_enable,
+
+    /// RTC Busy. `MXC_F_RTC_CTRL_BUSY`.
+    /// Set while a write is being synchronized into the RTC clock domain. No RTC
+    /// register may be written until it clears.
+    #[bit(3, RO, rro::RTC_CTRL)]
+    busy,
+
+    /// RTC Ready. `MXC_F_RTC_CTRL_RDY`.
+    /// Set by hardware when `seconds` and `subseconds` were just updated and can
+    /// be read, cleared by writing `0`.
+    #[bit(4, RW, rro::RTC_CTRL)]
+    ready,
+
+    /// Time-of-day Alarm Flag. `MXC_F_RTC_CTRL_TOD_ALARM`.
+    /// Set when the time-of-day alarm matches, cleared by writing `0`.
+    #[bit(6, RW, rro::RTC_CTRL)]
+    time_of_day_alarm_flag,
+
+    /// Write Enable. `MXC_F_RTC_CTRL_WR_EN`.
+    /// Allows writes to `seconds`, `subseconds` and `enable`.
+    #[bit(15, RW, rro::RTC_CTRL)]
+    write_enable,
+}
@@ -0,0 +1,73 @@
+//! # System Control Space
+//! The Cortex-M4 core registers the drivers share: the SysTick timer used by
+//! `time`, and the system control block registers used by `time` and `gcr`.
+//! See the ARMv7-M Architecture Reference Manual, B3.2 and B3.3.
+
+use hal_macros::RW;
+use hal_macros_derive::make_device;
+
+/// # System Control Space
+/// The base address of the Cortex-M4 system control space, which holds the
+/// SysTick timer and the system control block.
+pub const SYSTEM_CONTROL_SPACE: usize = 0xE000_E000;
+
+/// # Relative Register Offsets
+/// The offsets of the registers the drivers use within the system control space.
+pub(super) mod rro {
+    /// # SysTick Control and Status Register
+    pub const SYST_CSR: usize = 0x0010;
+    /// # SysTick Reload Value Register
+    pub const SYST_RVR: usize = 0x0014;
+    /// # SysTick Current Value Register
+    pub const SYST_CVR: usize = 0x0018;
+    /// # Interrupt Control and State Register
+    pub const ICSR: usize = 0x0D04;
+    /// # System Control Register
+    pub const SCR: usize = 0x0D10;
+}
+
+make_device! {
+    device_ports(SYSTEM_CONTROL_SPACE);
+
+    /// Counter Enable. `SysTick_CTRL_ENABLE_Msk`.
+    #[bit(0, RW, rro::SYST_CSR)]
+    enable,
+
+    /// Tick Interrupt. `SysTick_CTRL_TICKINT_Msk`.
+    /// Raise the SysTick exception every time the counter reaches zero.
+    #[bit(1, RW, rro::SYST_CSR)]
+    tick_interrupt,
+
+    /// Clock Source. `SysTick_CTRL_CLKSOURCE_Msk`.
+    /// - 0: External reference clock
+    /// - 1: Core clock
+    #[bit(2, RW, rro::SYST_CSR)]
+    clock_source,
+
+    /// Count Flag. `SysTick_CTRL_COUNTFLAG_Msk`.
+    /// Set when the counter reached zero since the last read of this register.
+    #[bit(16, RO, rro::SYST_CSR)]
+    count_flag,
+
+    /// Reload Value. `SysTick_LOAD_RELOAD_Msk`.
+    /// Loaded into the counter when it reaches zero, one less than the ticks
+    /// between interrupts.
+    #[bit(0..=23, RW, rro::SYST_RVR)]
+    reload,
+
+    /// Current Value. `SysTick_VAL_CURRENT_Msk`.
+    /// The counter, counting down. Writing any value clears it to zero.
+    #[bit(0..=23, RW, rro::SYST_CVR)]
+    current,
+
+    /// SysTick Pending. `SCB_ICSR_PENDSTSET_Msk`.
+    /// Set while the SysTick exception is pending.
+    #[bit(26, RO, rro::ICSR)]
+    systick_pending,
+
+    /// Sleep Deep. `SCB_SCR_SLEEPDEEP_Msk`.
+    /// Make the next WFI enter the low power mode selected in `GCR_PM`, instead
+    /// of only stopping the core clock.
+    #[bit(2, RW, rro::SCR)]
+    sleep_deep,
+}
@@ -0,0 +1,566 @@
+//! # SPI
+//! A blocking SPI master for SPI0 and SPI1, built with `Spi::init_port_0_master`
+//! or `Spi::init_port_1_master`. Slave select is not driven by the peripheral, so
+//! devices are selected with a GPIO output around each transfer.
+
+use crate::core_peripheral_clock;
+use crate::error::{ErrorKind, Result};
+use crate::gcr::{peripheral_reset, system_clock_enable};
+use crate::gpio::GpioPin;
+use crate::memory_map::mmio;
+use core::cell::Cell;
+use core::marker::PhantomData;
+
+use self::registers::{Fifo, Registers};
+
+pub mod registers;
+
+/// The depth of both SPI FIFOs, in bytes.
+const FIFO_DEPTH: usize = 32;
+
+/// The most characters one master transaction can count in `SPI_CTRL1`.
+const MAX_TRANSACTION_CHARACTERS: usize = u16::MAX as usize;
+
+/// The largest `clock_divider`, which divides the SPI clock by 256.
+const MAX_CLOCK_DIVIDER: u8 = 8;
+
+/// The SCK rate `init` starts with, until `set_frequency` is called.
+const DEFAULT_FREQUENCY_HZ: u32 = 1_000_000;
+
+/// How many times a transfer checks the FIFOs for progress before giving up with
+/// `ErrorKind::TimeOut`, until `Spi::set_timeout` is called.
+const DEFAULT_TIMEOUT_SPINS: u32 = 1_000_000;
+
+mod private {
+    pub trait SPIPortCompatable: crate::port::Port {
+        const HARDWARE_SOURCE: crate::gcr::HardwareSource;
+    }
+}
+
+pub struct NoPort {}
+pub struct SPIPort0 {}
+pub struct SPIPort1 {}
+
+impl crate::port::private::Sealed for SPIPort0 {}
+impl crate::port::Port for SPIPort0 {
+    const BASE: usize = mmio::SPI_0;
+    const NUM: usize = 0;
+}
+impl private::SPIPortCompatable for SPIPort0 {
+    const HARDWARE_SOURCE: crate::gcr::HardwareSource = crate::gcr::HardwareSource::SPI0;
+}
+impl crate::port::private::Sealed for SPIPort1 {}
+impl crate::port::Port for SPIPort1 {
+    const BASE: usize = mmio::SPI_1;
+    const NUM: usize = 1;
+}
+impl private::SPIPortCompatable for SPIPort1 {
+    const HARDWARE_SOURCE: crate::gcr::HardwareSource = crate::gcr::HardwareSource::SPI1;
+}
+
+/// # SPI Mode
+/// The SCK polarity (CPOL) and sampling phase (CPHA) of a transfer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpiMode {
+    /// SCK idles low, data is sampled on the rising edge.
+    #[default]
+    Mode0,
+    /// SCK idles low, data is sampled on the falling edge.
+    Mode1,
+    /// SCK idles high, data is sampled on the falling edge.
+    Mode2,
+    /// SCK idles high, data is sampled on the rising edge.
+    Mode3,
+}
+
+impl SpiMode {
+    /// # Clock Polarity
+    /// Whether SCK idles high (CPOL = 1).
+    pub const fn clock_polarity(self) -> bool {
+        matches!(self, SpiMode::Mode2 | SpiMode::Mode3)
+    }
+
+    /// # Clock Phase
+    /// Whether data is sampled on the second SCK edge (CPHA = 1).
+    pub const fn clock_phase(self) -> bool {
+        matches!(self, SpiMode::Mode1 | SpiMode::Mode3)
+    }
+}
+
+/// # Clock Settings
+/// The `SPI_CLKCTRL` fields for one SCK rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ClockSettings {
+    /// The SPI clock is the peripheral clock divided by 2^`divider`.
+    divider: u8,
+    /// SPI clocks SCK is held high, and then low, for.
+    half_period: u8,
+}
+
+impl ClockSettings {
+    /// The SCK rate these settings give from a `clock_hz` peripheral clock.
+    const fn frequency(self, clock_hz: u32) -> u32 {
+        (clock_hz >> self.divider) / (2 * self.half_period as u32)
+    }
+}
+
+/// Pick the clock settings for the fastest SCK rate that is not above
+/// `frequency_hz`, from a `clock_hz` peripheral clock. The smallest divider is
+/// used, so the high and low times have the finest resolution.
+///
+/// Returns `ErrorKind::BadParam` if `frequency_hz` is 0, or slower than the
+/// slowest rate of `clock_hz / 256 / 510`.
+fn clock_settings(clock_hz: u32, frequency_hz: u32) -> Result<ClockSettings> {
+    if frequency_hz == 0 {
+        return Err(ErrorKind::BadParam);
+    }
+
+    (0..=MAX_CLOCK_DIVIDER)
+        .find_map(|divider| {
+            let spi_clock = clock_hz >> divider;
+            let half_period = spi_clock.div_ceil(2 * frequency_hz).max(1);
+
+            u8::try_from(half_period)
+                .ok()
+                .map(|half_period| ClockSettings {
+                    divider,
+                    half_period,
+                })
+        })
+        .ok_or(ErrorKind::BadParam)
+}
+
+/// # SPI
+/// A blocking SPI master using 8 bit characters on MOSI and MISO.
+///
+/// # Example
+/// ```no_run
+/// use max78000_hal::spi::{Spi, SpiMode};
+///
+/// let mut spi = Spi::init_port_0_master().unwrap();
+/// spi.set_mode(SpiMode::Mode0);
+/// spi.set_frequency(4_000_000).unwrap();
+///
+/// // Select the device with a GPIO output here.
+/// let mut buffer = [0x9F, 0, 0, 0];
+/// spi.transfer(&mut buffer).unwrap();
+/// ```
+pub struct Spi<Port = NoPort> {
+    reg: Registers,
+    fifo: Fifo,
+    gpio: [GpioPin; 3],
+    timeout_spins: u32,
+    _ph: PhantomData<Port>,
+}
+
+impl Spi<NoPort> {
+    /// # Init Port 0 Master
+    /// Reset SPI0, enable its clock and take its pins, starting in `SpiMode::Mode0`
+    /// at 1MHz.
+    ///
+    /// Returns `ErrorKind::Busy` if the SPI0 pins are already taken.
+    pub fn init_port_0_master() -> Result<Spi<SPIPort0>> {
+        Spi::<SPIPort0>::init()
+    }
+
+    /// # Init Port 1 Master
+    /// Reset SPI1, enable its clock and take its pins, starting in `SpiMode::Mode0`
+    /// at 1MHz.
+    ///
+    /// Returns `ErrorKind::Busy` if the SPI1 pins are already taken.
+    pub fn init_port_1_master() -> Result<Spi<SPIPort1>> {
+        Spi::<SPIPort1>::init()
+    }
+}
+
+impl<Port: private::SPIPortCompatable> Spi<Port> {
+    fn init() -> Result<Self> {
+        peripheral_reset(Port::HARDWARE_SOURCE)?;
+        system_clock_enable(Port::HARDWARE_SOURCE, true);
+
+        let mut spi = Self::from_registers(
+            Registers::new(Port::BASE),
+            // `Port::BASE` is this port's register block.
+            unsafe { Fifo::new(Port::BASE) },
+            crate::gpio::hardware::spi_n(Port::NUM).ok_or(ErrorKind::Busy)?,
+        );
+        spi.configure()?;
+
+        Ok(spi)
+    }
+
+    fn from_registers(reg: Registers, fifo: Fifo, gpio: [GpioPin; 3]) -> Self {
+        Self {
+            reg,
+            fifo,
+            gpio,
+            timeout_spins: DEFAULT_TIMEOUT_SPINS,
+            _ph: PhantomData,
+        }
+    }
+
+    /// Program the master configuration this driver starts with.
+    fn configure(&mut self) -> Result<()> {
+        unsafe {
+            self.reg.set_enable(false);
+            self.reg.set_master_mode(true);
+            self.reg.set_slave_select_active(0);
+            self.reg.set_slave_select_control(false);
+            self.reg.set_character_bits(8);
+            self.reg.set_data_width(0);
+            self.reg.set_three_wire_mode(false);
+            self.reg.set_tx_fifo_enable(true);
+            self.reg.set_rx_fifo_enable(true);
+        }
+
+        self.set_mode(SpiMode::default());
+        self.set_frequency(DEFAULT_FREQUENCY_HZ)?;
+
+        unsafe { self.reg.set_enable(true) };
+        Ok(())
+    }
+
+    /// # Set Mode
+    /// Set the SCK polarity and phase used by the next transfer.
+    pub fn set_mode(&mut self, mode: SpiMode) {
+        unsafe {
+            self.reg.set_clock_polarity(mode.clock_polarity());
+            self.reg.set_clock_phase(mode.clock_phase());
+        }
+    }
+
+    /// # Mode
+    /// The SCK polarity and phase currently programmed.
+    pub fn mode(&self) -> SpiMode {
+        match (self.reg.get_clock_polarity(), self.reg.get_clock_phase()) {
+            (false, false) => SpiMode::Mode0,
+            (false, true) => SpiMode::Mode1,
+            (true, false) => SpiMode::Mode2,
+            (true, true) => SpiMode::Mode3,
+        }
+    }
+
+    /// # Set Frequency
+    /// Set SCK to the fastest rate not above `frequency_hz` that
+    /// `core_peripheral_clock` can be divided down to, returning that rate.
+    ///
+    /// Returns `ErrorKind::BadParam` if `frequency_hz` is 0 or too slow to reach.
+    pub fn set_frequency(&mut self, frequency_hz: u32) -> Result<u32> {
+        let clock_hz = core_peripheral_clock();
+        let settings = clock_settings(clock_hz, frequency_hz)?;
+
+        unsafe {
+            self.reg.set_clock_divider(settings.divider);
+            self.reg.set_clock_high(settings.half_period);
+            self.reg.set_clock_low(settings.half_period);
+        }
+
+        Ok(settings.frequency(clock_hz))
+    }
+
+    /// # Set Timeout
+    /// Give up on a transfer with `ErrorKind::TimeOut` once the FIFOs have made no
+    /// progress for about `us` microseconds. A `us` of 0 disables the timeout.
+    ///
+    /// The timeout is a spin count from `util::timeout_spins`, so it is never
+    /// shorter than `us`. The count starts over each time a byte moves through a
+    /// FIFO.
+    pub fn set_timeout(&mut self, us: usize) {
+        self.timeout_spins = crate::util::timeout_spins(us);
+    }
+
+    /// # Transfer
+    /// Send `buffer` while replacing it with the bytes received at the same time.
+    pub fn transfer(&mut self, buffer: &mut [u8]) -> Result<()> {
+        let cells = Cell::from_mut(buffer).as_slice_of_cells();
+
+        for chunk in cells.chunks(MAX_TRANSACTION_CHARACTERS) {
+            self.transaction(
+                chunk.len(),
+                |index| chunk[index].get(),
+                Some(|index: usize, byte: u8| chunk[index].set(byte)),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// # Write
+    /// Send `bytes`, ignoring whatever is received.
+    pub fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        for chunk in bytes.chunks(MAX_TRANSACTION_CHARACTERS) {
+            self.transaction(chunk.len(), |index| chunk[index], None::<fn(usize, u8)>)?;
+        }
+
+        Ok(())
+    }
+
+    /// # Release Pins
+    /// Stop using this SPI peripheral and give back its `[MOSI, MISO, SCK]` pins,
+    /// still configured for the SPI alternate function.
+    pub fn release_pins(self) -> [GpioPin; 3] {
+        self.gpio
+    }
+
+    /// Run one master transaction of `len` characters, taking each byte to send
+    /// from `tx` and handing each received byte to `rx`. Without `rx` the receive
+    /// FIFO is turned off for the transaction, so nothing has to be read back.
+    fn transaction(
+        &mut self,
+        len: usize,
+        tx: impl Fn(usize) -> u8,
+        mut rx: Option<impl FnMut(usize, u8)>,
+    ) -> Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+
+        let receiving = rx.is_some();
+        unsafe {
+            self.reg.activate_tx_fifo_flush();
+            self.reg.activate_rx_fifo_flush();
+            self.reg.set_rx_fifo_enable(receiving);
+            self.reg.set_tx_characters(len as u16);
+            self.reg
+                .set_rx_characters(if receiving { len as u16 } else { 0 });
+            self.reg.clear_master_done_interrupt_flag();
+            self.reg.activate_start();
+        }
+
+        let (mut sent, mut received) = (0, 0);
+        let mut spins = 0;
+        while sent < len || (receiving && received < len) {
+            let mut progress = false;
+
+            // Never have more bytes in flight than the receive FIFO can hold, or
+            // received bytes would be lost while the transmit FIFO drains.
+            let in_flight = if receiving { sent - received } else { 0 };
+            if sent < len
+                && in_flight < FIFO_DEPTH
+                && (self.reg.get_tx_fifo_level() as usize) < FIFO_DEPTH
+            {
+                unsafe { self.fifo.write(tx(sent)) };
+                sent += 1;
+                progress = true;
+            }
+
+            if let Some(rx) = rx.as_mut() {
+                if received < sent && self.reg.get_rx_fifo_level() > 0 {
+                    rx(received, self.fifo.read());
+                    received += 1;
+                    progress = true;
+                }
+            }
+
+            if progress {
+                spins = 0;
+            } else {
+                self.spend_spin(&mut spins)?;
+            }
+        }
+
+        self.reg
+            .wait_master_done_interrupt_flag(true, self.timeout_spins)?;
+        unsafe { self.reg.clear_master_done_interrupt_flag() };
+
+        Ok(())
+    }
+
+    /// Count one spin of a busy-wait against the budget set by `set_timeout`.
+    fn spend_spin(&self, spins: &mut u32) -> Result<()> {
+        crate::util::spend_spin(spins, self.timeout_spins)
+    }
+}
+
+/// # SPI Bus
+/// Blocking full duplex transfers, in the shape of `embedded_hal::spi::SpiBus`
+/// from embedded-hal 1.0, for drivers written against that interface. Every
+/// method returns once its transfer has finished. Only built with the `spi-bus`
+/// feature.
+#[cfg(feature = "spi-bus")]
+pub trait SpiBus<Word: Copy = u8> {
+    type Error;
+
+    /// Fill `words` with what is received, sending 0s meanwhile.
+    fn read(&mut self, words: &mut [Word]) -> core::result::Result<(), Self::Error>;
+
+    /// Send `words`, the same as `Spi::write`.
+    fn write(&mut self, words: &[Word]) -> core::result::Result<(), Self::Error>;
+
+    /// Send `write` while receiving into `read`, for as many words as the longer
+    /// of the two. Words received past the end of `read` are dropped, and 0s are
+    /// sent past the end of `write`.
+    fn transfer(
+        &mut self,
+        read: &mut [Word],
+        write: &[Word],
+    ) -> core::result::Result<(), Self::Error>;
+
+    /// Send `words` while replacing them with what is received, the same as
+    /// `Spi::transfer`.
+    fn transfer_in_place(&mut self, words: &mut [Word]) -> core::result::Result<(), Self::Error>;
+
+    /// Wait for the bus to be idle, which it already is after any of the others.
+    fn flush(&mut self) -> core::result::Result<(), Self::Error>;
+}
+
+#[cfg(feature = "spi-bus")]
+impl<Port: private::SPIPortCompatable> SpiBus<u8> for Spi<Port> {
+    type Error = ErrorKind;
+
+    fn read(&mut self, words: &mut [u8]) -> Result<()> {
+        SpiBus::transfer(self, words, &[])
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<()> {
+        Spi::write(self, words)
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<()> {
+        let len = read.len().max(write.len());
+        for start in (0..len).step_by(MAX_TRANSACTION_CHARACTERS) {
+            self.transaction(
+                (len - start).min(MAX_TRANSACTION_CHARACTERS),
+                |index| write.get(start + index).copied().unwrap_or(0),
+                Some(|index: usize, byte: u8| {
+                    if let Some(word) = read.get_mut(start + index) {
+                        *word = byte;
+                    }
+                }),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<()> {
+        Spi::transfer(self, words)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tests::FakeRegisters;
+    use registers::rro::{SPI_CTRL1, SPI_CTRL2, SPI_DMA, SPI_FIFO32, SPI_INTFL};
+
+    /// `SPI_DMA` with one byte always waiting in the receive FIFO.
+    const RX_LEVEL_ONE: u32 = 1 << 24;
+    /// `SPI_INTFL` with master done set.
+    const MASTER_DONE: u32 = 1 << 11;
+
+    /// Make an SPI driver on `fake_registers`, where the FIFO is the first byte of
+    /// memory, so every received byte is the byte sent just before it.
+    fn fake_spi(fake_registers: &FakeRegisters<16>) -> Spi<SPIPort0> {
+        fake_registers.set_word(SPI_DMA, RX_LEVEL_ONE);
+        fake_registers.set_word(SPI_INTFL, MASTER_DONE);
+        let base = fake_registers.address();
+
+        Spi::from_registers(
+            Registers::new(base),
+            unsafe { Fifo::new(base) },
+            crate::gpio::hardware::spi_n(0).unwrap(),
+        )
+    }
+
+    #[test]
+    fn clock_settings_test() {
+        let settings = clock_settings(50_000_000, 1_000_000).unwrap();
+        assert_eq!(
+            settings,
+            ClockSettings {
+                divider: 0,
+                half_period: 25,
+            }
+        );
+        assert_eq!(settings.frequency(50_000_000), 1_000_000);
+
+        // 8.3 SPI clocks per half period rounds up, to below 3MHz rather than above.
+        let settings = clock_settings(50_000_000, 3_000_000).unwrap();
+        assert_eq!(settings.half_period, 9);
+        assert!(settings.frequency(50_000_000) <= 3_000_000);
+
+        // Faster than the peripheral can go gives the fastest rate.
+        let settings = clock_settings(50_000_000, 100_000_000).unwrap();
+        assert_eq!(settings.frequency(50_000_000), 25_000_000);
+
+        // Too slow for 8 bit high and low times needs the divider.
+        let settings = clock_settings(50_000_000, 10_000).unwrap();
+        assert_eq!(
+            settings,
+            ClockSettings {
+                divider: 4,
+                half_period: 157,
+            }
+        );
+        assert!(settings.frequency(50_000_000) <= 10_000);
+
+        assert_eq!(clock_settings(50_000_000, 0), Err(ErrorKind::BadParam));
+        assert_eq!(clock_settings(50_000_000, 100), Err(ErrorKind::BadParam));
+    }
+
+    #[test]
+    fn mode_test() {
+        let fake = FakeRegisters::new();
+        let mut spi = fake_spi(&fake);
+
+        for mode in [
+            SpiMode::Mode0,
+            SpiMode::Mode1,
+            SpiMode::Mode2,
+            SpiMode::Mode3,
+        ] {
+            spi.set_mode(mode);
+            assert_eq!(spi.mode(), mode);
+        }
+        assert_eq!(fake.word(SPI_CTRL2) & 0b11, 0b11);
+    }
+
+    #[test]
+    fn transfer_test() {
+        let fake = FakeRegisters::new();
+        let mut spi = fake_spi(&fake);
+
+        let mut buffer = [1, 2, 3, 4];
+        assert_eq!(spi.transfer(&mut buffer), Ok(()));
+        assert_eq!(buffer, [1, 2, 3, 4]);
+        // Both character counts are programmed.
+        assert_eq!(fake.word(SPI_CTRL1), 4 | (4 << 16));
+
+        assert_eq!(spi.transfer(&mut []), Ok(()));
+        spi.release_pins();
+    }
+
+    #[test]
+    fn write_test() {
+        let fake = FakeRegisters::new();
+        let mut spi = fake_spi(&fake);
+
+        assert_eq!(spi.write(&[0xAB, 0xCD]), Ok(()));
+        assert_eq!(fake.word(SPI_FIFO32) & 0xFF, 0xCD);
+        // Nothing is received, and the receive FIFO is off.
+        assert_eq!(fake.word(SPI_CTRL1), 2);
+        assert_eq!(fake.word(SPI_DMA) & (1 << 22), 0);
+        spi.release_pins();
+    }
+
+    #[test]
+    fn transfer_timeout_test() {
+        // Nothing ever arrives in the receive FIFO.
+        let fake = FakeRegisters::<16>::new();
+        let base = fake.address();
+        let mut spi = Spi::<SPIPort0>::from_registers(
+            Registers::new(base),
+            unsafe { Fifo::new(base) },
+            crate::gpio::hardware::spi_n(0).unwrap(),
+        );
+        spi.set_timeout(1);
+
+        assert_eq!(spi.transfer(&mut [0; 2]), Err(ErrorKind::TimeOut));
+        spi.release_pins();
+    }
+}
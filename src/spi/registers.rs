@@ -0,0 +1,268 @@
+use crate::memory_map::mmio;
+use hal_macros::RW;
+use hal_macros_derive::make_device;
+
+/// # Relative Register Offsets
+/// These are the offsets for the SPI registers, shared by SPI0 and SPI1 and
+/// matching `mxc_spi_regs_t` in the MSDK.
+pub(super) mod rro {
+    /// # SPI FIFO Data Register
+    pub const SPI_FIFO32: usize = 0x0000;
+    /// # SPI Control 0 Register
+    pub const SPI_CTRL0: usize = 0x0004;
+    /// # SPI Transmit Packet Size Register
+    pub const SPI_CTRL1: usize = 0x0008;
+    /// # SPI Control 2 Register
+    pub const SPI_CTRL2: usize = 0x000C;
+    /// # SPI Slave Select Timing Register
+    pub const SPI_SSTIME: usize = 0x0010;
+    /// # SPI Master Clock Configuration Register
+    pub const SPI_CLKCTRL: usize = 0x0014;
+    /// # SPI DMA Control Register
+    pub const SPI_DMA: usize = 0x001C;
+    /// # SPI Interrupt Status Flags Register
+    pub const SPI_INTFL: usize = 0x0020;
+    /// # SPI Interrupt Enable Register
+    pub const SPI_INTEN: usize = 0x0024;
+    /// # SPI Active Status Register
+    pub const SPI_STAT: usize = 0x0030;
+}
+
+make_device! {
+    device_ports(mmio::SPI_0, mmio::SPI_1);
+
+    /// SPI Enable. `MXC_F_SPI_CTRL0_EN`.
+    /// - 0: Disabled, and the FIFOs are held in reset
+    /// - 1: Enabled
+    #[bit(0, RW, rro::SPI_CTRL0)]
+    enable,
+
+    /// Master Mode Enable. `MXC_F_SPI_CTRL0_MST_MODE`.
+    /// - 0: Slave mode
+    /// - 1: Master mode
+    #[bit(1, RW, rro::SPI_CTRL0)]
+    master_mode,
+
+    /// Slave Select 0 Input/Output. `MXC_F_SPI_CTRL0_SS_IO`.
+    /// - 0: SS0 is an output, driven by the master
+    /// - 1: SS0 is an input, for multi-master fault detection
+    #[bit(4, RW, rro::SPI_CTRL0)]
+    slave_select_io,
+
+    /// Start Transaction. `MXC_F_SPI_CTRL0_START`.
+    /// Write a 1 to start a master transaction of `tx_characters` and
+    /// `rx_characters`.
+    #[bit(5, RW1O, rro::SPI_CTRL0)]
+    start,
+
+    /// Slave Select Control. `MXC_F_SPI_CTRL0_SS_CTRL`.
+    /// - 0: Deassert the slave selects at the end of a transaction
+    /// - 1: Keep the slave selects asserted at the end of a transaction
+    #[bit(8, RW, rro::SPI_CTRL0)]
+    slave_select_control,
+
+    /// Slave Select Active. `MXC_F_SPI_CTRL0_SS_ACTIVE`.
+    /// One bit per slave select pin that the master asserts during a transaction.
+    #[bit(16..=19, RW, rro::SPI_CTRL0)]
+    slave_select_active,
+
+    /// Transmit Characters. `MXC_F_SPI_CTRL1_TX_NUM_CHAR`.
+    /// The number of characters to transmit in a master transaction.
+    #[bit(0..=15, RW, rro::SPI_CTRL1)]
+    tx_characters,
+
+    /// Receive Characters. `MXC_F_SPI_CTRL1_RX_NUM_CHAR`.
+    /// The number of characters to receive in a master transaction.
+    #[bit(16..=31, RW, rro::SPI_CTRL1)]
+    rx_characters,
+
+    /// Clock Phase. `MXC_F_SPI_CTRL2_CLKPHA`.
+    /// - 0: Data is sampled on the first SCK edge
+    /// - 1: Data is sampled on the second SCK edge
+    #[bit(0, RW, rro::SPI_CTRL2)]
+    clock_phase,
+
+    /// Clock Polarity. `MXC_F_SPI_CTRL2_CLKPOL`.
+    /// - 0: SCK idles low
+    /// - 1: SCK idles high
+    #[bit(1, RW, rro::SPI_CTRL2)]
+    clock_polarity,
+
+    /// Bits per Character. `MXC_F_SPI_CTRL2_NUMBITS`.
+    /// The number of bits in each character, where 0 means 16.
+    #[bit(8..=11, RW, rro::SPI_CTRL2)]
+    character_bits,
+
+    /// Data Width. `MXC_F_SPI_CTRL2_DATA_WIDTH`.
+    /// - 0: Mono (MOSI and MISO)
+    /// - 1: Dual
+    /// - 2: Quad
+    #[bit(12..=13, RW, rro::SPI_CTRL2)]
+    data_width,
+
+    /// Three Wire Mode. `MXC_F_SPI_CTRL2_THREE_WIRE`.
+    #[bit(15, RW, rro::SPI_CTRL2)]
+    three_wire_mode,
+
+    /// Slave Select Polarity. `MXC_F_SPI_CTRL2_SS_POL`.
+    /// One bit per slave select pin, set for an active high select.
+    #[bit(16..=19, RW, rro::SPI_CTRL2)]
+    slave_select_polarity,
+
+    /// Slave Select Pre Delay. `MXC_F_SPI_SSTIME_PRE`.
+    /// SPI clocks from asserting a slave select to the first SCK edge.
+    #[bit(0..=7, RW, rro::SPI_SSTIME)]
+    slave_select_pre_delay,
+
+    /// Slave Select Post Delay. `MXC_F_SPI_SSTIME_POST`.
+    /// SPI clocks from the last SCK edge to deasserting a slave select.
+    #[bit(8..=15, RW, rro::SPI_SSTIME)]
+    slave_select_post_delay,
+
+    /// Slave Select Inactive Delay. `MXC_F_SPI_SSTIME_INACT`.
+    /// SPI clocks a slave select stays deasserted between transactions.
+    #[bit(16..=23, RW, rro::SPI_SSTIME)]
+    slave_select_inactive_delay,
+
+    /// SCK Low Time. `MXC_F_SPI_CLKCTRL_LO`.
+    /// SPI clocks SCK is held low for, where 0 means 256.
+    #[bit(0..=7, RW, rro::SPI_CLKCTRL)]
+    clock_low,
+
+    /// SCK High Time. `MXC_F_SPI_CLKCTRL_HI`.
+    /// SPI clocks SCK is held high for, where 0 means 256.
+    #[bit(8..=15, RW, rro::SPI_CLKCTRL)]
+    clock_high,
+
+    /// Clock Divider. `MXC_F_SPI_CLKCTRL_CLKDIV`.
+    /// The SPI clock is the peripheral clock divided by 2^value, up to 8.
+    #[bit(16..=19, RW, rro::SPI_CLKCTRL)]
+    clock_divider,
+
+    /// Transmit FIFO Threshold. `MXC_F_SPI_DMA_TX_THD_VAL`.
+    #[bit(0..=4, RW, rro::SPI_DMA)]
+    tx_fifo_threshold,
+
+    /// Transmit FIFO Enable. `MXC_F_SPI_DMA_TX_FIFO_EN`.
+    #[bit(6, RW, rro::SPI_DMA)]
+    tx_fifo_enable,
+
+    /// Transmit FIFO Flush. `MXC_F_SPI_DMA_TX_FLUSH`.
+    /// Write a 1 to flush the transmit FIFO.
+    #[bit(7, RW1O, rro::SPI_DMA)]
+    tx_fifo_flush,
+
+    /// Transmit FIFO Level. `MXC_F_SPI_DMA_TX_LVL`.
+    /// The number of bytes in the transmit FIFO.
+    #[bit(8..=13, RO, rro::SPI_DMA)]
+    tx_fifo_level,
+
+    /// Transmit DMA Enable. `MXC_F_SPI_DMA_DMA_TX_EN`.
+    #[bit(15, RW, rro::SPI_DMA)]
+    tx_dma_enable,
+
+    /// Receive FIFO Threshold. `MXC_F_SPI_DMA_RX_THD_VAL`.
+    #[bit(16..=20, RW, rro::SPI_DMA)]
+    rx_fifo_threshold,
+
+    /// Receive FIFO Enable. `MXC_F_SPI_DMA_RX_FIFO_EN`.
+    #[bit(22, RW, rro::SPI_DMA)]
+    rx_fifo_enable,
+
+    /// Receive FIFO Flush. `MXC_F_SPI_DMA_RX_FLUSH`.
+    /// Write a 1 to flush the receive FIFO.
+    #[bit(23, RW1O, rro::SPI_DMA)]
+    rx_fifo_flush,
+
+    /// Receive FIFO Level. `MXC_F_SPI_DMA_RX_LVL`.
+    /// The number of bytes in the receive FIFO.
+    #[bit(24..=29, RO, rro::SPI_DMA)]
+    rx_fifo_level,
+
+    /// Receive DMA Enable. `MXC_F_SPI_DMA_DMA_RX_EN`.
+    #[bit(31, RW, rro::SPI_DMA)]
+    rx_dma_enable,
+
+    /// Transmit Threshold Flag. `MXC_F_SPI_INTFL_TX_THD`.
+    #[bit(0, RW1C, rro::SPI_INTFL)]
+    tx_threshold_interrupt_flag,
+
+    /// Transmit FIFO Empty Flag. `MXC_F_SPI_INTFL_TX_EM`.
+    #[bit(1, RW1C, rro::SPI_INTFL)]
+    tx_empty_interrupt_flag,
+
+    /// Receive Threshold Flag. `MXC_F_SPI_INTFL_RX_THD`.
+    #[bit(2, RW1C, rro::SPI_INTFL)]
+    rx_threshold_interrupt_flag,
+
+    /// Receive FIFO Full Flag. `MXC_F_SPI_INTFL_RX_FULL`.
+    #[bit(3, RW1C, rro::SPI_INTFL)]
+    rx_full_interrupt_flag,
+
+    /// Multi-Master Fault Flag. `MXC_F_SPI_INTFL_FAULT`.
+    #[bit(8, RW1C, rro::SPI_INTFL)]
+    fault_interrupt_flag,
+
+    /// Slave Mode Abort Flag. `MXC_F_SPI_INTFL_ABORT`.
+    #[bit(9, RW1C, rro::SPI_INTFL)]
+    abort_interrupt_flag,
+
+    /// Master Done Flag. `MXC_F_SPI_INTFL_MST_DONE`.
+    /// Set once a master transaction has sent and received all its characters.
+    #[bit(11, RW1C, rro::SPI_INTFL)]
+    master_done_interrupt_flag,
+
+    /// Transmit Overrun Flag. `MXC_F_SPI_INTFL_TX_OV`.
+    #[bit(12, RW1C, rro::SPI_INTFL)]
+    tx_overrun_interrupt_flag,
+
+    /// Receive Overrun Flag. `MXC_F_SPI_INTFL_RX_OV`.
+    #[bit(14, RW1C, rro::SPI_INTFL)]
+    rx_overrun_interrupt_flag,
+
+    /// Master Done Interrupt Enable. `MXC_F_SPI_INTEN_MST_DONE`.
+    #[bit(11, RW, rro::SPI_INTEN)]
+    master_done_interrupt_enable,
+
+    /// SPI Busy. `MXC_F_SPI_STAT_BUSY`.
+    /// Set while a transaction is in progress.
+    #[bit(0, RO, rro::SPI_STAT)]
+    busy,
+}
+
+/// # FIFO
+/// Byte wide access to `SPI_FIFO32`. Each access to the FIFO moves as many
+/// characters as the access is wide, so going through a 32-bit register field
+/// would push or pop four bytes at a time.
+pub struct Fifo {
+    ptr: *mut u8,
+}
+
+impl Fifo {
+    /// # New
+    /// Make the FIFO accessor for the SPI port at `port`.
+    ///
+    /// # Safety
+    /// `port` must be the base of an SPI register block, like `Registers::new`,
+    /// since `read` is safe to call.
+    pub(super) unsafe fn new(port: usize) -> Self {
+        Self {
+            ptr: (port + rro::SPI_FIFO32) as *mut u8,
+        }
+    }
+
+    /// # Write
+    /// Push one byte into the transmit FIFO.
+    ///
+    /// # Safety
+    /// The transmit FIFO must not be full.
+    pub unsafe fn write(&mut self, byte: u8) {
+        core::ptr::write_volatile(self.ptr, byte)
+    }
+
+    /// # Read
+    /// Pop one byte from the receive FIFO.
+    pub fn read(&mut self) -> u8 {
+        unsafe { core::ptr::read_volatile(self.ptr) }
+    }
+}
@@ -1,10 +1,11 @@
+use core::sync::atomic::{AtomicU32, Ordering};
 use hal_macros::{VolatileRead, RW};
 use hal_macros_derive::make_device;
 
 const FIRST_4_BYTES: usize = 0;
-const SECOND_4_BYTES: usize = 1;
-const THREE_4_BYTES: usize = 2;
-const FOUR_4_BYTES: usize = 3;
+const SECOND_4_BYTES: usize = 4;
+const THREE_4_BYTES: usize = 8;
+const FOUR_4_BYTES: usize = 12;
 
 make_device! {
     // No need for device ports when testing, since
@@ -306,6 +307,125 @@ fn test_making_device() {
     let mut reg = Registers::new(fake_device_storage.as_mut() as *mut [u32] as *mut u32 as usize);
 }
 
+#[test]
+fn test_try_making_device() {
+    let mut fake_device_storage = [0u32; 4];
+    let mut reg = Registers::try_new(fake_device_storage.as_mut_ptr() as usize).unwrap();
+    unsafe { reg.set_bit_0(true) };
+    assert_eq!(fake_device_storage[0], 1);
+
+    assert_eq!(
+        Registers::try_new(0).err(),
+        Some(crate::error::ErrorKind::NullPtr)
+    );
+}
+
+#[test]
+fn test_decode_register() {
+    let decoded = std::format!("{:?}", Registers::decode_four_4_bytes(0x1234_56ab));
+    assert_eq!(
+        decoded,
+        "FOUR_4_BYTES=0x123456ab { range_3: 0xab, range_4: 0x56, range_5: 0x34, range_6: 0x12 }"
+    );
+
+    let decoded = std::format!("{:?}", Registers::decode_first_4_bytes(0b101));
+    assert!(
+        decoded.starts_with("FIRST_4_BYTES=0x00000005 { bit_0: true, bit_1: false, bit_2: true,")
+    );
+    assert!(decoded.ends_with("bit_31: false }"));
+
+    let decoded = std::format!("{:?}", Registers::decode_second_4_bytes(u32::MAX));
+    assert_eq!(decoded, "SECOND_4_BYTES=0xffffffff { range_0: 0xffffffff }");
+
+    // A 32 bit register keeps its raw value, and its fields, as `u32`.
+    let decoded: hal_macros::RegisterDecode<u32> = Registers::decode_four_4_bytes(0x1234_56ab);
+    assert_eq!(decoded.value, 0x1234_56ab_u32);
+    assert_eq!(decoded.fields[3].value(decoded.value), 0x12_u32);
+}
+
+/// A second device holding the status and flag bits that `wait_<field>` is
+/// generated for.
+mod flags {
+    use hal_macros::RW;
+    use hal_macros_derive::make_device;
+
+    const STATUS: usize = 0;
+
+    make_device! {
+        device_ports();
+
+        #[bit(0, RO, STATUS)]
+        ready,
+
+        #[bit(1, RW1C, STATUS)]
+        done,
+    }
+}
+
+#[test]
+fn test_wait_bit() {
+    let mut fake_device_storage = [0u32; 1];
+    let status = fake_device_storage.as_mut_ptr();
+    let reg = flags::Registers::new(status as usize);
+
+    assert_eq!(reg.wait_ready(false, 1), Ok(()));
+    assert_eq!(
+        reg.wait_ready(true, 100),
+        Err(crate::error::ErrorKind::TimeOut)
+    );
+    assert_eq!(
+        reg.wait_ready(false, 0),
+        Err(crate::error::ErrorKind::TimeOut)
+    );
+    assert_eq!(
+        reg.wait_done(true, 100),
+        Err(crate::error::ErrorKind::TimeOut)
+    );
+
+    unsafe { status.write_volatile(0b11) };
+    assert_eq!(reg.wait_ready(true, 1), Ok(()));
+    assert_eq!(reg.wait_done(true, 1), Ok(()));
+}
+
+#[test]
+fn test_wait_bit_set_by_hardware() {
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    static FAKE_DEVICE: AtomicU32 = AtomicU32::new(0);
+    let reg = flags::Registers::new(FAKE_DEVICE.as_ptr() as usize);
+
+    // Another thread plays the peripheral finishing some time later.
+    let hardware = std::thread::spawn(|| {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        FAKE_DEVICE.fetch_or(1, Ordering::SeqCst);
+    });
+
+    assert_eq!(reg.wait_ready(true, u32::MAX), Ok(()));
+    hardware.join().unwrap();
+}
+
+#[test]
+fn test_spin_until_bit_flips_after_reads() {
+    let mut fake_device_storage = [0u32; 4];
+    let mut reg = Registers::new(fake_device_storage.as_mut_ptr() as usize);
+
+    // The bit flips on the 4th read, like hardware finishing after a while.
+    let mut flipping_read = |reads: &mut u32| {
+        *reads += 1;
+        if *reads == 4 {
+            unsafe { reg.set_bit_9(true) };
+        }
+        reg.get_bit_9()
+    };
+
+    let mut reads = 0;
+    assert!(!hal_macros::spin_until(3, || flipping_read(&mut reads)));
+    assert_eq!(reads, 3);
+
+    assert!(hal_macros::spin_until(10, || flipping_read(&mut reads)));
+    assert_eq!(reads, 4);
+}
+
 single_bit_test!(0, test_bit_0, set_bit_0, get_bit_0);
 single_bit_test!(1, test_bit_1, set_bit_1, get_bit_1);
 single_bit_test!(2, test_bit_2, set_bit_2, get_bit_2);
@@ -338,3 +458,505 @@ single_bit_test!(28, test_bit_28, set_bit_28, get_bit_28);
 single_bit_test!(29, test_bit_29, set_bit_29, get_bit_29);
 single_bit_test!(30, test_bit_30, set_bit_30, get_bit_30);
 single_bit_test!(31, test_bit_31, set_bit_31, get_bit_31);
+
+mod enum_field {
+    use crate::error::ErrorKind;
+    use hal_macros::{VolatileRead, RW};
+    use hal_macros_derive::make_device;
+
+    const MODE_REGISTER: usize = 0;
+
+    #[repr(u8)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Mode {
+        Off = 0,
+        Slow = 1,
+        Fast = 3,
+    }
+
+    impl TryFrom<u8> for Mode {
+        type Error = ErrorKind;
+
+        fn try_from(value: u8) -> Result<Self, Self::Error> {
+            match value {
+                0 => Ok(Self::Off),
+                1 => Ok(Self::Slow),
+                3 => Ok(Self::Fast),
+                _ => Err(ErrorKind::BadParam),
+            }
+        }
+    }
+
+    make_device! {
+        device_ports();
+
+        #[bit(0, RW, MODE_REGISTER)]
+        enable,
+
+        #[bit(4..=5, RW, MODE_REGISTER, Mode)]
+        mode,
+
+        #[bit(8..=15, RW, MODE_REGISTER)]
+        raw,
+    }
+
+    #[test]
+    fn enum_field_round_trip_test() {
+        let mut fake_device_storage = [0u32; 1];
+        let mut reg = Registers::new(fake_device_storage.as_mut_ptr() as usize);
+
+        unsafe {
+            reg.set_enable(true);
+            reg.set_raw(0xAB);
+            reg.set_mode(Mode::Fast);
+        }
+        assert_eq!(reg.mode_register.read(), 0xAB31);
+        assert_eq!(reg.get_mode(), Ok(Mode::Fast));
+        assert_eq!(unsafe { reg.get_mode_unchecked() }, Mode::Fast);
+
+        unsafe { reg.set_mode(Mode::Slow) };
+        assert_eq!(reg.mode_register.read(), 0xAB11);
+        assert_eq!(reg.get_mode(), Ok(Mode::Slow));
+        assert!(reg.get_enable());
+        assert_eq!(reg.get_raw(), 0xAB);
+    }
+
+    #[test]
+    fn enum_field_invalid_value_test() {
+        // 2 has no `Mode` variant, so the checked getter reports it.
+        let mut fake_device_storage = [2u32 << 4];
+        let reg = Registers::new(fake_device_storage.as_mut_ptr() as usize);
+        assert_eq!(reg.get_mode(), Err(ErrorKind::BadParam));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "no `Mode` variant")]
+    fn enum_field_unchecked_invalid_value_test() {
+        let mut fake_device_storage = [2u32 << 4];
+        let reg = Registers::new(fake_device_storage.as_mut_ptr() as usize);
+        let _ = unsafe { reg.get_mode_unchecked() };
+    }
+}
+
+mod wide_register {
+    use hal_macros::{VolatileRead, RW};
+    use hal_macros_derive::make_device;
+
+    const NARROW_REGISTER: usize = 0;
+    const WIDE_REGISTER: usize = 8;
+
+    make_device! {
+        device_ports();
+
+        #[bit(0..=31, RW, NARROW_REGISTER)]
+        narrow,
+
+        #[bit(0..=63, RW, WIDE_REGISTER)]
+        whole,
+
+        #[bit(24..=39, RW, WIDE_REGISTER)]
+        middle,
+
+        #[bit(63, RW1C, WIDE_REGISTER)]
+        top_flag,
+    }
+
+    #[test]
+    fn wide_register_round_trip_test() {
+        let mut fake_device_storage = [0u64; 2];
+        let mut reg = Registers::new(fake_device_storage.as_mut_ptr() as usize);
+
+        unsafe { reg.set_whole(0x0123_4567_89AB_CDEF) };
+        assert_eq!(reg.wide_register.read(), 0x0123_4567_89AB_CDEF);
+        assert_eq!(reg.get_whole(), 0x0123_4567_89AB_CDEF);
+        assert_eq!(reg.get_middle(), 0x6789);
+        assert_eq!(reg.narrow_register.read(), 0);
+
+        unsafe { reg.set_middle(0xBEEF) };
+        assert_eq!(reg.get_whole(), 0x0123_45BE_EFAB_CDEF);
+        assert_eq!(Registers::WHOLE_BIT_MASK, u64::MAX);
+        assert_eq!(Registers::MIDDLE_BIT_MASK, 0xFF_FF00_0000);
+
+        unsafe { reg.set_narrow(u32::MAX) };
+        assert_eq!(reg.get_narrow(), u32::MAX);
+        assert_eq!(reg.get_whole(), 0x0123_45BE_EFAB_CDEF);
+    }
+
+    #[test]
+    fn wide_register_flag_test() {
+        let mut fake_device_storage = [0u64, 1 << 63];
+        let mut reg = Registers::new(fake_device_storage.as_mut_ptr() as usize);
+        assert!(reg.is_top_flag_active());
+
+        // The RW1C flag is masked out of other writes to the register.
+        unsafe { reg.set_middle(0x1234) };
+        assert_eq!(reg.wide_register.read(), 0x12_3400_0000);
+
+        unsafe { reg.clear_top_flag() };
+        assert_eq!(reg.wide_register.read(), (1 << 63) | 0x12_3400_0000);
+    }
+
+    #[test]
+    fn wide_register_decode_test() {
+        let decoded = std::format!("{:?}", Registers::decode_wide_register(u64::MAX));
+        assert_eq!(
+            decoded,
+            "WIDE_REGISTER=0xffffffffffffffff { whole: 0xffffffffffffffff, middle: 0xffff, top_flag: true }"
+        );
+    }
+}
+
+/// A hook installed with `on_register_access`.
+type AccessHook = std::boxed::Box<dyn FnMut(hal_macros::access_log::Access)>;
+
+std::thread_local! {
+    /// The hook `on_register_access` installed for this thread.
+    static ACCESS_HOOK: core::cell::RefCell<Option<AccessHook>> =
+        const { core::cell::RefCell::new(None) };
+}
+
+fn dispatch_access(access: hal_macros::access_log::Access) {
+    ACCESS_HOOK.with_borrow_mut(|hook| {
+        if let Some(hook) = hook {
+            hook(access);
+        }
+    });
+}
+
+/// Call `hook` with every register access this thread makes from now on,
+/// replacing the hook it had before. The access log only has one global hook, so
+/// every test goes through this one and tests running in parallel on other threads
+/// keep their own. `hook` must not access registers itself.
+pub(crate) fn on_register_access(hook: impl FnMut(hal_macros::access_log::Access) + 'static) {
+    ACCESS_HOOK.set(Some(std::boxed::Box::new(hook)));
+    hal_macros::access_log::set_hook(Some(dispatch_access));
+}
+
+/// Memory standing in for `N` words of a peripheral's registers, so a driver made
+/// with `Registers::new(fake.address())` can run off target. Words are reached by
+/// their byte offset, the same `rro` offsets the registers are made from. They are
+/// atomics, so a `static` one can be shared with a thread playing the hardware.
+pub(crate) struct FakeRegisters<const N: usize>([AtomicU32; N]);
+
+impl<const N: usize> FakeRegisters<N> {
+    pub(crate) const fn new() -> Self {
+        Self([const { AtomicU32::new(0) }; N])
+    }
+
+    /// The base address to make the driver's registers at.
+    pub(crate) fn address(&self) -> usize {
+        self.0.as_ptr() as usize
+    }
+
+    /// Read the word at byte `offset`.
+    pub(crate) fn word(&self, offset: usize) -> u32 {
+        self.at(offset).load(Ordering::SeqCst)
+    }
+
+    /// Write the word at byte `offset`.
+    pub(crate) fn set_word(&self, offset: usize, value: u32) {
+        self.at(offset).store(value, Ordering::SeqCst);
+    }
+
+    /// Set `bits` in the word at byte `offset`, leaving the rest of it alone.
+    pub(crate) fn set_bits(&self, offset: usize, bits: u32) {
+        self.at(offset).fetch_or(bits, Ordering::SeqCst);
+    }
+
+    /// Clear `bits` in the word at byte `offset`, leaving the rest of it alone.
+    pub(crate) fn clear_bits(&self, offset: usize, bits: u32) {
+        self.at(offset).fetch_and(!bits, Ordering::SeqCst);
+    }
+
+    /// Zero every word.
+    pub(crate) fn reset(&self) {
+        for word in &self.0 {
+            word.store(0, Ordering::SeqCst);
+        }
+    }
+
+    fn at(&self, offset: usize) -> &AtomicU32 {
+        assert!(
+            offset.is_multiple_of(4),
+            "Unaligned register offset {offset:#x}"
+        );
+        &self.0[offset / 4]
+    }
+}
+
+mod modify_register {
+    use hal_macros::access_log::Access;
+    use hal_macros::{VolatileRead, RW};
+    use hal_macros_derive::make_device;
+
+    const CLOCK_REGISTER: usize = 0;
+
+    make_device! {
+        device_ports();
+
+        #[bit(0..=15, RW, CLOCK_REGISTER)]
+        whole_clock,
+
+        #[bit(0..=6, RW, CLOCK_REGISTER)]
+        low_time,
+
+        #[bit(8..=14, RW, CLOCK_REGISTER)]
+        high_time,
+
+        #[bit(15, RW, CLOCK_REGISTER)]
+        enable,
+
+        #[bit(16, RW1C, CLOCK_REGISTER)]
+        done_flag,
+
+        #[bit(24..=31, RO, CLOCK_REGISTER)]
+        status,
+    }
+
+    std::thread_local! {
+        /// The register accesses made by this thread while logging.
+        static ACCESSES: core::cell::RefCell<Option<std::vec::Vec<Access>>> =
+            const { core::cell::RefCell::new(None) };
+    }
+
+    fn log_access(access: Access) {
+        ACCESSES.with_borrow_mut(|log| {
+            if let Some(log) = log {
+                log.push(access);
+            }
+        });
+    }
+
+    #[test]
+    fn modify_writes_fields_once_test() {
+        let mut fake_device_storage = [0xAB01_0000_u32 | (10 << 8) | 5];
+        let raw = fake_device_storage.as_mut_ptr();
+        let mut reg = Registers::new(raw as usize);
+
+        super::on_register_access(log_access);
+        ACCESSES.set(Some(std::vec::Vec::new()));
+        unsafe {
+            reg.modify_clock_register(|fields| {
+                // Both fields are read from the one register read, and nothing is
+                // written until the closure returns.
+                assert_eq!(
+                    *fields,
+                    ClockRegisterFields {
+                        low_time: 5,
+                        high_time: 10,
+                        enable: false,
+                    }
+                );
+                fields.low_time = 0x7F;
+                fields.high_time = 0x42;
+                fields.enable = true;
+                assert_eq!(raw.read_volatile(), 0xAB01_0000 | (10 << 8) | 5);
+            })
+        };
+        assert_eq!(
+            ACCESSES.take().unwrap(),
+            [Access::Read(raw as usize), Access::Write(raw as usize)]
+        );
+
+        // The RW1C flag is written as 0 and the read only status is kept.
+        assert_eq!(
+            reg.clock_register.read(),
+            0xAB00_0000 | 0x8000 | (0x42 << 8) | 0x7F
+        );
+        assert_eq!(reg.get_low_time(), 0x7F);
+        assert_eq!(reg.get_high_time(), 0x42);
+        assert!(reg.get_enable());
+    }
+
+    #[test]
+    fn toggle_flips_one_bit_test() {
+        let mut fake_device_storage = [0xAB01_0000_u32 | (10 << 8) | 5];
+        let raw = fake_device_storage.as_mut_ptr();
+        let mut reg = Registers::new(raw as usize);
+
+        unsafe { reg.toggle_enable() };
+        // The RW1C flag is written as 0, and only the enable bit changed otherwise.
+        assert_eq!(reg.clock_register.read(), 0xAB00_8000 | (10 << 8) | 5);
+        assert!(reg.get_enable());
+
+        unsafe { reg.toggle_enable() };
+        assert_eq!(reg.clock_register.read(), 0xAB00_0000 | (10 << 8) | 5);
+        assert!(!reg.get_enable());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "too large for its range")]
+    fn modify_rejects_wide_field_test() {
+        let mut fake_device_storage = [0u32];
+        let mut reg = Registers::new(fake_device_storage.as_mut_ptr() as usize);
+        unsafe { reg.modify_clock_register(|fields| fields.low_time = 0x80) };
+    }
+}
+
+mod reset_register {
+    use hal_macros::{VolatileRead, RW};
+    use hal_macros_derive::make_device;
+
+    const CONTROL_REGISTER: usize = 0;
+    const WIDE_REGISTER: usize = 8;
+
+    make_device! {
+        device_ports();
+
+        #[bit(0..=7, RW, CONTROL_REGISTER)]
+        divider,
+
+        #[reset(0x0001_0044)]
+        #[bit(16, RW1C, CONTROL_REGISTER)]
+        done_flag,
+
+        #[reset(0x1_0000_0000)]
+        #[bit(0..=63, RW, WIDE_REGISTER)]
+        wide,
+    }
+
+    #[test]
+    fn reset_constant_test() {
+        assert_eq!(Registers::CONTROL_REGISTER_RESET, 0x0001_0044_u32);
+        assert_eq!(Registers::WIDE_REGISTER_RESET, 0x1_0000_0000_u64);
+    }
+
+    #[test]
+    fn reset_register_test() {
+        let mut fake_device_storage = [0u64; 2];
+        let raw = fake_device_storage.as_mut_ptr();
+        let mut reg = Registers::new(raw as usize);
+
+        unsafe {
+            (raw as *mut u32).write_volatile(0x0001_00FF);
+            reg.reset_control_register();
+            reg.reset_wide_register();
+        }
+
+        // The RW1C flag is left out of the write.
+        assert_eq!(reg.control_register.read(), 0x44);
+        assert_eq!(reg.get_wide(), 0x1_0000_0000);
+    }
+}
+
+mod indexed_register {
+    use hal_macros::RW;
+    use hal_macros_derive::make_device;
+
+    const CONTROL_REGISTER: usize = 0;
+    /// The four channel registers start at offset 0x0008, 8 bytes apart.
+    const CHANNEL_REGISTER: usize = 8;
+
+    make_device! {
+        device_ports();
+        // The last channel register ends exactly at the end of the window.
+        window_size(0x28);
+
+        #[bit(0, RW, CONTROL_REGISTER)]
+        enable,
+
+        #[bit(0..=7, RW, CHANNEL_REGISTER, index(0..4, stride = 8))]
+        channel_count,
+
+        #[bit(31, RW1C, CHANNEL_REGISTER, index(0..4, stride = 8))]
+        channel_done,
+    }
+
+    #[test]
+    fn indexed_accessor_test() {
+        let mut fake_device_storage = [0u32; 10];
+        let raw = fake_device_storage.as_mut_ptr();
+        let mut reg = Registers::new(raw as usize);
+        let word = |word: usize| unsafe { raw.add(word).read_volatile() };
+
+        assert_eq!(Registers::CHANNEL_REGISTER_INDEXES, 0..4);
+        for index in Registers::CHANNEL_REGISTER_INDEXES {
+            unsafe { reg.set_channel_count(index, 0x10 + index as u8) };
+        }
+
+        assert_eq!(word(0), 0);
+        assert_eq!(
+            [word(2), word(4), word(6), word(8)],
+            [0x10, 0x11, 0x12, 0x13]
+        );
+        assert_eq!([word(3), word(5), word(7), word(9)], [0; 4]);
+        assert_eq!(reg.get_channel_count(3), 0x13);
+
+        unsafe { raw.add(6).write_volatile(1 << 31 | 0x12) };
+        assert!(reg.is_channel_done_active(2));
+        assert!(!reg.is_channel_done_active(1));
+
+        unsafe {
+            reg.modify_channel_register(1, |fields| fields.channel_count = 0xAA);
+            reg.set_enable(true);
+        }
+        assert_eq!(word(4), 0xAA);
+        assert_eq!(word(0), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "outside of the register indexes")]
+    fn index_out_of_range_test() {
+        let mut fake_device_storage = [0u32; 10];
+        let reg = Registers::new(fake_device_storage.as_mut_ptr() as usize);
+        reg.get_channel_count(4);
+    }
+}
+
+mod dump_registers {
+    use hal_macros::RW;
+    use hal_macros_derive::make_device;
+
+    const CONTROL_REGISTER: usize = 0;
+    const FIFO_REGISTER: usize = 4;
+    const COMMAND_REGISTER: usize = 8;
+
+    make_device! {
+        device_ports();
+
+        #[bit(0..=15, RW, CONTROL_REGISTER)]
+        whole_control,
+
+        #[bit(0, RW, CONTROL_REGISTER)]
+        enable,
+
+        #[bit(4..=7, RO, CONTROL_REGISTER)]
+        state,
+
+        #[bit(8, RW1C, CONTROL_REGISTER)]
+        done_flag,
+
+        #[bit(12..=15, WO, CONTROL_REGISTER)]
+        command_bits,
+
+        #[no_dump]
+        #[bit(0..=7, RW, FIFO_REGISTER)]
+        fifo_data,
+
+        #[bit(0..=31, WO, COMMAND_REGISTER)]
+        command,
+    }
+
+    struct Dump<'a>(&'a Registers);
+
+    impl core::fmt::Debug for Dump<'_> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            self.0.fmt_registers(f)
+        }
+    }
+
+    #[test]
+    fn fmt_registers_test() {
+        let mut fake_device_storage = [0x0000_F151_u32, 0xAA, 0];
+        let reg = Registers::new(fake_device_storage.as_mut_ptr() as usize);
+
+        // The FIFO is marked `#[no_dump]`, the command register is write only, and
+        // the whole control field and write only command bits are left out.
+        assert_eq!(
+            std::format!("{:?}", Dump(&reg)),
+            "CONTROL_REGISTER=0x0000f151 { enable: true, state: 0x5, done_flag: true }\n"
+        );
+    }
+}
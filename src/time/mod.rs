@@ -0,0 +1,188 @@
+//! # Time
+//! A running millisecond clock on the Cortex-M SysTick timer, for logging and
+//! timeouts. This is only built with the `systick-time` feature.
+//!
+//! SysTick is set to interrupt once a millisecond, and the SysTick exception
+//! handler must call `on_systick` to count the milliseconds:
+//!
+//! ```no_run
+//! use max78000_hal::time::{self, Monotonic};
+//!
+//! #[no_mangle]
+//! extern "C" fn SysTick() {
+//!     time::on_systick();
+//! }
+//!
+//! let clock = Monotonic::init(unsafe { max78000_hal::SYSTEM_CORE_CLOCK }).unwrap();
+//! let started = clock.now();
+//! ```
+
+use crate::critical_section::{self, Mutex};
+use crate::error::{ErrorKind, Result};
+use crate::scs::{self, Registers};
+use crate::timer::MonotonicSource;
+use core::cell::Cell;
+
+/// The milliseconds counted by `on_systick` since `Monotonic::init`.
+static MILLIS: Mutex<Cell<u64>> = Mutex::new(Cell::new(0));
+
+/// # On SysTick
+/// Count one millisecond. Call this from the SysTick exception handler, and
+/// nowhere else.
+pub fn on_systick() {
+    critical_section::with(|cs| {
+        let millis = MILLIS.borrow(cs);
+        millis.set(millis.get() + 1);
+    });
+}
+
+/// # Monotonic
+/// The SysTick millisecond clock, made with `Monotonic::init`. Every `Monotonic`
+/// reads the same clock.
+pub struct Monotonic {
+    reg: Registers,
+    ticks_per_ms: u32,
+}
+
+impl Monotonic {
+    /// # Init
+    /// Start SysTick from the core clock at `core_clock_hz`, interrupting once a
+    /// millisecond, and restart the clock from 0.
+    ///
+    /// # Core Clock
+    /// `core_clock_hz` is usually `SYSTEM_CORE_CLOCK`, read after the clocks are
+    /// set up. SysTick counts the core clock directly, so if the core clock is
+    /// changed later `init` has to be called again with the new frequency, or the
+    /// clock runs fast or slow by the same factor.
+    ///
+    /// Returns `ErrorKind::BadParam` if `core_clock_hz` is under 1MHz, too slow to
+    /// tell microseconds apart.
+    pub fn init(core_clock_hz: u32) -> Result<Self> {
+        let mut clock = Self {
+            reg: Registers::new(scs::SYSTEM_CONTROL_SPACE),
+            ticks_per_ms: ticks_per_ms(core_clock_hz)?,
+        };
+
+        critical_section::with(|cs| {
+            MILLIS.borrow(cs).set(0);
+            clock.start();
+        });
+
+        Ok(clock)
+    }
+
+    /// Program and enable SysTick for one interrupt every `ticks_per_ms` ticks.
+    fn start(&mut self) {
+        unsafe {
+            self.reg.set_enable(false);
+            self.reg.set_reload(self.ticks_per_ms - 1);
+            self.reg.set_current(0);
+            self.reg.set_clock_source(true);
+            self.reg.set_tick_interrupt(true);
+            self.reg.set_enable(true);
+        }
+    }
+
+    /// # Now
+    /// The milliseconds since `init`.
+    pub fn now(&self) -> u64 {
+        self.now_ticks().0
+    }
+
+    /// # Now Micros
+    /// The microseconds since `init`, from the millisecond count and how far
+    /// SysTick is into the current millisecond.
+    pub fn now_micros(&self) -> u64 {
+        let (millis, ticks) = self.now_ticks();
+        micros(millis, ticks, self.ticks_per_ms)
+    }
+
+    /// The milliseconds counted, and the ticks into the next millisecond.
+    ///
+    /// If SysTick has wrapped but its exception is still pending (because this
+    /// runs with interrupts disabled, or an interrupt of higher priority is being
+    /// handled), the millisecond it has not counted yet is added here, and the
+    /// counter is read again in case it wrapped after the first read.
+    fn now_ticks(&self) -> (u64, u32) {
+        critical_section::with(|cs| {
+            let mut millis = MILLIS.borrow(cs).get();
+            let mut current = self.reg.get_current();
+            if self.reg.get_systick_pending() {
+                millis += 1;
+                current = self.reg.get_current();
+            }
+
+            let reload = self.reg.get_reload();
+            (millis, reload.saturating_sub(current))
+        })
+    }
+}
+
+impl MonotonicSource for Monotonic {
+    /// The microseconds since `init`, wrapping around at `u32::MAX`.
+    fn now_us(&mut self) -> u32 {
+        self.now_micros() as u32
+    }
+}
+
+/// The SysTick ticks in one millisecond at `core_clock_hz`. Any `u32` clock fits
+/// the 24 bit reload value.
+fn ticks_per_ms(core_clock_hz: u32) -> Result<u32> {
+    let ticks = core_clock_hz / 1000;
+    if ticks < 1000 {
+        return Err(ErrorKind::BadParam);
+    }
+
+    Ok(ticks)
+}
+
+/// `millis` milliseconds and `ticks` ticks of the next one, in microseconds.
+fn micros(millis: u64, ticks: u32, ticks_per_ms: u32) -> u64 {
+    millis * 1000 + (ticks as u64 * 1000) / ticks_per_ms as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scs::rro::{ICSR, SYST_CSR, SYST_CVR, SYST_RVR};
+    use crate::tests::FakeRegisters;
+
+    #[test]
+    fn tick_conversion_test() {
+        assert_eq!(ticks_per_ms(100_000_000), Ok(100_000));
+        assert_eq!(ticks_per_ms(60_000_000), Ok(60_000));
+        assert_eq!(ticks_per_ms(1_000_000), Ok(1000));
+        assert_eq!(ticks_per_ms(999_999), Err(ErrorKind::BadParam));
+        assert!(ticks_per_ms(u32::MAX).unwrap() < 1 << 24);
+
+        assert_eq!(micros(0, 0, 100_000), 0);
+        assert_eq!(micros(0, 99_999, 100_000), 999);
+        assert_eq!(micros(3, 50_000, 100_000), 3_500);
+        assert_eq!(micros(7, 100, 60_000), 7_001);
+        // A day of milliseconds is nowhere near overflowing.
+        assert_eq!(micros(86_400_000, 0, 100_000), 86_400_000_000);
+    }
+
+    #[test]
+    fn start_and_pending_tick_test() {
+        let fake = FakeRegisters::<{ ICSR / 4 + 1 }>::new();
+        let mut clock = Monotonic {
+            reg: Registers::new(fake.address()),
+            ticks_per_ms: 50_000,
+        };
+
+        clock.start();
+        assert_eq!(fake.word(SYST_CSR), 0b111);
+        assert_eq!(fake.word(SYST_RVR), 49_999);
+
+        critical_section::with(|_| {
+            let millis = clock.now();
+            fake.set_word(SYST_CVR, 24_999);
+            assert_eq!(clock.now_micros(), millis * 1000 + 500);
+
+            // A wrap the handler has not counted yet.
+            fake.set_word(ICSR, 1 << 26);
+            assert_eq!(clock.now(), millis + 1);
+        });
+    }
+}
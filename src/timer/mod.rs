@@ -1 +1,626 @@
+use crate::error::{ErrorKind, Result};
+use crate::gcr::{peripheral_reset, system_clock_enable, HardwareSource};
+use crate::gpio::GpioPin;
+use crate::memory_map::mmio;
+use core::marker::PhantomData;
+
+use self::registers::Registers;
+
 pub mod registers;
+
+mod private {
+    pub trait TimerPortCompatable: crate::port::Port {
+        const HARDWARE_SOURCE: crate::gcr::HardwareSource;
+    }
+}
+
+pub struct NoPort {}
+pub struct TMR0 {}
+pub struct TMR1 {}
+pub struct TMR2 {}
+pub struct TMR3 {}
+
+impl crate::port::private::Sealed for TMR0 {}
+impl crate::port::Port for TMR0 {
+    const BASE: usize = mmio::TIMER_0;
+    const NUM: usize = 0;
+}
+impl private::TimerPortCompatable for TMR0 {
+    const HARDWARE_SOURCE: HardwareSource = HardwareSource::TMR0;
+}
+impl crate::port::private::Sealed for TMR1 {}
+impl crate::port::Port for TMR1 {
+    const BASE: usize = mmio::TIMER_1;
+    const NUM: usize = 1;
+}
+impl private::TimerPortCompatable for TMR1 {
+    const HARDWARE_SOURCE: HardwareSource = HardwareSource::TMR1;
+}
+impl crate::port::private::Sealed for TMR2 {}
+impl crate::port::Port for TMR2 {
+    const BASE: usize = mmio::TIMER_2;
+    const NUM: usize = 2;
+}
+impl private::TimerPortCompatable for TMR2 {
+    const HARDWARE_SOURCE: HardwareSource = HardwareSource::TMR2;
+}
+impl crate::port::private::Sealed for TMR3 {}
+impl crate::port::Port for TMR3 {
+    const BASE: usize = mmio::TIMER_3;
+    const NUM: usize = 3;
+}
+impl private::TimerPortCompatable for TMR3 {
+    const HARDWARE_SOURCE: HardwareSource = HardwareSource::TMR3;
+}
+
+/// The TimerA mode that counts up to the compare value, raises the interrupt
+/// event, and starts again from 1. See Page 316-319, Table 19-13.
+const CONTINUOUS_MODE: u8 = 0b0001;
+
+/// The TimerA mode that drives the output pin high for the PWM value and low for
+/// the rest of each compare period. See Page 316-319, Table 19-13.
+const PWM_MODE: u8 = 0b0011;
+
+/// The TimerA clock source for the peripheral clock. See Page 319-321, Table 19-15.
+const PERIPHERAL_CLOCK_SOURCE: u8 = 0b00;
+
+/// How many times to check for the timer clock to come up before giving up.
+const CLOCK_READY_SPINS: u32 = 100_000;
+
+/// # Prescaler
+/// The legal timer clock divisions, the timer counts at the peripheral clock
+/// divided by this value. See Page 316-319, Table 19-13.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prescaler {
+    Div1 = 0,
+    Div2 = 1,
+    Div4 = 2,
+    Div8 = 3,
+    Div16 = 4,
+    Div32 = 5,
+    Div64 = 6,
+    Div128 = 7,
+    Div256 = 8,
+    Div512 = 9,
+    Div1024 = 10,
+    Div2048 = 11,
+    Div4096 = 12,
+}
+
+impl Prescaler {
+    /// All prescalers, from the smallest division to the largest.
+    pub const ALL: [Prescaler; 13] = [
+        Self::Div1,
+        Self::Div2,
+        Self::Div4,
+        Self::Div8,
+        Self::Div16,
+        Self::Div32,
+        Self::Div64,
+        Self::Div128,
+        Self::Div256,
+        Self::Div512,
+        Self::Div1024,
+        Self::Div2048,
+        Self::Div4096,
+    ];
+
+    /// # Divisor
+    /// The amount the peripheral clock is divided by.
+    pub const fn divisor(self) -> u32 {
+        1 << (self as u32)
+    }
+}
+
+/// # Best Prescaler And Count
+/// Find the prescaler and compare count that make the timer hit `target_hz` most
+/// closely at the current `core_peripheral_clock`. Returns `None` if `target_hz`
+/// cannot be reached.
+pub fn best_prescaler_and_count(target_hz: u32) -> Option<(Prescaler, u32)> {
+    best_prescaler_and_count_for(crate::core_peripheral_clock(), target_hz)
+}
+
+/// Search every prescaler for the combination with the lowest frequency error,
+/// preferring the smaller prescaler (finer resolution) on ties.
+fn best_prescaler_and_count_for(clock_hz: u32, target_hz: u32) -> Option<(Prescaler, u32)> {
+    if target_hz == 0 || target_hz > clock_hz {
+        return None;
+    }
+
+    let mut best: Option<(Prescaler, u32)> = None;
+
+    for prescaler in Prescaler::ALL {
+        let divided_target = prescaler.divisor() as u64 * target_hz as u64;
+        let count = (clock_hz as u64 + divided_target / 2) / divided_target;
+
+        if count == 0 || count > u32::MAX as u64 {
+            continue;
+        }
+
+        let candidate = (prescaler, count as u32);
+        best = match best {
+            Some(current) if !less_error(clock_hz, target_hz, candidate, current) => Some(current),
+            _ => Some(candidate),
+        };
+    }
+
+    best
+}
+
+/// Check if `a` produces a frequency strictly closer to `target_hz` than `b`.
+fn less_error(clock_hz: u32, target_hz: u32, a: (Prescaler, u32), b: (Prescaler, u32)) -> bool {
+    // The error of a combination is |clock / (div * count) - target|, which is
+    // compared exactly by multiplying through by both denominators.
+    let period = |(prescaler, count): (Prescaler, u32)| prescaler.divisor() as u128 * count as u128;
+    let error_numerator =
+        |combination| (clock_hz as u128).abs_diff(target_hz as u128 * period(combination));
+
+    error_numerator(a) * period(b) < error_numerator(b) * period(a)
+}
+
+/// # Timer
+/// One of the 32-bit timers `TMR0` to `TMR3`, running TimerA and TimerB cascaded
+/// as a single 32-bit timer clocked from the peripheral clock.
+pub struct Timer<Port = NoPort> {
+    reg: Registers,
+    pin: Option<GpioPin>,
+    _ph: PhantomData<Port>,
+}
+
+impl Timer<NoPort> {
+    /// # Port 0 Init
+    /// Reset and enable the clock of `TMR0`. Should never be called more than once.
+    pub fn port_0_init() -> Result<Timer<TMR0>> {
+        Timer::<TMR0>::init()
+    }
+
+    /// # Port 1 Init
+    /// Reset and enable the clock of `TMR1`. Should never be called more than once.
+    pub fn port_1_init() -> Result<Timer<TMR1>> {
+        Timer::<TMR1>::init()
+    }
+
+    /// # Port 2 Init
+    /// Reset and enable the clock of `TMR2`. Should never be called more than once.
+    pub fn port_2_init() -> Result<Timer<TMR2>> {
+        Timer::<TMR2>::init()
+    }
+
+    /// # Port 3 Init
+    /// Reset and enable the clock of `TMR3`. Should never be called more than once.
+    pub fn port_3_init() -> Result<Timer<TMR3>> {
+        Timer::<TMR3>::init()
+    }
+}
+
+impl<Port: private::TimerPortCompatable> Timer<Port> {
+    fn init() -> Result<Self> {
+        peripheral_reset(Port::HARDWARE_SOURCE)?;
+        system_clock_enable(Port::HARDWARE_SOURCE, true);
+        Ok(Self::from_registers(Registers::new(Port::BASE)))
+    }
+
+    fn from_registers(reg: Registers) -> Self {
+        Self {
+            reg,
+            pin: None,
+            _ph: PhantomData,
+        }
+    }
+
+    /// # Init Periodic
+    /// Stop the timer and set it up to tick at `freq_hz`, using the prescaler and
+    /// compare value from `best_prescaler_and_count`. Returns the frequency the timer
+    /// actually ticks at, or `ErrorKind::BadParam` if `freq_hz` cannot be reached at
+    /// the current `core_peripheral_clock`.
+    ///
+    /// The timer does not run until `start` is called.
+    pub fn init_periodic(&mut self, freq_hz: u32) -> Result<u32> {
+        self.configure(freq_hz, CONTINUOUS_MODE)
+    }
+
+    /// # Init PWM
+    /// Stop the timer and set it up to output a PWM signal at `freq_hz` on `gpio`,
+    /// starting at a duty of 0. `gpio` must be the TimerA output pin of this timer
+    /// (`P0.2`, `P0.14`, `P0.26`, or `P1.6` for `TMR0` to `TMR3`), otherwise this
+    /// returns `ErrorKind::BadParam`. On any error the pin is given back with it,
+    /// untouched if it did not match. The pin is kept until `release_pin`.
+    ///
+    /// Returns the frequency actually output, like `init_periodic`. The period is
+    /// `get_max_duty` timer counts, and the signal starts once `start` is called.
+    ///
+    /// # Polarity
+    /// With the default polarity the output is high for the first `duty` counts
+    /// of each period and low for the rest, so it idles low at a duty of 0 and
+    /// while the timer is stopped. `set_pwm_polarity(true)` inverts the output,
+    /// making it idle high and be low for the duty counts instead.
+    pub fn init_pwm(
+        &mut self,
+        freq_hz: u32,
+        gpio: GpioPin,
+    ) -> core::result::Result<u32, (GpioPin, ErrorKind)> {
+        if crate::gpio::hardware::configure_timer_output_n(Port::NUM, &gpio).is_none() {
+            return Err((gpio, ErrorKind::BadParam));
+        }
+
+        let frequency = match self.configure(freq_hz, PWM_MODE) {
+            Ok(frequency) => frequency,
+            Err(err) => return Err((gpio, err)),
+        };
+        self.pin = Some(gpio);
+        unsafe {
+            self.reg.set_pwm(0);
+            self.reg.set_output_enable(true);
+        }
+
+        Ok(frequency)
+    }
+
+    /// # Set PWM Polarity
+    /// Invert the PWM output when `inverted` is set. See `init_pwm`.
+    pub fn set_pwm_polarity(&mut self, inverted: bool) {
+        unsafe { self.reg.set_timera_polarity(inverted) };
+    }
+
+    /// # Get Max Duty
+    /// The duty of a signal that is always active, which is the PWM period in
+    /// timer counts.
+    pub fn get_max_duty(&self) -> u32 {
+        self.reg.get_timer_compare_value()
+    }
+
+    /// # Set Duty
+    /// Keep the PWM output active for `duty` timer counts of each period, clamped
+    /// to `get_max_duty`.
+    pub fn set_duty(&mut self, duty: u32) {
+        let duty = duty.min(self.get_max_duty());
+        unsafe { self.reg.set_pwm(duty) };
+    }
+
+    /// # Set Duty Fraction
+    /// Keep the PWM output active for `numerator / denominator` of each period, for
+    /// example `(1, 4)` for a 25% duty. Fractions over 1 are clamped to a 100% duty,
+    /// and a `denominator` of 0 returns `ErrorKind::BadParam`.
+    pub fn set_duty_fraction(&mut self, numerator: u32, denominator: u32) -> Result<()> {
+        if denominator == 0 {
+            return Err(ErrorKind::BadParam);
+        }
+
+        let duty = self.get_max_duty() as u64 * numerator as u64 / denominator as u64;
+        self.set_duty(duty.min(u32::MAX as u64) as u32);
+        Ok(())
+    }
+
+    /// # Release Pin
+    /// Give back the pin taken by `init_pwm`, if there is one. The timer keeps
+    /// running, but no longer drives the pin.
+    pub fn release_pin(&mut self) -> Option<GpioPin> {
+        unsafe { self.reg.set_output_enable(false) };
+        self.pin.take()
+    }
+
+    /// Stop the timer and set it up to count at `freq_hz` in `mode`, returning the
+    /// frequency reached.
+    fn configure(&mut self, freq_hz: u32, mode: u8) -> Result<u32> {
+        let (prescaler, count) = best_prescaler_and_count(freq_hz).ok_or(ErrorKind::BadParam)?;
+
+        self.stop();
+
+        unsafe {
+            self.reg.set_timera_clock_enable(false);
+            self.reg.set_bit32_cascade_timer_enable(true);
+            self.reg.set_timera_clock_source(PERIPHERAL_CLOCK_SOURCE);
+            self.reg.set_timera_prescaler_select(prescaler as u8);
+            self.reg.set_timera_mode_select(mode);
+            self.reg.set_timera_clock_enable(true);
+        }
+        self.reg.wait_timera_clock_ready(true, CLOCK_READY_SPINS)?;
+
+        unsafe {
+            self.reg.set_timer_count(1);
+            self.reg.set_timer_compare_value(count);
+            self.reg.clear_timera_interrupt_event();
+        }
+
+        Ok(crate::core_peripheral_clock() / (prescaler.divisor() * count))
+    }
+
+    /// # Start
+    /// Start counting from where the timer was stopped.
+    pub fn start(&mut self) {
+        unsafe { self.reg.set_timera_enable(true) };
+    }
+
+    /// # Stop
+    /// Stop counting, keeping the current count.
+    pub fn stop(&mut self) {
+        unsafe { self.reg.set_timera_enable(false) };
+    }
+
+    /// # Wait For Tick
+    /// Block until the timer next reaches its compare value, then clear the
+    /// interrupt event so the following call waits for the next tick. If a tick
+    /// already happened since the last call, this returns straight away.
+    ///
+    /// This never returns if the timer is not running.
+    pub fn wait_for_tick(&mut self) {
+        while !self.reg.is_timera_interrupt_event_active() {
+            core::hint::spin_loop();
+        }
+
+        unsafe { self.reg.clear_timera_interrupt_event() };
+    }
+}
+
+/// # Monotonic Source
+/// A clock that counts up in microseconds and wraps around at `u32::MAX`. Any
+/// `FnMut() -> u32` closure can be used as a source.
+pub trait MonotonicSource {
+    /// # Now Us
+    /// The current time in microseconds.
+    fn now_us(&mut self) -> u32;
+}
+
+impl<F: FnMut() -> u32> MonotonicSource for F {
+    fn now_us(&mut self) -> u32 {
+        self()
+    }
+}
+
+/// # Rate Limiter
+/// Answers "has at least `interval_us` passed since the last time this fired?",
+/// for things like blink rates, poll intervals, or log throttling.
+///
+/// Elapsed time is computed with wrapping arithmetic so the source wrapping
+/// around is handled, as long as `should_fire` is called at least once every
+/// `u32::MAX` microseconds (about 71 minutes).
+pub struct RateLimiter<M> {
+    monotonic: M,
+    interval_us: u32,
+    last_fired: Option<u32>,
+}
+
+impl<M: MonotonicSource> RateLimiter<M> {
+    pub fn new(monotonic: M, interval_us: u32) -> Self {
+        Self {
+            monotonic,
+            interval_us,
+            last_fired: None,
+        }
+    }
+
+    /// # Should Fire
+    /// Returns `true` at most once per interval, the first call always fires.
+    /// When this fires the interval restarts from the current time.
+    pub fn should_fire(&mut self) -> bool {
+        let now = self.monotonic.now_us();
+
+        let fire = match self.last_fired {
+            Some(last_fired) => now.wrapping_sub(last_fired) >= self.interval_us,
+            None => true,
+        };
+
+        if fire {
+            self.last_fired = Some(now);
+        }
+
+        fire
+    }
+
+    /// # Reset
+    /// Forget the last time this fired, so the next `should_fire` fires.
+    pub fn reset(&mut self) {
+        self.last_fired = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CLOCK: u32 = 50_000_000;
+
+    fn frequency(clock_hz: u32, (prescaler, count): (Prescaler, u32)) -> f64 {
+        clock_hz as f64 / (prescaler.divisor() as f64 * count as f64)
+    }
+
+    fn assert_minimal_error(clock_hz: u32, target_hz: u32) {
+        let best = best_prescaler_and_count_for(clock_hz, target_hz).unwrap();
+        let best_error = (frequency(clock_hz, best) - target_hz as f64).abs();
+
+        for prescaler in Prescaler::ALL {
+            let divided_target = prescaler.divisor() as u64 * target_hz as u64;
+            let floor = clock_hz as u64 / divided_target;
+            for count in [floor, floor + 1] {
+                if count == 0 || count > u32::MAX as u64 {
+                    continue;
+                }
+                let count = count as u32;
+                let error = (frequency(clock_hz, (prescaler, count)) - target_hz as f64).abs();
+                assert!(
+                    best_error <= error,
+                    "{target_hz}Hz: {best:?} is worse than {prescaler:?}/{count}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exact_frequencies_test() {
+        assert_eq!(
+            best_prescaler_and_count_for(CLOCK, 1_000),
+            Some((Prescaler::Div1, 50_000))
+        );
+        assert_eq!(
+            best_prescaler_and_count_for(CLOCK, 1),
+            Some((Prescaler::Div1, 50_000_000))
+        );
+        assert_eq!(
+            best_prescaler_and_count_for(CLOCK, CLOCK),
+            Some((Prescaler::Div1, 1))
+        );
+    }
+
+    #[test]
+    fn unreachable_frequencies_test() {
+        assert_eq!(best_prescaler_and_count_for(CLOCK, 0), None);
+        assert_eq!(best_prescaler_and_count_for(CLOCK, CLOCK + 1), None);
+    }
+
+    #[test]
+    fn minimal_error_test() {
+        for target in [1, 3, 7, 60, 440, 1_000, 32_768, 44_100, 333_333, 9_999_999] {
+            assert_minimal_error(CLOCK, target);
+        }
+        assert_minimal_error(7_372_800, 115_200);
+        assert_minimal_error(u32::MAX, 1);
+    }
+
+    #[test]
+    fn prescaler_divisor_test() {
+        assert_eq!(Prescaler::Div1.divisor(), 1);
+        assert_eq!(Prescaler::Div256.divisor(), 256);
+        assert_eq!(Prescaler::Div4096.divisor(), 4096);
+    }
+
+    #[test]
+    fn init_periodic_programs_timer_test() {
+        const CMP_WORD: usize = 0x0004 / 4;
+        const CTRL0_WORD: usize = 0x0010 / 4;
+        const CTRL1_WORD: usize = 0x0018 / 4;
+        let mut fake_registers = [0u32; 8];
+        let ptr = fake_registers.as_mut_ptr();
+        let mut timer: Timer<TMR0> =
+            Timer::from_registers(Registers::new(fake_registers.as_mut_ptr() as usize));
+        let read = |word: usize| unsafe { ptr.add(word).read_volatile() };
+
+        // The clock never comes up on the fake registers.
+        assert_eq!(timer.init_periodic(1_000), Err(ErrorKind::TimeOut));
+
+        unsafe { ptr.add(CTRL1_WORD).write_volatile(1 << 3) };
+        assert_eq!(timer.init_periodic(1_000), Ok(1_000));
+        assert_eq!(read(CMP_WORD), 50_000);
+        assert_eq!(read(CTRL0_WORD), (1 << 14) | CONTINUOUS_MODE as u32);
+        assert_eq!(read(CTRL1_WORD), (1 << 31) | (1 << 3));
+
+        timer.start();
+        assert_eq!(read(CTRL0_WORD) & (1 << 15), 1 << 15);
+        timer.stop();
+        assert_eq!(read(CTRL0_WORD) & (1 << 15), 0);
+
+        assert_eq!(timer.init_periodic(0), Err(ErrorKind::BadParam));
+    }
+
+    #[test]
+    fn pwm_duty_clamps_to_period_test() {
+        const PWM_WORD: usize = 0x0008 / 4;
+        const CTRL0_WORD: usize = 0x0010 / 4;
+        const CTRL1_WORD: usize = 0x0018 / 4;
+        let mut fake_registers = [0u32; 8];
+        let ptr = fake_registers.as_mut_ptr();
+        let mut timer: Timer<TMR2> =
+            Timer::from_registers(Registers::new(fake_registers.as_mut_ptr() as usize));
+        let read = |word: usize| unsafe { ptr.add(word).read_volatile() };
+
+        // Only P0.26 is the TMR2 output.
+        let wrong_pin = crate::gpio::GpioPin::new(crate::gpio::GpioSelect::Gpio0, 25).unwrap();
+        assert!(matches!(
+            timer.init_pwm(1_000, wrong_pin),
+            Err((_, ErrorKind::BadParam))
+        ));
+
+        // An unreachable frequency gives the pin back too.
+        let pin = crate::gpio::GpioPin::new(crate::gpio::GpioSelect::Gpio0, 26).unwrap();
+        let Err((pin, err)) = timer.init_pwm(0, pin) else {
+            panic!("a 0Hz PWM is not reachable");
+        };
+        assert_eq!(err, ErrorKind::BadParam);
+        assert!(timer.release_pin().is_none());
+
+        unsafe { ptr.add(CTRL1_WORD).write_volatile(1 << 3) };
+        assert!(matches!(timer.init_pwm(10_000, pin), Ok(10_000)));
+        assert_eq!(timer.get_max_duty(), 5_000);
+        assert_eq!(read(CTRL0_WORD) & 0b1111, PWM_MODE as u32);
+        assert_eq!(read(CTRL1_WORD) & (1 << 13), 1 << 13);
+
+        timer.set_duty(1_250);
+        assert_eq!(read(PWM_WORD), 1_250);
+        timer.set_duty(9_999);
+        assert_eq!(read(PWM_WORD), 5_000);
+
+        assert_eq!(timer.set_duty_fraction(3, 4), Ok(()));
+        assert_eq!(read(PWM_WORD), 3_750);
+        assert_eq!(timer.set_duty_fraction(5, 4), Ok(()));
+        assert_eq!(read(PWM_WORD), 5_000);
+        assert_eq!(timer.set_duty_fraction(1, 0), Err(ErrorKind::BadParam));
+
+        timer.set_pwm_polarity(true);
+        assert_eq!(read(CTRL0_WORD) & (1 << 8), 1 << 8);
+
+        assert!(timer.release_pin().is_some());
+        assert!(timer.release_pin().is_none());
+    }
+
+    #[test]
+    fn wait_for_tick_returns_on_event_test() {
+        let mut fake_registers = [0u32; 8];
+        let ptr = fake_registers.as_mut_ptr();
+        let mut timer: Timer<TMR1> =
+            Timer::from_registers(Registers::new(fake_registers.as_mut_ptr() as usize));
+
+        unsafe { ptr.add(0x000C / 4).write_volatile(1) };
+        timer.wait_for_tick();
+    }
+
+    fn simulated_clock(now: &core::cell::Cell<u32>) -> impl FnMut() -> u32 + '_ {
+        move || now.get()
+    }
+
+    #[test]
+    fn rate_limiter_cadence_test() {
+        let now = core::cell::Cell::new(0);
+        let mut limiter = RateLimiter::new(simulated_clock(&now), 1_000);
+
+        let mut fired_at = std::vec::Vec::new();
+        for time in (0..5_000).step_by(100) {
+            now.set(time);
+            if limiter.should_fire() {
+                fired_at.push(time);
+            }
+        }
+
+        assert_eq!(fired_at, [0, 1_000, 2_000, 3_000, 4_000]);
+    }
+
+    #[test]
+    fn rate_limiter_restarts_from_fire_time_test() {
+        let now = core::cell::Cell::new(0);
+        let mut limiter = RateLimiter::new(simulated_clock(&now), 1_000);
+
+        assert!(limiter.should_fire());
+        now.set(1_500);
+        assert!(limiter.should_fire());
+        now.set(2_000);
+        assert!(!limiter.should_fire());
+        now.set(2_500);
+        assert!(limiter.should_fire());
+
+        limiter.reset();
+        assert!(limiter.should_fire());
+    }
+
+    #[test]
+    fn rate_limiter_wrap_around_test() {
+        let now = core::cell::Cell::new(u32::MAX - 499);
+        let mut limiter = RateLimiter::new(simulated_clock(&now), 1_000);
+
+        assert!(limiter.should_fire());
+        now.set(u32::MAX);
+        assert!(!limiter.should_fire());
+        now.set(499);
+        assert!(!limiter.should_fire());
+        now.set(500);
+        assert!(limiter.should_fire());
+    }
+}
@@ -24,7 +24,7 @@ mod rro {
 }
 
 make_device! {
-    device_ports(mmio::TIMER_0, mmio::TIMER_1, mmio::TIMER_2);
+    device_ports(mmio::TIMER_0, mmio::TIMER_1, mmio::TIMER_2, mmio::TIMER_3);
 
     /// Timer Count. See Page 315, Table 19-9.
     #[bit(0..=31, RW, rro::TMR_CNT)]
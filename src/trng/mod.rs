@@ -1,38 +1,374 @@
 pub mod registers;
 
+use crate::error::Result;
 use crate::gcr::HardwareSource;
 use crate::gcr::{peripheral_reset, system_clock_enable};
 use crate::memory_map::mmio;
 use registers::Registers;
 
+/// How many times a read checks for the next random number before giving up. The
+/// TRNG makes a new one every few microseconds.
+const TRNG_READY_SPINS: u32 = 100_000;
+
 /// A wrapper around the TRNG register. Used to allow the borrow checker to keep
 /// track of who can mutate the state of TRNG.
 pub struct TRNG {
     registers: Registers,
+    spare: SpareBytes,
+}
+
+/// The bytes of the last word read by `TRNG::fill_bytes` that were not needed
+/// yet, kept for the next call.
+#[derive(Clone, Copy)]
+struct SpareBytes {
+    word: [u8; 4],
+    used: usize,
+}
+
+impl SpareBytes {
+    const fn new() -> Self {
+        Self {
+            word: [0; 4],
+            used: 4,
+        }
+    }
+}
+
+/// Fill `bytes` from the little-endian bytes of `next_word`, starting with the
+/// `spare` bytes and keeping whatever is left over of the last word in `spare`.
+/// Stops at the first error from `next_word`, leaving the rest of `bytes` as it was.
+fn fill_from_words<E>(
+    bytes: &mut [u8],
+    spare: &mut SpareBytes,
+    mut next_word: impl FnMut() -> core::result::Result<u32, E>,
+) -> core::result::Result<(), E> {
+    let from_spare = (4 - spare.used).min(bytes.len());
+    bytes[..from_spare].copy_from_slice(&spare.word[spare.used..spare.used + from_spare]);
+    spare.used += from_spare;
+
+    let mut chunks = bytes[from_spare..].chunks_exact_mut(4);
+    for chunk in &mut chunks {
+        chunk.copy_from_slice(&next_word()?.to_le_bytes());
+    }
+
+    let tail = chunks.into_remainder();
+    if !tail.is_empty() {
+        spare.word = next_word()?.to_le_bytes();
+        tail.copy_from_slice(&spare.word[..tail.len()]);
+        spare.used = tail.len();
+    }
+
+    Ok(())
 }
 
 impl TRNG {
     /// Initializes TRNG by resetting the TRNG peripheral, enabling TRNG's system
     /// clock, enabling AES's system clock, and clearing the TRNG control register.
     /// Should never be initialized more than once.
-    pub fn init() -> Self {
+    ///
+    /// Returns `ErrorKind::TimeOut` if the TRNG reset does not finish.
+    pub fn init() -> Result<Self> {
         system_clock_enable(HardwareSource::AES, true);
-        peripheral_reset(HardwareSource::TRNG);
+        peripheral_reset(HardwareSource::TRNG)?;
         system_clock_enable(HardwareSource::TRNG, true);
 
         let mut registers = Registers::new(mmio::TRNG);
         unsafe { registers.set_trng_control_register(0) };
-        Self { registers }
+        Ok(Self {
+            registers,
+            spare: SpareBytes::new(),
+        })
+    }
+
+    /// Make a TRNG driver around `registers`, for tests that run on fake memory.
+    #[cfg(test)]
+    pub(crate) fn from_registers(registers: Registers) -> Self {
+        Self {
+            registers,
+            spare: SpareBytes::new(),
+        }
     }
 
-    /// Get a random number from TRNG.
-    pub fn get_trng_data(&mut self) -> u32 {
-        while !self.registers.get_random_number_ready() {}
-        self.registers.get_trng_data()
+    /// Get a random number from TRNG, waiting for it to be `ready`.
+    ///
+    /// Returns `ErrorKind::TimeOut` if no random number is ready after
+    /// `TRNG_READY_SPINS` checks, like when the TRNG is not clocked.
+    pub fn get_trng_data(&mut self) -> Result<u32> {
+        read_word(&self.registers)
     }
 
     /// Check if `get_trng_data` is ready.
     pub fn ready(&self) -> bool {
         self.registers.get_random_number_ready()
     }
+
+    /// # Next U32
+    /// Get a random `u32`, waiting for as long as it takes to be `ready`.
+    pub fn next_u32(&mut self) -> u32 {
+        wait_word(&self.registers)
+    }
+
+    /// # Next U64
+    /// Get a random `u64`, made from two hardware reads.
+    pub fn next_u64(&mut self) -> u64 {
+        let low = self.next_u32() as u64;
+        let high = self.next_u32() as u64;
+        high << 32 | low
+    }
+
+    /// # Fill Bytes
+    /// Fill `bytes` with random data, writing each hardware read little-endian.
+    /// When the length is not a multiple of 4, the tail takes the low bytes of one
+    /// more read, and the unused bytes of that read are kept and used first by the
+    /// next `fill_bytes` or `try_fill_bytes`.
+    pub fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        let registers = &self.registers;
+        let Ok(()) = fill_from_words::<core::convert::Infallible>(bytes, &mut self.spare, || {
+            Ok(wait_word(registers))
+        });
+    }
+
+    /// # Try Next U32
+    /// Like `next_u32`, but gives up on a TRNG that never becomes `ready`.
+    ///
+    /// Returns `ErrorKind::TimeOut` after `TRNG_READY_SPINS` checks, like
+    /// `get_trng_data`.
+    pub fn try_next_u32(&mut self) -> Result<u32> {
+        self.get_trng_data()
+    }
+
+    /// # Try Next U64
+    /// Like `next_u64`, but each of the two reads gives up like `try_next_u32`.
+    pub fn try_next_u64(&mut self) -> Result<u64> {
+        let low = self.try_next_u32()? as u64;
+        let high = self.try_next_u32()? as u64;
+        Ok(high << 32 | low)
+    }
+
+    /// # Try Fill Bytes
+    /// Like `fill_bytes`, but every read gives up like `try_next_u32`.
+    ///
+    /// Returns `ErrorKind::TimeOut` with only the bytes before the stalled read
+    /// filled.
+    pub fn try_fill_bytes(&mut self, bytes: &mut [u8]) -> Result<()> {
+        let registers = &self.registers;
+        fill_from_words(bytes, &mut self.spare, || read_word(registers))
+    }
+}
+
+/// Wait as long as it takes for the next random number and read it.
+fn wait_word(registers: &Registers) -> u32 {
+    while !registers.get_random_number_ready() {
+        core::hint::spin_loop();
+    }
+    registers.get_trng_data()
+}
+
+/// Wait for the next random number and read it.
+fn read_word(registers: &Registers) -> Result<u32> {
+    registers.wait_random_number_ready(true, TRNG_READY_SPINS)?;
+    Ok(registers.get_trng_data())
+}
+
+/// # Seeded RNG
+/// A fast software PRNG (xoshiro128++) seeded from the TRNG, for things like
+/// dithering, jitter, or test data that need lots of random numbers quickly.
+/// After seeding it never touches the hardware again.
+///
+/// **This is NOT cryptographically secure.** Its output can be predicted from a
+/// few outputs, so for keys, nonces, or anything else security related read the
+/// `TRNG` directly.
+pub struct SeededRng {
+    state: [u32; 4],
+}
+
+impl SeededRng {
+    /// # From TRNG
+    /// Seed a new generator with two words from the TRNG.
+    ///
+    /// Returns `ErrorKind::TimeOut` if the TRNG does, see `TRNG::try_next_u64`.
+    pub fn from_trng(trng: &mut TRNG) -> Result<Self> {
+        Ok(Self::from_seed(trng.try_next_u64()?))
+    }
+
+    /// # From Seed
+    /// Make a generator from a fixed seed, the same seed always produces the same
+    /// sequence. The seed is expanded into the generator state with SplitMix64.
+    pub fn from_seed(seed: u64) -> Self {
+        let mut splitmix = seed;
+        let mut next = || {
+            splitmix = splitmix.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = splitmix;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+
+        let (first, second) = (next(), next());
+        Self::from_state([
+            first as u32,
+            (first >> 32) as u32,
+            second as u32,
+            (second >> 32) as u32,
+        ])
+    }
+
+    fn from_state(state: [u32; 4]) -> Self {
+        // An all zero state would only ever produce zeros.
+        let state = if state == [0; 4] {
+            [0x9e37_79b9, 0, 0, 0]
+        } else {
+            state
+        };
+
+        Self { state }
+    }
+
+    /// # Next U32
+    /// Get the next random `u32`.
+    pub fn next_u32(&mut self) -> u32 {
+        let [s0, s1, s2, s3] = &mut self.state;
+        let result = s0.wrapping_add(*s3).rotate_left(7).wrapping_add(*s0);
+
+        let t = *s1 << 9;
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+        *s2 ^= t;
+        *s3 = s3.rotate_left(11);
+
+        result
+    }
+
+    /// # Next U64
+    /// Get the next random `u64`, made from two `u32`s.
+    pub fn next_u64(&mut self) -> u64 {
+        let low = self.next_u32() as u64;
+        let high = self.next_u32() as u64;
+        high << 32 | low
+    }
+
+    /// # Fill Bytes
+    /// Fill `bytes` with random data.
+    pub fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(4) {
+            let random = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&random[..chunk.len()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn trng_fill_bytes_tail_test() {
+        // TRNG_STATUS reports data ready and TRNG_DATA always reads the same word.
+        let mut fake_registers = [0u32, 1, 0x0403_0201];
+        let mut trng = TRNG {
+            registers: Registers::new(fake_registers.as_mut_ptr() as usize),
+            spare: SpareBytes::new(),
+        };
+
+        let mut bytes = [0u8; 6];
+        trng.fill_bytes(&mut bytes);
+        assert_eq!(bytes, [1, 2, 3, 4, 1, 2]);
+
+        // The 2 spare bytes are shared with the bounded variant.
+        let mut bytes = [0u8; 3];
+        assert_eq!(trng.try_fill_bytes(&mut bytes), Ok(()));
+        assert_eq!(bytes, [3, 4, 1]);
+
+        assert_eq!(trng.next_u32(), 0x0403_0201);
+        assert_eq!(trng.next_u64(), 0x0403_0201_0403_0201);
+        assert_eq!(trng.try_next_u32(), Ok(0x0403_0201));
+        assert_eq!(trng.try_next_u64(), Ok(0x0403_0201_0403_0201));
+    }
+
+    #[test]
+    fn trng_not_ready_times_out_test() {
+        // TRNG_STATUS never reports data ready.
+        let mut fake_registers = [0u32, 0, 0x0403_0201];
+        let mut trng = TRNG::from_registers(Registers::new(fake_registers.as_mut_ptr() as usize));
+
+        let mut bytes = [0u8; 6];
+        assert_eq!(trng.try_fill_bytes(&mut bytes), Err(ErrorKind::TimeOut));
+        assert_eq!(bytes, [0; 6]);
+        assert_eq!(trng.get_trng_data(), Err(ErrorKind::TimeOut));
+        assert_eq!(trng.try_next_u32(), Err(ErrorKind::TimeOut));
+        assert_eq!(trng.try_next_u64(), Err(ErrorKind::TimeOut));
+        assert!(SeededRng::from_trng(&mut trng).is_err());
+    }
+
+    #[test]
+    fn fill_from_words_keeps_spare_bytes_test() {
+        let words = [0x4433_2211, 0x8877_6655, 0xccbb_aa99];
+        let mut next = words.iter().copied().cycle();
+        let mut spare = SpareBytes::new();
+
+        let mut first = [0u8; 6];
+        fill_from_words::<()>(&mut first, &mut spare, || Ok(next.next().unwrap())).unwrap();
+        assert_eq!(first, [0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+
+        // The 2 unused bytes of the second word come first, then a new word.
+        let mut second = [0u8; 3];
+        fill_from_words::<()>(&mut second, &mut spare, || Ok(next.next().unwrap())).unwrap();
+        assert_eq!(second, [0x77, 0x88, 0x99]);
+
+        let mut third = [0u8; 1];
+        fill_from_words::<()>(&mut third, &mut spare, || Ok(next.next().unwrap())).unwrap();
+        assert_eq!(third, [0xaa]);
+
+        let mut fourth = [0u8; 7];
+        fill_from_words::<()>(&mut fourth, &mut spare, || Ok(next.next().unwrap())).unwrap();
+        assert_eq!(fourth, [0xbb, 0xcc, 0x11, 0x22, 0x33, 0x44, 0x55]);
+
+        let mut empty = [0u8; 0];
+        fill_from_words::<()>(&mut empty, &mut spare, || unreachable!()).unwrap();
+    }
+
+    #[test]
+    fn xoshiro_reference_test() {
+        // The first outputs of the reference xoshiro128++ from the state {1, 2, 3, 4}.
+        let mut rng = SeededRng::from_state([1, 2, 3, 4]);
+        assert_eq!(rng.next_u32(), 641);
+        assert_eq!(rng.next_u32(), 1_573_767);
+        assert_eq!(rng.next_u32(), 3_222_811_527);
+    }
+
+    #[test]
+    fn same_seed_same_sequence_test() {
+        let mut first = SeededRng::from_seed(0xdead_beef);
+        let mut second = SeededRng::from_seed(0xdead_beef);
+        let mut other = SeededRng::from_seed(0xdead_bef0);
+
+        let mut first_bytes = [0u8; 13];
+        let mut second_bytes = [0u8; 13];
+        first.fill_bytes(&mut first_bytes);
+        second.fill_bytes(&mut second_bytes);
+        assert_eq!(first_bytes, second_bytes);
+
+        for _ in 0..64 {
+            let value = first.next_u64();
+            assert_eq!(value, second.next_u64());
+            assert_ne!(value, other.next_u64());
+        }
+    }
+
+    #[test]
+    fn fill_bytes_matches_next_u32_test() {
+        let mut words = SeededRng::from_seed(7);
+        let mut bytes = SeededRng::from_seed(7);
+
+        let mut filled = [0u8; 6];
+        bytes.fill_bytes(&mut filled);
+
+        let expected_first = words.next_u32().to_le_bytes();
+        let expected_second = words.next_u32().to_le_bytes();
+        assert_eq!(filled[..4], expected_first);
+        assert_eq!(filled[4..], expected_second[..2]);
+    }
 }
@@ -37,6 +37,7 @@ make_device! {
     random_number_ready,
 
     /// TRNG Data. See Page 364, Table 25-4.
+    #[no_dump]
     #[bit(0..=31, RO, rro::TRNG_DATA)]
     trng_data,
 }
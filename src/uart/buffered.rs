@@ -0,0 +1,353 @@
+use super::{private, UART};
+use crate::error::{ErrorKind, Result};
+
+/// # Ring Buffer
+/// A fixed size FIFO of `N` bytes, stored inline with no heap.
+pub struct RingBuffer<const N: usize> {
+    buffer: [u8; N],
+    start: usize,
+    len: usize,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// # Push
+    /// Add `byte` to the back of the buffer, returning `ErrorKind::Overflow` if
+    /// the buffer is full.
+    pub fn push(&mut self, byte: u8) -> Result<()> {
+        if self.len >= N {
+            return Err(ErrorKind::Overflow);
+        }
+
+        self.buffer[(self.start + self.len) % N] = byte;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// # Pop
+    /// Take the oldest byte out of the buffer.
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let byte = self.buffer[self.start];
+        self.start = (self.start + 1) % N;
+        self.len -= 1;
+        Some(byte)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len >= N
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<const N: usize> Default for RingBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # Line Events
+/// Frame boundaries seen by `BufferedUart::on_interrupt`, when enabled with
+/// `enable_break_detection` or `enable_idle_detection`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LineEvents {
+    /// A break (the line held low for longer than a character) was received. The
+    /// `0x00` byte the UART reads for a break is not put in the receive buffer.
+    pub break_received: bool,
+    /// The line was idle for at least the configured gap before the newest bytes
+    /// arrived. The value is how many bytes in the receive buffer came before the
+    /// gap, which is the end of the previous frame.
+    pub idle_frame_len: Option<usize>,
+}
+
+/// # Buffered UART
+/// An interrupt driven UART with an `RX` byte receive buffer and a `TX` byte
+/// transmit buffer, made with `UART::into_buffered`. The UART interrupt handler
+/// must call `on_interrupt`, which moves received bytes out of the hardware FIFO
+/// and refills the transmit FIFO from the transmit buffer.
+///
+/// # Placement
+/// Both buffers are stored inline in this struct, so it is `RX + TX` bytes plus
+/// a few words large. Since the interrupt handler needs to reach it, it usually
+/// lives in a `static` (for example a
+/// `critical_section::Mutex<RefCell<Option<BufferedUart64<UART0>>>>`), which
+/// places the buffers in `.bss`. Building a large one on the stack first can
+/// overflow a small stack, so prefer the smaller aliases or construct it
+/// directly into the static.
+pub struct BufferedUart<Port, const RX: usize, const TX: usize> {
+    uart: UART<Port>,
+    rx: RingBuffer<RX>,
+    tx: RingBuffer<TX>,
+    rx_dropped: usize,
+    break_detection: bool,
+    idle: Option<IdleDetector>,
+}
+
+/// A `BufferedUart` with 64 byte receive and transmit buffers.
+pub type BufferedUart64<Port> = BufferedUart<Port, 64, 64>;
+/// A `BufferedUart` with 256 byte receive and transmit buffers.
+pub type BufferedUart256<Port> = BufferedUart<Port, 256, 256>;
+
+impl<Port: private::UARTPortCompatable> UART<Port> {
+    /// # Into Buffered
+    /// Switch this UART to interrupt driven operation with `RX` and `TX` byte
+    /// buffers, enabling the receive threshold interrupt. See `BufferedUart`.
+    pub fn into_buffered<const RX: usize, const TX: usize>(mut self) -> BufferedUart<Port, RX, TX> {
+        unsafe {
+            self.reg.clear_receive_fifo_threshold_interrupt_flag();
+            self.reg.set_receive_fifo_thershold_event(true);
+        }
+
+        BufferedUart {
+            uart: self,
+            rx: RingBuffer::new(),
+            tx: RingBuffer::new(),
+            rx_dropped: 0,
+            break_detection: false,
+            idle: None,
+        }
+    }
+}
+
+impl<Port: private::UARTPortCompatable, const RX: usize, const TX: usize>
+    BufferedUart<Port, RX, TX>
+{
+    /// # On Interrupt
+    /// Service the UART, to be called from its interrupt handler. Received bytes
+    /// that do not fit in the receive buffer are dropped and counted in
+    /// `rx_dropped`.
+    ///
+    /// Returns the `LineEvents` seen, which are always empty unless break or idle
+    /// detection is enabled.
+    pub fn on_interrupt(&mut self) -> LineEvents {
+        let mut events = LineEvents::default();
+
+        loop {
+            let frame_error = self.break_detection
+                && self.uart.reg.is_receive_frame_error_interrupt_flag_active();
+            let Ok(byte) = self.uart.read_receive_fifo() else {
+                break;
+            };
+
+            // The flag is sticky, so clear it now that it has been tied to this
+            // byte, or every byte drained after it would look like a break too.
+            if frame_error {
+                unsafe { self.uart.reg.clear_receive_frame_error_interrupt_flag() };
+            }
+
+            if let Some(idle) = self.idle.as_mut() {
+                if idle.gap_before((idle.clock)()) && events.idle_frame_len.is_none() {
+                    events.idle_frame_len = Some(self.rx.len());
+                }
+            }
+
+            if frame_error && byte == 0 {
+                events.break_received = true;
+                continue;
+            }
+
+            if self.rx.push(byte).is_err() {
+                self.rx_dropped += 1;
+            }
+        }
+
+        unsafe {
+            self.uart.reg.clear_receive_frame_error_interrupt_flag();
+            self.uart.reg.clear_receive_fifo_threshold_interrupt_flag();
+            self.uart
+                .reg
+                .clear_transmit_fifo_half_empty_interrupt_flag();
+        }
+
+        self.fill_transmit_fifo();
+        events
+    }
+
+    /// # Enable Break Detection
+    /// Report received breaks in `LineEvents::break_received`, by interrupting on
+    /// frame errors. The UART reads a break as a `0x00` byte with no stop bit, so a
+    /// `0x00` received alongside a frame error is treated as a break.
+    ///
+    /// # LIN
+    /// A LIN frame starts with a break followed by the `0x55` sync byte, so a
+    /// `break_received` event marks the start of a new frame header.
+    pub fn enable_break_detection(&mut self, enable: bool) {
+        self.break_detection = enable;
+        unsafe {
+            self.uart.reg.clear_receive_frame_error_interrupt_flag();
+            self.uart.reg.set_receive_frame_error_event(enable);
+        }
+    }
+
+    /// # Enable Idle Detection
+    /// Report a gap of at least `gap_us` microseconds between received bytes in
+    /// `LineEvents::idle_frame_len`. `clock` must count up in microseconds, like a
+    /// `MonotonicSource`.
+    ///
+    /// The UART has no idle-line interrupt, so the gap is noticed when the first
+    /// byte after it arrives. This relies on the receive threshold of 1 byte set
+    /// by `init`, so every byte is timed as it arrives.
+    ///
+    /// # Modbus RTU
+    /// Modbus RTU frames are separated by at least 3.5 character times of silence,
+    /// so use that as `gap_us` (about 1750us above 19200 baud). A reported
+    /// `idle_frame_len` is then the length of the previous complete frame.
+    pub fn enable_idle_detection(&mut self, gap_us: u32, clock: fn() -> u32) {
+        self.idle = Some(IdleDetector::new(gap_us, clock));
+    }
+
+    /// # Disable Idle Detection
+    /// Stop reporting `LineEvents::idle_frame_len`.
+    pub fn disable_idle_detection(&mut self) {
+        self.idle = None;
+    }
+
+    /// # Read Byte
+    /// Take the next received byte, returning `ErrorKind::NoneAvailable` if the
+    /// receive buffer is empty.
+    pub fn read_byte(&mut self) -> Result<u8> {
+        self.rx.pop().ok_or(ErrorKind::NoneAvailable)
+    }
+
+    /// # Write Byte
+    /// Queue `data` for transmitting, returning `ErrorKind::Busy` if the transmit
+    /// buffer is full.
+    pub fn write_byte(&mut self, data: u8) -> Result<()> {
+        self.tx.push(data).map_err(|_| ErrorKind::Busy)?;
+        self.fill_transmit_fifo();
+        Ok(())
+    }
+
+    /// # RX Dropped
+    /// The number of received bytes dropped because the receive buffer was full.
+    pub fn rx_dropped(&self) -> usize {
+        self.rx_dropped
+    }
+
+    /// # Into Inner
+    /// Disable the UART interrupts and give back the UART. Any buffered bytes are
+    /// lost.
+    pub fn into_inner(mut self) -> UART<Port> {
+        unsafe {
+            self.uart.reg.set_receive_fifo_thershold_event(false);
+            self.uart.reg.set_transmit_fifo_half_empty_event(false);
+            self.uart.reg.set_receive_frame_error_event(false);
+        }
+
+        self.uart
+    }
+
+    /// Move as much of the transmit buffer into the hardware FIFO as fits, only
+    /// keeping the transmit interrupt enabled while there is more to send.
+    fn fill_transmit_fifo(&mut self) {
+        while !self.tx.is_empty() && !self.uart.reg.get_transmit_fifo_full() {
+            if let Some(byte) = self.tx.pop() {
+                unsafe { self.uart.reg.set_fifo_data(byte) };
+            }
+        }
+
+        unsafe {
+            self.uart
+                .reg
+                .set_transmit_fifo_half_empty_event(!self.tx.is_empty())
+        };
+    }
+}
+
+/// Times the gaps between received bytes, kept apart from the UART registers.
+struct IdleDetector {
+    gap_us: u32,
+    clock: fn() -> u32,
+    last_byte_at: Option<u32>,
+}
+
+impl IdleDetector {
+    const fn new(gap_us: u32, clock: fn() -> u32) -> Self {
+        Self {
+            gap_us,
+            clock,
+            last_byte_at: None,
+        }
+    }
+
+    /// Take in a byte received at `now_us`, returning `true` if the line was idle
+    /// for at least the gap before it.
+    fn gap_before(&mut self, now_us: u32) -> bool {
+        let gap = self
+            .last_byte_at
+            .is_some_and(|last| now_us.wrapping_sub(last) >= self.gap_us);
+        self.last_byte_at = Some(now_us);
+        gap
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ring_buffer_order_test() {
+        let mut ring: RingBuffer<4> = RingBuffer::new();
+        assert_eq!(ring.capacity(), 4);
+        assert_eq!(ring.pop(), None);
+
+        for byte in 0..4 {
+            assert_eq!(ring.push(byte), Ok(()));
+        }
+        assert!(ring.is_full());
+        assert_eq!(ring.push(4), Err(ErrorKind::Overflow));
+
+        assert_eq!(ring.pop(), Some(0));
+        assert_eq!(ring.push(4), Ok(()));
+        for byte in 1..5 {
+            assert_eq!(ring.pop(), Some(byte));
+        }
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn zero_size_ring_buffer_test() {
+        let mut ring: RingBuffer<0> = RingBuffer::new();
+        assert_eq!(ring.push(1), Err(ErrorKind::Overflow));
+        assert_eq!(ring.pop(), None);
+    }
+
+    #[test]
+    fn idle_detector_gap_test() {
+        let mut idle = IdleDetector::new(1_000, || 0);
+
+        // Nothing came before the first byte, so it never ends a frame.
+        assert!(!idle.gap_before(5_000));
+        assert!(!idle.gap_before(5_400));
+        assert!(!idle.gap_before(6_399));
+        assert!(idle.gap_before(7_399));
+        assert!(!idle.gap_before(7_500));
+
+        // The gap is measured across the clock wrapping around.
+        let mut idle = IdleDetector::new(1_000, || 0);
+        assert!(!idle.gap_before(u32::MAX - 100));
+        assert!(!idle.gap_before(800));
+        assert!(idle.gap_before(1_800));
+    }
+}
@@ -0,0 +1,201 @@
+use super::{private, registers, UART};
+use crate::dma::{Channel, Request, Transfer};
+use crate::error::{ErrorKind, Result};
+
+/// The transmit FIFO level below which the UART asks the DMA channel for another
+/// byte. Half of the 8 byte FIFO, the same point the half empty interrupt uses, so
+/// the line never runs dry while the channel waits for the bus.
+const TRANSMIT_DMA_THRESHOLD: u8 = super::FIFO_DEPTH / 2;
+
+impl<Port: private::UARTPortCompatable> UART<Port> {
+    /// # Write DMA
+    /// Queue `data` to be fed into the transmit FIFO by `channel`, returning as soon
+    /// as the channel is started. Use the returned `DmaWrite` to poll for or wait on
+    /// the end of the transfer.
+    ///
+    /// # Buffer
+    /// `data` must be `'static`, since the DMA controller keeps reading it for as
+    /// long as the channel runs. Forgetting the `DmaWrite` (with `mem::forget`)
+    /// skips the `Drop` that stops the channel, so a borrowed buffer could be freed
+    /// while it is still being read.
+    ///
+    /// # Concurrency
+    /// The `DmaWrite` borrows this UART, so nothing else (like
+    /// `write_blocking_transmit_fifo`) can push bytes in between the DMA ones. Once
+    /// it is done or dropped the UART is free again, and anything written next goes
+    /// into the FIFO after the bytes the channel already queued.
+    ///
+    /// Returns `ErrorKind::Busy` if `channel` is still running another transfer, and
+    /// `ErrorKind::BadParam` if `data` is longer than `dma::MAX_COUNT`.
+    pub fn write_dma<'a>(
+        &'a mut self,
+        data: &'static [u8],
+        channel: &'a mut Channel,
+    ) -> Result<DmaWrite<'a, Port>> {
+        let mut write = DmaWrite {
+            uart: self,
+            channel,
+            done: data.is_empty(),
+        };
+
+        if data.is_empty() {
+            return Ok(write);
+        }
+
+        let transfer = Transfer {
+            request: Request::uart_transmit(Port::NUM).ok_or(ErrorKind::BadParam)?,
+            source: data.as_ptr() as usize,
+            source_increment: true,
+            destination: Port::BASE + registers::FIFO_OFFSET,
+            destination_increment: false,
+            count: data.len(),
+        };
+
+        // `data` is `'static`, so it outlives the transfer even if the `DmaWrite` is
+        // never dropped.
+        unsafe {
+            write
+                .channel
+                .start(&transfer)
+                .inspect_err(|_| write.done = true)?;
+            write
+                .uart
+                .reg
+                .set_transmit_dma_level_dma_threshold(TRANSMIT_DMA_THRESHOLD);
+            write.uart.reg.set_transmit_dma_channel_enable(true);
+        }
+
+        Ok(write)
+    }
+}
+
+/// # DMA Write
+/// A transmit started by `UART::write_dma`. Dropping it before it is done stops
+/// the channel, leaving whatever bytes it already queued to be sent.
+pub struct DmaWrite<'a, Port: private::UARTPortCompatable> {
+    uart: &'a mut UART<Port>,
+    channel: &'a mut Channel,
+    done: bool,
+}
+
+impl<Port: private::UARTPortCompatable> DmaWrite<'_, Port> {
+    /// # Poll
+    /// Check if every byte has been queued in the transmit FIFO. The last few may
+    /// still be going out on the line, which `UART::flush` waits for.
+    ///
+    /// Returns `ErrorKind::ComError` if the channel stopped on a bus error.
+    pub fn poll(&mut self) -> crate::nb::Result<(), ErrorKind> {
+        if self.done {
+            return Ok(());
+        }
+
+        let result = self.channel.poll();
+        if !matches!(result, Err(crate::nb::Error::WouldBlock)) {
+            self.finish();
+        }
+
+        result
+    }
+
+    /// # Wait
+    /// Block until `poll` reports the transfer done.
+    pub fn wait(mut self) -> Result<()> {
+        crate::block!(self.poll())
+    }
+
+    /// # Remaining
+    /// The number of bytes the channel has not queued yet.
+    pub fn remaining(&self) -> usize {
+        if self.done {
+            0
+        } else {
+            self.channel.remaining()
+        }
+    }
+
+    fn finish(&mut self) {
+        self.done = true;
+        self.channel.stop();
+        unsafe { self.uart.reg.set_transmit_dma_channel_enable(false) };
+    }
+}
+
+impl<Port: private::UARTPortCompatable> Drop for DmaWrite<'_, Port> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dma::registers::rro::{DMA_CTRL, DMA_DST, DMA_SRC, DMA_STATUS};
+    use crate::tests::FakeRegisters;
+    use crate::uart::registers::rro::UART_DMA;
+    use crate::uart::UART0;
+    use core::marker::PhantomData;
+
+    const TRANSMIT_DMA_ENABLE: u32 = 1 << 4;
+    /// The count to zero flag in `DMA_STATUS`.
+    const COUNT_TO_ZERO: u32 = 1 << 2;
+
+    fn fake_uart(fake_registers: &FakeRegisters<16>) -> UART<UART0> {
+        UART {
+            reg: registers::Registers::new(fake_registers.address()),
+            _ph: PhantomData,
+            _gpio: crate::gpio::hardware::uart_n(0).unwrap(),
+        }
+    }
+
+    fn fake_channel(fake_registers: &FakeRegisters<8>) -> Channel {
+        Channel::from_registers(
+            crate::dma::registers::Registers::new(fake_registers.address()),
+            0,
+        )
+    }
+
+    #[test]
+    fn write_dma_test() {
+        let fake_uart_registers = FakeRegisters::new();
+        let mut uart = fake_uart(&fake_uart_registers);
+        let fake_channel_registers = FakeRegisters::new();
+        let mut channel = fake_channel(&fake_channel_registers);
+
+        static DATA: [u8; 100] = [0x55; 100];
+        let mut write = uart.write_dma(&DATA, &mut channel).unwrap();
+        assert_eq!(
+            fake_uart_registers.word(UART_DMA),
+            TRANSMIT_DMA_ENABLE | TRANSMIT_DMA_THRESHOLD as u32
+        );
+        assert_eq!(fake_channel_registers.word(DMA_SRC), DATA.as_ptr() as u32);
+        assert_eq!(
+            fake_channel_registers.word(DMA_DST),
+            crate::memory_map::mmio::UART_0 as u32 + 0x20
+        );
+        assert_eq!(write.remaining(), 100);
+        assert!(matches!(write.poll(), Err(crate::nb::Error::WouldBlock)));
+
+        fake_channel_registers.set_word(DMA_STATUS, COUNT_TO_ZERO);
+        assert_eq!(write.wait(), Ok(()));
+        assert_eq!(fake_uart_registers.word(UART_DMA) & TRANSMIT_DMA_ENABLE, 0);
+    }
+
+    #[test]
+    fn drop_stops_dma_test() {
+        let fake_uart_registers = FakeRegisters::new();
+        let mut uart = fake_uart(&fake_uart_registers);
+        let fake_channel_registers = FakeRegisters::new();
+        let mut channel = fake_channel(&fake_channel_registers);
+
+        drop(uart.write_dma(&[1, 2, 3], &mut channel).unwrap());
+        assert_eq!(fake_channel_registers.word(DMA_CTRL) & 1, 0);
+        assert_eq!(fake_uart_registers.word(UART_DMA) & TRANSMIT_DMA_ENABLE, 0);
+
+        // Nothing to send is done straight away, without touching the channel.
+        let mut write = uart.write_dma(&[], &mut channel).unwrap();
+        assert!(write.poll().is_ok());
+        assert_eq!(write.remaining(), 0);
+    }
+}
@@ -0,0 +1,202 @@
+use super::buffered::RingBuffer;
+use super::{private, UART};
+use crate::error::{ErrorKind, Result};
+
+/// The byte sent to ask the peer to resume transmitting.
+pub const XON: u8 = 0x11;
+/// The byte sent to ask the peer to stop transmitting.
+pub const XOFF: u8 = 0x13;
+
+/// The size of the software receive buffer.
+pub const RX_BUFFER_LEN: usize = 64;
+/// XOFF is sent once the receive buffer holds more than this many bytes.
+pub const HIGH_WATER_MARK: usize = RX_BUFFER_LEN * 3 / 4;
+/// XON is sent once the receive buffer drains below this many bytes.
+pub const LOW_WATER_MARK: usize = RX_BUFFER_LEN / 4;
+
+/// # XON/XOFF UART
+/// A UART with XON/XOFF software flow control, made with
+/// `UART::with_software_flow_control`.
+///
+/// Received bytes are moved from the receive FIFO into a `RX_BUFFER_LEN` byte
+/// buffer by `poll`. When that buffer fills past `HIGH_WATER_MARK` an `XOFF` is
+/// sent to the peer, and once it drains below `LOW_WATER_MARK` an `XON` is sent.
+/// Receiving `XOFF` pauses transmitting until `XON` is received.
+///
+/// The `XON` and `XOFF` bytes are consumed from the received stream and never
+/// returned by `read_byte`, so this should not be used with binary protocols
+/// that can contain those bytes. If the peer ignores `XOFF` and the buffer fills
+/// completely, newly received bytes are dropped.
+pub struct XonXoffUart<Port> {
+    uart: UART<Port>,
+    state: FlowState,
+}
+
+impl<Port: private::UARTPortCompatable> UART<Port> {
+    /// # With Software Flow Control
+    /// Enable XON/XOFF software flow control on this UART. See `XonXoffUart`.
+    pub fn with_software_flow_control(self) -> XonXoffUart<Port> {
+        XonXoffUart {
+            uart: self,
+            state: FlowState::new(),
+        }
+    }
+}
+
+impl<Port: private::UARTPortCompatable> XonXoffUart<Port> {
+    /// # Poll
+    /// Move every byte waiting in the receive FIFO into the receive buffer, handling
+    /// any `XON`/`XOFF` bytes received and sending `XOFF` if the buffer got too full.
+    pub fn poll(&mut self) {
+        while let Ok(byte) = self.uart.read_receive_fifo() {
+            if let Some(control) = self.state.receive(byte) {
+                self.uart.write_blocking_transmit_fifo(control);
+            }
+        }
+    }
+
+    /// # Read Byte
+    /// Read the next received byte, returning `ErrorKind::NoneAvailable` if nothing
+    /// has been received.
+    pub fn read_byte(&mut self) -> Result<u8> {
+        self.poll();
+
+        let (byte, control) = self.state.take();
+        if let Some(control) = control {
+            self.uart.write_blocking_transmit_fifo(control);
+        }
+
+        byte.ok_or(ErrorKind::NoneAvailable)
+    }
+
+    /// # Write Byte
+    /// Write a byte to the transmit FIFO, returning `ErrorKind::Busy` if the peer
+    /// has paused transmitting with `XOFF` or the FIFO is full.
+    pub fn write_byte(&mut self, data: u8) -> Result<()> {
+        self.poll();
+
+        if self.state.tx_paused {
+            return Err(ErrorKind::Busy);
+        }
+
+        self.uart.write_transmit_fifo(data)
+    }
+
+    /// # Is Transmit Paused
+    /// Check if the peer has paused transmitting with `XOFF`.
+    pub fn is_transmit_paused(&self) -> bool {
+        self.state.tx_paused
+    }
+
+    /// # Into Inner
+    /// Disable software flow control and give back the UART. Any bytes left in the
+    /// receive buffer are lost.
+    pub fn into_inner(self) -> UART<Port> {
+        self.uart
+    }
+}
+
+/// The flow control state, kept apart from the UART registers.
+struct FlowState {
+    buffer: RingBuffer<RX_BUFFER_LEN>,
+    tx_paused: bool,
+    sent_xoff: bool,
+}
+
+impl FlowState {
+    const fn new() -> Self {
+        Self {
+            buffer: RingBuffer::new(),
+            tx_paused: false,
+            sent_xoff: false,
+        }
+    }
+
+    /// Take in a received byte, returning the control byte to send if any.
+    fn receive(&mut self, byte: u8) -> Option<u8> {
+        match byte {
+            XON => self.tx_paused = false,
+            XOFF => self.tx_paused = true,
+            // A full buffer drops the newest byte.
+            _ => {
+                let _ = self.buffer.push(byte);
+            }
+        }
+
+        if !self.sent_xoff && self.buffer.len() > HIGH_WATER_MARK {
+            self.sent_xoff = true;
+            Some(XOFF)
+        } else {
+            None
+        }
+    }
+
+    /// Take the oldest buffered byte, along with the control byte to send if any.
+    fn take(&mut self) -> (Option<u8>, Option<u8>) {
+        let byte = self.buffer.pop();
+
+        let control = if self.sent_xoff && self.buffer.len() < LOW_WATER_MARK {
+            self.sent_xoff = false;
+            Some(XON)
+        } else {
+            None
+        };
+
+        (byte, control)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn consumes_control_bytes_test() {
+        let mut state = FlowState::new();
+
+        assert_eq!(state.receive(b'a'), None);
+        assert_eq!(state.receive(XOFF), None);
+        assert!(state.tx_paused);
+        assert_eq!(state.receive(b'b'), None);
+        assert_eq!(state.receive(XON), None);
+        assert!(!state.tx_paused);
+
+        assert_eq!(state.take(), (Some(b'a'), None));
+        assert_eq!(state.take(), (Some(b'b'), None));
+        assert_eq!(state.take(), (None, None));
+    }
+
+    #[test]
+    fn water_marks_test() {
+        let mut state = FlowState::new();
+
+        for _ in 0..HIGH_WATER_MARK {
+            assert_eq!(state.receive(0), None);
+        }
+        assert_eq!(state.receive(0), Some(XOFF));
+        // XOFF is only sent once
+        assert_eq!(state.receive(0), None);
+
+        let buffered = HIGH_WATER_MARK + 2;
+        for _ in 0..(buffered - LOW_WATER_MARK) {
+            assert_eq!(state.take(), (Some(0), None));
+        }
+        assert_eq!(state.take(), (Some(0), Some(XON)));
+        assert_eq!(state.take(), (Some(0), None));
+    }
+
+    #[test]
+    fn overflow_drops_newest_test() {
+        let mut state = FlowState::new();
+
+        // Start past XON and XOFF so every byte is data.
+        for byte in b' '..(b' ' + RX_BUFFER_LEN as u8 + 4) {
+            state.receive(byte);
+        }
+
+        for byte in b' '..(b' ' + RX_BUFFER_LEN as u8) {
+            assert_eq!(state.take().0, Some(byte));
+        }
+        assert_eq!(state.take().0, None);
+    }
+}
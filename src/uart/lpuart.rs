@@ -0,0 +1,112 @@
+//! # Low Power UART
+//! Baud rate timing for the LPUART (`UART3`), which can run from the low
+//! frequency ERTCO and so needs the fractional divider and a choice of
+//! oversampling rate that the other UARTs do not.
+
+use super::MAX_BAUD_RATE_DIVISOR;
+use crate::error::{ErrorKind, Result};
+
+/// The oversampling rates for `lpuart_oversampling_rate` 0 to 7 with fractional
+/// division mode (FDM) enabled.
+const FDM_OVERSAMPLING: [u32; 8] = [8, 12, 16, 20, 24, 28, 32, 36];
+
+/// The oversampling rates for `lpuart_oversampling_rate` 0 to 5 with FDM
+/// disabled. 6 and 7 are reserved.
+const OVERSAMPLING: [u32; 6] = [128, 64, 32, 16, 8, 4];
+
+/// # LPUART Timing
+/// The baud rate divisor and oversampling settings for one LPUART baud rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct LpuartTiming {
+    /// The baud rate divisor, counted in half clock cycles when `fractional` is set.
+    pub divisor: u32,
+    /// Whether fractional division mode is enabled.
+    pub fractional: bool,
+    /// The `lpuart_oversampling_rate` field value.
+    pub oversampling: u8,
+}
+
+/// Pick the LPUART timing for `baud_rate` from a `clock_hz` baud clock.
+///
+/// FDM is used whenever a bit is at least 8 clock cycles long (the smallest FDM
+/// oversampling rate), since its divisor has half cycle resolution. Shorter bits
+/// fall back to integer division, which can oversample down to 4x. In both cases
+/// the highest oversampling rate that fits in one bit is chosen.
+///
+/// Returns `ErrorKind::BadParam` if a bit is shorter than 4 cycles, or the divisor
+/// does not fit in its 20 bit field.
+pub(super) fn lpuart_timing(clock_hz: u32, baud_rate: u32) -> Result<LpuartTiming> {
+    if baud_rate == 0 {
+        return Err(ErrorKind::BadParam);
+    }
+
+    let clock_hz = clock_hz as u64;
+    let baud_rate = baud_rate as u64;
+    let half_cycles = (2 * clock_hz + baud_rate / 2) / baud_rate;
+
+    let (divisor, fractional, oversampling) = if half_cycles >= 2 * FDM_OVERSAMPLING[0] as u64 {
+        let oversampling = FDM_OVERSAMPLING
+            .iter()
+            .rposition(|&rate| 2 * rate as u64 <= half_cycles);
+        (half_cycles, true, oversampling)
+    } else {
+        let cycles = (clock_hz + baud_rate / 2) / baud_rate;
+        let oversampling = OVERSAMPLING.iter().position(|&rate| rate as u64 <= cycles);
+        (cycles, false, oversampling)
+    };
+
+    match (u32::try_from(divisor), oversampling) {
+        (Ok(divisor @ 1..=MAX_BAUD_RATE_DIVISOR), Some(oversampling)) => Ok(LpuartTiming {
+            divisor,
+            fractional,
+            oversampling: oversampling as u8,
+        }),
+        _ => Err(ErrorKind::BadParam),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::uart::{ERTCO_FREQUENCY, IBRO_FREQUENCY};
+
+    #[test]
+    fn lpuart_timing_test() {
+        // 64 cycles per bit, so the highest FDM oversampling of 36x fits.
+        assert_eq!(
+            lpuart_timing(IBRO_FREQUENCY, 115_200),
+            Ok(LpuartTiming {
+                divisor: 128,
+                fractional: true,
+                oversampling: 7,
+            })
+        );
+
+        // 13.65 cycles per bit: FDM gets 13.5, with 12x oversampling.
+        assert_eq!(
+            lpuart_timing(ERTCO_FREQUENCY, 2_400),
+            Ok(LpuartTiming {
+                divisor: 27,
+                fractional: true,
+                oversampling: 1,
+            })
+        );
+
+        // 6.8 cycles per bit is too short for FDM, so 4x integer division is used.
+        assert_eq!(
+            lpuart_timing(ERTCO_FREQUENCY, 4_800),
+            Ok(LpuartTiming {
+                divisor: 7,
+                fractional: false,
+                oversampling: 5,
+            })
+        );
+
+        assert_eq!(
+            lpuart_timing(ERTCO_FREQUENCY, 9_600),
+            Err(ErrorKind::BadParam)
+        );
+        assert_eq!(lpuart_timing(IBRO_FREQUENCY, 14), Err(ErrorKind::BadParam));
+        assert_eq!(lpuart_timing(IBRO_FREQUENCY, 0), Err(ErrorKind::BadParam));
+    }
+}
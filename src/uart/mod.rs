@@ -4,12 +4,17 @@ use crate::gpio::GpioPin;
 use crate::memory_map::mmio;
 use core::marker::PhantomData;
 
+pub mod buffered;
+pub mod dma;
+pub mod flow_control;
+mod lpuart;
 pub mod registers;
 
 mod private {
-    pub trait UARTPortCompatable {
-        const PORT_PTR: usize;
-        const PORT_NUM: usize;
+    pub trait UARTPortCompatable: crate::port::Port {
+        const HARDWARE_SOURCE: crate::gcr::HardwareSource;
+        /// If this is the LPUART, with its own clock sources and baud timing.
+        const LOW_POWER: bool = false;
     }
 }
 
@@ -17,18 +22,41 @@ pub struct NoPort {}
 pub struct UART0 {}
 pub struct UART1 {}
 pub struct UART2 {}
+/// The low power UART (LPUART).
+pub struct UART3 {}
 
+impl crate::port::private::Sealed for UART0 {}
+impl crate::port::Port for UART0 {
+    const BASE: usize = mmio::UART_0;
+    const NUM: usize = 0;
+}
 impl private::UARTPortCompatable for UART0 {
-    const PORT_PTR: usize = mmio::UART_0;
-    const PORT_NUM: usize = 0;
+    const HARDWARE_SOURCE: crate::gcr::HardwareSource = crate::gcr::HardwareSource::UART0;
+}
+impl crate::port::private::Sealed for UART1 {}
+impl crate::port::Port for UART1 {
+    const BASE: usize = mmio::UART_1;
+    const NUM: usize = 1;
 }
 impl private::UARTPortCompatable for UART1 {
-    const PORT_PTR: usize = mmio::UART_1;
-    const PORT_NUM: usize = 1;
+    const HARDWARE_SOURCE: crate::gcr::HardwareSource = crate::gcr::HardwareSource::UART1;
+}
+impl crate::port::private::Sealed for UART2 {}
+impl crate::port::Port for UART2 {
+    const BASE: usize = mmio::UART_2;
+    const NUM: usize = 2;
 }
 impl private::UARTPortCompatable for UART2 {
-    const PORT_PTR: usize = mmio::UART_2;
-    const PORT_NUM: usize = 2;
+    const HARDWARE_SOURCE: crate::gcr::HardwareSource = crate::gcr::HardwareSource::UART2;
+}
+impl crate::port::private::Sealed for UART3 {}
+impl crate::port::Port for UART3 {
+    const BASE: usize = mmio::LOW_POWER_UART_0;
+    const NUM: usize = 3;
+}
+impl private::UARTPortCompatable for UART3 {
+    const HARDWARE_SOURCE: crate::gcr::HardwareSource = crate::gcr::HardwareSource::UART3;
+    const LOW_POWER: bool = true;
 }
 
 pub struct UART<Port = NoPort> {
@@ -37,6 +65,10 @@ pub struct UART<Port = NoPort> {
     _gpio: [GpioPin; 2],
 }
 
+// A UART owns its port's registers and pins, so moving it to another context
+// (like handing it to `attach_debug`) takes every access to them along.
+unsafe impl<Port> Send for UART<Port> {}
+
 #[allow(unused)]
 impl UART<NoPort> {
     /// # Port 0 Init
@@ -71,10 +103,9 @@ impl UART<NoPort> {
         parity_value: ParityValueSelect,
         hfc: bool,
     ) -> Result<UART<UART0>> {
-        peripheral_reset(crate::gcr::HardwareSource::UART0);
-        system_clock_enable(crate::gcr::HardwareSource::UART0, true);
-        UART::<UART0>::init(
-            baud_rate,
+        UART::<UART0>::init_with_clock(
+            baud_rate as u32,
+            ClockSources::IBRO,
             character_length,
             stop_bits,
             transmit_parity,
@@ -115,10 +146,9 @@ impl UART<NoPort> {
         parity_value: ParityValueSelect,
         hfc: bool,
     ) -> Result<UART<UART1>> {
-        peripheral_reset(crate::gcr::HardwareSource::UART1);
-        system_clock_enable(crate::gcr::HardwareSource::UART1, true);
-        UART::<UART1>::init(
-            baud_rate,
+        UART::<UART1>::init_with_clock(
+            baud_rate as u32,
+            ClockSources::IBRO,
             character_length,
             stop_bits,
             transmit_parity,
@@ -160,10 +190,9 @@ impl UART<NoPort> {
         parity_value: ParityValueSelect,
         hfc: bool,
     ) -> Result<UART<UART2>> {
-        peripheral_reset(crate::gcr::HardwareSource::UART2);
-        system_clock_enable(crate::gcr::HardwareSource::UART2, true);
-        UART::<UART2>::init(
-            baud_rate,
+        UART::<UART2>::init_with_clock(
+            baud_rate as u32,
+            ClockSources::IBRO,
             character_length,
             stop_bits,
             transmit_parity,
@@ -172,8 +201,61 @@ impl UART<NoPort> {
             hfc,
         )
     }
+
+    /// # Port 3 Init
+    /// Initializes the LPUART (UART 3) in the low power domain, on `P2.6` (Rx) and
+    /// `P2.7` (Tx). It has no hardware flow control.
+    ///
+    /// The LPUART can be clocked from the IBRO or the 32.768kHz ERTCO, but not from
+    /// PCLK. The ERTCO must already be running. The divisor and oversampling rate
+    /// are picked for `baud_rate`: fractional division mode (FDM) is used when a
+    /// bit is at least 8 clock cycles long with the highest FDM oversampling that
+    /// fits, otherwise integer division with as little as 4x oversampling.
+    ///
+    /// # Reachable Baud Rates
+    /// - IBRO: about 15 baud up to 1.8432M baud (4 cycles per bit).
+    /// - ERTCO: up to 8192 baud. Of the `BaudRates`, 1200, 2400 and 4800 are
+    ///   reachable, 9600 and above return `ErrorKind::BadParam`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use max78000_hal::uart::{
+    ///     CharacterLength, ClockSources, Parity, ParityValueSelect, StopBits, UART,
+    /// };
+    ///
+    /// let lpuart = UART::port_3_init(
+    ///     2_400,
+    ///     ClockSources::ERTCO,
+    ///     CharacterLength::EightBits,
+    ///     StopBits::OneBit,
+    ///     false,
+    ///     Parity::Odd,
+    ///     ParityValueSelect::OneBased,
+    /// );
+    /// ```
+    pub fn port_3_init(
+        baud_rate: u32,
+        clock_source: ClockSources,
+        character_length: CharacterLength,
+        stop_bits: StopBits,
+        transmit_parity: bool,
+        parity: Parity,
+        parity_value: ParityValueSelect,
+    ) -> Result<UART<UART3>> {
+        UART::<UART3>::init_with_clock(
+            baud_rate,
+            clock_source,
+            character_length,
+            stop_bits,
+            transmit_parity,
+            parity,
+            parity_value,
+            false,
+        )
+    }
 }
 #[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BaudRates {
     Baud1200 = 1200,
     Baud2400 = 2400,
@@ -185,9 +267,28 @@ pub enum BaudRates {
     Baud115200 = 115200,
 }
 
+impl TryFrom<u32> for BaudRates {
+    type Error = ErrorKind;
+
+    fn try_from(value: u32) -> Result<Self> {
+        match value {
+            1200 => Ok(Self::Baud1200),
+            2400 => Ok(Self::Baud2400),
+            4800 => Ok(Self::Baud4800),
+            9600 => Ok(Self::Baud9600),
+            19200 => Ok(Self::Baud19200),
+            38400 => Ok(Self::Baud38400),
+            57600 => Ok(Self::Baud57600),
+            115200 => Ok(Self::Baud115200),
+            _ => Err(ErrorKind::BadParam),
+        }
+    }
+}
+
 /// # Character Length
 /// The number of data bits in a UART frame.
 #[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CharacterLength {
     FiveBits = 0,
     SixBits = 1,
@@ -195,26 +296,152 @@ pub enum CharacterLength {
     EightBits = 3,
 }
 
+impl TryFrom<u8> for CharacterLength {
+    type Error = ErrorKind;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Self::FiveBits),
+            1 => Ok(Self::SixBits),
+            2 => Ok(Self::SevenBits),
+            3 => Ok(Self::EightBits),
+            _ => Err(ErrorKind::BadParam),
+        }
+    }
+}
+
 /// # Clock Sources
-/// The clock source to use for UART
+/// The clock source to use for UART. The values are the `baud_clock_source`
+/// field values of UART 0 to 2, the LPUART numbers its sources differently.
 #[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ClockSources {
+    /// Not available on the LPUART.
     PCLK = 0,
     IBRO = 2,
+    /// The 32.768kHz ERTCO, only available on the LPUART.
+    ERTCO = 3,
+}
+
+impl ClockSources {
+    /// # Frequency
+    /// The frequency of this clock source in Hz.
+    pub fn frequency(self) -> u32 {
+        match self {
+            ClockSources::PCLK => crate::core_peripheral_clock(),
+            ClockSources::IBRO => IBRO_FREQUENCY,
+            ClockSources::ERTCO => ERTCO_FREQUENCY,
+        }
+    }
+}
+
+impl TryFrom<u8> for ClockSources {
+    type Error = ErrorKind;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Self::PCLK),
+            2 => Ok(Self::IBRO),
+            _ => Err(ErrorKind::BadParam),
+        }
+    }
+}
+
+/// How many times `init` checks for the baud clock to become ready before giving
+/// up with `ErrorKind::TimeOut`.
+const BAUD_CLOCK_READY_SPINS: u32 = 100_000;
+
+/// The frequency of the IBRO clock in Hz.
+pub const IBRO_FREQUENCY: u32 = 7_372_800;
+
+/// The frequency of the ERTCO (32.768kHz crystal oscillator) in Hz.
+pub const ERTCO_FREQUENCY: u32 = 32_768;
+
+/// The largest value the 20 bit baud rate divisor field can hold.
+const MAX_BAUD_RATE_DIVISOR: u32 = (1 << 20) - 1;
+
+/// The number of bytes each of the receive and transmit FIFOs hold.
+pub const FIFO_DEPTH: u8 = 8;
+
+/// The baud rate divisor for `baud_rate` from a `clock_hz` baud clock, rounded to
+/// the nearest divisor. Returns `ErrorKind::BadParam` if the divisor does not fit
+/// in the 20 bit field, or would be 0.
+fn baud_rate_divisor(clock_hz: u32, baud_rate: u32) -> Result<u32> {
+    if baud_rate == 0 {
+        return Err(ErrorKind::BadParam);
+    }
+
+    let divisor = (clock_hz as u64 + baud_rate as u64 / 2) / baud_rate as u64;
+    match u32::try_from(divisor) {
+        Ok(divisor @ 1..=MAX_BAUD_RATE_DIVISOR) => Ok(divisor),
+        _ => Err(ErrorKind::BadParam),
+    }
+}
+
+/// How the baud clock of a UART is set up for one baud rate.
+struct BaudTiming {
+    clock_select: u8,
+    divisor: u32,
+    fractional: bool,
+    oversampling: u8,
+}
+
+/// Pick the baud clock settings for `baud_rate` from `clock_source`, on the LPUART
+/// if `low_power` is set. Returns `ErrorKind::BadParam` if the clock source is not
+/// available on that UART, or the baud rate cannot be reached from it.
+fn baud_timing(low_power: bool, clock_source: ClockSources, baud_rate: u32) -> Result<BaudTiming> {
+    if low_power {
+        let clock_select = match clock_source {
+            ClockSources::IBRO => 0,
+            ClockSources::ERTCO => 1,
+            ClockSources::PCLK => return Err(ErrorKind::BadParam),
+        };
+        let timing = lpuart::lpuart_timing(clock_source.frequency(), baud_rate)?;
+
+        Ok(BaudTiming {
+            clock_select,
+            divisor: timing.divisor,
+            fractional: timing.fractional,
+            oversampling: timing.oversampling,
+        })
+    } else {
+        if clock_source == ClockSources::ERTCO {
+            return Err(ErrorKind::BadParam);
+        }
+
+        Ok(BaudTiming {
+            clock_select: clock_source as u8,
+            divisor: baud_rate_divisor(clock_source.frequency(), baud_rate)?,
+            fractional: false,
+            // The OSR of 28 that UART 0 to 2 have always been set to
+            oversampling: 5,
+        })
+    }
 }
 
 /// # Stop Bits
 /// The number of stop bits to use.
 /// Note: When using a character length of five bits, passing the variant
 /// `TwoBits` uses 1.5 bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum StopBits {
     OneBit,
     TwoBits,
 }
 
-impl Into<bool> for StopBits {
-    fn into(self) -> bool {
-        match self {
+impl From<bool> for StopBits {
+    fn from(value: bool) -> Self {
+        if value {
+            StopBits::TwoBits
+        } else {
+            StopBits::OneBit
+        }
+    }
+}
+
+impl From<StopBits> for bool {
+    fn from(value: StopBits) -> Self {
+        match value {
             StopBits::OneBit => false,
             StopBits::TwoBits => true,
         }
@@ -223,19 +450,40 @@ impl Into<bool> for StopBits {
 
 /// # Hardware Flow Control Deassert Condition
 /// When to deassert the hardware flow control
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum HFCDeassertCondition {
     EqualsFIFODepth,
     ExceedsRxThreshold,
 }
 
+impl From<HFCDeassertCondition> for bool {
+    fn from(value: HFCDeassertCondition) -> Self {
+        match value {
+            HFCDeassertCondition::EqualsFIFODepth => false,
+            HFCDeassertCondition::ExceedsRxThreshold => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ParityValueSelect {
     OneBased,
     ZeroBased,
 }
 
-impl Into<bool> for ParityValueSelect {
-    fn into(self) -> bool {
-        match self {
+impl From<bool> for ParityValueSelect {
+    fn from(value: bool) -> Self {
+        if value {
+            ParityValueSelect::ZeroBased
+        } else {
+            ParityValueSelect::OneBased
+        }
+    }
+}
+
+impl From<ParityValueSelect> for bool {
+    fn from(value: ParityValueSelect) -> Self {
+        match value {
             ParityValueSelect::OneBased => false,
             ParityValueSelect::ZeroBased => true,
         }
@@ -244,23 +492,110 @@ impl Into<bool> for ParityValueSelect {
 
 /// # Parity Odd / Even
 /// Which type of parity to use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Parity {
     Odd,
     Even,
 }
 
-impl Into<bool> for Parity {
-    fn into(self) -> bool {
-        match self {
+impl From<bool> for Parity {
+    fn from(value: bool) -> Self {
+        if value {
+            Parity::Even
+        } else {
+            Parity::Odd
+        }
+    }
+}
+
+impl From<Parity> for bool {
+    fn from(value: Parity) -> Self {
+        match value {
             Parity::Odd => false,
             Parity::Even => true,
         }
     }
 }
 
+/// # UART Config
+/// How a UART is configured, read back from its registers with
+/// `UART::current_config`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UartConfig {
+    /// The standard baud rate matching `effective_baud_rate`, if there is one.
+    pub baud_rate: Option<BaudRates>,
+    /// The baud rate the divisor produces from the baud clock, in bits per second.
+    pub effective_baud_rate: u32,
+    /// The raw baud rate divisor.
+    pub baud_rate_divisor: u32,
+    pub clock_source: ClockSources,
+    pub character_length: CharacterLength,
+    pub stop_bits: StopBits,
+    pub transmit_parity: bool,
+    pub parity: Parity,
+    pub parity_value: ParityValueSelect,
+    pub hfc: bool,
+}
+
 impl<Port: private::UARTPortCompatable> UART<Port> {
+    /// # Init With Clock
+    /// Reset and initialize this UART port, like the `port_n_init` functions, but
+    /// at any `baud_rate` in bits per second, clocked from `clock_source`. This
+    /// allows rates that are not in `BaudRates`, like 31250 baud for MIDI.
+    ///
+    /// The baud rate divisor is the clock source frequency divided by `baud_rate`,
+    /// rounded to the nearest whole divisor. Returns `ErrorKind::BadParam` if that
+    /// divisor is 0 or does not fit in its 20 bit field.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use max78000_hal::uart::{
+    ///     CharacterLength, ClockSources, Parity, ParityValueSelect, StopBits, UART, UART1,
+    /// };
+    ///
+    /// let midi = UART::<UART1>::init_with_clock(
+    ///     31_250,
+    ///     ClockSources::IBRO,
+    ///     CharacterLength::EightBits,
+    ///     StopBits::OneBit,
+    ///     false,
+    ///     Parity::Odd,
+    ///     ParityValueSelect::OneBased,
+    ///     false,
+    /// );
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn init_with_clock(
+        baud_rate: u32,
+        clock_source: ClockSources,
+        character_length: CharacterLength,
+        stop_bits: StopBits,
+        transmit_parity: bool,
+        parity: Parity,
+        parity_value: ParityValueSelect,
+        hfc: bool,
+    ) -> Result<Self> {
+        // Check the divisor before touching the hardware.
+        baud_timing(Port::LOW_POWER, clock_source, baud_rate)?;
+
+        peripheral_reset(Port::HARDWARE_SOURCE)?;
+        system_clock_enable(Port::HARDWARE_SOURCE, true);
+        Self::init(
+            baud_rate,
+            clock_source,
+            character_length,
+            stop_bits,
+            transmit_parity,
+            parity,
+            parity_value,
+            hfc,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn init(
-        baud_rate: BaudRates,
+        baud_rate: u32,
+        clock_source: ClockSources,
         character_length: CharacterLength,
         stop_bits: StopBits,
         transmit_parity: bool,
@@ -269,48 +604,221 @@ impl<Port: private::UARTPortCompatable> UART<Port> {
         hfc: bool,
     ) -> Result<Self> {
         let mut uart = Self {
-            reg: registers::Registers::new(Port::PORT_PTR),
-            _gpio: crate::gpio::hardware::uart_n(Port::PORT_NUM).ok_or(ErrorKind::Busy)?,
+            reg: registers::Registers::new(Port::BASE),
+            _gpio: crate::gpio::hardware::uart_n(Port::NUM).ok_or(ErrorKind::Busy)?,
+            _ph: PhantomData,
+        };
+
+        uart.configure(
+            baud_rate,
+            clock_source,
+            character_length,
+            stop_bits,
+            transmit_parity,
+            parity,
+            parity_value,
+            hfc,
+        )?;
+
+        Ok(uart)
+    }
+
+    /// # From Pins
+    /// Initialize this UART port on `pins` that are already owned, for example pins
+    /// given back by `release_pins`, or taken with `GpioPin::new` after being used
+    /// as plain GPIO. The pins must be the `[Rx, Tx]` pins of this port, otherwise
+    /// this returns `ErrorKind::BadParam`. On any error the pins are given back with
+    /// it, untouched if they did not match.
+    ///
+    /// The pins are switched to the UART alternate function (`AF1`) with no pull
+    /// resistors, replacing whatever function they were configured for, and the
+    /// UART is reset and configured just like the `port_n_init` functions.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_pins(
+        pins: [GpioPin; 2],
+        baud_rate: BaudRates,
+        character_length: CharacterLength,
+        stop_bits: StopBits,
+        transmit_parity: bool,
+        parity: Parity,
+        parity_value: ParityValueSelect,
+        hfc: bool,
+    ) -> core::result::Result<Self, ([GpioPin; 2], ErrorKind)> {
+        if crate::gpio::hardware::configure_uart_n(Port::NUM, &pins).is_none() {
+            return Err((pins, ErrorKind::BadParam));
+        }
+
+        if let Err(err) = peripheral_reset(Port::HARDWARE_SOURCE) {
+            return Err((pins, err));
+        }
+        system_clock_enable(Port::HARDWARE_SOURCE, true);
+
+        let mut uart = Self {
+            reg: registers::Registers::new(Port::BASE),
+            _gpio: pins,
             _ph: PhantomData,
         };
 
+        let configured = uart.configure(
+            baud_rate as u32,
+            ClockSources::IBRO,
+            character_length,
+            stop_bits,
+            transmit_parity,
+            parity,
+            parity_value,
+            hfc,
+        );
+
+        match configured {
+            Ok(()) => Ok(uart),
+            Err(err) => Err((uart.release_pins(), err)),
+        }
+    }
+
+    /// # Release Pins
+    /// Stop using this UART and give back its `[Rx, Tx]` pins, still configured for
+    /// the UART alternate function, so they can be handed to another peripheral.
+    pub fn release_pins(self) -> [GpioPin; 2] {
+        self._gpio
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn configure(
+        &mut self,
+        baud_rate: u32,
+        clock_source: ClockSources,
+        character_length: CharacterLength,
+        stop_bits: StopBits,
+        transmit_parity: bool,
+        parity: Parity,
+        parity_value: ParityValueSelect,
+        hfc: bool,
+    ) -> Result<()> {
+        let timing = baud_timing(Port::LOW_POWER, clock_source, baud_rate)?;
+
         // Clear the FIFOs
-        uart.clear_rx_fifo();
-        uart.clear_tx_fifo();
+        self.clear_rx_fifo();
+        self.clear_tx_fifo();
 
         unsafe {
             // Disable the baud clock
-            uart.reg.set_baud_clock_enable(false);
+            self.reg.set_baud_clock_enable(false);
             // Set the number of character bits to 8
-            uart.reg.set_character_length(character_length as u8);
+            self.reg.set_character_length(character_length);
             // Set the number of stop bits to 1
-            uart.reg.set_number_of_stop_bits(stop_bits.into());
-            uart.reg
+            self.reg.set_number_of_stop_bits(stop_bits.into());
+            self.reg
                 .set_transmit_parity_generation_enable(transmit_parity);
             // Set the parity value
-            uart.reg.set_parity_value(parity_value.into());
+            self.reg.set_parity_value(parity_value.into());
             // Set the parity
-            uart.reg.set_parity_odd_even(parity.into());
-            // Set the clock source to IBRO
-            uart.reg.set_baud_clock_source(ClockSources::IBRO as u8);
-            // Set the clock divisor to 7.3728 Mhz / baud rate
-            let divisor = 7372800u32 / baud_rate as u32;
-            uart.reg.set_baud_rate_divisor(divisor);
+            self.reg.set_parity_odd_even(parity.into());
+            // Set the clock source and the divisor for the baud rate
+            self.reg.set_baud_clock_source(timing.clock_select);
+            self.reg.set_baud_rate_divisor(timing.divisor);
+            self.reg.set_fractional_divison_mode(timing.fractional);
             // Set the Hardware Flow Control
-            uart.reg.set_hardware_flow_control(hfc);
+            self.reg.set_hardware_flow_control(hfc);
             // Disable UART auto gating
-            uart.reg.set_clock_auto_gating(false);
+            self.reg.set_clock_auto_gating(false);
             // Set RX threshold to 1 byte
-            uart.reg.set_recieve_fifo_threshold(1);
-            // Set the OSR to 28
-            uart.reg.set_lpuart_oversampling_rate(5);
+            self.reg.set_recieve_fifo_threshold(1);
+            // Set the OSR
+            self.reg.set_lpuart_oversampling_rate(timing.oversampling);
             // Enable the baud clock
-            uart.reg.set_baud_clock_enable(true);
-            // Wait until the baud clock is ready
-            while !uart.reg.get_baud_clock_ready() {}
+            self.reg.set_baud_clock_enable(true);
         }
 
-        Ok(uart)
+        // Wait until the baud clock is ready
+        self.reg.wait_baud_clock_ready(true, BAUD_CLOCK_READY_SPINS)
+    }
+
+    /// # Current Config
+    /// Read back how this UART is configured from its control and clock divisor
+    /// registers. Returns `ErrorKind::BadState` if the registers hold a clock
+    /// source this driver does not use, or a zero divisor.
+    pub fn current_config(&self) -> Result<UartConfig> {
+        let clock_source = match (Port::LOW_POWER, self.reg.get_baud_clock_source()) {
+            (true, 0) => ClockSources::IBRO,
+            (true, 1) => ClockSources::ERTCO,
+            (true, _) => return Err(ErrorKind::BadState),
+            (false, select) => ClockSources::try_from(select).map_err(|_| ErrorKind::BadState)?,
+        };
+        let baud_rate_divisor = self.reg.get_baud_rate_divisor();
+        if baud_rate_divisor == 0 {
+            return Err(ErrorKind::BadState);
+        }
+
+        // With fractional division the LPUART divisor counts half clock cycles.
+        let clock_hz = if Port::LOW_POWER && self.reg.get_fractional_divison_mode() {
+            2 * clock_source.frequency()
+        } else {
+            clock_source.frequency()
+        };
+        let effective_baud_rate = clock_hz / baud_rate_divisor;
+
+        Ok(UartConfig {
+            baud_rate: BaudRates::try_from(effective_baud_rate).ok(),
+            effective_baud_rate,
+            baud_rate_divisor,
+            clock_source,
+            character_length: self
+                .reg
+                .get_character_length()
+                .map_err(|_| ErrorKind::BadState)?,
+            stop_bits: self.reg.get_number_of_stop_bits().into(),
+            transmit_parity: self.reg.get_transmit_parity_generation_enable(),
+            parity: self.reg.get_parity_odd_even().into(),
+            parity_value: self.reg.get_parity_value().into(),
+            hfc: self.reg.get_hardware_flow_control(),
+        })
+    }
+
+    /// # Set RX FIFO Threshold
+    /// Set how many bytes, 1 to 8, the receive FIFO must hold to raise the receive
+    /// FIFO threshold event. `init` sets this to 1. With hardware flow control and
+    /// `HFCDeassertCondition::ExceedsRxThreshold`, this is also the level at which
+    /// RTS is deasserted.
+    ///
+    /// Returns `ErrorKind::BadParam` if `bytes` is outside `1..=8`.
+    pub fn set_rx_fifo_threshold(&mut self, bytes: u8) -> Result<()> {
+        if !(1..=FIFO_DEPTH).contains(&bytes) {
+            return Err(ErrorKind::BadParam);
+        }
+
+        unsafe { self.reg.set_recieve_fifo_threshold(bytes) };
+        Ok(())
+    }
+
+    /// # RX FIFO Threshold
+    /// The receive FIFO threshold, see `set_rx_fifo_threshold`.
+    pub fn rx_fifo_threshold(&self) -> u8 {
+        self.reg.get_recieve_fifo_threshold()
+    }
+
+    /// # Set TX Half Empty Event
+    /// Enable or disable the transmit FIFO half empty interrupt. The transmit FIFO
+    /// has no threshold of its own, this event at 4 of 8 bytes is the only level
+    /// it reports, so it is what to refill the FIFO on.
+    pub fn set_tx_half_empty_event(&mut self, enable: bool) {
+        unsafe { self.reg.set_transmit_fifo_half_empty_event(enable) };
+    }
+
+    /// # Set Hardware Flow Control
+    /// Enable or disable RTS/CTS hardware flow control, as set by `init`.
+    pub fn set_hardware_flow_control(&mut self, enable: bool) {
+        unsafe { self.reg.set_hardware_flow_control(enable) };
+    }
+
+    /// # Set Hardware Flow RTS Deassert Condition
+    /// Choose the receive FIFO level at which RTS is deasserted while hardware flow
+    /// control is enabled.
+    pub fn set_hardware_flow_rts_deassert_condition(&mut self, condition: HFCDeassertCondition) {
+        unsafe {
+            self.reg
+                .set_hardware_flow_rts_deassert_condition(condition.into())
+        };
     }
 
     /// # Print String
@@ -322,6 +830,39 @@ impl<Port: private::UARTPortCompatable> UART<Port> {
         }
     }
 
+    /// # Write All
+    /// Write every byte of `data`, blocking until the last one is in the transmit
+    /// FIFO. Bytes are pushed for as long as the FIFO is not full, so the 8 byte
+    /// FIFO is kept topped up instead of waiting for the line to go idle after
+    /// every byte like `write_blocking_transmit_fifo` does.
+    ///
+    /// At 115200 baud a byte takes about 87us on the line, so this only spins
+    /// while the FIFO is full rather than for every byte sent.
+    ///
+    /// Returns `ErrorKind::TimeOut` if the FIFO stays full for `WRITE_STALL_SPINS`
+    /// checks, like a line held back by flow control. The bytes before it have
+    /// been queued.
+    pub fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        for &byte in data {
+            crate::util::wait_until(WRITE_STALL_SPINS, || !self.reg.get_transmit_fifo_full())?;
+            unsafe { self.reg.set_fifo_data(byte) };
+        }
+
+        Ok(())
+    }
+
+    /// # TX FIFO Level
+    /// The number of bytes waiting in the transmit FIFO.
+    pub fn tx_fifo_level(&self) -> u8 {
+        self.reg.get_transmit_fifo_level()
+    }
+
+    /// # RX FIFO Level
+    /// The number of received bytes waiting in the receive FIFO.
+    pub fn rx_fifo_level(&self) -> u8 {
+        self.reg.get_receive_fifo_level()
+    }
+
     /// # Clear RX FIFO
     /// Clears all data from the receiving FIFO
     pub fn clear_rx_fifo(&mut self) {
@@ -374,6 +915,214 @@ impl<Port: private::UARTPortCompatable> UART<Port> {
             Ok(self.reg.get_fifo_data())
         }
     }
+
+    /// # Read Receive FIFO Checked
+    /// Reads from the receive FIFO if possible, like `read_receive_fifo`, but
+    /// returns `ErrorKind::ComError` if the byte was received with a parity or
+    /// frame error. The byte is taken out of the FIFO either way, and the parity
+    /// and frame error flags are cleared.
+    ///
+    /// The parity error bit is read together with the byte, since the FIFO keeps
+    /// it per byte. The frame error flag is only per UART, so it marks whichever
+    /// byte is read first after the error.
+    pub fn read_receive_fifo_checked(&mut self) -> Result<u8> {
+        if self.reg.get_receive_fifo_empty() {
+            return Err(ErrorKind::NoneAvailable);
+        }
+
+        let entry = self.reg.get_receive_fifo_entry();
+        let byte_parity_error = entry & (1 << 8) != 0;
+        let flagged_error = self.reg.is_receive_parity_error_interrupt_flag_active()
+            || self.reg.is_receive_frame_error_interrupt_flag_active();
+
+        unsafe {
+            self.reg.clear_receive_parity_error_interrupt_flag();
+            self.reg.clear_receive_frame_error_interrupt_flag();
+        }
+
+        if byte_parity_error || flagged_error {
+            Err(ErrorKind::ComError)
+        } else {
+            Ok(entry as u8)
+        }
+    }
+
+    /// # Bytes
+    /// Get an iterator over the bytes received by the UART, blocking on each byte
+    /// with `read_blocking_receive_fifo`.
+    ///
+    /// This iterator never ends, so use combinators like `take` or `take_while`
+    /// to stop reading.
+    pub fn bytes(&mut self) -> impl Iterator<Item = Result<u8>> + '_ {
+        core::iter::from_fn(move || Some(Ok(self.read_blocking_receive_fifo())))
+    }
+
+    /// # Try Bytes
+    /// Get an iterator over the bytes received by the UART without blocking. When
+    /// the receive FIFO is empty this yields `Err(ErrorKind::NoneAvailable)` instead
+    /// of waiting for the next byte.
+    ///
+    /// Like `bytes` this iterator never ends, so stopping on the first
+    /// `NoneAvailable` will drain only what is currently in the FIFO.
+    pub fn try_bytes(&mut self) -> impl Iterator<Item = Result<u8>> + '_ {
+        core::iter::from_fn(move || Some(self.read_receive_fifo()))
+    }
+
+    /// # Read Exact
+    /// Block until `buf` is filled with received bytes, read with
+    /// `read_receive_fifo_checked`.
+    ///
+    /// Returns `ErrorKind::ComError` as soon as a byte with a parity or frame
+    /// error is received, and `ErrorKind::TimeOut` if no byte arrives for
+    /// `READ_STALL_SPINS` checks of the receive FIFO, so a stalled line does not
+    /// hang forever. On an error `buf` holds the bytes received before it.
+    pub fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        read_exact_from(buf, READ_STALL_SPINS, || self.read_receive_fifo_checked())
+    }
+
+    /// # Read Available
+    /// Drain the bytes currently in the receive FIFO into `buf` without waiting,
+    /// returning how many were read. This stops once `buf` is full or the FIFO is
+    /// empty.
+    ///
+    /// Returns `ErrorKind::ComError` if a byte with a parity or frame error is
+    /// read. The bytes read before it are left at the start of `buf`.
+    pub fn read_available(&mut self, buf: &mut [u8]) -> Result<usize> {
+        read_available_from(buf, || self.read_receive_fifo_checked())
+    }
+
+    /// # Read
+    /// Read a byte without blocking, in the style of `embedded-hal-nb`'s serial
+    /// `Read`. Returns `WouldBlock` while the receive FIFO is empty.
+    ///
+    /// Bytes are read with `read_receive_fifo_checked`, so a byte whose parity
+    /// error bit is set, or one read after a parity or frame error was flagged,
+    /// is returned as `SerialError::Parity` or `SerialError::FrameFormat`. The
+    /// bad byte is taken out of the FIFO and dropped, so the next call does not
+    /// hand it out as good data. A receive overrun is returned as
+    /// `SerialError::Overrun` and its flag is cleared, but as it loses bytes the
+    /// FIFO had no room for, the bytes still in it are kept.
+    pub fn read(&mut self) -> crate::nb::Result<u8, SerialError> {
+        if self.reg.is_receive_fifo_overrun_interrupt_flag_active() {
+            unsafe { self.reg.clear_receive_fifo_overrun_interrupt_flag() };
+            return Err(crate::nb::Error::Other(SerialError::Overrun));
+        }
+
+        // The checked read clears the error flags, so note which one is set first.
+        let error = if !self.reg.is_receive_parity_error_interrupt_flag_active()
+            && self.reg.is_receive_frame_error_interrupt_flag_active()
+        {
+            SerialError::FrameFormat
+        } else {
+            SerialError::Parity
+        };
+
+        match self.read_receive_fifo_checked() {
+            Ok(byte) => Ok(byte),
+            Err(ErrorKind::ComError) => Err(crate::nb::Error::Other(error)),
+            // An error flagged without a byte left to drop is still reported.
+            Err(_) => match self.take_receive_error() {
+                Some(error) => Err(crate::nb::Error::Other(error)),
+                None => Err(crate::nb::Error::WouldBlock),
+            },
+        }
+    }
+
+    /// # Write
+    /// Queue a byte for transmitting without blocking, in the style of
+    /// `embedded-hal-nb`'s serial `Write`. Returns `WouldBlock` while the transmit
+    /// FIFO is full.
+    pub fn write(&mut self, data: u8) -> crate::nb::Result<(), SerialError> {
+        self.write_transmit_fifo(data)
+            .map_err(|_| crate::nb::Error::WouldBlock)
+    }
+
+    /// # Flush
+    /// Returns `WouldBlock` until the transmit FIFO is empty.
+    pub fn flush(&mut self) -> crate::nb::Result<(), SerialError> {
+        if self.reg.get_transmit_fifo_empty() {
+            Ok(())
+        } else {
+            Err(crate::nb::Error::WouldBlock)
+        }
+    }
+
+    /// Check the receive error flags, clearing and returning the first one set.
+    fn take_receive_error(&mut self) -> Option<SerialError> {
+        unsafe {
+            if self.reg.is_receive_fifo_overrun_interrupt_flag_active() {
+                self.reg.clear_receive_fifo_overrun_interrupt_flag();
+                Some(SerialError::Overrun)
+            } else if self.reg.is_receive_parity_error_interrupt_flag_active() {
+                self.reg.clear_receive_parity_error_interrupt_flag();
+                Some(SerialError::Parity)
+            } else if self.reg.is_receive_frame_error_interrupt_flag_active() {
+                self.reg.clear_receive_frame_error_interrupt_flag();
+                Some(SerialError::FrameFormat)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// How many times `read_exact` checks an empty receive FIFO for the next byte
+/// before giving up with `ErrorKind::TimeOut`.
+pub const READ_STALL_SPINS: u32 = 1_000_000;
+
+/// How many times `write_all` checks a full transmit FIFO for room for the next
+/// byte before giving up with `ErrorKind::TimeOut`.
+pub const WRITE_STALL_SPINS: u32 = 1_000_000;
+
+/// Fill `buf` from `read_byte`, retrying while it returns `NoneAvailable`, at most
+/// `max_spins` times in a row.
+fn read_exact_from(
+    buf: &mut [u8],
+    max_spins: u32,
+    mut read_byte: impl FnMut() -> Result<u8>,
+) -> Result<()> {
+    for slot in buf.iter_mut() {
+        let mut spins = 0;
+        *slot = loop {
+            match read_byte() {
+                Err(ErrorKind::NoneAvailable) if spins < max_spins => {
+                    spins += 1;
+                    core::hint::spin_loop();
+                }
+                Err(ErrorKind::NoneAvailable) => return Err(ErrorKind::TimeOut),
+                result => break result?,
+            }
+        };
+    }
+
+    Ok(())
+}
+
+/// Fill `buf` from `read_byte` until it is full or `read_byte` returns
+/// `NoneAvailable`, returning how many bytes were read.
+fn read_available_from(buf: &mut [u8], mut read_byte: impl FnMut() -> Result<u8>) -> Result<usize> {
+    for (count, slot) in buf.iter_mut().enumerate() {
+        match read_byte() {
+            Ok(byte) => *slot = byte,
+            Err(ErrorKind::NoneAvailable) => return Ok(count),
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(buf.len())
+}
+
+/// # Serial Error
+/// A receive error reported by `UART::read`. The names follow the
+/// `embedded-hal-nb` serial `ErrorKind`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SerialError {
+    /// The receive FIFO was full and received data was lost.
+    Overrun,
+    /// A byte was received with the wrong parity bit.
+    Parity,
+    /// A byte was received without a valid stop bit.
+    FrameFormat,
 }
 
 impl<Port: private::UARTPortCompatable> core::fmt::Write for UART<Port> {
@@ -382,3 +1131,563 @@ impl<Port: private::UARTPortCompatable> core::fmt::Write for UART<Port> {
         Ok(())
     }
 }
+
+/// # Any UART
+/// A UART whose port is only known at runtime, for keeping UARTs on different
+/// ports in one array or picking a port from user input. Every method forwards
+/// to the typed `UART` with a `match`, so prefer `UART<Port>` when the port is
+/// known at compile time.
+pub enum AnyUart {
+    Port0(UART<UART0>),
+    Port1(UART<UART1>),
+    Port2(UART<UART2>),
+    Port3(UART<UART3>),
+}
+
+macro_rules! with_any_uart {
+    ($any:expr, $uart:ident => $body:expr) => {
+        match $any {
+            AnyUart::Port0($uart) => $body,
+            AnyUart::Port1($uart) => $body,
+            AnyUart::Port2($uart) => $body,
+            AnyUart::Port3($uart) => $body,
+        }
+    };
+}
+
+impl AnyUart {
+    /// # Port
+    /// The number of the UART port this is.
+    pub fn port(&self) -> usize {
+        match self {
+            AnyUart::Port0(_) => <UART0 as crate::port::Port>::NUM,
+            AnyUart::Port1(_) => <UART1 as crate::port::Port>::NUM,
+            AnyUart::Port2(_) => <UART2 as crate::port::Port>::NUM,
+            AnyUart::Port3(_) => <UART3 as crate::port::Port>::NUM,
+        }
+    }
+
+    /// # Print String
+    /// See `UART::print_string`.
+    pub fn print_string(&mut self, string: &str) {
+        with_any_uart!(self, uart => uart.print_string(string))
+    }
+
+    /// # Write All
+    /// See `UART::write_all`.
+    pub fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        with_any_uart!(self, uart => uart.write_all(data))
+    }
+
+    /// # TX FIFO Level
+    /// See `UART::tx_fifo_level`.
+    pub fn tx_fifo_level(&self) -> u8 {
+        with_any_uart!(self, uart => uart.tx_fifo_level())
+    }
+
+    /// # RX FIFO Level
+    /// See `UART::rx_fifo_level`.
+    pub fn rx_fifo_level(&self) -> u8 {
+        with_any_uart!(self, uart => uart.rx_fifo_level())
+    }
+
+    /// # Clear RX FIFO
+    /// See `UART::clear_rx_fifo`.
+    pub fn clear_rx_fifo(&mut self) {
+        with_any_uart!(self, uart => uart.clear_rx_fifo())
+    }
+
+    /// # Clear TX FIFO
+    /// See `UART::clear_tx_fifo`.
+    pub fn clear_tx_fifo(&mut self) {
+        with_any_uart!(self, uart => uart.clear_tx_fifo())
+    }
+
+    /// # Write Blocking Transmit FIFO
+    /// See `UART::write_blocking_transmit_fifo`.
+    pub fn write_blocking_transmit_fifo(&mut self, data: u8) {
+        with_any_uart!(self, uart => uart.write_blocking_transmit_fifo(data))
+    }
+
+    /// # Read Blocking Receive FIFO
+    /// See `UART::read_blocking_receive_fifo`.
+    pub fn read_blocking_receive_fifo(&mut self) -> u8 {
+        with_any_uart!(self, uart => uart.read_blocking_receive_fifo())
+    }
+
+    /// # Write Transmit FIFO
+    /// See `UART::write_transmit_fifo`.
+    pub fn write_transmit_fifo(&mut self, data: u8) -> Result<()> {
+        with_any_uart!(self, uart => uart.write_transmit_fifo(data))
+    }
+
+    /// # Read Receive FIFO
+    /// See `UART::read_receive_fifo`.
+    pub fn read_receive_fifo(&mut self) -> Result<u8> {
+        with_any_uart!(self, uart => uart.read_receive_fifo())
+    }
+
+    /// # Read Receive FIFO Checked
+    /// See `UART::read_receive_fifo_checked`.
+    pub fn read_receive_fifo_checked(&mut self) -> Result<u8> {
+        with_any_uart!(self, uart => uart.read_receive_fifo_checked())
+    }
+
+    /// # Read Exact
+    /// See `UART::read_exact`.
+    pub fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        with_any_uart!(self, uart => uart.read_exact(buf))
+    }
+
+    /// # Read Available
+    /// See `UART::read_available`.
+    pub fn read_available(&mut self, buf: &mut [u8]) -> Result<usize> {
+        with_any_uart!(self, uart => uart.read_available(buf))
+    }
+
+    /// # Read
+    /// See `UART::read`.
+    pub fn read(&mut self) -> crate::nb::Result<u8, SerialError> {
+        with_any_uart!(self, uart => uart.read())
+    }
+
+    /// # Write
+    /// See `UART::write`.
+    pub fn write(&mut self, data: u8) -> crate::nb::Result<(), SerialError> {
+        with_any_uart!(self, uart => uart.write(data))
+    }
+
+    /// # Flush
+    /// See `UART::flush`.
+    pub fn flush(&mut self) -> crate::nb::Result<(), SerialError> {
+        with_any_uart!(self, uart => uart.flush())
+    }
+}
+
+impl From<UART<UART0>> for AnyUart {
+    fn from(uart: UART<UART0>) -> Self {
+        AnyUart::Port0(uart)
+    }
+}
+
+impl From<UART<UART1>> for AnyUart {
+    fn from(uart: UART<UART1>) -> Self {
+        AnyUart::Port1(uart)
+    }
+}
+
+impl From<UART<UART2>> for AnyUart {
+    fn from(uart: UART<UART2>) -> Self {
+        AnyUart::Port2(uart)
+    }
+}
+
+impl From<UART<UART3>> for AnyUart {
+    fn from(uart: UART<UART3>) -> Self {
+        AnyUart::Port3(uart)
+    }
+}
+
+impl core::fmt::Write for AnyUart {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.print_string(s);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The number of 32 bit registers in the UART register block.
+    const FAKE_REGISTER_WORDS: usize = 0x0038 / 4 + 1;
+    const BAUD_CLOCK_READY: u32 = 1 << 19;
+
+    #[test]
+    fn uart_can_be_a_debug_stream_test() {
+        fn debug_stream<T: core::fmt::Write + Send>() {}
+        debug_stream::<UART<UART0>>();
+        debug_stream::<AnyUart>();
+    }
+
+    #[test]
+    fn from_pins_returns_pins_test() {
+        use crate::gpio::GpioSelect;
+
+        let from_pins = |pins| {
+            UART::<UART0>::from_pins(
+                pins,
+                BaudRates::Baud115200,
+                CharacterLength::EightBits,
+                StopBits::OneBit,
+                false,
+                Parity::Even,
+                ParityValueSelect::ZeroBased,
+                false,
+            )
+        };
+
+        // The I2C0 pins are not UART0's, and come back untouched.
+        let i2c_pins = [
+            GpioPin::new(GpioSelect::Gpio0, 10).unwrap(),
+            GpioPin::new(GpioSelect::Gpio0, 11).unwrap(),
+        ];
+        let Err((pins, err)) = from_pins(i2c_pins) else {
+            panic!("the I2C pins are not UART pins");
+        };
+        assert_eq!(err, ErrorKind::BadParam);
+        assert_eq!(pins.each_ref().map(GpioPin::get_pin), [10, 11]);
+        drop(pins);
+
+        // The UART0 pins get past the pin check, up to the fake reset that never
+        // finishes, and still come back.
+        let uart_pins = [
+            GpioPin::new(GpioSelect::Gpio0, 0).unwrap(),
+            GpioPin::new(GpioSelect::Gpio0, 1).unwrap(),
+        ];
+        let Err((pins, err)) = crate::gcr::with_fake_gcr_restored(|| from_pins(uart_pins)) else {
+            panic!("the fake UART reset never finishes");
+        };
+        assert_eq!(err, ErrorKind::TimeOut);
+        assert_eq!(pins.each_ref().map(GpioPin::get_pin), [0, 1]);
+    }
+
+    #[test]
+    fn current_config_round_trip_test() {
+        let mut fake_registers = [0u32; FAKE_REGISTER_WORDS];
+        // The fake baud clock is always ready.
+        fake_registers[0] = BAUD_CLOCK_READY;
+        let mut uart: UART<UART0> = UART {
+            reg: registers::Registers::new(fake_registers.as_mut_ptr() as usize),
+            _ph: PhantomData,
+            _gpio: crate::gpio::hardware::uart_n(0).unwrap(),
+        };
+
+        uart.configure(
+            BaudRates::Baud57600 as u32,
+            ClockSources::IBRO,
+            CharacterLength::SevenBits,
+            StopBits::TwoBits,
+            true,
+            Parity::Even,
+            ParityValueSelect::ZeroBased,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(
+            uart.current_config(),
+            Ok(UartConfig {
+                baud_rate: Some(BaudRates::Baud57600),
+                effective_baud_rate: 57600,
+                baud_rate_divisor: IBRO_FREQUENCY / 57600,
+                clock_source: ClockSources::IBRO,
+                character_length: CharacterLength::SevenBits,
+                stop_bits: StopBits::TwoBits,
+                transmit_parity: true,
+                parity: Parity::Even,
+                parity_value: ParityValueSelect::ZeroBased,
+                hfc: true,
+            })
+        );
+    }
+
+    #[test]
+    fn nonblocking_read_write_test() {
+        const STATUS_WORD: usize = 1;
+        const FLAGS_WORD: usize = 3;
+        const FIFO_WORD: usize = 8;
+        const BYTE_PARITY_ERROR: u32 = 1 << 8;
+        const RX_EMPTY: u32 = 1 << 4;
+        const TX_EMPTY: u32 = 1 << 6;
+        const TX_FULL: u32 = 1 << 7;
+
+        let mut fake_registers = [0u32; FAKE_REGISTER_WORDS];
+        let registers = fake_registers.as_mut_ptr();
+        let mut uart: UART<UART0> = UART {
+            reg: registers::Registers::new(registers as usize),
+            _ph: PhantomData,
+            _gpio: crate::gpio::hardware::uart_n(0).unwrap(),
+        };
+        let set_word =
+            |word: usize, value: u32| unsafe { registers.add(word).write_volatile(value) };
+
+        set_word(STATUS_WORD, RX_EMPTY | TX_FULL);
+        assert!(matches!(uart.read(), Err(crate::nb::Error::WouldBlock)));
+        assert!(matches!(
+            uart.write(b'a'),
+            Err(crate::nb::Error::WouldBlock)
+        ));
+        assert!(matches!(uart.flush(), Err(crate::nb::Error::WouldBlock)));
+
+        set_word(STATUS_WORD, TX_EMPTY);
+        set_word(FIFO_WORD, u32::from(b'z'));
+        assert!(matches!(uart.read(), Ok(b'z')));
+        assert!(matches!(uart.flush(), Ok(())));
+        assert!(matches!(uart.write(b'a'), Ok(())));
+
+        // The fake flags are plain memory, so each error is checked on its own.
+        for (flag, error) in [
+            (1 << 3, SerialError::Overrun),
+            (1 << 1, SerialError::Parity),
+            (1 << 0, SerialError::FrameFormat),
+        ] {
+            set_word(FLAGS_WORD, flag);
+            assert!(matches!(uart.read(), Err(crate::nb::Error::Other(e)) if e == error));
+        }
+
+        // A byte received with its parity error bit set is not handed out.
+        set_word(FLAGS_WORD, 0);
+        set_word(FIFO_WORD, BYTE_PARITY_ERROR | u32::from(b'z'));
+        assert!(matches!(
+            uart.read(),
+            Err(crate::nb::Error::Other(SerialError::Parity))
+        ));
+
+        set_word(STATUS_WORD, RX_EMPTY);
+        set_word(FLAGS_WORD, 1 << 0);
+        assert!(matches!(
+            uart.read(),
+            Err(crate::nb::Error::Other(SerialError::FrameFormat))
+        ));
+    }
+
+    #[test]
+    fn read_receive_fifo_checked_test() {
+        const STATUS_WORD: usize = 1;
+        const FLAGS_WORD: usize = 3;
+        const FIFO_WORD: usize = 8;
+        const BYTE_PARITY_ERROR: u32 = 1 << 8;
+        const RX_EMPTY: u32 = 1 << 4;
+
+        let mut fake_registers = [0u32; FAKE_REGISTER_WORDS];
+        let registers = fake_registers.as_mut_ptr();
+        let mut uart: UART<UART0> = UART {
+            reg: registers::Registers::new(registers as usize),
+            _ph: PhantomData,
+            _gpio: crate::gpio::hardware::uart_n(0).unwrap(),
+        };
+        let set_word =
+            |word: usize, value: u32| unsafe { registers.add(word).write_volatile(value) };
+
+        set_word(FIFO_WORD, u32::from(b'k'));
+        assert_eq!(uart.read_receive_fifo_checked(), Ok(b'k'));
+
+        set_word(FIFO_WORD, BYTE_PARITY_ERROR | u32::from(b'k'));
+        assert_eq!(uart.read_receive_fifo_checked(), Err(ErrorKind::ComError));
+        // The unchecked read still hands back the corrupt byte.
+        assert_eq!(uart.read_receive_fifo(), Ok(b'k'));
+
+        set_word(FIFO_WORD, u32::from(b'k'));
+        for flag in [1 << 1, 1 << 0] {
+            set_word(FLAGS_WORD, flag);
+            assert_eq!(uart.read_receive_fifo_checked(), Err(ErrorKind::ComError));
+        }
+
+        set_word(STATUS_WORD, RX_EMPTY);
+        assert_eq!(
+            uart.read_receive_fifo_checked(),
+            Err(ErrorKind::NoneAvailable)
+        );
+    }
+
+    #[test]
+    fn baud_rate_divisor_test() {
+        assert_eq!(baud_rate_divisor(IBRO_FREQUENCY, 115_200), Ok(64));
+        assert_eq!(baud_rate_divisor(IBRO_FREQUENCY, 9_600), Ok(768));
+        // MIDI is not an exact divisor of the IBRO, so the nearest one is used.
+        assert_eq!(baud_rate_divisor(IBRO_FREQUENCY, 31_250), Ok(236));
+        assert_eq!(baud_rate_divisor(50_000_000, 115_200), Ok(434));
+        assert_eq!(baud_rate_divisor(50_000_000, 1_000_000), Ok(50));
+        assert_eq!(baud_rate_divisor(50_000_000, 48), Ok(1_041_667));
+
+        // Too slow for the 20 bit divisor, too fast for the clock, or no rate.
+        assert_eq!(baud_rate_divisor(50_000_000, 47), Err(ErrorKind::BadParam));
+        assert_eq!(
+            baud_rate_divisor(IBRO_FREQUENCY, 20_000_000),
+            Err(ErrorKind::BadParam)
+        );
+        assert_eq!(
+            baud_rate_divisor(IBRO_FREQUENCY, 0),
+            Err(ErrorKind::BadParam)
+        );
+    }
+
+    #[test]
+    fn lpuart_current_config_test() {
+        let mut fake_registers = [0u32; FAKE_REGISTER_WORDS];
+        fake_registers[0] = BAUD_CLOCK_READY;
+        let mut uart: UART<UART3> = UART {
+            reg: registers::Registers::new(fake_registers.as_mut_ptr() as usize),
+            _ph: PhantomData,
+            _gpio: crate::gpio::hardware::uart_n(3).unwrap(),
+        };
+
+        assert_eq!(
+            uart.configure(
+                2_400,
+                ClockSources::PCLK,
+                CharacterLength::EightBits,
+                StopBits::OneBit,
+                false,
+                Parity::Odd,
+                ParityValueSelect::OneBased,
+                false,
+            ),
+            Err(ErrorKind::BadParam)
+        );
+
+        uart.configure(
+            2_400,
+            ClockSources::ERTCO,
+            CharacterLength::EightBits,
+            StopBits::OneBit,
+            false,
+            Parity::Odd,
+            ParityValueSelect::OneBased,
+            false,
+        )
+        .unwrap();
+
+        let config = uart.current_config().unwrap();
+        assert_eq!(config.clock_source, ClockSources::ERTCO);
+        assert_eq!(config.baud_rate_divisor, 27);
+        assert_eq!(config.effective_baud_rate, 2 * ERTCO_FREQUENCY / 27);
+        assert!(uart.reg.get_fractional_divison_mode());
+        assert_eq!(uart.reg.get_lpuart_oversampling_rate(), 1);
+    }
+
+    /// A fake receive FIFO handing out `script` one read at a time.
+    fn scripted(script: &[Result<u8>]) -> impl FnMut() -> Result<u8> + '_ {
+        let mut script = script.iter().copied();
+        move || script.next().unwrap_or(Err(ErrorKind::NoneAvailable))
+    }
+
+    #[test]
+    fn read_exact_test() {
+        const EMPTY: Result<u8> = Err(ErrorKind::NoneAvailable);
+
+        let mut buf = [0; 3];
+        let script = [Ok(1), EMPTY, EMPTY, Ok(2), EMPTY, Ok(3), Ok(4)];
+        assert_eq!(read_exact_from(&mut buf, 2, scripted(&script)), Ok(()));
+        assert_eq!(buf, [1, 2, 3]);
+
+        // The line stalls for longer than the spin budget.
+        let script = [Ok(5), EMPTY, EMPTY, EMPTY, Ok(6)];
+        assert_eq!(
+            read_exact_from(&mut buf, 2, scripted(&script)),
+            Err(ErrorKind::TimeOut)
+        );
+        assert_eq!(buf[0], 5);
+
+        let script = [Ok(7), Err(ErrorKind::ComError), Ok(8), Ok(9)];
+        assert_eq!(
+            read_exact_from(&mut buf, 2, scripted(&script)),
+            Err(ErrorKind::ComError)
+        );
+
+        assert_eq!(read_exact_from(&mut [], 0, scripted(&[])), Ok(()));
+    }
+
+    #[test]
+    fn read_available_test() {
+        let mut buf = [0; 4];
+        assert_eq!(
+            read_available_from(&mut buf, scripted(&[Ok(1), Ok(2)])),
+            Ok(2)
+        );
+        assert_eq!(buf[..2], [1, 2]);
+
+        let script = [Ok(3), Ok(4), Ok(5), Ok(6), Ok(7)];
+        assert_eq!(read_available_from(&mut buf, scripted(&script)), Ok(4));
+        assert_eq!(buf, [3, 4, 5, 6]);
+
+        let script = [Ok(8), Err(ErrorKind::ComError), Ok(9)];
+        assert_eq!(
+            read_available_from(&mut buf, scripted(&script)),
+            Err(ErrorKind::ComError)
+        );
+        assert_eq!(buf[0], 8);
+
+        assert_eq!(read_available_from(&mut buf, scripted(&[])), Ok(0));
+    }
+
+    #[test]
+    fn write_all_and_fifo_levels_test() {
+        const STATUS_WORD: usize = 1;
+        const FIFO_WORD: usize = 8;
+        const TX_FULL: u32 = 1 << 7;
+
+        let mut fake_registers = [0u32; FAKE_REGISTER_WORDS];
+        let registers = fake_registers.as_mut_ptr();
+        let mut uart: UART<UART0> = UART {
+            reg: registers::Registers::new(registers as usize),
+            _ph: PhantomData,
+            _gpio: crate::gpio::hardware::uart_n(0).unwrap(),
+        };
+        let word = |word: usize| unsafe { registers.add(word).read_volatile() };
+        let set_word =
+            |word: usize, value: u32| unsafe { registers.add(word).write_volatile(value) };
+
+        // The fake transmit FIFO never fills up, and keeps the last byte written.
+        assert_eq!(uart.write_all(b"hello"), Ok(()));
+        assert_eq!(word(FIFO_WORD), u32::from(b'o'));
+
+        // A transmit FIFO that never drains times out instead of hanging.
+        set_word(STATUS_WORD, TX_FULL);
+        assert_eq!(uart.write_all(b"!"), Err(ErrorKind::TimeOut));
+        assert_eq!(word(FIFO_WORD), u32::from(b'o'));
+
+        set_word(STATUS_WORD, 5 << 12 | 3 << 8);
+        assert_eq!(uart.tx_fifo_level(), 5);
+        assert_eq!(uart.rx_fifo_level(), 3);
+    }
+
+    #[test]
+    fn fifo_threshold_and_flow_control_test() {
+        const CTRL_WORD: usize = 0;
+        const INT_EN_WORD: usize = 2;
+
+        let mut fake_registers = [0u32; FAKE_REGISTER_WORDS];
+        let registers = fake_registers.as_mut_ptr();
+        let mut uart: UART<UART0> = UART {
+            reg: registers::Registers::new(registers as usize),
+            _ph: PhantomData,
+            _gpio: crate::gpio::hardware::uart_n(0).unwrap(),
+        };
+        let word = |word: usize| unsafe { registers.add(word).read_volatile() };
+
+        for bytes in [1, 4, 8] {
+            assert_eq!(uart.set_rx_fifo_threshold(bytes), Ok(()));
+            assert_eq!(word(CTRL_WORD) & 0xF, u32::from(bytes));
+            assert_eq!(uart.rx_fifo_threshold(), bytes);
+        }
+        assert_eq!(uart.set_rx_fifo_threshold(0), Err(ErrorKind::BadParam));
+        assert_eq!(uart.set_rx_fifo_threshold(9), Err(ErrorKind::BadParam));
+        assert_eq!(uart.rx_fifo_threshold(), 8);
+
+        uart.set_hardware_flow_control(true);
+        uart.set_hardware_flow_rts_deassert_condition(HFCDeassertCondition::ExceedsRxThreshold);
+        assert_eq!(word(CTRL_WORD), 1 << 14 | 1 << 13 | 8);
+        uart.set_hardware_flow_rts_deassert_condition(HFCDeassertCondition::EqualsFIFODepth);
+        uart.set_hardware_flow_control(false);
+        assert_eq!(word(CTRL_WORD), 8);
+
+        uart.set_tx_half_empty_event(true);
+        assert_eq!(word(INT_EN_WORD), 1 << 6);
+        uart.set_tx_half_empty_event(false);
+        assert_eq!(word(INT_EN_WORD), 0);
+
+        assert!(!bool::from(HFCDeassertCondition::EqualsFIFODepth));
+        assert!(bool::from(HFCDeassertCondition::ExceedsRxThreshold));
+    }
+
+    #[test]
+    fn enum_conversions_test() {
+        assert_eq!(BaudRates::try_from(9600), Ok(BaudRates::Baud9600));
+        assert_eq!(BaudRates::try_from(9601), Err(ErrorKind::BadParam));
+        assert_eq!(CharacterLength::try_from(3), Ok(CharacterLength::EightBits));
+        assert_eq!(CharacterLength::try_from(4), Err(ErrorKind::BadParam));
+        assert_eq!(ClockSources::try_from(1), Err(ErrorKind::BadParam));
+    }
+}
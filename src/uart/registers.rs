@@ -4,7 +4,7 @@ use hal_macros_derive::make_device;
 
 /// # UART Register Offsets
 /// See Max 78000 User Guide Page 180, Table 12-7.
-mod rro {
+pub(super) mod rro {
     /// # UART Control Register
     pub const UART_CTRL: usize = 0x0000;
     /// # UART Status Register
@@ -31,8 +31,11 @@ mod rro {
     pub const UART_WKFL: usize = 0x0038;
 }
 
+/// The offset of the FIFO data register, which a DMA channel writes to.
+pub(super) const FIFO_OFFSET: usize = rro::UART_FIFO;
+
 make_device! {
-    device_ports(mmio::UART_0, mmio::UART_1, mmio::UART_2);
+    device_ports(mmio::UART_0, mmio::UART_1, mmio::UART_2, mmio::LOW_POWER_UART_0);
     /// Receive Dual Edge Sampling. See Page 180, Table 12-8.
     /// This feature can **only** be used with `LPUART`
     /// Can choose to sample only on the rising edge, or both the rising and falling edges.
@@ -125,7 +128,7 @@ make_device! {
     /// - 1: 6 bits
     /// - 2: 7 bits
     /// - 3: 8 bits
-    #[bit(10..=11, RW, rro::UART_CTRL)]
+    #[bit(10..=11, RW, rro::UART_CTRL, crate::uart::CharacterLength)]
     character_length,
 
 
@@ -399,6 +402,7 @@ make_device! {
     /// Shows if parity error occurred while receiving last byte
     /// - 0: No error
     /// - 1: Error occurred
+    #[no_dump]
     #[bit(8, RO, rro::UART_FIFO)]
     receive_fifo_byte_parity,
 
@@ -407,6 +411,12 @@ make_device! {
     #[bit(0..=7, RW, rro::UART_FIFO)]
     fifo_data,
 
+    /// Receive FIFO Entry. See Page 185, Table 12-16.
+    /// The received byte and its parity error bit (bit 8) in one read, since every
+    /// read of the FIFO register takes the next byte out of the inbound FIFO
+    #[bit(0..=8, RO, rro::UART_FIFO)]
+    receive_fifo_entry,
+
     /// Receive DMA Channel Enable. See Page 185, Table 12-17.
     /// Enabling Direct Memory Access for inbound UART to allow using multiple buffers
     /// The documentation has a typo for this bit's access.
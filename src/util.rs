@@ -0,0 +1,149 @@
+use crate::error::{ErrorKind, Result};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// # Constant Time Equal
+/// Check if `a` and `b` hold the same bytes, taking the same time no matter
+/// where (or if) they differ. Use this instead of `==` when comparing a received
+/// MAC, PEC or other check value against the expected one.
+///
+/// # Timing
+/// Every byte pair is XORed and ORed into one accumulator with no early exit,
+/// and the accumulator goes through `black_box` so the compiler cannot turn the
+/// loop back into a short-circuiting compare. The time still depends on the
+/// lengths, and slices of different lengths return `false` straight away, so
+/// only the contents are kept secret. This is not `#[inline]`, so the loop is
+/// compiled once here rather than re-optimized around each caller's data.
+#[inline(never)]
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let difference = a
+        .iter()
+        .zip(b)
+        .fold(0u8, |acc, (x, y)| core::hint::black_box(acc | (x ^ y)));
+
+    core::hint::black_box(difference) == 0
+}
+
+/// # Init Once
+/// Run `init` unless `taken` is already set, setting it first so the peripheral
+/// `init` sets up is only ever handed out once. `taken` is cleared again if `init`
+/// fails, so the caller can retry.
+///
+/// Returns `ErrorKind::Busy` if `taken` was already set.
+pub(crate) fn init_once<T>(taken: &AtomicBool, init: impl FnOnce() -> Result<T>) -> Result<T> {
+    if taken.swap(true, Ordering::AcqRel) {
+        return Err(ErrorKind::Busy);
+    }
+
+    init().inspect_err(|_| taken.store(false, Ordering::Release))
+}
+
+/// # Timeout Spins
+/// The number of busy-wait spins that take at least `us` microseconds, for drivers
+/// that bound their waits by a spin count. Every spin takes at least one cycle of
+/// the core clock, so the spins are counted from `SYSTEM_CORE_CLOCK` rather than
+/// the half speed `core_peripheral_clock`. A `us` of 0 gives `u32::MAX` spins, as
+/// good as no timeout.
+pub(crate) fn timeout_spins(us: usize) -> u32 {
+    if us == 0 {
+        return u32::MAX;
+    }
+
+    let cycles_per_us = (unsafe { crate::SYSTEM_CORE_CLOCK } / 1_000_000).max(1) as usize;
+    us.saturating_mul(cycles_per_us).min(u32::MAX as usize) as u32
+}
+
+/// # Wait Until
+/// Check `condition` at most `max_spins` times, like `hal_macros::spin_until`, for
+/// the waits that the generated `wait_<field>` functions do not cover: anything
+/// other than a single status or flag bit.
+///
+/// Returns `ErrorKind::TimeOut` if `condition` never holds.
+pub(crate) fn wait_until(max_spins: u32, condition: impl FnMut() -> bool) -> Result<()> {
+    if hal_macros::spin_until(max_spins, condition) {
+        Ok(())
+    } else {
+        Err(ErrorKind::TimeOut)
+    }
+}
+
+/// # Spend Spin
+/// Count one spin of a busy-wait against a budget from `timeout_spins`, returning
+/// `ErrorKind::TimeOut` once more than `timeout_spins` have been spent. The count
+/// stops at `u32::MAX`, so a budget of `u32::MAX` never runs out.
+pub(crate) fn spend_spin(spins: &mut u32, timeout_spins: u32) -> Result<()> {
+    *spins = spins.saturating_add(1);
+    if *spins > timeout_spins {
+        Err(ErrorKind::TimeOut)
+    } else {
+        core::hint::spin_loop();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ct_eq_test() {
+        assert!(ct_eq(&[], &[]));
+        assert!(ct_eq(&[0x12, 0x34, 0x56], &[0x12, 0x34, 0x56]));
+
+        // A difference anywhere, even a single bit, is found.
+        assert!(!ct_eq(&[0x12, 0x34, 0x56], &[0x13, 0x34, 0x56]));
+        assert!(!ct_eq(&[0x12, 0x34, 0x56], &[0x12, 0x34, 0xD6]));
+        assert!(!ct_eq(&[0xFF; 16], &[0x00; 16]));
+
+        assert!(!ct_eq(&[0x12, 0x34], &[0x12, 0x34, 0x56]));
+        assert!(!ct_eq(&[], &[0x00]));
+    }
+
+    #[test]
+    fn init_once_test() {
+        let taken = AtomicBool::new(false);
+
+        assert_eq!(
+            init_once(&taken, || Err::<(), _>(ErrorKind::TimeOut)),
+            Err(ErrorKind::TimeOut)
+        );
+        assert_eq!(init_once(&taken, || Ok(1)), Ok(1));
+        assert_eq!(init_once(&taken, || Ok(2)), Err(ErrorKind::Busy));
+        assert!(taken.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn wait_until_test() {
+        // Like a status word whose ready bit sets on the third read.
+        let mut reads = 0;
+        let ready = || {
+            reads += 1;
+            reads >= 3
+        };
+        assert_eq!(wait_until(10, ready), Ok(()));
+        assert_eq!(reads, 3);
+        assert_eq!(wait_until(10, || false), Err(ErrorKind::TimeOut));
+    }
+
+    #[test]
+    fn timeout_spins_test() {
+        // The test core clock is 100MHz.
+        assert_eq!(timeout_spins(0), u32::MAX);
+        assert_eq!(timeout_spins(10), 1_000);
+        assert_eq!(timeout_spins(usize::MAX), u32::MAX);
+
+        let mut spins = 0;
+        assert_eq!(spend_spin(&mut spins, 2), Ok(()));
+        assert_eq!(spend_spin(&mut spins, 2), Ok(()));
+        assert_eq!(spend_spin(&mut spins, 2), Err(ErrorKind::TimeOut));
+
+        // The budget from `set_timeout(0)` does not overflow the count.
+        let mut spins = u32::MAX - 1;
+        assert_eq!(spend_spin(&mut spins, u32::MAX), Ok(()));
+        assert_eq!(spend_spin(&mut spins, u32::MAX), Ok(()));
+        assert_eq!(spins, u32::MAX);
+    }
+}
@@ -0,0 +1,357 @@
+//! # Watchdog Timer
+//! The watchdog (WDT0) and low power watchdog (WDT1) reset the system if they
+//! are not fed in time.
+
+pub mod registers;
+
+use crate::error::{ErrorKind, Result};
+use crate::memory_map::mmio;
+use core::marker::PhantomData;
+use registers::Registers;
+
+mod private {
+    pub trait WatchdogPortCompatable: crate::port::Port {
+        const HARDWARE_SOURCE: crate::gcr::HardwareSource;
+    }
+}
+
+pub struct NoPort {}
+pub struct WDT0 {}
+/// The low power watchdog.
+pub struct WDT1 {}
+
+impl crate::port::private::Sealed for WDT0 {}
+impl crate::port::Port for WDT0 {
+    const BASE: usize = mmio::WATCHDOG_TIMER0;
+    const NUM: usize = 0;
+}
+impl private::WatchdogPortCompatable for WDT0 {
+    const HARDWARE_SOURCE: crate::gcr::HardwareSource = crate::gcr::HardwareSource::WDT0;
+}
+impl crate::port::private::Sealed for WDT1 {}
+impl crate::port::Port for WDT1 {
+    const BASE: usize = mmio::LOW_POWER_WATCHDOG_TIMER_0;
+    const NUM: usize = 1;
+}
+impl private::WatchdogPortCompatable for WDT1 {
+    const HARDWARE_SOURCE: crate::gcr::HardwareSource = crate::gcr::HardwareSource::WDT1;
+}
+
+/// How many times `enable` and `disable` check for the watchdog clock to take the
+/// change before giving up.
+const CLOCK_READY_SPINS: u32 = 100_000;
+
+/// # Period
+/// How many watchdog clocks the watchdog waits for a feed, from 2^31 down to
+/// 2^16. The values are the `_VAL` fields of the watchdog control register.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Period {
+    Clocks2Pow31 = 0,
+    Clocks2Pow30 = 1,
+    Clocks2Pow29 = 2,
+    Clocks2Pow28 = 3,
+    Clocks2Pow27 = 4,
+    Clocks2Pow26 = 5,
+    Clocks2Pow25 = 6,
+    Clocks2Pow24 = 7,
+    Clocks2Pow23 = 8,
+    Clocks2Pow22 = 9,
+    Clocks2Pow21 = 10,
+    Clocks2Pow20 = 11,
+    Clocks2Pow19 = 12,
+    Clocks2Pow18 = 13,
+    Clocks2Pow17 = 14,
+    Clocks2Pow16 = 15,
+}
+
+impl Period {
+    const ALL: [Period; 16] = [
+        Self::Clocks2Pow31,
+        Self::Clocks2Pow30,
+        Self::Clocks2Pow29,
+        Self::Clocks2Pow28,
+        Self::Clocks2Pow27,
+        Self::Clocks2Pow26,
+        Self::Clocks2Pow25,
+        Self::Clocks2Pow24,
+        Self::Clocks2Pow23,
+        Self::Clocks2Pow22,
+        Self::Clocks2Pow21,
+        Self::Clocks2Pow20,
+        Self::Clocks2Pow19,
+        Self::Clocks2Pow18,
+        Self::Clocks2Pow17,
+        Self::Clocks2Pow16,
+    ];
+
+    /// # Clocks
+    /// The number of watchdog clocks in this period.
+    pub const fn clocks(self) -> u32 {
+        1 << (31 - self as u32)
+    }
+
+    /// # At Least Clocks
+    /// The shortest period of at least `clocks` watchdog clocks, or `None` if that
+    /// is longer than 2^31 clocks.
+    pub fn at_least_clocks(clocks: u32) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .rev()
+            .copied()
+            .find(|period| period.clocks() >= clocks)
+    }
+}
+
+impl TryFrom<u8> for Period {
+    type Error = ErrorKind;
+
+    fn try_from(value: u8) -> Result<Self> {
+        Self::ALL
+            .get(value as usize)
+            .copied()
+            .ok_or(ErrorKind::BadParam)
+    }
+}
+
+/// # Watchdog
+/// A watchdog timer, made with `Watchdog::port_0_init` or `Watchdog::port_1_init`.
+/// Once enabled it resets the whole system unless `feed` is called at least once
+/// every period.
+///
+/// # Example
+/// ```no_run
+/// use max78000_hal::wdt::{Period, Watchdog};
+///
+/// let mut watchdog = Watchdog::port_0_init().unwrap();
+/// // About 1.3s from the 50MHz peripheral clock.
+/// watchdog.enable(Period::Clocks2Pow26).unwrap();
+///
+/// loop {
+///     // ... work that must not get stuck ...
+///     watchdog.feed();
+/// }
+/// ```
+pub struct Watchdog<Port = NoPort> {
+    reg: Registers,
+    _ph: PhantomData<Port>,
+}
+
+impl Watchdog<NoPort> {
+    /// # Port 0 Init
+    /// Reset WDT0 and enable its clock. The watchdog stays disabled until `enable`.
+    ///
+    /// Returns `ErrorKind::TimeOut` if the reset does not finish.
+    pub fn port_0_init() -> Result<Watchdog<WDT0>> {
+        Watchdog::<WDT0>::init()
+    }
+
+    /// # Port 1 Init
+    /// Reset the low power watchdog (WDT1) and enable its clock. The watchdog stays
+    /// disabled until `enable`.
+    ///
+    /// Returns `ErrorKind::TimeOut` if the reset does not finish.
+    pub fn port_1_init() -> Result<Watchdog<WDT1>> {
+        Watchdog::<WDT1>::init()
+    }
+}
+
+impl<Port: private::WatchdogPortCompatable> Watchdog<Port> {
+    fn init() -> Result<Self> {
+        crate::gcr::peripheral_reset(Port::HARDWARE_SOURCE)?;
+        crate::gcr::system_clock_enable(Port::HARDWARE_SOURCE, true);
+
+        Ok(Self::from_registers(Registers::new(Port::BASE)))
+    }
+
+    fn from_registers(reg: Registers) -> Self {
+        Self {
+            reg,
+            _ph: PhantomData,
+        }
+    }
+
+    /// # Enable
+    /// Start the watchdog, resetting the system if it is not fed within `period`
+    /// watchdog clocks (the peripheral clock, unless changed). The count starts
+    /// from a feed, so a full period is allowed for the first one.
+    ///
+    /// Returns `ErrorKind::TimeOut` if the watchdog clock never takes the change.
+    pub fn enable(&mut self, period: Period) -> Result<()> {
+        unsafe {
+            self.reg.set_enable(false);
+            self.reg.set_window_enable(false);
+            self.reg.set_interrupt_enable(false);
+            self.reg.set_interrupt_late_period(period);
+            self.reg.set_reset_late_period(period);
+            self.reg.set_reset_enable(true);
+        }
+
+        self.feed();
+        self.write_sequence(0xFE, 0xED);
+        unsafe { self.reg.set_enable(true) };
+        self.reg.wait_clock_ready(true, CLOCK_READY_SPINS)
+    }
+
+    /// # Disable
+    /// Stop the watchdog.
+    ///
+    /// Returns `ErrorKind::TimeOut` if the watchdog clock never takes the change.
+    pub fn disable(&mut self) -> Result<()> {
+        self.write_sequence(0xDE, 0xAD);
+        unsafe { self.reg.set_enable(false) };
+        self.reg.wait_clock_ready(true, CLOCK_READY_SPINS)
+    }
+
+    /// # Feed
+    /// Restart the watchdog's count, so the system is not reset for another period.
+    pub fn feed(&mut self) {
+        self.write_sequence(0xA5, 0x5A);
+    }
+
+    /// # Is Enabled
+    /// Check if the watchdog is running.
+    pub fn is_enabled(&self) -> bool {
+        self.reg.get_enable()
+    }
+
+    /// # Period
+    /// The reset period programmed by `enable`, or `None` if the register holds a
+    /// value this driver does not know.
+    pub fn period(&self) -> Option<Period> {
+        self.reg.get_reset_late_period().ok()
+    }
+
+    /// # Caused Reset
+    /// Check if the last system reset was this watchdog running out, before
+    /// `clear_reset_flags` is called.
+    pub fn caused_reset(&self) -> bool {
+        self.reg.get_reset_late_flag() || self.reg.get_reset_early_flag()
+    }
+
+    /// # Clear Reset Flags
+    /// Clear the flags read by `caused_reset`.
+    pub fn clear_reset_flags(&mut self) {
+        unsafe {
+            self.reg.set_reset_late_flag(false);
+            self.reg.set_reset_early_flag(false);
+        }
+    }
+
+    fn write_sequence(&mut self, first: u8, second: u8) {
+        unsafe {
+            self.reg.set_reset_sequence(first);
+            self.reg.set_reset_sequence(second);
+        }
+    }
+}
+
+/// # Traits
+/// The watchdog in the shape of `embedded_hal::watchdog` from embedded-hal 0.2,
+/// for drivers written against that interface. They get their own module because
+/// `Watchdog` already names the driver. Only built with the `wdt-watchdog` feature.
+#[cfg(feature = "wdt-watchdog")]
+pub mod traits {
+    /// # Watchdog
+    /// Feeding a watchdog that is running.
+    pub trait Watchdog {
+        /// Restart the count, the same as `wdt::Watchdog::feed`.
+        fn feed(&mut self);
+    }
+
+    /// # Watchdog Enable
+    /// Starting a watchdog. Unlike embedded-hal's, `start` can fail.
+    pub trait WatchdogEnable {
+        type Error;
+        type Time;
+
+        /// Start the watchdog with `period`, the same as `wdt::Watchdog::enable`.
+        fn start<T: Into<Self::Time>>(&mut self, period: T) -> Result<(), Self::Error>;
+    }
+}
+
+#[cfg(feature = "wdt-watchdog")]
+impl<Port: private::WatchdogPortCompatable> traits::Watchdog for Watchdog<Port> {
+    fn feed(&mut self) {
+        Watchdog::feed(self)
+    }
+}
+
+#[cfg(feature = "wdt-watchdog")]
+impl<Port: private::WatchdogPortCompatable> traits::WatchdogEnable for Watchdog<Port> {
+    type Error = ErrorKind;
+    type Time = Period;
+
+    fn start<T: Into<Period>>(&mut self, period: T) -> Result<()> {
+        self.enable(period.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CLOCK_READY: u32 = 1 << 28;
+
+    #[test]
+    fn period_test() {
+        assert_eq!(Period::Clocks2Pow31.clocks(), 1 << 31);
+        assert_eq!(Period::Clocks2Pow16.clocks(), 1 << 16);
+        assert_eq!(Period::at_least_clocks(0), Some(Period::Clocks2Pow16));
+        assert_eq!(
+            Period::at_least_clocks((1 << 20) + 1),
+            Some(Period::Clocks2Pow21)
+        );
+        assert_eq!(Period::at_least_clocks(1 << 31), Some(Period::Clocks2Pow31));
+        assert_eq!(Period::at_least_clocks((1 << 31) + 1), None);
+
+        for period in Period::ALL {
+            assert_eq!(Period::try_from(period as u8), Ok(period));
+        }
+        assert_eq!(Period::try_from(16), Err(ErrorKind::BadParam));
+    }
+
+    #[test]
+    fn enable_feed_disable_test() {
+        let mut fake_registers = [0u32; 4];
+        let ptr = fake_registers.as_mut_ptr();
+        // Reset and early interrupt settings left over from before are kept.
+        fake_registers[0] = CLOCK_READY | (1 << 31) | (0x3 << 16);
+        let mut watchdog =
+            Watchdog::<WDT0>::from_registers(Registers::new(fake_registers.as_mut_ptr() as usize));
+
+        assert!(watchdog.caused_reset());
+        watchdog.clear_reset_flags();
+        assert!(!watchdog.caused_reset());
+
+        assert_eq!(watchdog.enable(Period::Clocks2Pow26), Ok(()));
+        assert!(watchdog.is_enabled());
+        assert_eq!(watchdog.period(), Some(Period::Clocks2Pow26));
+        let ctrl = unsafe { ptr.read_volatile() };
+        assert_eq!(
+            ctrl,
+            CLOCK_READY | (0x3 << 16) | (1 << 11) | (1 << 8) | (5 << 4) | 5
+        );
+        // The enable sequence is the last thing written to `WDT_RST`.
+        assert_eq!(unsafe { ptr.add(1).read_volatile() }, 0xED);
+
+        watchdog.feed();
+        assert_eq!(unsafe { ptr.add(1).read_volatile() }, 0x5A);
+
+        assert_eq!(watchdog.disable(), Ok(()));
+        assert!(!watchdog.is_enabled());
+        assert_eq!(unsafe { ptr.add(1).read_volatile() }, 0xAD);
+    }
+
+    #[test]
+    fn clock_not_ready_test() {
+        let mut fake_registers = [0u32; 4];
+        let mut watchdog =
+            Watchdog::<WDT1>::from_registers(Registers::new(fake_registers.as_mut_ptr() as usize));
+
+        assert_eq!(
+            watchdog.enable(Period::Clocks2Pow16),
+            Err(ErrorKind::TimeOut)
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use crate::memory_map::mmio;
+use hal_macros::RW;
+use hal_macros_derive::make_device;
+
+/// # Watchdog Timer Register Offsets
+/// The register layout is shared by the watchdog (WDT0) and the low power
+/// watchdog (WDT1), and matches `mxc_wdt_regs_t` in the MSDK.
+mod rro {
+    /// # Watchdog Timer Control Register
+    pub const WDT_CTRL: usize = 0x0000;
+    /// # Watchdog Timer Reset Register
+    pub const WDT_RST: usize = 0x0004;
+    /// # Watchdog Timer Clock Source Select Register
+    pub const WDT_CLKSEL: usize = 0x0008;
+    /// # Watchdog Timer Count Register
+    pub const WDT_CNT: usize = 0x000C;
+}
+
+make_device! {
+    device_ports(mmio::WATCHDOG_TIMER0, mmio::LOW_POWER_WATCHDOG_TIMER_0);
+
+    /// Late Interrupt Period. `MXC_F_WDT_CTRL_INT_LATE_VAL`.
+    /// The interrupt fires after 2^(31 - value) watchdog clocks without a feed.
+    #[bit(0..=3, RW, rro::WDT_CTRL, crate::wdt::Period)]
+    interrupt_late_period,
+
+    /// Late Reset Period. `MXC_F_WDT_CTRL_RST_LATE_VAL`.
+    /// The system resets after 2^(31 - value) watchdog clocks without a feed.
+    #[bit(4..=7, RW, rro::WDT_CTRL, crate::wdt::Period)]
+    reset_late_period,
+
+    /// Watchdog Timer Enable. `MXC_F_WDT_CTRL_EN`.
+    /// Only changes after the enable or disable sequence is written to `WDT_RST`.
+    #[bit(8, RW, rro::WDT_CTRL)]
+    enable,
+
+    /// Late Interrupt Flag. `MXC_F_WDT_CTRL_INT_LATE`.
+    /// Set when the late interrupt period passes, cleared by writing `0`.
+    #[bit(9, RW, rro::WDT_CTRL)]
+    interrupt_late_flag,
+
+    /// Watchdog Interrupt Enable. `MXC_F_WDT_CTRL_WDT_INT_EN`.
+    #[bit(10, RW, rro::WDT_CTRL)]
+    interrupt_enable,
+
+    /// Watchdog Reset Enable. `MXC_F_WDT_CTRL_WDT_RST_EN`.
+    /// Reset the system when the late reset period passes.
+    #[bit(11, RW, rro::WDT_CTRL)]
+    reset_enable,
+
+    /// Early Interrupt Flag. `MXC_F_WDT_CTRL_INT_EARLY`.
+    /// Set when the watchdog is fed too early in windowed mode, cleared by writing `0`.
+    #[bit(12, RW, rro::WDT_CTRL)]
+    interrupt_early_flag,
+
+    /// Early Interrupt Period. `MXC_F_WDT_CTRL_INT_EARLY_VAL`.
+    #[bit(16..=19, RW, rro::WDT_CTRL)]
+    interrupt_early_period,
+
+    /// Early Reset Period. `MXC_F_WDT_CTRL_RST_EARLY_VAL`.
+    #[bit(20..=23, RW, rro::WDT_CTRL)]
+    reset_early_period,
+
+    /// Clock Ready Interrupt Enable. `MXC_F_WDT_CTRL_CLKRDY_IE`.
+    #[bit(27, RW, rro::WDT_CTRL)]
+    clock_ready_interrupt_enable,
+
+    /// Clock Ready. `MXC_F_WDT_CTRL_CLKRDY`.
+    /// Set once a write to `enable` has crossed into the watchdog clock domain.
+    #[bit(28, RO, rro::WDT_CTRL)]
+    clock_ready,
+
+    /// Windowed Mode Enable. `MXC_F_WDT_CTRL_WIN_EN`.
+    #[bit(29, RW, rro::WDT_CTRL)]
+    window_enable,
+
+    /// Early Reset Flag. `MXC_F_WDT_CTRL_RST_EARLY`.
+    /// Set when the last reset was caused by feeding too early, cleared by writing `0`.
+    #[bit(30, RW, rro::WDT_CTRL)]
+    reset_early_flag,
+
+    /// Late Reset Flag. `MXC_F_WDT_CTRL_RST_LATE`.
+    /// Set when the last reset was caused by not feeding in time, cleared by writing `0`.
+    #[bit(31, RW, rro::WDT_CTRL)]
+    reset_late_flag,
+
+    /// Reset Sequence. `MXC_F_WDT_RST_RESET`.
+    /// Feed with `0xA5` then `0x5A`, enable with `0xFE` then `0xED`, and disable
+    /// with `0xDE` then `0xAD`.
+    #[bit(0..=7, WO, rro::WDT_RST)]
+    reset_sequence,
+
+    /// Clock Source Select. `MXC_F_WDT_CLKSEL_SOURCE`.
+    /// The watchdog clock, `0` is the peripheral clock.
+    #[bit(0..=2, RW, rro::WDT_CLKSEL)]
+    clock_source,
+
+    /// Count. `MXC_F_WDT_CNT_COUNT`.
+    /// The current count of the watchdog, in watchdog clocks.
+    #[bit(0..=31, RO, rro::WDT_CNT)]
+    count,
+}